@@ -6,11 +6,12 @@
 //! - Book depth impact on performance
 //! - Memory allocation pressure tests
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId, Throughput};
-use flash_lob::{Engine, Command, PlaceOrder, CancelOrder, Side, OrderType};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkGroup, BenchmarkId, Throughput};
+use criterion::measurement::WallTime;
+use flash_lob::{Engine, EngineConfig, Command, PlaceOrder, CancelOrder, Side, OrderType, LevelStorage, LadderConfig, BookBackend, VecBook};
+use flash_lob::matching::MatchingEngine;
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
-use std::time::Instant;
 
 /// Benchmark: Match across multiple price levels
 fn bench_multi_level_match(c: &mut Criterion) {
@@ -48,7 +49,7 @@ fn bench_multi_level_match(c: &mut Criterion) {
                         price: 10000 + (levels as u64 - 1) * 10,
                         qty: levels as u32 * 10, // Match one order per level
                         order_type: OrderType::Limit,
-                    }));
+                    })).len();
                     
                     // Replenish
                     for i in 0..levels {
@@ -104,7 +105,7 @@ fn bench_book_depth_impact(c: &mut Criterion) {
                         side: Side::Bid,
                         price: 8000, // Won't match
                         qty: 100, order_type: OrderType::Limit,
-                    })))
+                    })).len())
                 })
             },
         );
@@ -136,7 +137,6 @@ fn bench_cancel_scaling(c: &mut Criterion) {
                     }));
                 }
                 
-                let mut rng = ChaCha8Rng::seed_from_u64(0xDEADBEEF);
                 let mut cancel_id = 0u64;
                 let mut next_id = book_size as u64;
                 
@@ -144,13 +144,13 @@ fn bench_cancel_scaling(c: &mut Criterion) {
                     // Cancel random order
                     let result = engine.process_command(Command::Cancel(CancelOrder {
                         order_id: cancel_id,
-                    }));
+                    })).len();
                     
                     // Replenish
                     engine.process_command(Command::Place(PlaceOrder {
                         order_id: next_id,
                         user_id: 1,
-                        side: if cancel_id % 2 == 0 { Side::Bid } else { Side::Ask },
+                        side: if cancel_id.is_multiple_of(2) { Side::Bid } else { Side::Ask },
                         price: 9000 + (cancel_id % 200) * 10,
                         qty: 100, order_type: OrderType::Limit,
                     }));
@@ -201,7 +201,7 @@ fn bench_modify_order(c: &mut Criterion) {
                             new_price: 9500,
                             new_qty: 150,
                         }
-                    ));
+                    )).len();
                     
                     modify_id = new_id;
                     new_id += 1;
@@ -225,27 +225,25 @@ fn bench_realistic_hft(c: &mut Criterion) {
         engine.warm_up();
         
         let mut rng = ChaCha8Rng::seed_from_u64(0xCAFEBABE);
-        let mut order_id = 0u64;
-        
         // Pre-populate with tight spread
         for i in 0..500 {
             engine.process_command(Command::Place(PlaceOrder {
                 order_id: i,
                 user_id: 1,
                 side: Side::Bid,
-                price: 9990 + (i % 10) as u64, // 9990-9999
+                price: 9990 + i % 10, // 9990-9999
                 qty: 100, order_type: OrderType::Limit,
             }));
             engine.process_command(Command::Place(PlaceOrder {
                 order_id: 500 + i,
                 user_id: 1,
                 side: Side::Ask,
-                price: 10001 + (i % 10) as u64, // 10001-10010
+                price: 10001 + i % 10, // 10001-10010
                 qty: 100, order_type: OrderType::Limit,
             }));
         }
         
-        order_id = 1000;
+        let mut order_id = 1000u64;
         
         b.iter(|| {
             let op = rng.gen_range(0..100);
@@ -259,7 +257,7 @@ fn bench_realistic_hft(c: &mut Criterion) {
                     side: Side::Bid,
                     price: 9990 + rng.gen_range(0..10),
                     qty: rng.gen_range(10..200), order_type: OrderType::Limit,
-                }))
+                })).len()
             } else if op < 80 {
                 // 40% place ask
                 order_id += 1;
@@ -269,13 +267,13 @@ fn bench_realistic_hft(c: &mut Criterion) {
                     side: Side::Ask,
                     price: 10001 + rng.gen_range(0..10),
                     qty: rng.gen_range(10..200), order_type: OrderType::Limit,
-                }))
+                })).len()
             } else {
                 // 20% cancel
                 let cancel_id = rng.gen_range(0..order_id);
                 engine.process_command(Command::Cancel(CancelOrder {
                     order_id: cancel_id,
-                }))
+                })).len()
             };
             
             black_box(result)
@@ -314,7 +312,7 @@ fn bench_cache_effects(c: &mut Criterion) {
                 side: Side::Bid,
                 price: 10000,
                 qty: 100, order_type: OrderType::Limit,
-            }));
+            })).len();
             
             // Replenish
             engine.process_command(Command::Place(PlaceOrder {
@@ -339,7 +337,7 @@ fn bench_cache_effects(c: &mut Criterion) {
                 order_id: i,
                 user_id: 1,
                 side: if i % 2 == 0 { Side::Ask } else { Side::Bid },
-                price: 9000 + (i % 1000) as u64,
+                price: 9000 + i % 1000,
                 qty: 100, order_type: OrderType::Limit,
             }));
         }
@@ -354,7 +352,7 @@ fn bench_cache_effects(c: &mut Criterion) {
                 side: Side::Bid,
                 price: 9500, // Somewhere in the middle
                 qty: 100, order_type: OrderType::Limit,
-            }));
+            })).len();
             
             black_box(result)
         })
@@ -399,6 +397,70 @@ fn bench_batch_throughput(c: &mut Criterion) {
     group.finish();
 }
 
+/// Mixed place/cancel flow over 500 resting levels per side
+fn bench_mixed_flow<B: BookBackend>(group: &mut BenchmarkGroup<'_, WallTime>, name: &str, make: impl Fn() -> Engine<B>) {
+    group.bench_function(BenchmarkId::new("mixed_flow", name), |b| {
+        let mut engine = make();
+        engine.warm_up();
+        let mut rng = ChaCha8Rng::seed_from_u64(0xBADC0FFEE);
+        
+        // Resting depth: 500 levels each side around the mid
+        let mut order_id = 0u64;
+        for level in 1..=500u64 {
+            for side in [Side::Bid, Side::Ask] {
+                order_id += 1;
+                let price = match side {
+                    Side::Bid => 1_000_000 - level * 100,
+                    Side::Ask => 1_000_000 + level * 100,
+                };
+                engine.process_command(Command::Place(PlaceOrder {
+                    order_id,
+                    user_id: 1,
+                    side,
+                    price,
+                    qty: 100, order_type: OrderType::Limit,
+                }));
+            }
+        }
+        
+        let mut resting: Vec<u64> = (1..=order_id).collect();
+        
+        b.iter(|| {
+            order_id += 1;
+            let cmd = if rng.gen_range(0..100) < 40 && !resting.is_empty() {
+                let idx = rng.gen_range(0..resting.len());
+                Command::Cancel(CancelOrder { order_id: resting.swap_remove(idx) })
+            } else {
+                resting.push(order_id);
+                Command::Place(PlaceOrder {
+                    order_id,
+                    user_id: 2,
+                    side: if rng.gen_bool(0.5) { Side::Bid } else { Side::Ask },
+                    price: (1_000_000 + rng.gen_range(-500i64..=500) * 100) as u64,
+                    qty: rng.gen_range(1..200), order_type: OrderType::Limit,
+                })
+            };
+            black_box(engine.process_command(cmd).len())
+        })
+    });
+}
+
+/// Benchmark: Order book backends (pooled skip list, tick ladder, sorted vector)
+fn bench_book_backend(c: &mut Criterion) {
+    let mut group = c.benchmark_group("book_backend");
+    
+    bench_mixed_flow(&mut group, "tree", || Engine::new(100_000));
+    bench_mixed_flow(&mut group, "ladder", || {
+        let storage = LevelStorage::Ladder(LadderConfig::centered(1_000_000, 100, 4096));
+        Engine::with_matcher(MatchingEngine::with_storage(100_000, storage), EngineConfig::default())
+    });
+    bench_mixed_flow(&mut group, "vec_book", || {
+        Engine::with_matcher(MatchingEngine::with_book(100_000, VecBook::with_capacity(100_000)), EngineConfig::default())
+    });
+    
+    group.finish();
+}
+
 criterion_group!(
     extended_benches,
     bench_multi_level_match,
@@ -408,6 +470,7 @@ criterion_group!(
     bench_realistic_hft,
     bench_cache_effects,
     bench_batch_throughput,
+    bench_book_backend,
 );
 
 criterion_main!(extended_benches);
@@ -134,7 +134,7 @@ fn bench_cancel(c: &mut Criterion) {
                 engine.process_command(Command::Place(PlaceOrder {
                     order_id: next_order_id,
                     user_id: 1,
-                    side: if cancel_id % 2 == 0 { Side::Bid } else { Side::Ask },
+                    side: if cancel_id.is_multiple_of(2) { Side::Bid } else { Side::Ask },
                     price: 9000 + (cancel_id % 100) * 10,
                     qty: 100,
                     order_type: OrderType::Limit,
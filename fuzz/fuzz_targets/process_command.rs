@@ -0,0 +1,11 @@
+//! Feed arbitrary bytes through `Engine::process_command`, checking book
+//! invariants and agreement with the reference matcher after every command.
+//! See `flash_lob::fuzzing` for the input format.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    flash_lob::fuzzing::check_commands(data);
+});
@@ -3,9 +3,20 @@
 //! The arena pre-allocates a contiguous block of memory at startup,
 //! eliminating heap allocation in the hot path. Uses a free list for
 //! O(1) allocation and deallocation.
+//!
+//! A growable arena (`Arena::growable`) instead starts with one chunk and
+//! appends fixed-size chunks when the free list runs dry, up to a hard cap.
+//! Chunks never move, so an `ArenaIndex` stays valid as the arena grows.
+//!
+//! Each node carries a generation counter, bumped on every alloc and free.
+//! An `ArenaHandle` (index + generation) goes stale once its node is freed;
+//! debug builds and the `checked-arena` feature check handles and catch
+//! double frees instead of silently aliasing a reused slot.
 
 use std::fmt;
 
+use crate::arena_memory::{AllocReport, AllocStrategy, NodeChunk};
+
 /// Sentinel value representing a null/invalid index (like nullptr)
 pub const NULL_INDEX: u32 = u32::MAX;
 
@@ -14,6 +25,46 @@ pub const NULL_INDEX: u32 = u32::MAX;
 /// doubling cache efficiency.
 pub type ArenaIndex = u32;
 
+/// Whether handle and double-free checks run (debug builds, or release
+/// builds with the `checked-arena` feature)
+const CHECKED: bool = cfg!(any(debug_assertions, feature = "checked-arena"));
+
+/// An arena index paired with the generation it was allocated at.
+///
+/// Goes stale once the node is freed, even if the slot is reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ArenaHandle {
+    pub index: ArenaIndex,
+    pub generation: u32,
+}
+
+/// Misuse detected by the checked arena operations
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArenaError {
+    /// Index beyond the arena's capacity
+    OutOfBounds(ArenaIndex),
+    /// Node is already on the free list
+    DoubleFree(ArenaIndex),
+    /// Node was freed (and possibly reused) since the handle was taken
+    StaleHandle(ArenaHandle),
+}
+
+impl fmt::Display for ArenaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArenaError::OutOfBounds(index) => write!(f, "arena index {} out of bounds", index),
+            ArenaError::DoubleFree(index) => write!(f, "double free of arena index {}", index),
+            ArenaError::StaleHandle(handle) => write!(
+                f,
+                "stale handle to arena index {} (generation {})",
+                handle.index, handle.generation
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArenaError {}
+
 /// A single order in the book - exactly 64 bytes (one cache line).
 ///
 /// # Memory Layout
@@ -27,7 +78,8 @@ pub type ArenaIndex = u32;
 /// | user_id    | u64     | 24     | 8    |
 /// | next       | u32     | 32     | 4    |
 /// | prev       | u32     | 36     | 4    |
-/// | _reserved  | [u8;24] | 40     | 24   |
+/// | generation | u32     | 40     | 4    |
+/// | _reserved  | [u8;20] | 44     | 20   |
 /// | **Total**  |         |        | 64   |
 ///
 /// Note: There's 4 bytes of padding after `qty` due to u64 alignment.
@@ -59,11 +111,14 @@ pub struct OrderNode {
     /// Index of previous order (enables O(1) cancel)
     pub prev: ArenaIndex,
     
-    // === Reserved Space (28 bytes) ===
+    /// Bumped on every alloc and free; odd while the node is allocated
+    pub generation: u32,
+    
+    // === Reserved Space (20 bytes) ===
     // Future use: timestamp, side enum, flags, etc.
-    // Current layout: 8 + 4 + (4 padding) + 8 + 8 + 4 + 4 = 40 bytes
-    // Need: 64 - 40 = 24 bytes padding
-    pub _reserved: [u8; 24],
+    // Current layout: 8 + 4 + (4 padding) + 8 + 8 + 4 + 4 + 4 = 44 bytes
+    // Need: 64 - 44 = 20 bytes padding
+    pub _reserved: [u8; 20],
 }
 
 // Compile-time assertion: OrderNode must be exactly 64 bytes
@@ -89,7 +144,8 @@ impl OrderNode {
             user_id,
             next: NULL_INDEX,
             prev: NULL_INDEX,
-            _reserved: [0u8; 24],
+            generation: 0,
+            _reserved: [0u8; 20],
         }
     }
     
@@ -103,11 +159,13 @@ impl OrderNode {
             user_id: 0,
             next: NULL_INDEX,
             prev: NULL_INDEX,
-            _reserved: [0u8; 24],
+            generation: 0,
+            _reserved: [0u8; 20],
         }
     }
     
-    /// Reset the node for reuse (when returning to free list)
+    /// Reset the node for reuse (when returning to free list).
+    /// The generation is kept.
     #[inline]
     pub fn reset(&mut self) {
         self.price = 0;
@@ -128,17 +186,50 @@ impl fmt::Debug for OrderNode {
             .field("qty", &self.qty)
             .field("prev", &self.prev)
             .field("next", &self.next)
+            .field("generation", &self.generation)
             .finish()
     }
 }
 
+/// Growth policy for a chunked `Arena`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArenaGrowth {
+    /// Nodes per chunk (rounded up to a power of two)
+    pub chunk_size: u32,
+    /// Hard cap on nodes; allocation fails once it is reached
+    pub max_capacity: u32,
+    /// Allocated-node count that raises a high-watermark warning (0 = off)
+    pub high_watermark: u32,
+}
+
+impl ArenaGrowth {
+    /// Grow in `chunk_size` steps up to `max_capacity`, warning at 90% of it
+    pub fn new(chunk_size: u32, max_capacity: u32) -> Self {
+        Self { chunk_size, max_capacity, high_watermark: max_capacity - max_capacity / 10 }
+    }
+}
+
 /// Pre-allocated memory pool with O(1) allocation and deallocation.
 ///
 /// Uses a free list threaded through the `next` field of unused nodes.
-/// No system calls or locks in the hot path.
+/// No system calls or locks in the hot path (a growable arena allocates
+/// only when it appends a chunk).
 pub struct Arena {
-    /// Contiguous block of pre-allocated nodes
-    nodes: Vec<OrderNode>,
+    /// Blocks of pre-allocated nodes; a fixed arena has exactly one
+    chunks: Vec<NodeChunk>,
+    
+    /// How chunk memory is allocated
+    strategy: AllocStrategy,
+    
+    /// What the strategy achieved across all chunks
+    report: AllocReport,
+    
+    /// log2 of nodes per chunk (32 for a fixed arena, so every index maps
+    /// to chunk 0)
+    chunk_shift: u32,
+    
+    /// Offset-within-chunk mask
+    chunk_mask: u32,
     
     /// Head of the free list (index of first available node)
     free_head: ArenaIndex,
@@ -146,8 +237,20 @@ pub struct Arena {
     /// Number of currently allocated nodes
     allocated_count: u32,
     
-    /// Total capacity
+    /// Nodes currently backed by chunks
     capacity: u32,
+    
+    /// Growth policy (`None` for a fixed arena)
+    growth: Option<ArenaGrowth>,
+    
+    /// Allocation count below which the high watermark re-arms
+    rearm_below: u32,
+    
+    /// Whether reaching the high watermark will raise a warning
+    watermark_armed: bool,
+    
+    /// A warning raised and not yet taken
+    watermark_hit: bool,
 }
 
 impl Arena {
@@ -159,70 +262,167 @@ impl Arena {
     /// # Panics
     /// Panics if capacity exceeds u32::MAX - 1 (we reserve MAX for NULL_INDEX)
     pub fn new(capacity: u32) -> Self {
+        Self::with_strategy(capacity, AllocStrategy::default())
+    }
+    
+    /// Create a fixed arena whose memory is allocated following `strategy`
+    /// (huge pages, mlock, NUMA binding). Options the host does not support
+    /// fall back silently; see `alloc_report`.
+    pub fn with_strategy(capacity: u32, strategy: AllocStrategy) -> Self {
         assert!(capacity < NULL_INDEX, "Capacity must be less than NULL_INDEX");
         
-        // Pre-allocate all nodes
-        let mut nodes = vec![OrderNode::empty(); capacity as usize];
+        let mut arena = Self {
+            chunks: Vec::with_capacity(1),
+            strategy,
+            report: AllocReport::default(),
+            chunk_shift: 32,
+            chunk_mask: u32::MAX,
+            free_head: NULL_INDEX,
+            allocated_count: 0,
+            capacity: 0,
+            growth: None,
+            rearm_below: 0,
+            watermark_armed: false,
+            watermark_hit: false,
+        };
+        arena.push_chunk(capacity);
+        arena
+    }
+    
+    /// Create an arena that starts with one chunk and grows on demand.
+    ///
+    /// # Panics
+    /// Panics if `max_capacity` is not below NULL_INDEX or `chunk_size`
+    /// exceeds 2^31
+    pub fn growable(growth: ArenaGrowth) -> Self {
+        Self::growable_with_strategy(growth, AllocStrategy::default())
+    }
+    
+    /// Create a growable arena whose chunks are allocated following
+    /// `strategy`.
+    pub fn growable_with_strategy(growth: ArenaGrowth, strategy: AllocStrategy) -> Self {
+        assert!(growth.max_capacity < NULL_INDEX, "Capacity must be less than NULL_INDEX");
+        assert!(growth.chunk_size <= 1 << 31, "Chunk size must be at most 2^31");
+        let chunk_size = growth.chunk_size.max(1).next_power_of_two();
+        let max_chunks = growth.max_capacity.div_ceil(chunk_size) as usize;
+        
+        let mut arena = Self {
+            chunks: Vec::with_capacity(max_chunks),
+            strategy,
+            report: AllocReport::default(),
+            chunk_shift: chunk_size.trailing_zeros(),
+            chunk_mask: chunk_size - 1,
+            free_head: NULL_INDEX,
+            allocated_count: 0,
+            capacity: 0,
+            growth: Some(growth),
+            rearm_below: growth.high_watermark - growth.high_watermark / 8,
+            watermark_armed: growth.high_watermark > 0,
+            watermark_hit: false,
+        };
+        arena.push_chunk(chunk_size.min(growth.max_capacity));
+        arena
+    }
+    
+    /// Append a chunk of `len` nodes and thread them onto the free list.
+    fn push_chunk(&mut self, len: u32) {
+        let start = self.capacity;
+        let (mut nodes, report) = NodeChunk::new(len as usize, &self.strategy);
+        self.report = if self.chunks.is_empty() { report } else { self.report.weakest(report) };
         
-        // Thread the free list through all nodes
-        // Each node's `next` points to the following node
-        for i in 0..(capacity - 1) {
-            nodes[i as usize].next = i + 1;
+        // Each node's `next` points to the following node; the last one
+        // continues into whatever was free before
+        for (i, node) in nodes.iter_mut().enumerate() {
+            node.next = start + i as u32 + 1;
         }
-        // Last node points to NULL
-        if capacity > 0 {
-            nodes[(capacity - 1) as usize].next = NULL_INDEX;
+        if let Some(last) = nodes.last_mut() {
+            last.next = self.free_head;
+            self.free_head = start;
         }
         
-        Self {
-            nodes,
-            free_head: if capacity > 0 { 0 } else { NULL_INDEX },
-            allocated_count: 0,
-            capacity,
+        self.chunks.push(nodes);
+        self.capacity += len;
+    }
+    
+    /// Append the next chunk if the growth policy allows it.
+    ///
+    /// # Returns
+    /// `false` for a fixed arena or once the hard cap is reached
+    #[cold]
+    fn grow(&mut self) -> bool {
+        let Some(growth) = self.growth else { return false };
+        if self.capacity >= growth.max_capacity {
+            return false;
         }
+        let chunk_size = self.chunk_mask + 1;
+        self.push_chunk(chunk_size.min(growth.max_capacity - self.capacity));
+        true
+    }
+    
+    /// Chunk and offset of an index
+    #[inline(always)]
+    fn slot(&self, index: ArenaIndex) -> (usize, usize) {
+        ((index as u64 >> self.chunk_shift) as usize, (index & self.chunk_mask) as usize)
     }
     
     /// Allocate a node from the arena.
     ///
-    /// Returns `None` if the arena is full.
+    /// Returns `None` if the arena is full (and cannot grow).
     ///
     /// # Complexity
     /// O(1) - pops from head of free list
     #[inline]
     pub fn alloc(&mut self) -> Option<ArenaIndex> {
-        if self.free_head == NULL_INDEX {
+        if self.free_head == NULL_INDEX && !self.grow() {
             return None;
         }
         
         let index = self.free_head;
-        self.free_head = self.nodes[index as usize].next;
-        self.allocated_count += 1;
+        let node = self.get_mut(index);
+        let next_free = node.next;
         
         // Reset the node for use
-        self.nodes[index as usize].next = NULL_INDEX;
-        self.nodes[index as usize].prev = NULL_INDEX;
+        node.next = NULL_INDEX;
+        node.prev = NULL_INDEX;
+        node.generation = node.generation.wrapping_add(1);
+        
+        self.free_head = next_free;
+        self.allocated_count += 1;
+        if self.watermark_armed && self.growth.is_some_and(|g| self.allocated_count >= g.high_watermark) {
+            self.watermark_armed = false;
+            self.watermark_hit = true;
+        }
         
         Some(index)
     }
     
     /// Free a node back to the arena.
     ///
-    /// # Safety
-    /// The caller must ensure the index was previously allocated and
-    /// has not already been freed (no double-free protection).
+    /// # Panics
+    /// In checked builds, panics if the node is already free. Unchecked
+    /// builds do not detect a double free; use `try_free` with a handle
+    /// where that matters.
     ///
     /// # Complexity
     /// O(1) - pushes to head of free list
     #[inline]
     pub fn free(&mut self, index: ArenaIndex) {
         debug_assert!(index < self.capacity, "Index out of bounds");
-        debug_assert!(self.allocated_count > 0, "Double free detected");
+        if CHECKED {
+            assert!(self.is_live(index), "Double free detected");
+        }
         
         // Reset and push to free list head
-        self.nodes[index as usize].reset();
-        self.nodes[index as usize].next = self.free_head;
+        let free_head = self.free_head;
+        let node = self.get_mut(index);
+        node.reset();
+        node.generation = node.generation.wrapping_add(1);
+        node.next = free_head;
         self.free_head = index;
         self.allocated_count -= 1;
+        if !self.watermark_armed && self.allocated_count < self.rearm_below {
+            self.watermark_armed = true;
+        }
     }
     
     /// Get an immutable reference to a node.
@@ -232,7 +432,8 @@ impl Arena {
     #[inline]
     pub fn get(&self, index: ArenaIndex) -> &OrderNode {
         debug_assert!(index < self.capacity, "Index out of bounds");
-        &self.nodes[index as usize]
+        let (chunk, offset) = self.slot(index);
+        &self.chunks[chunk][offset]
     }
     
     /// Get a mutable reference to a node.
@@ -242,7 +443,8 @@ impl Arena {
     #[inline]
     pub fn get_mut(&mut self, index: ArenaIndex) -> &mut OrderNode {
         debug_assert!(index < self.capacity, "Index out of bounds");
-        &mut self.nodes[index as usize]
+        let (chunk, offset) = self.slot(index);
+        &mut self.chunks[chunk][offset]
     }
     
     /// Returns the number of currently allocated nodes.
@@ -251,22 +453,122 @@ impl Arena {
         self.allocated_count
     }
     
-    /// Returns the total capacity of the arena.
+    /// Returns true if the node at `index` is allocated.
+    #[inline]
+    pub fn is_live(&self, index: ArenaIndex) -> bool {
+        self.get(index).generation & 1 == 1
+    }
+    
+    /// Handle to the node at `index` as of its current generation.
+    #[inline]
+    pub fn handle(&self, index: ArenaIndex) -> ArenaHandle {
+        ArenaHandle { index, generation: self.get(index).generation }
+    }
+    
+    /// Check that a handle still refers to a live node.
+    pub fn check(&self, handle: ArenaHandle) -> Result<ArenaIndex, ArenaError> {
+        if handle.index >= self.capacity {
+            return Err(ArenaError::OutOfBounds(handle.index));
+        }
+        if self.get(handle.index).generation != handle.generation {
+            return Err(ArenaError::StaleHandle(handle));
+        }
+        Ok(handle.index)
+    }
+    
+    /// Index a handle refers to.
+    ///
+    /// # Panics
+    /// In checked builds, panics if the handle is stale
+    #[inline]
+    pub fn resolve(&self, handle: ArenaHandle) -> ArenaIndex {
+        if CHECKED {
+            if let Err(e) = self.check(handle) {
+                panic!("{}", e);
+            }
+        }
+        handle.index
+    }
+    
+    /// Node a handle refers to (see `resolve`).
+    #[inline]
+    pub fn get_handle(&self, handle: ArenaHandle) -> &OrderNode {
+        self.get(self.resolve(handle))
+    }
+    
+    /// Mutable node a handle refers to (see `resolve`).
+    #[inline]
+    pub fn get_handle_mut(&mut self, handle: ArenaHandle) -> &mut OrderNode {
+        let index = self.resolve(handle);
+        self.get_mut(index)
+    }
+    
+    /// Node a handle refers to, or `None` if the handle is stale.
+    #[inline]
+    pub fn try_get(&self, handle: ArenaHandle) -> Option<&OrderNode> {
+        self.check(handle).ok().map(|index| self.get(index))
+    }
+    
+    /// Free the node a handle refers to, rejecting double frees and stale
+    /// handles in every build.
+    pub fn try_free(&mut self, handle: ArenaHandle) -> Result<(), ArenaError> {
+        // Freed since the handle was taken, and not reused: a second free
+        let index = self.check(handle).map_err(|e| match e {
+            ArenaError::StaleHandle(h) if self.get(h.index).generation == h.generation.wrapping_add(1) => {
+                ArenaError::DoubleFree(h.index)
+            }
+            e => e,
+        })?;
+        if !self.is_live(index) {
+            return Err(ArenaError::DoubleFree(index));
+        }
+        self.free(index);
+        Ok(())
+    }
+    
+    /// Returns the number of nodes currently backed by memory.
     #[inline]
     pub fn capacity(&self) -> u32 {
         self.capacity
     }
     
+    /// Returns the most nodes the arena can ever hold (the hard cap for a
+    /// growable arena, otherwise `capacity`).
+    #[inline]
+    pub fn max_capacity(&self) -> u32 {
+        self.growth.map_or(self.capacity, |g| g.max_capacity)
+    }
+    
+    /// Which parts of the allocation strategy took effect.
+    #[inline]
+    pub fn alloc_report(&self) -> AllocReport {
+        self.report
+    }
+    
+    /// Growth policy, if the arena is growable.
+    #[inline]
+    pub fn growth(&self) -> Option<ArenaGrowth> {
+        self.growth
+    }
+    
+    /// Returns true once if allocations reached the high watermark since
+    /// the last call. The warning re-arms when usage falls an eighth below
+    /// the watermark.
+    #[inline]
+    pub fn take_high_watermark(&mut self) -> bool {
+        std::mem::take(&mut self.watermark_hit)
+    }
+    
     /// Returns true if the arena is empty (no allocated nodes).
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.allocated_count == 0
     }
     
-    /// Returns true if the arena is full (no free nodes).
+    /// Returns true if the arena is full (no free nodes and no room to grow).
     #[inline]
     pub fn is_full(&self) -> bool {
-        self.free_head == NULL_INDEX
+        self.free_head == NULL_INDEX && self.capacity >= self.max_capacity()
     }
     
     /// Pre-fault all memory pages (warm-up routine).
@@ -275,7 +577,7 @@ impl Arena {
     /// to physical RAM, preventing page faults in the hot path.
     pub fn warm_up(&mut self) {
         // Touch every node to fault in pages
-        for node in &mut self.nodes {
+        for node in self.chunks.iter_mut().flat_map(|chunk| chunk.iter_mut()) {
             // Volatile write to prevent optimization
             unsafe {
                 std::ptr::write_volatile(&mut node._reserved[0], 0);
@@ -288,6 +590,8 @@ impl fmt::Debug for Arena {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Arena")
             .field("capacity", &self.capacity)
+            .field("max_capacity", &self.max_capacity())
+            .field("alloc", &self.report)
             .field("allocated", &self.allocated_count)
             .field("free_head", &self.free_head)
             .finish()
@@ -378,4 +682,127 @@ mod tests {
         let mut arena = Arena::new(1000);
         arena.warm_up(); // Should not panic
     }
+    
+    #[test]
+    fn test_arena_with_strategy() {
+        let strategy = AllocStrategy { huge_pages: true, mlock: true, numa_node: None };
+        let mut arena = Arena::with_strategy(1000, strategy);
+        assert_eq!(arena.capacity(), 1000);
+        let indices: Vec<_> = (0..1000).map(|_| arena.alloc().unwrap()).collect();
+        assert!(arena.is_full());
+        for idx in indices {
+            arena.free(idx);
+        }
+        assert!(arena.is_empty());
+        
+        let arena = Arena::new(10);
+        assert_eq!(arena.alloc_report(), AllocReport::default());
+    }
+    
+    #[test]
+    fn test_handle_goes_stale_on_free() {
+        let mut arena = Arena::new(2);
+        let idx = arena.alloc().unwrap();
+        let handle = arena.handle(idx);
+        assert!(arena.is_live(idx));
+        assert_eq!(arena.check(handle), Ok(idx));
+        
+        arena.free(idx);
+        assert!(!arena.is_live(idx));
+        assert!(arena.try_get(handle).is_none());
+        
+        // The slot is reused, but the old handle does not alias it
+        assert_eq!(arena.alloc(), Some(idx));
+        assert_eq!(arena.check(handle), Err(ArenaError::StaleHandle(handle)));
+        assert!(arena.try_get(arena.handle(idx)).is_some());
+        assert_eq!(arena.check(ArenaHandle { index: 7, generation: 1 }), Err(ArenaError::OutOfBounds(7)));
+    }
+    
+    #[test]
+    fn test_try_free_detects_double_free() {
+        let mut arena = Arena::new(2);
+        let idx = arena.alloc().unwrap();
+        let handle = arena.handle(idx);
+        
+        assert_eq!(arena.try_free(handle), Ok(()));
+        assert_eq!(arena.try_free(handle), Err(ArenaError::DoubleFree(idx)));
+        assert_eq!(arena.try_free(arena.handle(idx)), Err(ArenaError::DoubleFree(idx)));
+        assert_eq!(arena.allocated(), 0);
+        
+        // Once the slot is reused, the old handle is merely stale
+        arena.alloc().unwrap();
+        assert_eq!(arena.try_free(handle), Err(ArenaError::StaleHandle(handle)));
+        assert_eq!(arena.allocated(), 1);
+    }
+    
+    #[cfg(any(debug_assertions, feature = "checked-arena"))]
+    #[test]
+    #[should_panic(expected = "Double free detected")]
+    fn test_checked_free_panics_on_double_free() {
+        let mut arena = Arena::new(2);
+        let idx = arena.alloc().unwrap();
+        arena.free(idx);
+        arena.free(idx);
+    }
+    
+    #[cfg(any(debug_assertions, feature = "checked-arena"))]
+    #[test]
+    #[should_panic(expected = "stale handle")]
+    fn test_checked_resolve_panics_on_stale_handle() {
+        let mut arena = Arena::new(2);
+        let idx = arena.alloc().unwrap();
+        let handle = arena.handle(idx);
+        arena.free(idx);
+        arena.get_handle(handle);
+    }
+    
+    #[test]
+    fn test_growable_arena_keeps_indices_stable() {
+        let mut arena = Arena::growable(ArenaGrowth { chunk_size: 6, max_capacity: 20, high_watermark: 0 });
+        assert_eq!(arena.capacity(), 8, "chunk size rounds up to a power of two");
+        assert_eq!(arena.max_capacity(), 20);
+        
+        let indices: Vec<_> = (0..20u64).map(|i| {
+            let idx = arena.alloc().expect("Should grow");
+            arena.get_mut(idx).order_id = i;
+            idx
+        }).collect();
+        assert_eq!(arena.capacity(), 20, "last chunk is trimmed to the cap");
+        assert!(arena.is_full());
+        assert!(arena.alloc().is_none(), "Should stop at the hard cap");
+        
+        // Nodes written before later chunks were added are still in place
+        for (i, &idx) in indices.iter().enumerate() {
+            assert_eq!(arena.get(idx).order_id, i as u64);
+        }
+        
+        arena.free(indices[3]);
+        assert!(!arena.is_full());
+        assert_eq!(arena.alloc(), Some(indices[3]));
+    }
+    
+    #[test]
+    fn test_growable_arena_high_watermark() {
+        let mut arena = Arena::growable(ArenaGrowth { chunk_size: 4, max_capacity: 64, high_watermark: 16 });
+        let mut indices: Vec<_> = (0..15).map(|_| arena.alloc().unwrap()).collect();
+        assert!(!arena.take_high_watermark());
+        
+        indices.push(arena.alloc().unwrap());
+        assert!(arena.take_high_watermark());
+        assert!(!arena.take_high_watermark(), "raised once per crossing");
+        
+        // Dipping just below the watermark does not re-arm it
+        arena.free(indices.pop().unwrap());
+        indices.push(arena.alloc().unwrap());
+        assert!(!arena.take_high_watermark());
+        
+        // Falling an eighth below it does
+        for _ in 0..3 {
+            arena.free(indices.pop().unwrap());
+        }
+        while indices.len() < 16 {
+            indices.push(arena.alloc().unwrap());
+        }
+        assert!(arena.take_high_watermark());
+    }
 }
@@ -0,0 +1,266 @@
+//! Arena Memory - Backing storage for arena chunks.
+//!
+//! By default chunks are ordinary heap allocations. On Linux an
+//! `AllocStrategy` can ask for 2MB huge pages, mlocked memory, and binding
+//! to a NUMA node; each is best effort, and `AllocReport` records what
+//! actually took effect. Anything that fails falls back quietly (down to a
+//! plain heap allocation), so the same configuration runs on any host.
+
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+use crate::arena::OrderNode;
+
+/// How arena chunks should be allocated. All options are best effort.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocStrategy {
+    /// Back chunks with 2MB huge pages (reserved hugetlb pages, else
+    /// transparent huge pages)
+    pub huge_pages: bool,
+    /// Lock chunks in RAM so they are never swapped out
+    pub mlock: bool,
+    /// Bind chunk memory to this NUMA node
+    pub numa_node: Option<u32>,
+}
+
+impl AllocStrategy {
+    /// Huge pages and mlock, bound to the NUMA node of the calling thread's
+    /// CPU (call after pinning the engine thread)
+    pub fn pinned() -> Self {
+        Self { huge_pages: true, mlock: true, numa_node: current_numa_node() }
+    }
+}
+
+/// Kind of huge page backing, weakest first
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HugePages {
+    /// Regular pages
+    #[default]
+    None,
+    /// Transparent huge pages requested via `madvise` (the kernel may still
+    /// use regular pages)
+    Transparent,
+    /// Reserved hugetlb pages (`MAP_HUGETLB`)
+    Explicit,
+}
+
+/// What an `AllocStrategy` achieved. For a growable arena this is the
+/// weakest outcome across all chunks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocReport {
+    /// Huge page backing in effect
+    pub huge_pages: HugePages,
+    /// Whether the memory is mlocked
+    pub locked: bool,
+    /// NUMA node the memory is bound to
+    pub numa_node: Option<u32>,
+}
+
+impl AllocReport {
+    /// Combine with the report of another chunk, keeping the weakest outcome
+    pub(crate) fn weakest(self, other: AllocReport) -> AllocReport {
+        AllocReport {
+            huge_pages: self.huge_pages.min(other.huge_pages),
+            locked: self.locked && other.locked,
+            numa_node: if self.numa_node == other.numa_node { self.numa_node } else { None },
+        }
+    }
+}
+
+/// NUMA node of the CPU the calling thread is running on
+#[cfg(target_os = "linux")]
+pub fn current_numa_node() -> Option<u32> {
+    let mut cpu: libc::c_uint = 0;
+    let mut node: libc::c_uint = 0;
+    // SAFETY: getcpu writes two c_uints; the third argument is unused
+    let rc = unsafe {
+        libc::syscall(libc::SYS_getcpu, &mut cpu as *mut libc::c_uint, &mut node as *mut libc::c_uint, std::ptr::null_mut::<libc::c_void>())
+    };
+    (rc == 0).then_some(node)
+}
+
+/// NUMA node of the CPU the calling thread is running on
+#[cfg(not(target_os = "linux"))]
+pub fn current_numa_node() -> Option<u32> {
+    None
+}
+
+/// A fixed block of initialised nodes, on the heap or in its own mapping
+pub(crate) struct NodeChunk {
+    ptr: NonNull<OrderNode>,
+    len: usize,
+    /// Length of the mapping, or 0 for a heap allocation
+    mapped_len: usize,
+}
+
+// SAFETY: NodeChunk uniquely owns its memory, like a Box<[OrderNode]>
+unsafe impl Send for NodeChunk {}
+unsafe impl Sync for NodeChunk {}
+
+impl NodeChunk {
+    /// Allocate `len` empty nodes following `strategy`
+    pub(crate) fn new(len: usize, strategy: &AllocStrategy) -> (NodeChunk, AllocReport) {
+        #[cfg(target_os = "linux")]
+        if len > 0 && *strategy != AllocStrategy::default() {
+            if let Some(mapped) = Self::mapped(len, strategy) {
+                return mapped;
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = strategy;
+
+        let nodes = vec![OrderNode::empty(); len].into_boxed_slice();
+        let ptr = NonNull::new(Box::into_raw(nodes) as *mut OrderNode).expect("Box pointer is non-null");
+        (NodeChunk { ptr, len, mapped_len: 0 }, AllocReport::default())
+    }
+
+    /// Anonymous mapping with the requested policies applied in order:
+    /// NUMA binding and huge pages before the first touch, mlock after it.
+    #[cfg(target_os = "linux")]
+    fn mapped(len: usize, strategy: &AllocStrategy) -> Option<(NodeChunk, AllocReport)> {
+        const HUGE_PAGE: usize = 2 << 20;
+        let bytes = len * std::mem::size_of::<OrderNode>();
+        let mut report = AllocReport::default();
+
+        let map = |size: usize, extra: libc::c_int| {
+            // SAFETY: fresh private anonymous mapping; no existing memory is affected
+            let addr = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    size,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | extra,
+                    -1,
+                    0,
+                )
+            };
+            (addr != libc::MAP_FAILED).then_some(addr)
+        };
+
+        let (addr, mapped_len) = if strategy.huge_pages {
+            let size = bytes.div_ceil(HUGE_PAGE) * HUGE_PAGE;
+            if let Some(addr) = map(size, libc::MAP_HUGETLB) {
+                report.huge_pages = HugePages::Explicit;
+                (addr, size)
+            } else {
+                let addr = map(size, 0)?;
+                // SAFETY: advice on a range we just mapped
+                if unsafe { libc::madvise(addr, size, libc::MADV_HUGEPAGE) } == 0 {
+                    report.huge_pages = HugePages::Transparent;
+                }
+                (addr, size)
+            }
+        } else {
+            (map(bytes, 0)?, bytes)
+        };
+
+        if let Some(node) = strategy.numa_node {
+            if bind_to_node(addr, mapped_len, node) {
+                report.numa_node = Some(node);
+            }
+        }
+
+        // Page-aligned, so suitably aligned for OrderNode
+        let ptr = NonNull::new(addr as *mut OrderNode)?;
+        for i in 0..len {
+            // SAFETY: i < len and the mapping holds at least len nodes
+            unsafe { ptr.as_ptr().add(i).write(OrderNode::empty()) };
+        }
+
+        // SAFETY: locking a range we own
+        if strategy.mlock && unsafe { libc::mlock(addr, mapped_len) } == 0 {
+            report.locked = true;
+        }
+
+        Some((NodeChunk { ptr, len, mapped_len }, report))
+    }
+}
+
+/// Bind a mapping to one NUMA node with `mbind(MPOL_BIND)`
+#[cfg(target_os = "linux")]
+fn bind_to_node(addr: *mut libc::c_void, len: usize, node: u32) -> bool {
+    const MAX_NODES: usize = 1024;
+    let node = node as usize;
+    if node >= MAX_NODES {
+        return false;
+    }
+    let mut mask = [0u64; MAX_NODES / 64];
+    mask[node / 64] |= 1 << (node % 64);
+    // SAFETY: mbind reads MAX_NODES bits from `mask` and only changes the
+    // policy of a range we own
+    let rc = unsafe {
+        libc::syscall(libc::SYS_mbind, addr, len, libc::MPOL_BIND, mask.as_ptr(), MAX_NODES as libc::c_ulong, 0 as libc::c_uint)
+    };
+    rc == 0
+}
+
+impl Deref for NodeChunk {
+    type Target = [OrderNode];
+
+    #[inline(always)]
+    fn deref(&self) -> &[OrderNode] {
+        // SAFETY: ptr points to len initialised nodes owned by self
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for NodeChunk {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut [OrderNode] {
+        // SAFETY: as above, and &mut self guarantees exclusive access
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for NodeChunk {
+    fn drop(&mut self) {
+        if self.mapped_len == 0 {
+            // SAFETY: ptr/len came from Box::into_raw of a boxed slice
+            drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len)) });
+        } else {
+            #[cfg(target_os = "linux")]
+            // SAFETY: unmapping the region mapped in `mapped`; munmap also
+            // drops any mlock
+            unsafe {
+                libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.mapped_len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_strategy_uses_heap() {
+        let (chunk, report) = NodeChunk::new(100, &AllocStrategy::default());
+        assert_eq!(chunk.len(), 100);
+        assert_eq!(report, AllocReport::default());
+        assert!(chunk.iter().all(|n| n.next == crate::arena::NULL_INDEX));
+    }
+
+    #[test]
+    fn test_strategy_falls_back_gracefully() {
+        // Whatever the host allows, the chunk is usable and the report only
+        // claims what was requested
+        let strategy = AllocStrategy { huge_pages: true, mlock: true, numa_node: Some(0) };
+        let (mut chunk, report) = NodeChunk::new(1_000, &strategy);
+        assert_eq!(chunk.len(), 1_000);
+        chunk[999].order_id = 7;
+        assert_eq!(chunk[999].order_id, 7);
+        assert!(report.numa_node.is_none() || report.numa_node == Some(0));
+
+        let (_, plain) = NodeChunk::new(1_000, &AllocStrategy { huge_pages: false, mlock: false, numa_node: None });
+        assert_eq!(plain.huge_pages, HugePages::None);
+        assert!(!plain.locked);
+    }
+
+    #[test]
+    fn test_report_keeps_weakest_outcome() {
+        let strong = AllocReport { huge_pages: HugePages::Explicit, locked: true, numa_node: Some(1) };
+        let weak = AllocReport { huge_pages: HugePages::Transparent, locked: false, numa_node: Some(1) };
+        assert_eq!(strong.weakest(weak), weak);
+        assert_eq!(strong.weakest(AllocReport { numa_node: Some(0), ..strong }).numa_node, None);
+    }
+}
@@ -0,0 +1,121 @@
+//! Book Backend - The order book operations the matching core relies on.
+//!
+//! `MatchingEngine` is generic over `BookBackend`, so level storage and
+//! order lookup can be swapped without touching the matching algorithm.
+//! `OrderBook` (the default) keeps levels in a pooled skip list or a tick
+//! ladder; `VecBook` keeps them in sorted vectors.
+
+use std::ops::RangeBounds;
+
+use crate::arena::{Arena, ArenaHandle, ArenaIndex};
+use crate::command::Side;
+use crate::order_book::OrderInfo;
+use crate::price_level::PriceLevel;
+
+/// Price levels and order lookup for one instrument.
+///
+/// Levels hold their orders as intrusive lists in the shared `Arena`; the
+/// backend only decides how levels are found and ordered, and how order IDs
+/// map to arena slots.
+pub trait BookBackend {
+    /// Returns true if a level can be stored at `price` on `side`
+    #[inline]
+    fn accepts_price(&self, _side: Side, _price: u64) -> bool {
+        true
+    }
+
+    /// Highest bid price
+    fn best_bid(&self) -> Option<u64>;
+
+    /// Lowest ask price
+    fn best_ask(&self) -> Option<u64>;
+
+    /// Best price an incoming order on `side` would match against
+    #[inline]
+    fn best_opposite_price(&self, side: Side) -> Option<u64> {
+        match side {
+            Side::Bid => self.best_ask(),
+            Side::Ask => self.best_bid(),
+        }
+    }
+
+    fn get_level(&self, side: Side, price: u64) -> Option<&PriceLevel>;
+
+    fn get_level_mut(&mut self, side: Side, price: u64) -> Option<&mut PriceLevel>;
+
+    /// Levels on `side` within a price range, ascending by price
+    fn level_range<R: RangeBounds<u64>>(
+        &self,
+        side: Side,
+        range: R,
+    ) -> impl DoubleEndedIterator<Item = (u64, &PriceLevel)> + '_;
+
+    /// Drop the level at `price` (called once it has no orders)
+    fn remove_empty_level(&mut self, side: Side, price: u64);
+
+    /// Append a populated arena node to the back of its level.
+    ///
+    /// # Returns
+    /// `false` if `order_id` is already in the book
+    fn add_order(
+        &mut self,
+        arena: &mut Arena,
+        order_id: u64,
+        user_id: u64,
+        side: Side,
+        price: u64,
+        arena_index: ArenaIndex,
+    ) -> bool;
+
+    /// Unlink an order from its level and forget it, dropping the level if
+    /// it empties. The arena slot is left for the caller to free.
+    fn remove_order(&mut self, arena: &mut Arena, order_id: u64) -> Option<OrderInfo>;
+
+    /// Forget an order already unlinked from its level (filled while matching)
+    fn remove_order_from_map(&mut self, order_id: u64) -> Option<OrderInfo>;
+
+    /// Forget an order already unlinked from its level, located by its arena
+    /// handle (cancel by handle). Backends with a slot-indexed order table
+    /// skip the ID lookup; by default this falls back to
+    /// `remove_order_from_map`.
+    #[inline]
+    fn remove_order_by_handle(&mut self, order_id: u64, _handle: ArenaHandle) -> Option<OrderInfo> {
+        self.remove_order_from_map(order_id)
+    }
+
+    fn get_order(&self, order_id: u64) -> Option<&OrderInfo>;
+
+    fn get_order_mut(&mut self, order_id: u64) -> Option<&mut OrderInfo>;
+
+    #[inline]
+    fn contains_order(&self, order_id: u64) -> bool {
+        self.get_order(order_id).is_some()
+    }
+
+    /// All resting orders, in no particular order
+    fn orders(&self) -> impl Iterator<Item = (u64, &OrderInfo)> + '_;
+
+    fn order_count(&self) -> usize;
+
+    /// A resting order's quantity went down in place (partial fill or reduce)
+    #[inline]
+    fn note_qty_reduced(&mut self, _order_id: u64, _qty: u32) {}
+
+    /// Best ask minus best bid, if both sides are present and uncrossed
+    #[inline]
+    fn spread(&self) -> Option<u64> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) if ask > bid => Some(ask - bid),
+            _ => None,
+        }
+    }
+
+    /// Total quantity and order count at a price (zero if no level)
+    #[inline]
+    fn depth_at(&self, side: Side, price: u64) -> (u64, u32) {
+        self.get_level(side, price).map_or((0, 0), |l| (l.total_qty, l.count))
+    }
+
+    /// Remove all levels and orders (the arena is not touched)
+    fn clear(&mut self);
+}
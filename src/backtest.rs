@@ -0,0 +1,631 @@
+//! Backtester - Strategy simulation against replayed Coinbase L3 data.
+//!
+//! Historical orders and strategy orders share one `MatchingEngine`, so they
+//! compete under the same price-time priority. The historical stream is
+//! applied passively (`open`/`done`/`change`), and every historical `match`
+//! is treated as real aggressor flow:
+//!
+//! - Strategy orders at a better price than the match, or ahead of the
+//!   historical maker in its queue, are filled first.
+//! - The historical maker receives only what is left.
+//!
+//! These fills are booked as executions (`MatchingEngine::process_external_fill`),
+//! so `order_status` reports them as filled quantity, not reductions.
+//!
+//! Strategy orders that cross the book trade against historical liquidity
+//! through the normal matching path. Either way the simulated book drifts
+//! from history; the historical quantities are tracked separately so these
+//! strategy-induced deviations can be reported.
+//!
+//! Strategy commands are executed immediately after the callback that
+//! issued them (zero latency).
+
+use rustc_hash::FxHashMap;
+
+use crate::arena::NULL_INDEX;
+use crate::coinbase::CoinbaseMessage;
+use crate::command::{CancelOrder, OrderType, OutputEvent, PlaceOrder, Side, TradeEvent};
+use crate::matching::MatchingEngine;
+use crate::price_level::PriceLevel;
+
+/// User ID for historical orders
+pub const HISTORICAL_USER_ID: u64 = 0;
+/// User ID for strategy orders
+pub const STRATEGY_USER_ID: u64 = 1;
+/// First strategy order ID; historical IDs must stay below this
+pub const STRATEGY_ID_BASE: u64 = 1 << 62;
+
+/// Which side of the trade the strategy was on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Liquidity {
+    /// Resting order hit by aggressor flow
+    Maker,
+    /// Strategy order crossed the book
+    Taker,
+}
+
+/// A strategy execution
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrategyFill {
+    pub order_id: u64,
+    pub side: Side,
+    pub price: u64,
+    pub qty: u32,
+    pub liquidity: Liquidity,
+    /// Feed time of the fill (nanoseconds)
+    pub time_ns: u64,
+    /// Mid price when the fill happened
+    pub mid_at_fill: Option<f64>,
+    /// Mid price at each configured markout horizon (filled in as time passes)
+    pub mid_after: Vec<Option<f64>>,
+}
+
+impl StrategyFill {
+    /// Signed markout per unit at horizon `h` (positive = favourable)
+    pub fn markout(&self, h: usize) -> Option<f64> {
+        let mid = self.mid_after.get(h).copied().flatten()?;
+        let price = self.price as f64;
+        Some(match self.side {
+            Side::Bid => mid - price,
+            Side::Ask => price - mid,
+        })
+    }
+}
+
+/// A command issued by the strategy
+#[derive(Clone, Copy, Debug)]
+enum StrategyCommand {
+    Place(PlaceOrder),
+    Cancel(u64),
+}
+
+/// What a strategy can see and do from a callback
+pub struct Context<'a> {
+    engine: &'a MatchingEngine,
+    time_ns: u64,
+    position: i64,
+    next_order_id: &'a mut u64,
+    commands: &'a mut Vec<StrategyCommand>,
+}
+
+impl Context<'_> {
+    /// Current feed time (nanoseconds)
+    #[inline]
+    pub fn time_ns(&self) -> u64 {
+        self.time_ns
+    }
+
+    /// Simulated book (historical and strategy orders)
+    #[inline]
+    pub fn engine(&self) -> &MatchingEngine {
+        self.engine
+    }
+
+    #[inline]
+    pub fn best_bid(&self) -> Option<u64> {
+        self.engine.best_bid()
+    }
+
+    #[inline]
+    pub fn best_ask(&self) -> Option<u64> {
+        self.engine.best_ask()
+    }
+
+    /// Net position (positive = long)
+    #[inline]
+    pub fn position(&self) -> i64 {
+        self.position
+    }
+
+    /// Queue an order; returns its ID.
+    pub fn place(&mut self, side: Side, price: u64, qty: u32, order_type: OrderType) -> u64 {
+        let order_id = *self.next_order_id;
+        *self.next_order_id += 1;
+        self.commands.push(StrategyCommand::Place(PlaceOrder {
+            order_id,
+            user_id: STRATEGY_USER_ID,
+            side,
+            price,
+            qty,
+            order_type,
+        }));
+        order_id
+    }
+
+    /// Queue a limit order; returns its ID.
+    pub fn place_limit(&mut self, side: Side, price: u64, qty: u32) -> u64 {
+        self.place(side, price, qty, OrderType::Limit)
+    }
+
+    /// Queue a cancel for a strategy order.
+    pub fn cancel(&mut self, order_id: u64) {
+        self.commands.push(StrategyCommand::Cancel(order_id));
+    }
+}
+
+/// Strategy callbacks
+pub trait Strategy {
+    /// Called after each historical message has been applied.
+    fn on_message(&mut self, msg: &CoinbaseMessage, ctx: &mut Context<'_>);
+
+    /// Called for each strategy fill.
+    fn on_fill(&mut self, _fill: &StrategyFill, _ctx: &mut Context<'_>) {}
+}
+
+/// Backtest options
+#[derive(Clone, Debug)]
+pub struct BacktestConfig {
+    /// Arena capacity (historical + strategy orders)
+    pub capacity: u32,
+    /// Markout horizons in nanoseconds
+    pub markout_horizons_ns: Vec<u64>,
+    /// Finished orders kept for `order_status` (0 = off)
+    pub order_history: usize,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000_000,
+            markout_horizons_ns: vec![1_000_000_000, 10_000_000_000, 60_000_000_000],
+            order_history: 0,
+        }
+    }
+}
+
+/// Summary of a backtest
+#[derive(Clone, Debug, PartialEq)]
+pub struct BacktestReport {
+    pub fills: usize,
+    pub maker_fills: usize,
+    pub taker_fills: usize,
+    /// Total strategy quantity traded
+    pub volume: u64,
+    /// Net position (positive = long)
+    pub position: i64,
+    /// Cash flow from fills, in price x qty units
+    pub cash: i128,
+    /// Cash plus position marked at the last mid
+    pub pnl: Option<f64>,
+    /// Quantity-weighted average markout per horizon (per unit)
+    pub avg_markouts: Vec<Option<f64>>,
+    /// Historical orders whose simulated quantity differs from history
+    pub deviated_orders: usize,
+    /// Total absolute quantity difference across those orders
+    pub deviated_qty: u64,
+}
+
+/// Replays a Coinbase L3 stream with a strategy injected.
+pub struct Backtester {
+    engine: MatchingEngine,
+    strategy: Box<dyn Strategy>,
+    config: BacktestConfig,
+    /// Historical remaining quantity per resting historical order
+    historical: FxHashMap<u64, u32>,
+    fills: Vec<StrategyFill>,
+    /// Per horizon: index of the first fill still awaiting its markout
+    markout_cursor: Vec<usize>,
+    position: i64,
+    cash: i128,
+    time_ns: u64,
+    next_order_id: u64,
+    commands: Vec<StrategyCommand>,
+    events: Vec<OutputEvent>,
+}
+
+impl Backtester {
+    pub fn new(strategy: Box<dyn Strategy>, config: BacktestConfig) -> Self {
+        let mut engine = MatchingEngine::new(config.capacity);
+        engine.set_history_capacity(config.order_history);
+        Self {
+            engine,
+            strategy,
+            markout_cursor: vec![0; config.markout_horizons_ns.len()],
+            config,
+            historical: FxHashMap::default(),
+            fills: Vec::new(),
+            position: 0,
+            cash: 0,
+            time_ns: 0,
+            next_order_id: STRATEGY_ID_BASE,
+            commands: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Returns true if an order ID belongs to the strategy
+    #[inline]
+    pub fn is_strategy(order_id: u64) -> bool {
+        order_id >= STRATEGY_ID_BASE
+    }
+
+    /// Simulated book
+    #[inline]
+    pub fn engine(&self) -> &MatchingEngine {
+        &self.engine
+    }
+
+    /// All strategy fills so far
+    #[inline]
+    pub fn fills(&self) -> &[StrategyFill] {
+        &self.fills
+    }
+
+    /// Historical remaining quantity of a resting historical order
+    #[inline]
+    pub fn historical_qty(&self, order_id: u64) -> Option<u32> {
+        self.historical.get(&order_id).copied()
+    }
+
+    /// Mid price of the simulated book
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.engine.best_bid()? as f64 + self.engine.best_ask()? as f64) / 2.0)
+    }
+
+    /// Historical orders whose simulated quantity differs from history,
+    /// as `(order_id, historical_qty, simulated_qty)`.
+    pub fn deviations(&self) -> impl Iterator<Item = (u64, u32, u32)> + '_ {
+        self.historical.iter().filter_map(|(&id, &hist)| {
+            let sim = self.sim_qty(id);
+            (sim != hist).then_some((id, hist, sim))
+        })
+    }
+
+    /// Apply one historical message at `time_ns`, then run the strategy.
+    pub fn process(&mut self, time_ns: u64, msg: &CoinbaseMessage) {
+        self.time_ns = time_ns;
+        self.record_markouts();
+
+        match *msg {
+            CoinbaseMessage::Received { .. } => {}
+            CoinbaseMessage::Open { order_id, side, price, qty } => {
+                self.historical.insert(order_id, qty);
+                // Crossing strategy orders are filled by this order as it arrives
+                self.events.clear();
+                self.engine.process_place(PlaceOrder::limit(order_id, HISTORICAL_USER_ID, side, price, qty), &mut self.events);
+                self.collect_fills();
+            }
+            CoinbaseMessage::Done { order_id, .. } => {
+                self.historical.remove(&order_id);
+                if self.engine.book.contains_order(order_id) {
+                    self.events.clear();
+                    self.engine.process_cancel(CancelOrder { order_id }, &mut self.events);
+                }
+            }
+            CoinbaseMessage::Change { order_id, new_qty, .. } => {
+                if let Some(hist) = self.historical.get_mut(&order_id) {
+                    let reduce_by = hist.saturating_sub(new_qty);
+                    *hist = new_qty;
+                    self.reduce(order_id, reduce_by);
+                }
+            }
+            CoinbaseMessage::Match { maker_order_id, price, qty, .. } => {
+                if let Some(hist) = self.historical.get_mut(&maker_order_id) {
+                    *hist = hist.saturating_sub(qty);
+                }
+                self.apply_aggressor(maker_order_id, price, qty);
+            }
+        }
+
+        let mut ctx = Context {
+            engine: &self.engine,
+            time_ns: self.time_ns,
+            position: self.position,
+            next_order_id: &mut self.next_order_id,
+            commands: &mut self.commands,
+        };
+        self.strategy.on_message(msg, &mut ctx);
+        self.run_commands();
+    }
+
+    /// Summarize the run so far.
+    pub fn report(&self) -> BacktestReport {
+        let mut avg_markouts = Vec::with_capacity(self.config.markout_horizons_ns.len());
+        for h in 0..self.config.markout_horizons_ns.len() {
+            let (sum, qty) = self.fills.iter()
+                .filter_map(|f| f.markout(h).map(|m| (m * f.qty as f64, f.qty as f64)))
+                .fold((0.0, 0.0), |(s, q), (m, fq)| (s + m, q + fq));
+            avg_markouts.push((qty > 0.0).then(|| sum / qty));
+        }
+
+        let (deviated_orders, deviated_qty) = self.deviations()
+            .fold((0, 0u64), |(n, q), (_, hist, sim)| (n + 1, q + hist.abs_diff(sim) as u64));
+
+        BacktestReport {
+            fills: self.fills.len(),
+            maker_fills: self.fills.iter().filter(|f| f.liquidity == Liquidity::Maker).count(),
+            taker_fills: self.fills.iter().filter(|f| f.liquidity == Liquidity::Taker).count(),
+            volume: self.fills.iter().map(|f| f.qty as u64).sum(),
+            position: self.position,
+            cash: self.cash,
+            pnl: self.mid().map(|mid| self.cash as f64 + self.position as f64 * mid),
+            avg_markouts,
+            deviated_orders,
+            deviated_qty,
+        }
+    }
+
+    // ========================================================================
+    // Internals
+    // ========================================================================
+
+    /// Route a historical aggressor of `qty` at `price` against `maker`.
+    ///
+    /// Strategy orders at better prices, or ahead of the maker at its price,
+    /// are filled first; the maker gets the remainder.
+    fn apply_aggressor(&mut self, maker_order_id: u64, price: u64, qty: u32) {
+        let Some(maker) = self.engine.book.get_order(maker_order_id).copied() else {
+            return;
+        };
+        let maker_side = maker.side;
+
+        // Collect strategy orders ahead of the maker, best price first
+        let mut ahead = Vec::new();
+        let levels: Box<dyn Iterator<Item = (u64, &PriceLevel)>> = match maker_side {
+            Side::Bid => Box::new(self.engine.book.bids.range(price..).rev()),
+            Side::Ask => Box::new(self.engine.book.asks.range(..=price)),
+        };
+        'levels: for (level_price, level) in levels {
+            let mut idx = level.head;
+            while idx != NULL_INDEX {
+                let node = self.engine.arena.get(idx);
+                if node.order_id == maker_order_id {
+                    break 'levels;
+                }
+                if Self::is_strategy(node.order_id) {
+                    ahead.push((node.order_id, level_price, node.qty));
+                }
+                idx = node.next;
+            }
+        }
+
+        let mut remaining = qty;
+        for (order_id, fill_price, available) in ahead {
+            if remaining == 0 {
+                break;
+            }
+            let fill_qty = available.min(remaining);
+            remaining -= fill_qty;
+            self.fill(order_id, fill_qty);
+            self.record_fill(order_id, maker_side, fill_price, fill_qty, Liquidity::Maker);
+        }
+
+        self.fill(maker_order_id, remaining);
+        self.run_commands();
+    }
+
+    fn sim_qty(&self, order_id: u64) -> u32 {
+        self.engine.book.get_order(order_id).map_or(0, |info| self.engine.arena.get_handle(info.handle).qty)
+    }
+
+    fn reduce(&mut self, order_id: u64, by: u32) {
+        if by > 0 {
+            self.events.clear();
+            self.engine.process_reduce(order_id, by, &mut self.events);
+        }
+    }
+
+    /// Execute a resting order against historical aggressor flow
+    fn fill(&mut self, order_id: u64, qty: u32) {
+        if qty > 0 {
+            self.events.clear();
+            self.engine.process_external_fill(order_id, qty, &mut self.events);
+        }
+    }
+
+    /// Turn strategy trades in `self.events` into fills
+    fn collect_fills(&mut self) {
+        let trades: Vec<TradeEvent> = self.events.iter()
+            .filter_map(|e| if let OutputEvent::Trade(t) = e { Some(*t) } else { None })
+            .collect();
+        for t in trades {
+            if Self::is_strategy(t.maker_order_id) {
+                self.record_fill(t.maker_order_id, t.taker_side.opposite(), t.price, t.qty, Liquidity::Maker);
+            }
+            if Self::is_strategy(t.taker_order_id) {
+                self.record_fill(t.taker_order_id, t.taker_side, t.price, t.qty, Liquidity::Taker);
+            }
+        }
+        self.run_commands();
+    }
+
+    fn record_fill(&mut self, order_id: u64, side: Side, price: u64, qty: u32, liquidity: Liquidity) {
+        let notional = price as i128 * qty as i128;
+        match side {
+            Side::Bid => {
+                self.position += qty as i64;
+                self.cash -= notional;
+            }
+            Side::Ask => {
+                self.position -= qty as i64;
+                self.cash += notional;
+            }
+        }
+
+        let fill = StrategyFill {
+            order_id,
+            side,
+            price,
+            qty,
+            liquidity,
+            time_ns: self.time_ns,
+            mid_at_fill: self.mid(),
+            mid_after: vec![None; self.config.markout_horizons_ns.len()],
+        };
+
+        let mut ctx = Context {
+            engine: &self.engine,
+            time_ns: self.time_ns,
+            position: self.position,
+            next_order_id: &mut self.next_order_id,
+            commands: &mut self.commands,
+        };
+        self.strategy.on_fill(&fill, &mut ctx);
+        self.fills.push(fill);
+    }
+
+    /// Execute queued strategy commands (and any they trigger)
+    fn run_commands(&mut self) {
+        while !self.commands.is_empty() {
+            let commands = std::mem::take(&mut self.commands);
+            for cmd in commands {
+                self.events.clear();
+                match cmd {
+                    StrategyCommand::Place(order) => self.engine.process_place(order, &mut self.events),
+                    StrategyCommand::Cancel(order_id) => {
+                        self.engine.process_cancel(CancelOrder { order_id }, &mut self.events)
+                    }
+                }
+                self.collect_fills();
+            }
+        }
+    }
+
+    /// Fill in mids for fills whose markout horizon has passed
+    fn record_markouts(&mut self) {
+        let mid = self.mid();
+        for (h, &horizon) in self.config.markout_horizons_ns.iter().enumerate() {
+            let cursor = &mut self.markout_cursor[h];
+            while let Some(fill) = self.fills.get_mut(*cursor) {
+                if fill.time_ns.saturating_add(horizon) > self.time_ns {
+                    break;
+                }
+                fill.mid_after[h] = mid;
+                *cursor += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coinbase::DoneReason;
+    use crate::order_status::OrderState;
+
+    /// Places scripted orders when the N-th message arrives
+    #[derive(Default)]
+    struct Scripted {
+        at: Vec<(usize, Side, u64, u32, OrderType)>,
+        seen: usize,
+        fills: Vec<(u64, u32)>,
+    }
+
+    impl Strategy for Scripted {
+        fn on_message(&mut self, _msg: &CoinbaseMessage, ctx: &mut Context<'_>) {
+            for &(n, side, price, qty, order_type) in &self.at {
+                if n == self.seen {
+                    ctx.place(side, price, qty, order_type);
+                }
+            }
+            self.seen += 1;
+        }
+
+        fn on_fill(&mut self, fill: &StrategyFill, _ctx: &mut Context<'_>) {
+            self.fills.push((fill.price, fill.qty));
+        }
+    }
+
+    fn open(order_id: u64, side: Side, price: u64, qty: u32) -> CoinbaseMessage {
+        CoinbaseMessage::Open { order_id, side, price, qty }
+    }
+
+    fn matched(maker_order_id: u64, price: u64, qty: u32) -> CoinbaseMessage {
+        CoinbaseMessage::Match { maker_order_id, taker_order_id: 99, trade_id: None, price, qty }
+    }
+
+    fn config() -> BacktestConfig {
+        BacktestConfig { capacity: 1000, markout_horizons_ns: vec![10], order_history: 16 }
+    }
+
+    #[test]
+    fn test_queue_position_against_historical_orders() {
+        let strategy = Scripted { at: vec![(0, Side::Bid, 100, 5, OrderType::Limit)], ..Default::default() };
+        let mut bt = Backtester::new(Box::new(strategy), config());
+
+        // Historical bid 1 is ahead of us, bid 2 behind
+        bt.process(0, &open(1, Side::Bid, 100, 10));
+        bt.process(1, &open(2, Side::Bid, 100, 10));
+        bt.process(2, &open(3, Side::Ask, 102, 10));
+
+        // Aggressor hits bid 1: we are behind it, no fill
+        bt.process(3, &matched(1, 100, 4));
+        assert!(bt.fills().is_empty());
+        assert_eq!(bt.historical_qty(1), Some(6));
+
+        // Aggressor hits bid 2: we are ahead of it and absorb the flow
+        bt.process(4, &matched(2, 100, 7));
+        assert_eq!(bt.fills().len(), 1);
+        assert_eq!((bt.fills()[0].price, bt.fills()[0].qty, bt.fills()[0].liquidity), (100, 5, Liquidity::Maker));
+
+        // Bid 2 only lost the 2 we left; history says it lost 7
+        assert_eq!(bt.historical_qty(2), Some(3));
+        let deviations: Vec<_> = bt.deviations().collect();
+        assert_eq!(deviations, vec![(2, 3, 8)]);
+
+        bt.process(5, &CoinbaseMessage::Done { order_id: 2, side: Side::Bid, reason: DoneReason::Canceled, remaining_qty: None });
+        assert_eq!(bt.deviations().count(), 0);
+    }
+
+    #[test]
+    fn test_taker_fill_markout_and_pnl() {
+        let strategy = Scripted { at: vec![(2, Side::Bid, 101, 4, OrderType::IOC)], ..Default::default() };
+        let mut bt = Backtester::new(Box::new(strategy), config());
+
+        bt.process(0, &open(1, Side::Bid, 99, 10));
+        bt.process(1, &open(2, Side::Ask, 101, 10));
+        // Strategy lifts 4 @ 101 after the third message
+        bt.process(2, &open(3, Side::Ask, 102, 10));
+        assert_eq!(bt.fills()[0].liquidity, Liquidity::Taker);
+        assert_eq!(bt.historical_qty(2), Some(10));
+
+        // Book moves up; markout horizon (10ns) passes
+        bt.process(5, &open(4, Side::Bid, 100, 10));
+        bt.process(20, &CoinbaseMessage::Done { order_id: 1, side: Side::Bid, reason: DoneReason::Canceled, remaining_qty: None });
+
+        let report = bt.report();
+        assert_eq!((report.fills, report.taker_fills, report.volume, report.position), (1, 1, 4, 4));
+        assert_eq!(report.cash, -404);
+        // Mid when the horizon passed: (100 + 101) / 2 = 100.5, bought at 101
+        assert_eq!(report.avg_markouts, vec![Some(-0.5)]);
+        assert_eq!(report.pnl, Some(-404.0 + 4.0 * 100.5));
+        assert_eq!(report.deviated_orders, 1);
+    }
+
+    #[test]
+    fn test_better_priced_strategy_order_fills_first() {
+        let strategy = Scripted { at: vec![(1, Side::Ask, 101, 3, OrderType::Limit)], ..Default::default() };
+        let mut bt = Backtester::new(Box::new(strategy), config());
+
+        bt.process(0, &open(1, Side::Ask, 102, 10));
+        bt.process(1, &open(2, Side::Bid, 99, 10));
+        bt.process(2, &matched(1, 102, 5));
+
+        assert_eq!(bt.fills()[0].price, 101);
+        assert_eq!(bt.fills()[0].qty, 3);
+        // The historical maker got the other 2
+        assert_eq!(bt.engine().book.get_level(Side::Ask, 102).unwrap().total_qty, 8);
+    }
+
+    #[test]
+    fn test_maker_fills_reported_as_executions() {
+        let strategy = Scripted { at: vec![(0, Side::Ask, 100, 5, OrderType::Limit)], ..Default::default() };
+        let mut bt = Backtester::new(Box::new(strategy), config());
+        let order_id = STRATEGY_ID_BASE;
+
+        bt.process(0, &open(1, Side::Ask, 101, 10));
+        bt.process(1, &matched(1, 101, 2));
+        bt.process(2, &matched(1, 101, 2));
+        let status = bt.engine().order_status(order_id).unwrap();
+        assert_eq!((status.filled_qty, status.reduced_qty, status.remaining_qty), (4, 0, 1));
+        assert_eq!(status.state, OrderState::PartiallyFilled);
+
+        bt.process(3, &matched(1, 101, 3));
+        let status = bt.engine().order_status(order_id).unwrap();
+        assert_eq!((status.filled_qty, status.reduced_qty, status.remaining_qty), (5, 0, 0));
+        assert_eq!(status.state, OrderState::Filled);
+
+        // The historical maker's share is an execution too
+        assert_eq!(bt.engine().order_status(1).unwrap().filled_qty, 2);
+    }
+}
@@ -1,12 +1,16 @@
-use flash_lob::{Engine, Command, PlaceOrder, Side, OrderType};
+use flash_lob::{Engine, EngineConfig, Command, PlaceOrder, Side, OrderType, Arena, AllocStrategy, OrderBook, LevelStorage};
+use flash_lob::matching::MatchingEngine;
 use hdrhistogram::Histogram;
 use std::time::Instant;
 
 fn main() {
     println!("Preparing Latency Benchmark...");
     
-    // Setup
-    let mut engine = Engine::new(100_000);
+    // Setup: huge pages + mlock on this thread's NUMA node, where available
+    let arena = Arena::with_strategy(100_000, AllocStrategy::pinned());
+    println!("Arena memory: {:?}", arena.alloc_report());
+    let book = OrderBook::with_storage(LevelStorage::Tree, 100_000);
+    let mut engine = Engine::with_matcher(MatchingEngine::with_arena(arena, book), EngineConfig::default());
     engine.warm_up();
     
     let mut histogram = Histogram::<u64>::new_with_bounds(1, 100_000, 3).unwrap();
@@ -37,7 +41,7 @@ fn main() {
         // Actually, reusing commands with same ID might be weird if checking for duplicates,
         // but engine doesn't check duplicates strictly in this microbenchmark (it's HashMap insert).
         // To be safe, let's just run some dummy commands.
-        let warm_cmd = cmd.clone();
+        let warm_cmd = *cmd;
         std::hint::black_box(engine.process_command(warm_cmd));
     }
     
@@ -62,7 +66,7 @@ fn main() {
         // `PlaceOrder` is Copy? No, it has `OrderType` which is Copy.
         // `PlaceOrder` should be `Copy` ideally. Let's assume Clone is cheap (memcpy).
         
-        let exec_cmd = cmd.clone();
+        let exec_cmd = cmd;
         
         // Critical measurement section
         let start = Instant::now();
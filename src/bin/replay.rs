@@ -2,60 +2,125 @@ use std::error::Error;
 use std::fs::File;
 use std::path::PathBuf;
 use clap::Parser;
-use flash_lob::{Engine, Command, PlaceOrder, CancelOrder, OutputEvent, OrderType};
-use flash_lob::coinbase::{TardisL3Row, CoinbaseMessage, DoneReason};
+use flash_lob::{Engine, Side};
+use flash_lob::coinbase::{TardisL3Row, L3Reconstructor, L3Replayer, Scale, ScaleTable};
+use flash_lob::interner::OrderIdInterner;
+use flash_lob::lobster::{BookReader, CommandConverter, MessageReader};
+use flash_lob::l2_replay::{ShrinkPolicy, SyntheticBook, TardisL2Row};
 
 #[derive(Parser)]
 #[command(author, version, about = "Flash-LOB Replay Validator")]
 struct Args {
-    /// Input CSV file (Tardis.dev L3 format)
+    /// Input CSV file (Tardis.dev L3 format, or a LOBSTER message file with --lobster)
     #[arg(short, long)]
     input: PathBuf,
 
+    /// Treat the input as a LOBSTER message file
+    #[arg(long)]
+    lobster: bool,
+
+    /// Treat the input as a Tardis incremental_book_L2 file and build a
+    /// synthetic order book from it
+    #[arg(long, conflicts_with = "lobster")]
+    l2: bool,
+
+    /// LOBSTER orderbook file to check the engine's top-N book against
+    #[arg(long, requires = "lobster")]
+    lobster_book: Option<PathBuf>,
+
     /// Max orders to replay
     #[arg(long)]
     limit: Option<usize>,
+
+    /// Build the book passively from open/done/change/match and diff
+    /// predicted fills against every reported match
+    #[arg(long)]
+    reconstruct: bool,
+
+    /// Default price multiplier (e.g. 100 for cents)
+    #[arg(long, default_value_t = 100)]
+    price_scale: u64,
+
+    /// Default size multiplier (e.g. 100000000 for satoshis)
+    #[arg(long, default_value_t = 100_000_000)]
+    size_scale: u64,
+
+    /// Per-product override as SYMBOL=PRICE:SIZE (repeatable)
+    #[arg(long = "scale", value_parser = parse_product_scale)]
+    scales: Vec<(String, Scale)>,
+
+    /// Restore the UUID -> order ID mapping from a checkpoint before replaying
+    #[arg(long)]
+    ids_in: Option<PathBuf>,
+
+    /// Save the UUID -> order ID mapping after replaying
+    #[arg(long)]
+    ids_out: Option<PathBuf>,
+}
+
+fn parse_product_scale(s: &str) -> Result<(String, Scale), String> {
+    let (symbol, scale) = s.split_once('=').ok_or_else(|| format!("expected SYMBOL=PRICE:SIZE, got {:?}", s))?;
+    Ok((symbol.to_string(), scale.parse()?))
+}
+
+impl Args {
+    fn scale_table(&self) -> ScaleTable {
+        let mut table = ScaleTable::new(Scale { price: self.price_scale, size: self.size_scale });
+        for (symbol, scale) in &self.scales {
+            table.insert(symbol.clone(), *scale);
+        }
+        table
+    }
+
+    fn load_ids(&self) -> Result<OrderIdInterner, Box<dyn Error>> {
+        match &self.ids_in {
+            Some(path) => {
+                let ids = OrderIdInterner::load(File::open(path)?)?;
+                println!("Restored {} live order IDs from {:?}", ids.len(), path);
+                Ok(ids)
+            }
+            None => Ok(OrderIdInterner::new()),
+        }
+    }
+
+    fn save_ids(&self, ids: &OrderIdInterner) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = &self.ids_out {
+            ids.save(File::create(path)?)?;
+            println!("Saved {} live order IDs to {:?}", ids.len(), path);
+        }
+        Ok(())
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
+
+    if args.lobster {
+        return replay_lobster(&args);
+    }
+    if args.l2 {
+        return replay_l2(&args);
+    }
+    if args.reconstruct {
+        return reconstruct(&args);
+    }
     
     println!("Initializing Replay Engine...");
-    let mut engine = Engine::new(10_000_000); // Large capacity for replay
+    let mut replay = L3Replayer::new(10_000_000); // Large capacity for replay
+    let scales = args.scale_table();
+    let mut ids = args.load_ids()?;
     
-    let file = File::open(args.input)?;
+    let file = File::open(&args.input)?;
     let mut rdr = csv::Reader::from_reader(file);
     
     let mut messages_processed = 0;
-    let mut matches_validated = 0;
-    let mut matches_missed = 0;
+    let mut parse_errors = 0;
     
     println!("Starting replay from {:?}...", rdr);
     
-    // We need to queue expected matches to verify them?
-    // Or simpler: As we perform updates, we just check consistency.
-    // Ideally, for exact replay: 
-    // 1. We process 'received' -> PlaceOrder
-    // 2. We capture engine output trades
-    // 3. We look ahead in CSV for 'match' events? 
-    // 
-    // Strategy: We just feed input and print stats for now. 
-    // Validation is complex because 'match' row in CSV might come *after* 'received' row.
-    // But engine produces trade *immediately* upon 'PlaceOrder'.
-    // So if engine output says "Trade", we expect a "match" row in CSV shortly.
-    
-    // Simplification for V1:
-    // Just ensure no errors and final book state (if we had a snapshot to check against).
-    // For "Correctness" demo: We will count how many explicit "match" messages from CSV 
-    // align with our internal trades.
-    
-    let mut pending_trades = 0; // Trades engine generated but haven't seen 'match' in CSV yet?
-    // Actually, usually CSV order is time-ordered.
-    // 'received' (Taker) arrives.
-    // 'match' (Trade) follows immediately.
-    // So engine trades should correlate with 'match' rows.
-    
-    for result in rdr.deserialize() {
+    // The engine matches each 'received' order itself; the feed's 'match'
+    // rows that follow are counted against the trades it produced.
+    for (row_idx, result) in rdr.deserialize().enumerate() {
         if let Some(limit) = args.limit {
             if messages_processed >= limit {
                 break;
@@ -63,61 +128,213 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
         
         let row: TardisL3Row = result?;
-        // Convert prices to cents (x100) or satoshis? Let's use x100 (cents) for USD pairs.
-        let msg = row.to_message(100); 
-        
-        if let Some(msg) = msg {
-            match msg {
-                CoinbaseMessage::Received { order_id, side, price, qty } => {
-                    // Place the order
-                    // Default to Limit. If it crosses, it matches.
-                    let cmd = Command::Place(PlaceOrder {
-                        order_id,
-                        user_id: 1, // Dummy user
-                        side,
-                        price, 
-                        qty,
-                        order_type: OrderType::Limit,
-                    });
-                    
-                    let events = engine.process_command(cmd);
-                    
-                    // Count trades generated by engine
-                    let engine_trades = events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count();
-                    pending_trades += engine_trades;
-                },
-                CoinbaseMessage::Done { order_id, reason, .. } => {
-                    if reason == DoneReason::Canceled {
-                        engine.process_command(Command::Cancel(CancelOrder { order_id }));
-                    }
-                },
-                CoinbaseMessage::Match { .. } => {
-                    // This is the exchange confirming a match.
-                    // If our engine was correct, we should have generated a trade already.
-                    if pending_trades > 0 {
-                        matches_validated += 1;
-                        pending_trades -= 1;
-                    } else {
-                        // We missed a trade? Or maybe timing issue?
-                        matches_missed += 1;
-                    }
-                },
-                _ => {}
+        match row.to_message(&scales, &mut ids) {
+            Ok(msg) => {
+                replay.apply(&msg);
+                messages_processed += 1;
+            }
+            Err(e) => {
+                println!("[row {}] parse error: {}", row_idx + 2, e);
+                parse_errors += 1;
             }
-            messages_processed += 1;
         }
         
         if messages_processed % 100_000 == 0 {
-            println!("Processed {} messages. Validated Matches: {}", messages_processed, matches_validated);
+            println!("Processed {} messages. Validated Matches: {}", messages_processed, replay.matches_validated);
         }
     }
     
     println!("\n=== Replay Complete ===");
     println!("Total Messages: {}", messages_processed);
-    println!("Matches Validated: {}", matches_validated);
-    println!("Matches Missed (Lag/Diff): {}", matches_missed);
-    println!("Remaining Pending Engine Trades: {}", pending_trades); 
-    println!("Final Book Depth: {} bids, {} asks", engine.matcher.book.bids.len(), engine.matcher.book.asks.len());
+    println!("Matches Validated: {}", replay.matches_validated);
+    println!("Matches Missed (Lag/Diff): {}", replay.matches_missed);
+    println!("Filled in Feed, Resting in Engine: {}", replay.unfilled_dones);
+    println!("Parse Errors: {}", parse_errors);
+    println!("Remaining Pending Engine Trades: {}", replay.pending_trades); 
+    let book = &replay.engine().matcher.book;
+    println!("Final Book Depth: {} bids, {} asks", book.bids.len(), book.asks.len());
     
+    args.save_ids(&ids)
+}
+
+/// Reconstruction mode: the book follows the feed exactly, and each
+/// reported match is checked against what our matching rules predict.
+fn reconstruct(args: &Args) -> Result<(), Box<dyn Error>> {
+    println!("Initializing Reconstruction...");
+    let mut recon = L3Reconstructor::new(10_000_000);
+    let scales = args.scale_table();
+    let mut ids = args.load_ids()?;
+    let mut rdr = csv::Reader::from_reader(File::open(&args.input)?);
+
+    let mut messages_processed = 0;
+    let mut parse_errors = 0;
+    let mut divergences = Vec::new();
+
+    for (row_idx, result) in rdr.deserialize().enumerate() {
+        if args.limit.is_some_and(|limit| messages_processed >= limit) {
+            break;
+        }
+
+        let row: TardisL3Row = result?;
+        let msg = match row.to_message(&scales, &mut ids) {
+            Ok(msg) => msg,
+            Err(e) => {
+                println!("[row {}] parse error: {}", row_idx + 2, e);
+                parse_errors += 1;
+                continue;
+            }
+        };
+
+        divergences.clear();
+        recon.apply(&msg, &mut divergences);
+        messages_processed += 1;
+
+        for divergence in &divergences {
+            let engine = recon.engine();
+            println!(
+                "[row {}] {} | {} | msg {:?} | book bid {:?} ask {:?}",
+                row_idx + 2, // 1-based, after header
+                row.timestamp.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                divergence,
+                msg,
+                engine.best_bid(),
+                engine.best_ask(),
+            );
+        }
+
+        if messages_processed % 100_000 == 0 {
+            println!(
+                "Processed {} messages. Validated: {} Divergences: {}",
+                messages_processed, recon.matches_validated, recon.divergences
+            );
+        }
+    }
+
+    divergences.clear();
+    recon.finish(&mut divergences);
+    for divergence in &divergences {
+        println!("[end of input] {}", divergence);
+    }
+
+    let total = recon.matches_validated + recon.divergences;
+    println!("\n=== Reconstruction Complete ===");
+    println!("Total Messages: {}", messages_processed);
+    println!("Matches Validated: {}", recon.matches_validated);
+    println!("Divergences: {}", recon.divergences);
+    println!("Parse Errors: {}", parse_errors);
+    if total > 0 {
+        println!("Agreement: {:.2}%", recon.matches_validated as f64 * 100.0 / total as f64);
+    }
+    let book = &recon.engine().matcher.book;
+    println!("Final Book Depth: {} bids, {} asks", book.bids.len(), book.asks.len());
+
+    args.save_ids(&ids)
+}
+
+/// LOBSTER mode: convert messages to commands and, if an orderbook file is
+/// given, check the engine's book against each row.
+fn replay_lobster(args: &Args) -> Result<(), Box<dyn Error>> {
+    println!("Initializing LOBSTER Replay...");
+    let mut engine = Engine::new(10_000_000);
+    let mut converter = CommandConverter::new();
+    let mut books = args.lobster_book.as_ref().map(|p| File::open(p).map(BookReader::new)).transpose()?;
+
+    let mut messages_processed = 0;
+    let mut commands = 0;
+    let mut book_mismatches = 0;
+
+    for (row_idx, msg) in MessageReader::new(File::open(&args.input)?).enumerate() {
+        if args.limit.is_some_and(|limit| messages_processed >= limit) {
+            break;
+        }
+
+        let msg = msg?;
+        if let Some(action) = converter.convert(&msg) {
+            action.apply(&mut engine);
+            commands += 1;
+        }
+        messages_processed += 1;
+
+        let Some(expected) = books.as_mut().and_then(|b| b.next()).transpose()? else {
+            continue;
+        };
+        let levels = expected.asks.len().max(expected.bids.len());
+        let book = &engine.matcher.book;
+        let asks: Vec<_> = book.top_levels(Side::Ask, levels).map(|l| (l.price, l.qty)).collect();
+        let bids: Vec<_> = book.top_levels(Side::Bid, levels).map(|l| (l.price, l.qty)).collect();
+        if asks != expected.asks || bids != expected.bids {
+            book_mismatches += 1;
+            println!(
+                "[row {}] book mismatch after {:?}\n  expected asks {:?} bids {:?}\n  engine   asks {:?} bids {:?}",
+                row_idx + 1, msg, expected.asks, expected.bids, asks, bids
+            );
+        }
+    }
+
+    println!("\n=== LOBSTER Replay Complete ===");
+    println!("Total Messages: {}", messages_processed);
+    println!("Commands Replayed: {}", commands);
+    if books.is_some() {
+        println!("Book Mismatches: {}", book_mismatches);
+    }
+    println!("Final Book Depth: {} bids, {} asks", engine.matcher.book.bids.len(), engine.matcher.book.asks.len());
+
+    Ok(())
+}
+
+/// L2 mode: maintain a synthetic book from depth diffs and check every
+/// level matches the displayed size.
+fn replay_l2(args: &Args) -> Result<(), Box<dyn Error>> {
+    println!("Initializing L2 Synthetic Book...");
+    let mut book = SyntheticBook::new(10_000_000, ShrinkPolicy::default());
+    let scales = args.scale_table();
+    let mut rdr = csv::Reader::from_reader(File::open(&args.input)?);
+
+    let mut rows_processed = 0;
+    let mut parse_errors = 0;
+    let mut level_mismatches = 0;
+    let mut events = Vec::new();
+
+    for (row_idx, result) in rdr.deserialize().enumerate() {
+        if args.limit.is_some_and(|limit| rows_processed >= limit) {
+            break;
+        }
+
+        let row: TardisL2Row = result?;
+        let update = match row.to_update(&scales) {
+            Ok(update) => update,
+            Err(e) => {
+                println!("[row {}] parse error: {}", row_idx + 2, e);
+                parse_errors += 1;
+                continue;
+            }
+        };
+
+        events.clear();
+        book.apply(&update, &mut events);
+        rows_processed += 1;
+
+        let shown = book.synthetic_qty(update.side, update.price);
+        if shown != update.qty {
+            level_mismatches += 1;
+            println!(
+                "[row {}] {:?} level {} holds {} after update to {}",
+                row_idx + 2, update.side, update.price, shown, update.qty
+            );
+        }
+
+        if rows_processed % 100_000 == 0 {
+            println!("Processed {} rows. BBO: {:?} / {:?}", rows_processed, book.engine().best_bid(), book.engine().best_ask());
+        }
+    }
+
+    println!("\n=== L2 Replay Complete ===");
+    println!("Total Rows: {}", rows_processed);
+    println!("Parse Errors: {}", parse_errors);
+    println!("Level Mismatches: {}", level_mismatches);
+    println!("Final BBO: {:?} / {:?}", book.engine().best_bid(), book.engine().best_ask());
+    let engine_book = &book.engine().matcher.book;
+    println!("Final Book Depth: {} bids, {} asks", engine_book.bids.len(), engine_book.asks.len());
+
     Ok(())
 }
@@ -11,13 +11,14 @@ use std::{io, time::Duration};
 use std::sync::{Arc, RwLock};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread;
-use flash_lob::{Engine, Command, PlaceOrder, Side, OrderType};
+use flash_lob::{Engine, EngineConfig, Command, PlaceOrder, Side, OrderType, OutputEvent, Arena, ArenaGrowth, OrderBook};
+use flash_lob::matching::MatchingEngine;
 
 // [NEW] A Snapshot of the top levels to share with the UI
 #[derive(Default, Clone)]
 struct BookSnapshot {
-    bids: Vec<(u64, u32)>, // (Price, Qty)
-    asks: Vec<(u64, u32)>,
+    bids: Vec<(u64, u64)>, // (Price, Qty)
+    asks: Vec<(u64, u64)>,
 }
 
 struct SharedStats {
@@ -43,7 +44,7 @@ impl SharedStats {
 }
 
 // Helper to generate the ASCII Bar string
-fn render_level_bars(levels: &[(u64, u32)], side: Side, _max_width: usize) -> String {
+fn render_level_bars(levels: &[(u64, u64)], side: Side, _max_width: usize) -> String {
     let mut out = String::new();
     let max_qty = levels.iter().map(|(_, q)| *q).max().unwrap_or(1) as f32;
 
@@ -67,6 +68,13 @@ fn render_level_bars(levels: &[(u64, u32)], side: Side, _max_width: usize) -> St
     out
 }
 
+/// Engine whose arena starts small and grows up to `capacity`, warning at 90%
+fn new_engine(capacity: u32) -> Engine {
+    let arena = Arena::growable(ArenaGrowth::new(1 << 16, capacity));
+    let matcher = MatchingEngine::with_arena(arena, OrderBook::new());
+    Engine::with_matcher(matcher, EngineConfig::default())
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Setup terminal
     enable_raw_mode()?;
@@ -82,8 +90,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Spawn Engine Thread (Synthetic Load)
     thread::spawn(move || {
-        let mut engine = Engine::new(capacity);
+        let mut engine = new_engine(capacity);
         engine.warm_up();
+        let mut nearly_full = false;
         
         let mut order_id = 1u64;
         let mut rng = 12345u64; // Simple LCG for speed
@@ -106,16 +115,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                 // [NEW] Random Walk Logic (Simulate Volatility)
                 // Apply drift to the mid-price (Brownian Motion)
-                if r % 100 == 0 { // 1% chance to drift per order for smoother action
-                    let drift = (r % 5) as i64 - 2; // -2, -1, 0, 1, 2
-                    // Add multiplier to drift to make it more visible? No, small steps are fine.
-                    // Let's allow slightly larger steps: -5 to +5
+                if r.is_multiple_of(100) { // 1% chance to drift per order for smoother action
+                    // Steps of -5 to +5 keep the walk visible without jumping
                     let drift_mag = (r % 11) as i64 - 5; 
                     current_mid_price = (current_mid_price as i64 + drift_mag).max(1000) as u64;
                 }
 
                 // Determine Side (50/50 bid/ask)
-                let side = if r % 2 == 0 { Side::Bid } else { Side::Ask };
+                let side = if r.is_multiple_of(2) { Side::Bid } else { Side::Ask };
                 
                 // Place orders AROUND the mid-price (Spread generation)
                 // Spread can be tight or wide.
@@ -145,7 +152,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     order_type: OrderType::Limit,
                 });
                 
-                engine.process_command(cmd);
+                let events = engine.process_command(cmd);
+                nearly_full |= events.iter().any(|e| matches!(e, OutputEvent::ArenaHighWater(_)));
             }
             
             loop_count += 1;
@@ -161,19 +169,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             // [NEW] Publish Snapshot (Only once per batch/loop iteration)
             // Use loop_count to guarantee updates every 50 batches (approx 5ms at 10M ops/sec)
-            if loop_count % 50 == 0 { 
+            if loop_count.is_multiple_of(50) { 
                 if let Ok(mut write_guard) = stats_clone.book_snapshot.write() {
-                    // Extract Top 15 Bids/Asks manually
-                    write_guard.bids = engine.matcher.book.bids.iter()
-                        .rev().take(15).map(|(p, l)| (*p, l.total_qty as u32)).collect();
-                    write_guard.asks = engine.matcher.book.asks.iter()
-                        .take(15).map(|(p, l)| (*p, l.total_qty as u32)).collect();
+                    // Extract Top 15 Bids/Asks (reusing the snapshot buffers)
+                    let book = &engine.matcher.book;
+                    write_guard.bids.clear();
+                    write_guard.bids.extend(book.top_levels(Side::Bid, 15).map(|l| (l.price, l.qty)));
+                    write_guard.asks.clear();
+                    write_guard.asks.extend(book.top_levels(Side::Ask, 15).map(|l| (l.price, l.qty)));
                 }
             }
             
-            // Reset if full
-            if engine.order_count() > (capacity as usize) * 9 / 10 {
-                engine = Engine::new(capacity); // Hard reset for demo loop
+            // Reset once the arena warns it is nearly at its cap
+            if nearly_full {
+                engine = new_engine(capacity); // Hard reset for demo loop
+                nearly_full = false;
             }
         }
     });
@@ -0,0 +1,71 @@
+use std::error::Error;
+use clap::Parser;
+use flash_lob::ws::{WsConfig, WsServer};
+use flash_lob::{LadderConfig, LevelStorage};
+use tokio::net::TcpListener;
+
+#[derive(Parser)]
+#[command(author, version, about = "Flash-LOB WebSocket Gateway")]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:9001")]
+    bind: String,
+
+    /// Instrument symbols (one engine each)
+    #[arg(long = "instrument", default_value = "ETH-USD")]
+    instruments: Vec<String>,
+
+    /// Arena capacity per engine
+    #[arg(long, default_value_t = 1_000_000)]
+    capacity: u32,
+
+    /// Levels per side in depth messages
+    #[arg(long, default_value_t = 10)]
+    depth: usize,
+
+    /// Pin engine threads to the last CPU core
+    #[arg(long)]
+    pin: bool,
+
+    /// Use an array price ladder for a symbol: SYMBOL=TICK_SIZE:TICKS (repeatable)
+    #[arg(long = "ladder", value_parser = parse_ladder)]
+    ladders: Vec<(String, LadderConfig)>,
+}
+
+fn parse_ladder(s: &str) -> Result<(String, LadderConfig), String> {
+    let usage = || format!("expected SYMBOL=TICK_SIZE:TICKS, got {:?}", s);
+    let (symbol, spec) = s.split_once('=').ok_or_else(usage)?;
+    let (tick, ticks) = spec.split_once(':').ok_or_else(usage)?;
+    let tick: u64 = tick.parse().map_err(|_| usage())?;
+    let ticks: u32 = ticks.parse().map_err(|_| usage())?;
+    if tick == 0 || ticks == 0 {
+        return Err(usage());
+    }
+    // The ladder recenters on the first order, so the base price is arbitrary
+    Ok((symbol.to_string(), LadderConfig::new(0, tick, ticks)))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    let config = WsConfig {
+        instruments: args.instruments,
+        capacity: args.capacity,
+        depth_levels: args.depth,
+        pin_engines: args.pin,
+        level_storage: args.ladders.into_iter()
+            .map(|(symbol, ladder)| (symbol, LevelStorage::Ladder(ladder)))
+            .collect(),
+        ..WsConfig::default()
+    };
+
+    println!("Starting engines for {:?}...", config.instruments);
+    let server = WsServer::start(config);
+
+    let listener = TcpListener::bind(&args.bind).await?;
+    println!("Listening on ws://{}", args.bind);
+    server.serve(listener).await?;
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+//! Book Side - Price level storage for one side of the book.
+//!
+//! `OrderBook` keeps each side in either a `LevelMap` (any price, no setup)
+//! or a `PriceLadder` (preallocated, tick-indexed, for instruments with a
+//! bounded tick range). The choice is made per book via `LevelStorage`;
+//! both expose the same ordered-map operations.
+
+use std::ops::RangeBounds;
+
+use crate::ladder::{LadderConfig, LadderIter, PriceLadder};
+use crate::level_map::{LevelMap, LevelMapIter};
+use crate::price_level::PriceLevel;
+
+/// Level storage backend for an `OrderBook`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LevelStorage {
+    /// Sparse ordered map of levels (pooled skip list)
+    #[default]
+    Tree,
+    /// Tick-indexed array with an occupancy bitmap
+    Ladder(LadderConfig),
+}
+
+/// Ordered price levels for one side
+#[derive(Clone, Debug)]
+pub enum BookSide {
+    Tree(LevelMap),
+    Ladder(PriceLadder),
+}
+
+impl BookSide {
+    pub fn new(storage: LevelStorage) -> Self {
+        match storage {
+            LevelStorage::Tree => BookSide::Tree(LevelMap::new()),
+            LevelStorage::Ladder(config) => BookSide::Ladder(PriceLadder::new(config)),
+        }
+    }
+
+    /// Returns true if a level can be stored at `price` (tick-aligned and
+    /// within reach of the live levels for ladders)
+    #[inline]
+    pub fn accepts_price(&self, price: u64) -> bool {
+        match self {
+            BookSide::Tree(_) => true,
+            BookSide::Ladder(ladder) => ladder.accepts(price),
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, price: u64) -> Option<&PriceLevel> {
+        match self {
+            BookSide::Tree(map) => map.get(price),
+            BookSide::Ladder(ladder) => ladder.get(price),
+        }
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, price: u64) -> Option<&mut PriceLevel> {
+        match self {
+            BookSide::Tree(map) => map.get_mut(price),
+            BookSide::Ladder(ladder) => ladder.get_mut(price),
+        }
+    }
+
+    /// Level at `price`, created empty if absent
+    #[inline]
+    pub fn get_or_insert(&mut self, price: u64) -> &mut PriceLevel {
+        match self {
+            BookSide::Tree(map) => map.get_or_insert(price),
+            BookSide::Ladder(ladder) => ladder.get_or_insert(price),
+        }
+    }
+
+    #[inline]
+    pub fn remove(&mut self, price: u64) -> Option<PriceLevel> {
+        match self {
+            BookSide::Tree(map) => map.remove(price),
+            BookSide::Ladder(ladder) => ladder.remove(price),
+        }
+    }
+
+    /// Lowest-priced level
+    #[inline]
+    pub fn first(&self) -> Option<(u64, &PriceLevel)> {
+        match self {
+            BookSide::Tree(map) => map.first(),
+            BookSide::Ladder(ladder) => ladder.first(),
+        }
+    }
+
+    /// Highest-priced level
+    #[inline]
+    pub fn last(&self) -> Option<(u64, &PriceLevel)> {
+        match self {
+            BookSide::Tree(map) => map.last(),
+            BookSide::Ladder(ladder) => ladder.last(),
+        }
+    }
+
+    /// Levels within a price range, ascending (empty if the range is inverted)
+    #[inline]
+    pub fn range<R: RangeBounds<u64>>(&self, range: R) -> SideIter<'_> {
+        match self {
+            BookSide::Tree(map) => SideIter::Tree(map.range(range)),
+            BookSide::Ladder(ladder) => SideIter::Ladder(ladder.range(range)),
+        }
+    }
+
+    /// All levels, ascending
+    #[inline]
+    pub fn iter(&self) -> SideIter<'_> {
+        self.range(..)
+    }
+
+    /// Level prices, ascending
+    #[inline]
+    pub fn prices(&self) -> impl DoubleEndedIterator<Item = u64> + '_ {
+        self.iter().map(|(p, _)| p)
+    }
+
+    /// Number of levels
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self {
+            BookSide::Tree(map) => map.len(),
+            BookSide::Ladder(ladder) => ladder.len(),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        match self {
+            BookSide::Tree(map) => map.clear(),
+            BookSide::Ladder(ladder) => ladder.clear(),
+        }
+    }
+}
+
+impl Default for BookSide {
+    fn default() -> Self {
+        Self::new(LevelStorage::Tree)
+    }
+}
+
+/// Levels of a `BookSide`, ascending (double-ended)
+#[derive(Clone, Debug)]
+pub enum SideIter<'a> {
+    Tree(LevelMapIter<'a>),
+    Ladder(LadderIter<'a>),
+}
+
+impl<'a> Iterator for SideIter<'a> {
+    type Item = (u64, &'a PriceLevel);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            SideIter::Tree(it) => it.next(),
+            SideIter::Ladder(it) => it.next(),
+        }
+    }
+}
+
+impl DoubleEndedIterator for SideIter<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self {
+            SideIter::Tree(it) => it.next_back(),
+            SideIter::Ladder(it) => it.next_back(),
+        }
+    }
+}
@@ -1,19 +1,42 @@
+//! Coinbase L3 - Parsing Tardis.dev `coinbase` L3 CSV exports.
+//!
+//! Rows are converted to typed `CoinbaseMessage`s using per-product fixed
+//! point scales. Malformed rows produce a `ParseError` rather than a
+//! silently wrong message.
+
 use serde::Deserialize;
 use rust_decimal::Decimal;
 use rust_decimal::prelude::ToPrimitive;
 use chrono::{DateTime, Utc};
-use crate::command::Side;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use crate::command::{CancelOrder, Command, OutputEvent, PlaceOrder, Side};
+use crate::engine::Engine;
+use crate::interner::OrderIdInterner;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Deserialize)]
 pub struct TardisL3Row {
+    #[serde(default)]
+    pub symbol: Option<String>,
     pub r#type: String,
     pub side: Option<String>,
     pub price: Option<Decimal>,
     pub amount: Option<Decimal>,
-    pub order_id: Option<String>, // Changed to String to handle UUIDs
+    pub order_id: Option<String>, // UUID, interned to a dense u64
     pub trade_id: Option<u64>,
+    /// `done`: "filled" or "canceled"
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// `match`: aggressing order
+    #[serde(default)]
+    pub taker_order_id: Option<String>,
+    /// `change`: size after the change
+    #[serde(default)]
+    pub new_size: Option<Decimal>,
+    /// `open`/`done`: unfilled size
+    #[serde(default)]
+    pub remaining_size: Option<Decimal>,
     pub timestamp: Option<DateTime<Utc>>,
     pub local_timestamp: Option<u64>,
 }
@@ -23,8 +46,10 @@ pub enum CoinbaseMessage {
     Received {
         order_id: u64,
         side: Side,
-        price: u64, // Converted to cents/satoshis
-        qty: u32,
+        /// Limit price; `None` for market orders
+        price: Option<u64>,
+        /// Order size; `None` for funds-based market orders
+        qty: Option<u32>,
     },
     Open {
         order_id: u64,
@@ -36,10 +61,13 @@ pub enum CoinbaseMessage {
         order_id: u64,
         side: Side,
         reason: DoneReason,
+        /// Unfilled size when the order left the book (if reported)
+        remaining_qty: Option<u32>,
     },
     Match {
         maker_order_id: u64,
         taker_order_id: u64,
+        trade_id: Option<u64>,
         price: u64,
         qty: u32,
     },
@@ -56,60 +84,702 @@ pub enum DoneReason {
     Canceled,
 }
 
+/// Why a row could not be converted to a message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Required column is empty or absent
+    MissingField(&'static str),
+    /// Unrecognized `type` column
+    UnknownType(String),
+    /// Unrecognized `side` column
+    InvalidSide(String),
+    /// Unrecognized `done` reason
+    InvalidReason(String),
+    /// Value is negative or does not fit the engine's integer type after scaling
+    OutOfRange { field: &'static str, value: Decimal },
+    /// Value has more decimal places than the product scale allows
+    Precision { field: &'static str, value: Decimal },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingField(field) => write!(f, "missing field `{}`", field),
+            ParseError::UnknownType(t) => write!(f, "unknown message type {:?}", t),
+            ParseError::InvalidSide(s) => write!(f, "invalid side {:?}", s),
+            ParseError::InvalidReason(r) => write!(f, "invalid done reason {:?}", r),
+            ParseError::OutOfRange { field, value } => write!(f, "`{}` value {} out of range", field, value),
+            ParseError::Precision { field, value } => {
+                write!(f, "`{}` value {} exceeds scale precision", field, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Fixed-point scales for one product.
+///
+/// Prices and sizes are multiplied by these factors and must be integral
+/// afterwards; sizes must also fit in `u32`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scale {
+    /// Multiplier for prices (e.g. 100 for cents)
+    pub price: u64,
+    /// Multiplier for sizes (e.g. 100_000_000 for satoshis)
+    pub size: u64,
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self { price: 100, size: 100_000_000 }
+    }
+}
+
+impl FromStr for Scale {
+    type Err = String;
+
+    /// Parse `PRICE:SIZE`, e.g. `100:10000`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (price, size) = s.split_once(':').ok_or_else(|| format!("expected PRICE:SIZE, got {:?}", s))?;
+        let price = price.parse().map_err(|e| format!("bad price scale {:?}: {}", price, e))?;
+        let size = size.parse().map_err(|e| format!("bad size scale {:?}: {}", size, e))?;
+        if price == 0 || size == 0 {
+            return Err("scales must be non-zero".to_string());
+        }
+        Ok(Self { price, size })
+    }
+}
+
+/// Per-product scales with a fallback for unlisted symbols
+#[derive(Clone, Debug, Default)]
+pub struct ScaleTable {
+    pub default: Scale,
+    pub products: HashMap<String, Scale>,
+}
+
+impl ScaleTable {
+    pub fn new(default: Scale) -> Self {
+        Self { default, products: HashMap::new() }
+    }
+
+    /// Set the scale for a symbol
+    pub fn insert(&mut self, symbol: impl Into<String>, scale: Scale) {
+        self.products.insert(symbol.into(), scale);
+    }
+
+    /// Scale for a symbol, or the default
+    pub fn get(&self, symbol: Option<&str>) -> Scale {
+        symbol.and_then(|s| self.products.get(s)).copied().unwrap_or(self.default)
+    }
+}
+
+pub(crate) fn scale_value(value: Decimal, mult: u64, field: &'static str) -> Result<u64, ParseError> {
+    let scaled = value
+        .checked_mul(Decimal::from(mult))
+        .ok_or(ParseError::OutOfRange { field, value })?;
+    if !scaled.fract().is_zero() {
+        return Err(ParseError::Precision { field, value });
+    }
+    scaled.to_u64().ok_or(ParseError::OutOfRange { field, value })
+}
+
+pub(crate) fn scale_qty(value: Decimal, scale: &Scale, field: &'static str) -> Result<u32, ParseError> {
+    let qty = scale_value(value, scale.size, field)?;
+    u32::try_from(qty).map_err(|_| ParseError::OutOfRange { field, value })
+}
+
 impl TardisL3Row {
-    /// Convert raw row to typed internal message
-    /// Price multiplier: e.g. 100 for cents, 100000000 for satoshis
-    pub fn to_message(&self, price_mult: u64) -> Option<CoinbaseMessage> {
-        let side = match self.side.as_deref() {
-            Some("buy") | Some("bid") => Side::Bid,
-            Some("sell") | Some("ask") => Side::Ask,
-            _ => Side::Bid, // Default, mostly relevant for types that have side
-        };
-        
-        let price = self.price.map(|d| (d * Decimal::from(price_mult)).to_u64().unwrap_or(0));
-        let qty = self.amount.map(|d| (d * Decimal::from(100000000u64)).to_u32().unwrap_or(0)); // Assuming max 8 decimals for size
-        
-        // Hash the UUID string to a u64
-        let raw_id = self.order_id.as_deref().unwrap_or("0");
-        let mut hasher = DefaultHasher::new();
-        raw_id.hash(&mut hasher);
-        let order_id = hasher.finish();
-        
+    /// Convert raw row to typed internal message using the row's product scale.
+    ///
+    /// Order UUIDs are mapped through `ids`; a `done` row releases its ID.
+    pub fn to_message(&self, scales: &ScaleTable, ids: &mut OrderIdInterner) -> Result<CoinbaseMessage, ParseError> {
+        self.to_message_scaled(&scales.get(self.symbol.as_deref()), ids)
+    }
+
+    /// Convert raw row to typed internal message with an explicit scale.
+    ///
+    /// All fields are validated before any ID is interned, so a rejected
+    /// row leaves `ids` untouched.
+    pub fn to_message_scaled(&self, scale: &Scale, ids: &mut OrderIdInterner) -> Result<CoinbaseMessage, ParseError> {
         match self.r#type.as_str() {
-            "received" => Some(CoinbaseMessage::Received {
-                order_id,
-                side,
-                price: price.unwrap_or(0),
-                qty: qty.unwrap_or(0),
-            }),
-            "open" => Some(CoinbaseMessage::Open {
-                order_id,
-                side,
-                price: price.unwrap_or(0),
-                qty: qty.unwrap_or(0),
-            }),
+            "received" => {
+                let uuid = self.uuid()?;
+                let side = self.side()?;
+                let price = self.price.map(|p| scale_value(p, scale.price, "price")).transpose()?;
+                let qty = self.amount.map(|a| scale_qty(a, scale, "amount")).transpose()?;
+                Ok(CoinbaseMessage::Received { order_id: ids.intern(uuid), side, price, qty })
+            }
+            "open" => {
+                // Coinbase reports `remaining_size`; older exports carry it in `amount`
+                let (field, size) = match (self.remaining_size, self.amount) {
+                    (Some(r), _) => ("remaining_size", r),
+                    (None, Some(a)) => ("amount", a),
+                    (None, None) => return Err(ParseError::MissingField("remaining_size")),
+                };
+                let uuid = self.uuid()?;
+                let side = self.side()?;
+                let price = self.price(scale)?;
+                let qty = scale_qty(size, scale, field)?;
+                Ok(CoinbaseMessage::Open { order_id: ids.intern(uuid), side, price, qty })
+            }
             "done" => {
-                // Done messages can be filled or canceled
-                // We infer reason? Tardis usually has 'reason' column but we didn't add it to struct
-                // For simplified replay, 'done' implies remove from book.
-                Some(CoinbaseMessage::Done {
-                    order_id,
-                    side,
-                    reason: DoneReason::Canceled, // Simplification for now
+                let reason = match self.reason.as_deref() {
+                    Some("filled") => DoneReason::Filled,
+                    Some("canceled") => DoneReason::Canceled,
+                    Some(other) => return Err(ParseError::InvalidReason(other.to_string())),
+                    None => return Err(ParseError::MissingField("reason")),
+                };
+                let uuid = self.uuid()?;
+                let side = self.side()?;
+                let remaining_qty = self.remaining_size
+                    .map(|r| scale_qty(r, scale, "remaining_size"))
+                    .transpose()?;
+                Ok(CoinbaseMessage::Done { order_id: ids.retire(uuid), side, reason, remaining_qty })
+            }
+            "match" => {
+                let taker = self.taker_order_id.as_deref().ok_or(ParseError::MissingField("taker_order_id"))?;
+                let maker = self.uuid()?;
+                let price = self.price(scale)?;
+                let qty = scale_qty(self.amount.ok_or(ParseError::MissingField("amount"))?, scale, "amount")?;
+                Ok(CoinbaseMessage::Match {
+                    maker_order_id: ids.intern(maker),
+                    taker_order_id: ids.intern(taker),
+                    trade_id: self.trade_id,
+                    price,
+                    qty,
                 })
-            },
-            "match" => Some(CoinbaseMessage::Match {
-                maker_order_id: order_id, // For match, order_id is usually maker
-                taker_order_id: self.trade_id.unwrap_or(0), // Taker/Trade ID? Validation requires care
-                price: price.unwrap_or(0),
-                qty: qty.unwrap_or(0),
-            }),
-            "change" => Some(CoinbaseMessage::Change {
-                order_id,
-                new_qty: qty.unwrap_or(0),
-                price: price.unwrap_or(0),
-            }),
-            _ => None,
+            }
+            "change" => {
+                let (field, size) = match (self.new_size, self.amount) {
+                    (Some(n), _) => ("new_size", n),
+                    (None, Some(a)) => ("amount", a),
+                    (None, None) => return Err(ParseError::MissingField("new_size")),
+                };
+                let uuid = self.uuid()?;
+                let new_qty = scale_qty(size, scale, field)?;
+                let price = self.price(scale)?;
+                Ok(CoinbaseMessage::Change { order_id: ids.intern(uuid), new_qty, price })
+            }
+            other => Err(ParseError::UnknownType(other.to_string())),
+        }
+    }
+
+    fn uuid(&self) -> Result<&str, ParseError> {
+        self.order_id.as_deref().ok_or(ParseError::MissingField("order_id"))
+    }
+
+    fn side(&self) -> Result<Side, ParseError> {
+        match self.side.as_deref() {
+            Some("buy") | Some("bid") => Ok(Side::Bid),
+            Some("sell") | Some("ask") => Ok(Side::Ask),
+            Some(other) => Err(ParseError::InvalidSide(other.to_string())),
+            None => Err(ParseError::MissingField("side")),
+        }
+    }
+
+    fn price(&self, scale: &Scale) -> Result<u64, ParseError> {
+        scale_value(self.price.ok_or(ParseError::MissingField("price"))?, scale.price, "price")
+    }
+}
+
+// ============================================================================
+// Active Replay
+// ============================================================================
+
+/// Replays `received` orders through the engine and lets it do the matching,
+/// counting how many of the feed's `match` rows the engine's trades account for.
+///
+/// Every `done` cancels the order in the engine, whatever its reason: the
+/// interner recycles the order's ID on `done`, so an order the engine still
+/// holds (the feed filled it but the engine did not) must be removed before
+/// the ID is handed to a new UUID.
+pub struct L3Replayer {
+    engine: Engine,
+    /// Engine trades not yet matched to a feed `match`
+    pub pending_trades: u64,
+    /// Feed matches accounted for by an engine trade
+    pub matches_validated: u64,
+    /// Feed matches with no engine trade left to account for them
+    pub matches_missed: u64,
+    /// `done filled` for orders the engine still held (fill disagreements)
+    pub unfilled_dones: u64,
+}
+
+impl L3Replayer {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            engine: Engine::new(capacity),
+            pending_trades: 0,
+            matches_validated: 0,
+            matches_missed: 0,
+            unfilled_dones: 0,
+        }
+    }
+
+    /// The replayed book
+    #[inline]
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Apply one feed message.
+    pub fn apply(&mut self, msg: &CoinbaseMessage) {
+        match *msg {
+            // Funds-based market orders have no size to replay
+            CoinbaseMessage::Received { order_id, side, price, qty: Some(qty) } => {
+                // Market orders take any price
+                let price = price.unwrap_or(match side {
+                    Side::Bid => u64::MAX,
+                    Side::Ask => 0,
+                });
+                let events = self.engine.process_command(Command::Place(PlaceOrder::limit(order_id, 1, side, price, qty)));
+                self.pending_trades += events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count() as u64;
+            }
+            CoinbaseMessage::Done { order_id, ref reason, .. } => {
+                let events = self.engine.process_command(Command::Cancel(CancelOrder { order_id }));
+                let was_resting = matches!(events.first(), Some(OutputEvent::Canceled(_)));
+                if was_resting && *reason == DoneReason::Filled {
+                    self.unfilled_dones += 1;
+                }
+            }
+            CoinbaseMessage::Match { .. } => {
+                if self.pending_trades > 0 {
+                    self.pending_trades -= 1;
+                    self.matches_validated += 1;
+                } else {
+                    self.matches_missed += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// ============================================================================
+// Passive Book Reconstruction
+// ============================================================================
+
+/// A single fill: maker order, execution price and quantity
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fill {
+    pub maker_order_id: u64,
+    pub price: u64,
+    pub qty: u32,
+}
+
+/// A disagreement between the engine's predicted matching and the feed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Divergence {
+    /// The feed reported a match where the engine predicted none
+    Unpredicted { taker_order_id: u64, actual: Fill },
+    /// The engine predicted a different fill than the feed reported
+    Mismatch { taker_order_id: u64, predicted: Fill, actual: Fill },
+    /// The taker still crossed resting liquidity when its matches ended
+    Missed { taker_order_id: u64, predicted: Fill },
+    /// An `open` order would cross the reconstructed book
+    CrossedOpen { order_id: u64, side: Side, price: u64 },
+    /// A `match` or `change` referenced an order not in the reconstructed book
+    UnknownOrder { order_id: u64 },
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Divergence::Unpredicted { taker_order_id, actual } => write!(
+                f,
+                "taker #{}: unpredicted match maker #{} {}@{}",
+                taker_order_id, actual.maker_order_id, actual.qty, actual.price
+            ),
+            Divergence::Mismatch { taker_order_id, predicted, actual } => write!(
+                f,
+                "taker #{}: predicted maker #{} {}@{}, actual maker #{} {}@{}",
+                taker_order_id,
+                predicted.maker_order_id, predicted.qty, predicted.price,
+                actual.maker_order_id, actual.qty, actual.price
+            ),
+            Divergence::Missed { taker_order_id, predicted } => write!(
+                f,
+                "taker #{}: missed predicted match maker #{} {}@{}",
+                taker_order_id, predicted.maker_order_id, predicted.qty, predicted.price
+            ),
+            Divergence::CrossedOpen { order_id, side, price } => write!(
+                f,
+                "open #{} {:?}@{} crosses reconstructed book",
+                order_id, side, price
+            ),
+            Divergence::UnknownOrder { order_id } => write!(f, "unknown order #{}", order_id),
+        }
+    }
+}
+
+/// The taker whose matches are currently being checked
+#[derive(Clone, Copy, Debug)]
+struct ActiveTaker {
+    order_id: u64,
+    side: Side,
+    /// Limit price (`u64::MAX`/0 for market buys/sells)
+    price: u64,
+    /// Unfilled quantity (`u32::MAX` if unknown, e.g. funds-based market orders)
+    remaining: u32,
+}
+
+/// Builds the book passively from `open`/`done`/`change`/`match` exactly as
+/// the exchange reported them, and checks each `match` against the fill our
+/// matching rules predict for the current taker.
+///
+/// The prediction is made lazily per match from the reconstructed book, so a
+/// single divergence does not cascade into the rest of the taker's fills.
+pub struct L3Reconstructor {
+    engine: Engine,
+    taker: Option<ActiveTaker>,
+    /// Matches that agreed with the prediction
+    pub matches_validated: u64,
+    /// Total divergences reported
+    pub divergences: u64,
+}
+
+impl L3Reconstructor {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            engine: Engine::new(capacity),
+            taker: None,
+            matches_validated: 0,
+            divergences: 0,
         }
     }
+
+    /// The reconstructed book
+    #[inline]
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Apply one feed message, appending any divergences found.
+    pub fn apply(&mut self, msg: &CoinbaseMessage, out: &mut Vec<Divergence>) {
+        let start = out.len();
+
+        if !matches!(msg, CoinbaseMessage::Match { .. }) {
+            self.finish_taker(out);
+        }
+
+        match *msg {
+            CoinbaseMessage::Received { order_id, side, price, qty } => {
+                let price = price.unwrap_or(match side {
+                    Side::Bid => u64::MAX,
+                    Side::Ask => 0,
+                });
+                let remaining = qty.unwrap_or(u32::MAX);
+                self.taker = Some(ActiveTaker { order_id, side, price, remaining });
+            }
+            CoinbaseMessage::Open { order_id, side, price, qty } => {
+                let crosses = match side {
+                    Side::Bid => self.engine.best_ask().is_some_and(|ask| price >= ask),
+                    Side::Ask => self.engine.best_bid().is_some_and(|bid| price <= bid),
+                };
+                if crosses {
+                    out.push(Divergence::CrossedOpen { order_id, side, price });
+                } else if qty > 0 {
+                    self.engine.process_command(Command::Place(PlaceOrder::limit(order_id, 0, side, price, qty)));
+                }
+            }
+            CoinbaseMessage::Done { order_id, .. } => {
+                self.engine.process_command(Command::Cancel(CancelOrder { order_id }));
+            }
+            CoinbaseMessage::Change { order_id, new_qty, .. } => {
+                match self.engine.matcher.book.get_order(order_id).copied() {
+                    Some(info) => {
+                        let qty = self.engine.matcher.arena.get_handle(info.handle).qty;
+                        if new_qty < qty {
+                            self.reduce(order_id, qty - new_qty);
+                        }
+                    }
+                    None => out.push(Divergence::UnknownOrder { order_id }),
+                }
+            }
+            CoinbaseMessage::Match { maker_order_id, price, qty, .. } => {
+                let actual = Fill { maker_order_id, price, qty };
+                self.check_match(actual, out);
+                if self.engine.matcher.book.contains_order(maker_order_id) {
+                    self.reduce(maker_order_id, qty);
+                } else {
+                    out.push(Divergence::UnknownOrder { order_id: maker_order_id });
+                }
+            }
+        }
+
+        self.divergences += (out.len() - start) as u64;
+    }
+
+    /// Close out the last taker at end of stream.
+    pub fn finish(&mut self, out: &mut Vec<Divergence>) {
+        let start = out.len();
+        self.finish_taker(out);
+        self.divergences += (out.len() - start) as u64;
+    }
+
+    /// The fill our matching rules would produce next for the taker
+    fn predict(&self, taker: &ActiveTaker) -> Option<Fill> {
+        let book = &self.engine.matcher.book;
+        let (price, level) = match taker.side {
+            Side::Bid => book.asks.first().filter(|&(p, _)| p <= taker.price)?,
+            Side::Ask => book.bids.last().filter(|&(p, _)| p >= taker.price)?,
+        };
+        let head = self.engine.matcher.arena.get(level.peek_head());
+        Some(Fill {
+            maker_order_id: head.order_id,
+            price,
+            qty: head.qty.min(taker.remaining),
+        })
+    }
+
+    fn check_match(&mut self, actual: Fill, out: &mut Vec<Divergence>) {
+        let Some(taker) = self.taker else {
+            out.push(Divergence::Unpredicted { taker_order_id: 0, actual });
+            return;
+        };
+        let taker_order_id = taker.order_id;
+
+        match self.predict(&taker) {
+            Some(predicted) => {
+                // With unknown taker size, any fill up to the maker's qty is consistent
+                let qty_ok = if taker.remaining == u32::MAX {
+                    actual.qty <= predicted.qty
+                } else {
+                    actual.qty == predicted.qty
+                };
+                if predicted.maker_order_id == actual.maker_order_id && predicted.price == actual.price && qty_ok {
+                    self.matches_validated += 1;
+                } else {
+                    out.push(Divergence::Mismatch { taker_order_id, predicted, actual });
+                }
+            }
+            None => out.push(Divergence::Unpredicted { taker_order_id, actual }),
+        }
+
+        if let Some(taker) = self.taker.as_mut() {
+            if taker.remaining != u32::MAX {
+                taker.remaining = taker.remaining.saturating_sub(actual.qty);
+            }
+        }
+    }
+
+    /// Close the current taker, flagging liquidity it should have taken
+    fn finish_taker(&mut self, out: &mut Vec<Divergence>) {
+        if let Some(taker) = self.taker.take() {
+            if taker.remaining > 0 && taker.remaining != u32::MAX {
+                if let Some(predicted) = self.predict(&taker) {
+                    out.push(Divergence::Missed { taker_order_id: taker.order_id, predicted });
+                }
+            }
+        }
+    }
+
+    /// Reduce a resting order in place, keeping its queue priority
+    fn reduce(&mut self, order_id: u64, by: u32) {
+        self.engine.event_buffer.clear();
+        self.engine.matcher.process_reduce(order_id, by, &mut self.engine.event_buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(recon: &mut L3Reconstructor, msgs: &[CoinbaseMessage]) -> Vec<Divergence> {
+        let mut out = Vec::new();
+        for msg in msgs {
+            recon.apply(msg, &mut out);
+        }
+        out
+    }
+
+    fn open(order_id: u64, side: Side, price: u64, qty: u32) -> CoinbaseMessage {
+        CoinbaseMessage::Open { order_id, side, price, qty }
+    }
+
+    fn matched(maker_order_id: u64, taker_order_id: u64, price: u64, qty: u32) -> CoinbaseMessage {
+        CoinbaseMessage::Match { maker_order_id, taker_order_id, trade_id: None, price, qty }
+    }
+
+    fn row(csv_text: &str) -> TardisL3Row {
+        let header = "symbol,type,side,price,amount,order_id,trade_id,reason,taker_order_id,new_size,remaining_size,timestamp,local_timestamp";
+        let data = format!("{}\n{}\n", header, csv_text);
+        csv::Reader::from_reader(data.as_bytes()).deserialize().next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_parse_done_and_match() {
+        let scales = ScaleTable::default();
+        let mut ids = OrderIdInterner::new();
+        let abc = ids.intern("abc");
+
+        let done = row("BTC-USD,done,sell,,,abc,,filled,,,0.25,,");
+        match done.to_message(&scales, &mut ids).unwrap() {
+            CoinbaseMessage::Done { order_id, side, reason, remaining_qty } => {
+                assert_eq!(order_id, abc);
+                assert_eq!(ids.get("abc"), None, "done releases the ID");
+                assert_eq!(side, Side::Ask);
+                assert_eq!(reason, DoneReason::Filled);
+                assert_eq!(remaining_qty, Some(25_000_000));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let matched = row("BTC-USD,match,buy,100.5,0.1,maker,42,,taker,,,,");
+        match matched.to_message(&scales, &mut ids).unwrap() {
+            CoinbaseMessage::Match { maker_order_id, taker_order_id, trade_id, price, qty } => {
+                assert_eq!(ids.resolve(maker_order_id), Some("maker"));
+                assert_eq!(ids.resolve(taker_order_id), Some("taker"));
+                assert_eq!(trade_id, Some(42));
+                assert_eq!((price, qty), (10050, 10_000_000));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let change = row("BTC-USD,change,buy,100,,abc,,,,0.5,,,");
+        assert!(matches!(change.to_message(&scales, &mut ids), Ok(CoinbaseMessage::Change { new_qty: 50_000_000, .. })));
+
+        let market = row("BTC-USD,received,buy,,,abc,,,,,,,");
+        assert!(matches!(market.to_message(&scales, &mut ids), Ok(CoinbaseMessage::Received { price: None, qty: None, .. })));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let scales = ScaleTable::default();
+        let mut ids = OrderIdInterner::new();
+
+        let bad_side = row("BTC-USD,open,up,100,1,abc,,,,,,,");
+        assert_eq!(bad_side.to_message(&scales, &mut ids).unwrap_err(), ParseError::InvalidSide("up".to_string()));
+
+        let no_reason = row("BTC-USD,done,buy,,,abc,,,,,,,");
+        assert_eq!(no_reason.to_message(&scales, &mut ids).unwrap_err(), ParseError::MissingField("reason"));
+
+        // 50 BTC at 1e8 does not fit in u32
+        let too_big = row("BTC-USD,open,buy,100,50,abc,,,,,,,");
+        assert!(matches!(too_big.to_message(&scales, &mut ids), Err(ParseError::OutOfRange { field: "amount", .. })));
+
+        let too_precise = row("BTC-USD,open,buy,100.001,1,abc,,,,,,,");
+        assert!(matches!(too_precise.to_message(&scales, &mut ids), Err(ParseError::Precision { field: "price", .. })));
+
+        let unknown = row("BTC-USD,activate,buy,,,abc,,,,,,,");
+        assert_eq!(unknown.to_message(&scales, &mut ids).unwrap_err(), ParseError::UnknownType("activate".to_string()));
+
+        // Rejected rows never consume an ID
+        assert!(ids.is_empty());
+        assert_eq!(ids.high_watermark(), 1);
+    }
+
+    #[test]
+    fn test_per_product_scales() {
+        let mut ids = OrderIdInterner::new();
+        let mut scales = ScaleTable::default();
+        scales.insert("BTC-USD", "100:10000".parse().unwrap());
+
+        let btc = row("BTC-USD,open,buy,100,50,abc,,,,,,,");
+        assert!(matches!(btc.to_message(&scales, &mut ids), Ok(CoinbaseMessage::Open { qty: 500_000, price: 10000, .. })));
+
+        // Unlisted products fall back to the default scale
+        let eth = row("ETH-USD,open,buy,100,50,abc,,,,,,,");
+        assert!(eth.to_message(&scales, &mut ids).is_err());
+
+        assert!("100".parse::<Scale>().is_err());
+        assert!("0:1".parse::<Scale>().is_err());
+    }
+
+    #[test]
+    fn test_replay_cancels_orders_the_feed_filled() {
+        let scales = ScaleTable::default();
+        let mut ids = OrderIdInterner::new();
+        let mut replay = L3Replayer::new(100);
+        let rows = [
+            "BTC-USD,received,sell,100,1,maker,,,,,,,",
+            // Funds-based market buy: the feed fills the maker, the engine can't
+            "BTC-USD,received,buy,,,taker,,,,,,,",
+            "BTC-USD,match,sell,100,1,maker,1,,taker,,,,",
+            "BTC-USD,done,buy,,,taker,,filled,,,,,",
+            "BTC-USD,done,sell,,,maker,,filled,,,0,,",
+        ];
+        let mut maker_id = None;
+        for text in rows {
+            let msg = row(text).to_message(&scales, &mut ids).unwrap();
+            maker_id = maker_id.or(ids.get("maker"));
+            replay.apply(&msg);
+        }
+
+        assert_eq!((replay.matches_validated, replay.matches_missed), (0, 1));
+        assert_eq!(replay.unfilled_dones, 1);
+        assert_eq!(replay.engine().order_count(), 0, "done removes the order the engine still held");
+
+        // The maker's recycled ID now rests cleanly under a new UUID
+        let msg = row("BTC-USD,received,sell,101,1,next,,,,,,,").to_message(&scales, &mut ids).unwrap();
+        let CoinbaseMessage::Received { order_id, .. } = msg else { panic!("unexpected {:?}", msg) };
+        assert_eq!(Some(order_id), maker_id);
+        replay.apply(&msg);
+        assert_eq!(replay.engine().best_ask(), Some(10100));
+        assert_eq!(replay.engine().order_count(), 1);
+    }
+
+    #[test]
+    fn test_reconstruction_validates_fifo_matches() {
+        let mut recon = L3Reconstructor::new(100);
+        let out = run(&mut recon, &[
+            open(1, Side::Ask, 100, 5),
+            open(2, Side::Ask, 100, 5),
+            open(3, Side::Ask, 101, 5),
+            CoinbaseMessage::Received { order_id: 9, side: Side::Bid, price: Some(101), qty: Some(12) },
+            matched(1, 9, 100, 5),
+            matched(2, 9, 100, 5),
+            matched(3, 9, 101, 2),
+            CoinbaseMessage::Done { order_id: 9, side: Side::Bid, reason: DoneReason::Filled, remaining_qty: Some(0) },
+        ]);
+
+        assert!(out.is_empty(), "{:?}", out);
+        assert_eq!(recon.matches_validated, 3);
+        assert_eq!(recon.engine().best_ask(), Some(101));
+        let level = recon.engine().matcher.book.get_level(Side::Ask, 101).unwrap();
+        assert_eq!(level.total_qty, 3);
+    }
+
+    #[test]
+    fn test_reconstruction_reports_divergences() {
+        let mut recon = L3Reconstructor::new(100);
+        let out = run(&mut recon, &[
+            open(1, Side::Ask, 100, 5),
+            open(2, Side::Ask, 100, 5),
+            // Exchange fills order 2 ahead of order 1
+            CoinbaseMessage::Received { order_id: 9, side: Side::Bid, price: Some(100), qty: Some(8) },
+            matched(2, 9, 100, 5),
+            // Then stops short, leaving crossable liquidity
+            CoinbaseMessage::Done { order_id: 9, side: Side::Bid, reason: DoneReason::Canceled, remaining_qty: Some(3) },
+        ]);
+
+        let expected_first = Fill { maker_order_id: 1, price: 100, qty: 5 };
+        assert_eq!(out[0], Divergence::Mismatch {
+            taker_order_id: 9,
+            predicted: expected_first,
+            actual: Fill { maker_order_id: 2, price: 100, qty: 5 },
+        });
+        assert_eq!(out[1], Divergence::Missed {
+            taker_order_id: 9,
+            predicted: Fill { maker_order_id: 1, price: 100, qty: 3 },
+        });
+        assert_eq!(recon.divergences, 2);
+    }
+
+    #[test]
+    fn test_reconstruction_change_keeps_priority() {
+        let mut recon = L3Reconstructor::new(100);
+        let out = run(&mut recon, &[
+            open(1, Side::Bid, 100, 10),
+            open(2, Side::Bid, 100, 10),
+            CoinbaseMessage::Change { order_id: 1, new_qty: 4, price: 100 },
+            CoinbaseMessage::Received { order_id: 9, side: Side::Ask, price: None, qty: None },
+            matched(1, 9, 100, 4),
+            matched(2, 9, 100, 1),
+        ]);
+
+        assert!(out.is_empty(), "{:?}", out);
+        assert_eq!(recon.matches_validated, 2);
+    }
 }
@@ -3,8 +3,12 @@
 //! Commands are inputs from the network thread.
 //! Events are outputs to market data consumers.
 
+use serde::{Deserialize, Serialize};
+
+use crate::arena::ArenaHandle;
+
 /// Order side (bid = buy, ask = sell)
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum Side {
     /// Buy side (bids)
@@ -29,7 +33,7 @@ impl Side {
 // ============================================================================
 
 /// Order type determines matching behavior
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum OrderType {
     /// Limit order - rests in book if not fully matched (default)
@@ -42,7 +46,7 @@ pub enum OrderType {
 }
 
 /// Place a new limit order
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct PlaceOrder {
     /// External order ID (client-assigned)
     pub order_id: u64,
@@ -55,6 +59,7 @@ pub struct PlaceOrder {
     /// Order quantity
     pub qty: u32,
     /// Order type (Limit, IOC, FOK)
+    #[serde(default)]
     pub order_type: OrderType,
 }
 
@@ -100,14 +105,14 @@ impl PlaceOrder {
 }
 
 /// Cancel an existing order
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct CancelOrder {
     /// Order ID to cancel
     pub order_id: u64,
 }
 
 /// Modify an existing order (cancel + replace)
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct ModifyOrder {
     /// Original order ID
     pub order_id: u64,
@@ -119,8 +124,32 @@ pub struct ModifyOrder {
     pub new_qty: u32,
 }
 
+/// Opaque reference to a resting order, returned in `OrderAccepted`.
+///
+/// Lets a client cancel or modify without an order ID lookup. Goes stale
+/// once the order leaves the book; the default handle never matches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OrderHandle {
+    index: u32,
+    generation: u32,
+}
+
+impl From<ArenaHandle> for OrderHandle {
+    #[inline]
+    fn from(handle: ArenaHandle) -> Self {
+        Self { index: handle.index, generation: handle.generation }
+    }
+}
+
+impl From<OrderHandle> for ArenaHandle {
+    #[inline]
+    fn from(handle: OrderHandle) -> Self {
+        ArenaHandle { index: handle.index, generation: handle.generation }
+    }
+}
+
 /// Input commands from the network thread
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Command {
     /// Place a new limit order
     Place(PlaceOrder),
@@ -128,6 +157,10 @@ pub enum Command {
     Cancel(CancelOrder),
     /// Modify an existing order
     Modify(ModifyOrder),
+    /// Cancel via the handle from `OrderAccepted` (no order ID lookup)
+    CancelByHandle(CancelOrder, OrderHandle),
+    /// Modify via the handle from `OrderAccepted` (no order ID lookup)
+    ModifyByHandle(ModifyOrder, OrderHandle),
 }
 
 // ============================================================================
@@ -135,7 +168,7 @@ pub enum Command {
 // ============================================================================
 
 /// A trade was executed
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TradeEvent {
     /// Execution price
     pub price: u64,
@@ -154,7 +187,7 @@ pub struct TradeEvent {
 }
 
 /// Order book level update (Level 2 market data)
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BookUpdate {
     /// Which side changed
     pub side: Side,
@@ -166,32 +199,73 @@ pub struct BookUpdate {
     pub new_count: u32,
 }
 
+/// Top of book changed (best bid/ask price or size)
+///
+/// A missing side is reported as `None` price with zero quantity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BboUpdate {
+    /// Best bid price
+    pub bid_price: Option<u64>,
+    /// Total quantity at the best bid
+    pub bid_qty: u64,
+    /// Best ask price
+    pub ask_price: Option<u64>,
+    /// Total quantity at the best ask
+    pub ask_qty: u64,
+}
+
+/// A growable arena's allocations reached its high watermark
+///
+/// Raised once per crossing; the warning re-arms after usage falls back
+/// an eighth below the watermark.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArenaHighWater {
+    /// Orders allocated in the arena
+    pub allocated: u32,
+    /// Nodes currently backed by memory
+    pub capacity: u32,
+    /// Hard cap the arena can grow to
+    pub max_capacity: u32,
+}
+
 /// Order was accepted and resting in the book
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OrderAccepted {
     pub order_id: u64,
     pub price: u64,
     pub qty: u32,
     pub side: Side,
+    /// Handle for `Command::CancelByHandle` / `Command::ModifyByHandle`
+    pub handle: OrderHandle,
 }
 
 /// Order was canceled
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OrderCanceled {
     pub order_id: u64,
     /// Remaining quantity that was canceled
     pub canceled_qty: u32,
 }
 
+/// Resting order's quantity was reduced in place, keeping its queue priority
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderReduced {
+    pub order_id: u64,
+    /// Quantity taken off the order
+    pub reduced_by: u32,
+    /// Quantity still resting
+    pub remaining: u32,
+}
+
 /// Order was rejected
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OrderRejected {
     pub order_id: u64,
     pub reason: RejectReason,
 }
 
 /// Reasons for order rejection
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum RejectReason {
     /// Order ID already exists
@@ -209,18 +283,24 @@ pub enum RejectReason {
 }
 
 /// Output events from the matching engine
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OutputEvent {
     /// Trade executed
     Trade(TradeEvent),
     /// Book level changed
     BookDelta(BookUpdate),
+    /// Best bid/ask changed (only when enabled in `EngineConfig`)
+    Bbo(BboUpdate),
     /// Order accepted and resting
     Accepted(OrderAccepted),
     /// Order canceled
     Canceled(OrderCanceled),
+    /// Resting order reduced without losing priority (feed replay only)
+    Reduced(OrderReduced),
     /// Order rejected
     Rejected(OrderRejected),
+    /// Order arena is nearly exhausted (growable arenas only)
+    ArenaHighWater(ArenaHighWater),
 }
 
 #[cfg(test)]
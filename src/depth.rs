@@ -0,0 +1,112 @@
+//! Depth - Read-only views over book levels.
+//!
+//! Iterators and summaries returned by the `OrderBook` depth queries
+//! (`levels`, `top_levels`, `level_orders`, `cumulative_depth`,
+//! `cost_to_fill`). None of them allocate, so they are safe to call from
+//! risk checks and UI snapshot code without touching the level storage.
+
+use crate::arena::{Arena, ArenaIndex, OrderNode, NULL_INDEX};
+use crate::book_side::SideIter;
+use crate::command::Side;
+use crate::price_level::PriceLevel;
+
+/// Aggregate state of one price level
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LevelDepth {
+    pub price: u64,
+    /// Total resting quantity
+    pub qty: u64,
+    /// Number of resting orders
+    pub count: u32,
+}
+
+impl LevelDepth {
+    #[inline]
+    fn new(price: u64, level: &PriceLevel) -> Self {
+        Self { price, qty: level.total_qty, count: level.count }
+    }
+}
+
+/// Levels of one side, best price first
+#[derive(Clone, Debug)]
+pub struct Levels<'a> {
+    inner: SideIter<'a>,
+    side: Side,
+}
+
+impl<'a> Levels<'a> {
+    #[inline]
+    pub(crate) fn new(inner: SideIter<'a>, side: Side) -> Self {
+        Self { inner, side }
+    }
+}
+
+impl Iterator for Levels<'_> {
+    type Item = LevelDepth;
+
+    #[inline]
+    fn next(&mut self) -> Option<LevelDepth> {
+        let (price, level) = match self.side {
+            Side::Bid => self.inner.next_back(),
+            Side::Ask => self.inner.next(),
+        }?;
+        Some(LevelDepth::new(price, level))
+    }
+}
+
+/// Orders resting at one level, in time priority
+#[derive(Clone, Debug)]
+pub struct LevelOrders<'a> {
+    arena: &'a Arena,
+    next: ArenaIndex,
+}
+
+impl<'a> LevelOrders<'a> {
+    #[inline]
+    pub(crate) fn new(arena: &'a Arena, head: ArenaIndex) -> Self {
+        Self { arena, next: head }
+    }
+}
+
+impl<'a> Iterator for LevelOrders<'a> {
+    type Item = &'a OrderNode;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a OrderNode> {
+        if self.next == NULL_INDEX {
+            return None;
+        }
+        let node = self.arena.get(self.next);
+        self.next = node.next;
+        Some(node)
+    }
+}
+
+/// Result of walking the book for a hypothetical market order
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FillEstimate {
+    /// Quantity asked for
+    pub requested: u64,
+    /// Quantity available to fill (at most `requested`)
+    pub filled_qty: u64,
+    /// Sum of price x qty over the filled quantity
+    pub notional: u128,
+    /// Price of the last level touched
+    pub worst_price: Option<u64>,
+    /// Number of levels touched
+    pub levels: u32,
+}
+
+impl FillEstimate {
+    /// Returns true if the book holds the full requested quantity
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.filled_qty == self.requested
+    }
+
+    /// Volume-weighted average price of the filled quantity
+    #[inline]
+    pub fn vwap(&self) -> Option<f64> {
+        (self.filled_qty > 0).then(|| self.notional as f64 / self.filled_qty as f64)
+    }
+}
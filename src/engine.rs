@@ -2,26 +2,127 @@
 //!
 //! Wraps the matching engine with I/O handling via rtrb ring buffers.
 
-use crate::command::{Command, OutputEvent};
+use crate::backend::BookBackend;
+use crate::command::{BboUpdate, Command, ModifyOrder, OutputEvent, Side};
 use crate::matching::MatchingEngine;
+use crate::order_book::{OrderBook, OrderInfo};
+use crate::validate::InvariantViolation;
+
+/// Events the output buffer holds before growing. A command emits about
+/// two events per level it touches, so only sweeps through more than ~100
+/// levels allocate (once; the buffer keeps its capacity).
+const EVENT_BUFFER_CAPACITY: usize = 256;
+
+/// Commands between book invariant checks (`validate` feature)
+#[cfg(feature = "validate")]
+pub const VALIDATE_INTERVAL: u32 = 1024;
+
+/// Optional output shaping applied per command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EngineConfig {
+    /// Emit at most one `BookDelta` per (side, price) per command,
+    /// carrying the level's final state
+    pub conflate_deltas: bool,
+    /// Emit `OutputEvent::Bbo` after any command that changes the best
+    /// bid/ask price or size
+    pub emit_bbo: bool,
+}
+
+/// An item on the engine's command ring.
+///
+/// Plain `Command`s produce plain `OutputEvent`s; a `(tag, Command)` pair
+/// tags each of its events with the same value (e.g. the connection that
+/// submitted it), so the consumer can attribute them.
+#[cfg(feature = "runtime")]
+pub trait EngineInput: Copy {
+    /// Item on the output ring
+    type Output: Copy;
+
+    fn command(&self) -> Command;
+
+    fn output(&self, event: OutputEvent) -> Self::Output;
+}
+
+#[cfg(feature = "runtime")]
+impl EngineInput for Command {
+    type Output = OutputEvent;
+
+    #[inline]
+    fn command(&self) -> Command {
+        *self
+    }
+
+    #[inline]
+    fn output(&self, event: OutputEvent) -> OutputEvent {
+        event
+    }
+}
+
+#[cfg(feature = "runtime")]
+impl<T: Copy> EngineInput for (T, Command) {
+    type Output = (T, OutputEvent);
+
+    #[inline]
+    fn command(&self) -> Command {
+        self.1
+    }
+
+    #[inline]
+    fn output(&self, event: OutputEvent) -> (T, OutputEvent) {
+        (self.0, event)
+    }
+}
 
 /// The main engine that processes commands from a ring buffer.
 ///
 /// Uses the rtrb crate for lock-free SPSC communication.
-pub struct Engine {
+pub struct Engine<B: BookBackend = OrderBook> {
     /// The underlying matching engine
-    pub matcher: MatchingEngine,
+    pub matcher: MatchingEngine<B>,
     /// Reusable buffer for output events to avoid allocation
     pub event_buffer: Vec<OutputEvent>,
+    config: EngineConfig,
+    /// Last published top of book (BBO mode)
+    last_bbo: BboUpdate,
+    /// (side, price, buffer index) of each delta in the current command,
+    /// reused across commands (conflation mode)
+    delta_keys: Vec<(u8, u64, u32)>,
+    /// Commands since the last invariant check
+    #[cfg(feature = "validate")]
+    commands_since_validate: u32,
 }
 
 impl Engine {
     pub fn new(capacity: u32) -> Self {
+        Self::with_config(capacity, EngineConfig::default())
+    }
+
+    /// Create an engine with delta conflation and/or BBO events enabled.
+    pub fn with_config(capacity: u32, config: EngineConfig) -> Self {
+        Self::with_matcher(MatchingEngine::new(capacity), config)
+    }
+}
+
+impl<B: BookBackend> Engine<B> {
+    /// Wrap an existing matching engine (e.g. one built with
+    /// `MatchingEngine::with_storage` or `MatchingEngine::with_book`).
+    pub fn with_matcher(matcher: MatchingEngine<B>, config: EngineConfig) -> Self {
         Self {
-            matcher: MatchingEngine::new(capacity),
-            event_buffer: Vec::with_capacity(16), // Pre-allocate small buffer
+            matcher,
+            event_buffer: Vec::with_capacity(EVENT_BUFFER_CAPACITY),
+            config,
+            last_bbo: BboUpdate::default(),
+            delta_keys: Vec::with_capacity(EVENT_BUFFER_CAPACITY),
+            #[cfg(feature = "validate")]
+            commands_since_validate: 0,
         }
     }
+
+    /// Output shaping options in effect.
+    #[inline]
+    pub fn config(&self) -> EngineConfig {
+        self.config
+    }
     
     /// Run the engine event loop.
     ///
@@ -31,13 +132,29 @@ impl Engine {
     /// * `pin_to_core` - Whether to pin to the last available CPU core
     ///
     /// # Note
-    /// This function runs forever (until the program terminates).
+    /// This function runs forever (until the program terminates). Events
+    /// are never dropped: when the output ring is full the engine spins
+    /// until the consumer makes room.
+    #[cfg(feature = "runtime")]
+    pub fn run<I: EngineInput>(
+        &mut self,
+        input: &mut rtrb::Consumer<I>,
+        output: &mut rtrb::Producer<I::Output>,
+        pin_to_core: bool,
+    ) {
+        self.run_notifying(input, output, pin_to_core, || {});
+    }
+
+    /// Like `run`, calling `notify` after each batch of events is pushed
+    /// and whenever the output ring is full (e.g. to unpark a consumer
+    /// that sleeps while the ring is empty).
     #[cfg(feature = "runtime")]
-    pub fn run(
+    pub fn run_notifying<I: EngineInput>(
         &mut self,
-        input: &mut rtrb::Consumer<Command>,
-        output: &mut rtrb::Producer<OutputEvent>,
+        input: &mut rtrb::Consumer<I>,
+        output: &mut rtrb::Producer<I::Output>,
         pin_to_core: bool,
+        mut notify: impl FnMut(),
     ) {
         // Pin to isolated CPU core
         if pin_to_core {
@@ -49,13 +166,24 @@ impl Engine {
         
         // Main event loop (busy-wait)
         loop {
-            while let Ok(cmd) = input.pop() {
-                let events = self.process_command(cmd);
+            let mut produced = false;
+            while let Ok(item) = input.pop() {
+                let events = self.process_command(item.command());
                 for event in events {
-                    // Best effort - drop if full
-                    let _ = output.push(event);
+                    let mut out = item.output(*event);
+                    // Back-pressure: a dropped delta or report would leave
+                    // downstream book images and order state wrong for good
+                    while let Err(rtrb::PushError::Full(rejected)) = output.push(out) {
+                        out = rejected;
+                        notify();
+                        std::hint::spin_loop();
+                    }
+                    produced = true;
                 }
             }
+            if produced {
+                notify();
+            }
             std::hint::spin_loop();
         }
     }
@@ -93,21 +221,124 @@ impl Engine {
                 // 2. Place if succeeded
                 if cancel_succeeded {
                     if let Some(info) = original_info {
-                        self.matcher.process_place(crate::command::PlaceOrder {
-                            order_id: modify.new_order_id,
-                            user_id: info.user_id,
-                            side: info.side,
-                            price: modify.new_price,
-                            qty: modify.new_qty,
-                            order_type: crate::command::OrderType::Limit,
-                        }, &mut self.event_buffer);
+                        self.place_replacement(modify, &info);
                     }
                 }
             }
+            Command::CancelByHandle(cancel, handle) => {
+                self.matcher.process_cancel_by_handle(cancel, handle, &mut self.event_buffer);
+            }
+            Command::ModifyByHandle(modify, handle) => {
+                let cancel = crate::command::CancelOrder { order_id: modify.order_id };
+                if let Some(info) = self.matcher.process_cancel_by_handle(cancel, handle, &mut self.event_buffer) {
+                    self.place_replacement(modify, &info);
+                }
+            }
+        }
+
+        if self.config.conflate_deltas {
+            self.conflate_deltas();
         }
+        if self.config.emit_bbo {
+            self.push_bbo_if_changed();
+        }
+        #[cfg(feature = "validate")]
+        self.validate_periodically();
         
         &self.event_buffer
     }
+
+    /// Check book invariants (see `MatchingEngine::validate`).
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        self.matcher.validate()
+    }
+
+    /// Validate every `VALIDATE_INTERVAL` commands, panicking on a violation
+    #[cfg(feature = "validate")]
+    fn validate_periodically(&mut self) {
+        self.commands_since_validate += 1;
+        if self.commands_since_validate >= VALIDATE_INTERVAL {
+            self.commands_since_validate = 0;
+            if let Err(violation) = self.matcher.validate() {
+                panic!("book invariant violated: {}", violation);
+            }
+        }
+    }
+
+    /// Place the new order of a modify whose original was just canceled
+    fn place_replacement(&mut self, modify: ModifyOrder, original: &OrderInfo) {
+        self.matcher.process_place(crate::command::PlaceOrder {
+            order_id: modify.new_order_id,
+            user_id: original.user_id,
+            side: original.side,
+            price: modify.new_price,
+            qty: modify.new_qty,
+            order_type: crate::command::OrderType::Limit,
+        }, &mut self.event_buffer);
+    }
+
+    /// Drop every `BookDelta` that a later delta for the same level supersedes.
+    ///
+    /// Sorts the deltas' (side, price, index) keys so repeats of a level
+    /// are adjacent, keeps the indices of all but the last one, then
+    /// compacts the buffer in one pass. Relative order is preserved and the
+    /// surviving delta for each level is the last (final-state) one.
+    ///
+    /// # Complexity
+    /// O(n log n) in the command's delta count
+    fn conflate_deltas(&mut self) {
+        let keys = &mut self.delta_keys;
+        keys.clear();
+        for (i, event) in self.event_buffer.iter().enumerate() {
+            if let OutputEvent::BookDelta(d) = event {
+                keys.push((d.side as u8, d.price, i as u32));
+            }
+        }
+        if keys.len() < 2 {
+            return;
+        }
+
+        // Keep only superseded entries, then order them by buffer index
+        keys.sort_unstable();
+        let mut superseded = 0;
+        for i in 0..keys.len() - 1 {
+            if (keys[i].0, keys[i].1) == (keys[i + 1].0, keys[i + 1].1) {
+                keys[superseded] = keys[i];
+                superseded += 1;
+            }
+        }
+        if superseded == 0 {
+            return;
+        }
+        keys.truncate(superseded);
+        keys.sort_unstable_by_key(|&(_, _, i)| i);
+
+        let mut drop = keys.iter().map(|&(_, _, i)| i as usize).peekable();
+        let mut read = 0;
+        self.event_buffer.retain(|_| {
+            let keep = drop.next_if_eq(&read).is_none();
+            read += 1;
+            keep
+        });
+    }
+
+    /// Append a BBO event if the top of book differs from the last one sent.
+    fn push_bbo_if_changed(&mut self) {
+        let book = &self.matcher.book;
+        let bid_price = book.best_bid();
+        let ask_price = book.best_ask();
+        let bbo = BboUpdate {
+            bid_price,
+            bid_qty: bid_price.map_or(0, |p| book.depth_at(Side::Bid, p).0),
+            ask_price,
+            ask_qty: ask_price.map_or(0, |p| book.depth_at(Side::Ask, p).0),
+        };
+
+        if bbo != self.last_bbo {
+            self.last_bbo = bbo;
+            self.event_buffer.push(OutputEvent::Bbo(bbo));
+        }
+    }
     
     /// Pin the current thread to the last available CPU core.
     ///
@@ -239,6 +470,125 @@ mod tests {
         assert_eq!(engine1.state_hash(), engine2.state_hash());
     }
     
+    #[test]
+    fn test_conflated_deltas() {
+        let config = EngineConfig { conflate_deltas: true, emit_bbo: false };
+        let mut engine = Engine::with_config(1000, config);
+
+        for i in 0..5 {
+            engine.process_command(Command::Place(PlaceOrder::limit(i, 1, Side::Ask, 10000, 10)));
+        }
+        engine.process_command(Command::Place(PlaceOrder::limit(10, 1, Side::Ask, 10010, 10)));
+
+        // Sweep four makers at 10000 and part of 10010
+        let events = engine.process_command(Command::Place(PlaceOrder::limit(20, 2, Side::Bid, 10010, 55)));
+        let trades = events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count();
+        let deltas: Vec<_> = events.iter()
+            .filter_map(|e| if let OutputEvent::BookDelta(d) = e { Some(*d) } else { None })
+            .collect();
+
+        assert_eq!(trades, 6);
+        assert_eq!(deltas.len(), 2);
+        assert_eq!((deltas[0].price, deltas[0].new_qty, deltas[0].new_count), (10000, 0, 0));
+        assert_eq!((deltas[1].price, deltas[1].new_qty, deltas[1].new_count), (10010, 5, 1));
+        // Final delta for each level follows all of its trades
+        assert!(matches!(events.last(), Some(OutputEvent::BookDelta(_))));
+    }
+
+    #[test]
+    fn test_conflated_sweep_across_many_levels() {
+        let config = EngineConfig { conflate_deltas: true, emit_bbo: false };
+        let mut engine = Engine::with_config(2000, config);
+        for level in 0..300 {
+            for i in 0..2 {
+                engine.process_command(Command::Place(PlaceOrder::limit(level * 2 + i, 1, Side::Ask, 10000 + level, 10)));
+            }
+        }
+
+        let events = engine.process_command(Command::Place(PlaceOrder::limit(9999, 2, Side::Bid, 10299, 5995)));
+        let deltas: Vec<_> = events.iter()
+            .filter_map(|e| if let OutputEvent::BookDelta(d) = e { Some(*d) } else { None })
+            .collect();
+        assert_eq!(events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count(), 600);
+        assert_eq!(deltas.len(), 300);
+        assert!(deltas[..299].iter().zip(10000..).all(|(d, price)| (d.price, d.new_qty, d.new_count) == (price, 0, 0)));
+        assert_eq!((deltas[299].price, deltas[299].new_qty, deltas[299].new_count), (10299, 5, 1));
+    }
+
+    #[test]
+    fn test_conflated_modify_same_level() {
+        let config = EngineConfig { conflate_deltas: true, emit_bbo: false };
+        let mut engine = Engine::with_config(1000, config);
+        engine.process_command(Command::Place(PlaceOrder::limit(1, 1, Side::Bid, 10000, 10)));
+
+        let events = engine.process_command(Command::Modify(crate::command::ModifyOrder {
+            order_id: 1,
+            new_order_id: 2,
+            new_price: 10000,
+            new_qty: 20,
+        }));
+        let deltas: Vec<_> = events.iter()
+            .filter_map(|e| if let OutputEvent::BookDelta(d) = e { Some(*d) } else { None })
+            .collect();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!((deltas[0].new_qty, deltas[0].new_count), (20, 1));
+    }
+
+    #[test]
+    fn test_modify_by_handle_chains_handles() {
+        let mut engine = Engine::new(1000);
+        let accepted = |events: &[OutputEvent]| events.iter().find_map(|e| {
+            if let OutputEvent::Accepted(a) = e { Some(*a) } else { None }
+        });
+        let mut handle = accepted(engine.process_command(Command::Place(PlaceOrder::limit(1, 7, Side::Ask, 10100, 10)))).unwrap().handle;
+
+        for (old, new) in [(1, 2), (2, 3)] {
+            let modify = ModifyOrder { order_id: old, new_order_id: new, new_price: 10100 + new, new_qty: 5 };
+            let events = engine.process_command(Command::ModifyByHandle(modify, handle));
+            assert!(matches!(events[0], OutputEvent::Canceled(_)));
+            let replaced = accepted(events).unwrap();
+            assert_eq!((replaced.order_id, replaced.side), (new, Side::Ask));
+            handle = replaced.handle;
+        }
+        assert_eq!(engine.order_count(), 1);
+        assert_eq!(engine.best_ask(), Some(10103));
+
+        // A stale handle rejects the modify without placing the replacement
+        let modify = ModifyOrder { order_id: 3, new_order_id: 4, new_price: 10100, new_qty: 5 };
+        engine.process_command(Command::CancelByHandle(crate::command::CancelOrder { order_id: 3 }, handle));
+        let events = engine.process_command(Command::ModifyByHandle(modify, handle));
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], OutputEvent::Rejected(_)));
+        assert_eq!(engine.order_count(), 0);
+    }
+
+    #[test]
+    fn test_bbo_events_only_on_change() {
+        let config = EngineConfig { conflate_deltas: false, emit_bbo: true };
+        let mut engine = Engine::with_config(1000, config);
+        let bbo = |events: &[OutputEvent]| events.iter().find_map(|e| {
+            if let OutputEvent::Bbo(b) = e { Some(*b) } else { None }
+        });
+
+        let events = engine.process_command(Command::Place(PlaceOrder::limit(1, 1, Side::Bid, 10000, 10)));
+        assert_eq!(bbo(events), Some(BboUpdate { bid_price: Some(10000), bid_qty: 10, ask_price: None, ask_qty: 0 }));
+
+        // Behind the top of book: no BBO event
+        let events = engine.process_command(Command::Place(PlaceOrder::limit(2, 1, Side::Bid, 9990, 10)));
+        assert_eq!(bbo(events), None);
+
+        // Size change at the top
+        let events = engine.process_command(Command::Place(PlaceOrder::limit(3, 1, Side::Bid, 10000, 5)));
+        assert_eq!(bbo(events).map(|b| b.bid_qty), Some(15));
+
+        // Rejection leaves the book unchanged
+        let events = engine.process_command(Command::Cancel(CancelOrder { order_id: 99 }));
+        assert_eq!(bbo(events), None);
+
+        let events = engine.process_command(Command::Place(PlaceOrder::limit(4, 2, Side::Ask, 10010, 7)));
+        assert_eq!(bbo(events), Some(BboUpdate { bid_price: Some(10000), bid_qty: 15, ask_price: Some(10010), ask_qty: 7 }));
+    }
+
     #[test]
     fn test_engine_warm_up() {
         let mut engine = Engine::new(1000);
@@ -0,0 +1,198 @@
+//! Fuzzing - Decode arbitrary bytes into command streams and check them.
+//!
+//! Shared by the cargo-fuzz targets in `fuzz/` and the deterministic runs in
+//! `tests/fuzz.rs`. `check_commands` decodes a byte string into `Command`s,
+//! runs them through an `Engine` and the `ReferenceMatcher`, and panics on
+//! the first problem: a panic inside the engine, a broken invariant (see
+//! `MatchingEngine::validate`, which covers crossed books and arena
+//! accounting), or an event stream that differs from the reference.
+//!
+//! # Input format
+//!
+//! The first byte picks the backend (tree, ladder or vec book). The rest is
+//! a sequence of commands, each a tag byte followed by field bytes; a
+//! truncated command reads zeros. Field bytes mostly map to a narrow band of
+//! IDs, prices and quantities so orders collide and cross, with the top of
+//! each byte range reserved for edge values such as `u64::MAX` prices and
+//! `u32::MAX` quantities. The ladder won't rest orders beyond
+//! `MAX_SPAN_TICKS` of its live levels, which the reference does not model,
+//! so its edge prices stay within one span (far enough to force recentering
+//! and growth).
+
+use crate::backend::BookBackend;
+use crate::book_side::LevelStorage;
+use crate::command::{
+    CancelOrder, Command, ModifyOrder, OrderAccepted, OrderHandle, OrderType, OutputEvent, PlaceOrder, Side,
+};
+use crate::engine::{Engine, EngineConfig};
+use crate::ladder::{LadderConfig, MAX_SPAN_TICKS};
+use crate::matching::MatchingEngine;
+use crate::reference::ReferenceMatcher;
+use crate::vec_book::VecBook;
+
+/// Arena capacity of the fuzzed engine; small, so `ArenaFull` is reachable
+pub const FUZZ_CAPACITY: u32 = 64;
+
+/// Prices selected by field bytes at the top of the range
+const EDGE_PRICES: [u64; 8] = [0, 1, u64::MAX, u64::MAX - 1, u32::MAX as u64, u32::MAX as u64 + 1, 1 << 63, 1 << 32];
+
+/// Edge prices for the ladder backend, all within one span of each other
+const LADDER_EDGE_PRICES: [u64; 8] = [0, 1, 999, 1_016, 4_096, 1 << 16, MAX_SPAN_TICKS / 2, MAX_SPAN_TICKS - 1];
+
+/// Quantities selected by field bytes at the top of the range
+const EDGE_QTYS: [u32; 4] = [0, u32::MAX, u32::MAX - 1, 1 << 31];
+
+/// Reads command fields from fuzzer bytes
+struct Decoder<'a> {
+    data: &'a [u8],
+    edge_prices: &'static [u64; 8],
+}
+
+impl Decoder<'_> {
+    fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((&b, rest)) => {
+                self.data = rest;
+                b
+            }
+            None => 0,
+        }
+    }
+
+    fn order_id(&mut self) -> u64 {
+        // A small pool, so IDs collide (duplicates, reuse after fills)
+        1 + (self.byte() % 24) as u64
+    }
+
+    fn price(&mut self) -> u64 {
+        match self.byte() {
+            b @ 0..=247 => 1_000 + (b % 16) as u64,
+            b => self.edge_prices[(b - 248) as usize],
+        }
+    }
+
+    fn qty(&mut self) -> u32 {
+        match self.byte() {
+            b @ 0..=251 => 1 + (b % 32) as u32,
+            b => EDGE_QTYS[(b - 252) as usize],
+        }
+    }
+
+    fn side(&mut self) -> Side {
+        if self.byte() & 1 == 0 { Side::Bid } else { Side::Ask }
+    }
+
+    fn place(&mut self, order_type: OrderType) -> PlaceOrder {
+        PlaceOrder {
+            order_id: self.order_id(),
+            user_id: (self.byte() % 4) as u64,
+            side: self.side(),
+            price: self.price(),
+            qty: self.qty(),
+            order_type,
+        }
+    }
+
+    fn modify(&mut self) -> ModifyOrder {
+        ModifyOrder { order_id: self.order_id(), new_order_id: self.order_id(), new_price: self.price(), new_qty: self.qty() }
+    }
+}
+
+/// Engine and reference run side by side, with every handle the engine has
+/// handed out (stale ones included)
+struct Harness<B: BookBackend> {
+    engine: Engine<B>,
+    reference: ReferenceMatcher,
+    handles: Vec<(u64, OrderHandle)>,
+}
+
+impl<B: BookBackend> Harness<B> {
+    fn new(matcher: MatchingEngine<B>) -> Self {
+        Self {
+            engine: Engine::with_matcher(matcher, EngineConfig::default()),
+            reference: ReferenceMatcher::new(FUZZ_CAPACITY as usize),
+            handles: Vec::new(),
+        }
+    }
+
+    /// Next command, or `None` at the end of the input
+    fn decode(&mut self, input: &mut Decoder<'_>) -> Option<Command> {
+        if input.data.is_empty() {
+            return None;
+        }
+        let tag = input.byte();
+        Some(match tag % 8 {
+            0 | 1 => Command::Place(input.place(OrderType::Limit)),
+            2 => Command::Place(input.place(OrderType::IOC)),
+            3 => Command::Place(input.place(OrderType::FOK)),
+            4 => Command::Cancel(CancelOrder { order_id: input.order_id() }),
+            5 => Command::Modify(input.modify()),
+            // Handle commands use handles the engine handed out, including
+            // stale ones whose order has left the book or whose ID was placed
+            // again; the reference expects those to be rejected
+            _ => {
+                let pick = input.byte() as usize;
+                let Some(&(order_id, handle)) = self.handles.get(pick % self.handles.len().max(1)) else {
+                    return Some(Command::Cancel(CancelOrder { order_id: input.order_id() }));
+                };
+                if tag % 8 == 6 {
+                    Command::CancelByHandle(CancelOrder { order_id }, handle)
+                } else {
+                    Command::ModifyByHandle(ModifyOrder { order_id, ..input.modify() }, handle)
+                }
+            }
+        })
+    }
+
+    fn step(&mut self, step: usize, cmd: Command) {
+        let events = self.engine.process_command(cmd);
+        let expected = self.reference.process(cmd);
+        let actual: Vec<_> = events
+            .iter()
+            .map(|e| match *e {
+                OutputEvent::Accepted(a) => OutputEvent::Accepted(OrderAccepted { handle: OrderHandle::default(), ..a }),
+                e => e,
+            })
+            .collect();
+        assert_eq!(actual, expected, "step {}: events differ from the reference for {:?}", step, cmd);
+
+        for event in events {
+            if let OutputEvent::Accepted(a) = event {
+                self.reference.bind_handle(a.handle, a.order_id);
+                self.handles.push((a.order_id, a.handle));
+            }
+        }
+
+        if let Err(violation) = self.engine.validate() {
+            panic!("step {}: {} after {:?}", step, violation, cmd);
+        }
+        assert_eq!(self.engine.order_count(), self.reference.order_count(), "step {}", step);
+    }
+
+    fn run(&mut self, mut input: Decoder<'_>) {
+        let mut step = 0;
+        while let Some(cmd) = self.decode(&mut input) {
+            self.step(step, cmd);
+            step += 1;
+        }
+    }
+}
+
+/// Decode `data` into commands, run them and check every invariant.
+///
+/// # Panics
+/// On any engine panic, invariant violation or divergence from the
+/// reference matcher
+pub fn check_commands(data: &[u8]) {
+    let Some((&backend, rest)) = data.split_first() else { return };
+    let input = Decoder { data: rest, edge_prices: &EDGE_PRICES };
+    match backend % 3 {
+        0 => Harness::new(MatchingEngine::new(FUZZ_CAPACITY)).run(input),
+        1 => {
+            let storage = LevelStorage::Ladder(LadderConfig::default());
+            let input = Decoder { edge_prices: &LADDER_EDGE_PRICES, ..input };
+            Harness::new(MatchingEngine::with_storage(FUZZ_CAPACITY, storage)).run(input)
+        }
+        _ => Harness::new(MatchingEngine::with_book(FUZZ_CAPACITY, VecBook::new())).run(input),
+    }
+}
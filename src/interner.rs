@@ -0,0 +1,269 @@
+//! Order ID Interner - Dense `u64` IDs for UUID-keyed feeds.
+//!
+//! Exchange feeds such as Coinbase identify orders by UUID strings, while
+//! the engine keys orders by `u64`. Hashing risks silent collisions and is
+//! not stable across Rust versions, so the interner assigns sequential IDs
+//! instead and recycles them once an order is done.
+//!
+//! The full mapping (including free-list order) can be saved and restored,
+//! so a replay resumed from a checkpoint assigns exactly the same IDs.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+/// First ID handed out; 0 is left free as a "no order" sentinel
+const FIRST_ID: u64 = 1;
+
+/// Assigns dense sequential IDs to order UUIDs.
+#[derive(Clone, Debug)]
+pub struct OrderIdInterner {
+    ids: HashMap<Box<str>, u64>,
+    /// Reverse mapping, indexed by ID
+    uuids: Vec<Option<Box<str>>>,
+    /// Released IDs, reused LIFO
+    free: Vec<u64>,
+    next_id: u64,
+}
+
+/// One row of a saved interner
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    kind: RecordKind,
+    id: u64,
+    uuid: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RecordKind {
+    Next,
+    Free,
+    Order,
+}
+
+impl OrderIdInterner {
+    pub fn new() -> Self {
+        Self {
+            ids: HashMap::new(),
+            uuids: Vec::new(),
+            free: Vec::new(),
+            next_id: FIRST_ID,
+        }
+    }
+
+    /// ID for a UUID, assigning one if it is not live.
+    pub fn intern(&mut self, uuid: &str) -> u64 {
+        if let Some(&id) = self.ids.get(uuid) {
+            return id;
+        }
+
+        let id = self.free.pop().unwrap_or_else(|| {
+            let id = self.next_id;
+            self.next_id += 1;
+            id
+        });
+        self.ids.insert(uuid.into(), id);
+        self.set_uuid(id, Some(uuid.into()));
+        id
+    }
+
+    /// ID of a live UUID
+    #[inline]
+    pub fn get(&self, uuid: &str) -> Option<u64> {
+        self.ids.get(uuid).copied()
+    }
+
+    /// UUID currently mapped to an ID
+    #[inline]
+    pub fn resolve(&self, id: u64) -> Option<&str> {
+        self.uuids.get(id as usize)?.as_deref()
+    }
+
+    /// Release a UUID so its ID can be reused. Returns the released ID.
+    pub fn release(&mut self, uuid: &str) -> Option<u64> {
+        let id = self.ids.remove(uuid)?;
+        self.set_uuid(id, None);
+        self.free.push(id);
+        Some(id)
+    }
+
+    /// ID for an order that is leaving the book: interned if unseen, then released.
+    pub fn retire(&mut self, uuid: &str) -> u64 {
+        let id = self.intern(uuid);
+        self.release(uuid);
+        id
+    }
+
+    /// Number of live UUIDs
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns true if no UUIDs are live
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// One past the highest ID ever assigned
+    #[inline]
+    pub fn high_watermark(&self) -> u64 {
+        self.next_id
+    }
+
+    fn set_uuid(&mut self, id: u64, uuid: Option<Box<str>>) {
+        let idx = id as usize;
+        if idx >= self.uuids.len() {
+            self.uuids.resize(idx + 1, None);
+        }
+        self.uuids[idx] = uuid;
+    }
+
+    // ========================================================================
+    // Checkpointing
+    // ========================================================================
+
+    /// Write the full mapping as CSV (`kind,id,uuid`).
+    pub fn save<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.serialize(Record { kind: RecordKind::Next, id: self.next_id, uuid: None })?;
+        for &id in &self.free {
+            wtr.serialize(Record { kind: RecordKind::Free, id, uuid: None })?;
+        }
+        for (id, uuid) in self.uuids.iter().enumerate() {
+            if let Some(uuid) = uuid {
+                wtr.serialize(Record { kind: RecordKind::Order, id: id as u64, uuid: Some(uuid.to_string()) })?;
+            }
+        }
+        wtr.flush()
+    }
+
+    /// Restore a mapping written by `save`.
+    pub fn load<R: Read>(reader: R) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut interner = Self::new();
+
+        for record in csv::Reader::from_reader(reader).deserialize() {
+            let record: Record = record?;
+            match record.kind {
+                RecordKind::Next => interner.next_id = record.id,
+                RecordKind::Free => interner.free.push(record.id),
+                RecordKind::Order => {
+                    let uuid = record.uuid.ok_or_else(|| invalid(format!("order {} has no uuid", record.id)))?;
+                    if interner.ids.insert(uuid.as_str().into(), record.id).is_some() {
+                        return Err(invalid(format!("duplicate uuid {}", uuid)));
+                    }
+                    if interner.resolve(record.id).is_some() {
+                        return Err(invalid(format!("order id {} assigned twice", record.id)));
+                    }
+                    interner.set_uuid(record.id, Some(uuid.into()));
+                }
+            }
+        }
+
+        let max_id = interner.uuids.len().saturating_sub(1) as u64;
+        if interner.next_id < FIRST_ID || (!interner.uuids.is_empty() && max_id >= interner.next_id) {
+            return Err(invalid(format!("next id {} below assigned ids", interner.next_id)));
+        }
+
+        // A free ID must be one handed out before, not live and listed once,
+        // or it would later be assigned to two orders at the same time
+        let mut seen = HashSet::with_capacity(interner.free.len());
+        for &id in &interner.free {
+            if !(FIRST_ID..interner.next_id).contains(&id) {
+                return Err(invalid(format!("free id {} was never assigned", id)));
+            }
+            if interner.resolve(id).is_some() {
+                return Err(invalid(format!("free id {} is still live", id)));
+            }
+            if !seen.insert(id) {
+                return Err(invalid(format!("free id {} listed twice", id)));
+            }
+        }
+        Ok(interner)
+    }
+}
+
+impl Default for OrderIdInterner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dense_ids_and_recycling() {
+        let mut ids = OrderIdInterner::new();
+        assert_eq!(ids.intern("a"), 1);
+        assert_eq!(ids.intern("b"), 2);
+        assert_eq!(ids.intern("a"), 1);
+        assert_eq!(ids.resolve(2), Some("b"));
+
+        assert_eq!(ids.release("a"), Some(1));
+        assert_eq!(ids.get("a"), None);
+        assert_eq!(ids.resolve(1), None);
+
+        // Freed ID is reused before growing
+        assert_eq!(ids.intern("c"), 1);
+        assert_eq!(ids.intern("d"), 3);
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ids.high_watermark(), 4);
+
+        // Unseen order leaving the book gets an ID that is immediately free
+        let retired = ids.retire("e");
+        assert_eq!(retired, 4);
+        assert_eq!(ids.intern("f"), 4);
+    }
+
+    #[test]
+    fn test_save_restore_round_trip() {
+        let mut ids = OrderIdInterner::new();
+        for uuid in ["a", "b", "c", "d"] {
+            ids.intern(uuid);
+        }
+        ids.release("b");
+        ids.release("d");
+
+        let mut buf = Vec::new();
+        ids.save(&mut buf).unwrap();
+        let mut restored = OrderIdInterner::load(buf.as_slice()).unwrap();
+
+        assert_eq!(restored.get("a"), Some(1));
+        assert_eq!(restored.get("c"), Some(3));
+        assert_eq!(restored.len(), 2);
+
+        // Same assignment sequence as the original after restore
+        for uuid in ["x", "y", "z"] {
+            assert_eq!(restored.intern(uuid), ids.intern(uuid));
+        }
+    }
+
+    #[test]
+    fn test_load_rejects_inconsistent_state() {
+        let data = "kind,id,uuid\nnext,2,\norder,5,a\n";
+        assert!(OrderIdInterner::load(data.as_bytes()).is_err());
+
+        let data = "kind,id,uuid\nnext,9,\norder,1,a\norder,2,a\n";
+        assert!(OrderIdInterner::load(data.as_bytes()).is_err());
+
+        let data = "kind,id,uuid\nnext,9,\norder,1,a\norder,1,b\n";
+        assert!(OrderIdInterner::load(data.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_load_rejects_bad_free_ids() {
+        for free in ["free,2,\nfree,2,", "free,1,", "free,3,", "free,0,"] {
+            let data = format!("kind,id,uuid\nnext,3,\norder,1,a\n{}\n", free);
+            assert!(OrderIdInterner::load(data.as_bytes()).is_err(), "{:?}", free);
+        }
+        let data = "kind,id,uuid\nnext,3,\norder,1,a\nfree,2,\n";
+        let mut ids = OrderIdInterner::load(data.as_bytes()).unwrap();
+        assert_eq!(ids.intern("b"), 2);
+    }
+}
@@ -0,0 +1,895 @@
+//! ITCH-style binary market data.
+//!
+//! Encodes the `OutputEvent` stream into fixed-length, big-endian messages
+//! modelled on NASDAQ TotalView-ITCH, framed into MoldUDP64 packets and
+//! published over UDP. The decoder half is used on the receive side.
+//!
+//! ## Message Set
+//!
+//! | Type | Message         | Source event                  | Length |
+//! |------|-----------------|-------------------------------|--------|
+//! | `A`  | Add Order       | `OutputEvent::Accepted`       | 22     |
+//! | `E`  | Order Executed  | `OutputEvent::Trade`          | 38     |
+//! | `X`  | Order Cancel    | `OutputEvent::Reduced`        | 17     |
+//! | `D`  | Order Delete    | `OutputEvent::Canceled`       | 13     |
+//! | `L`  | Level Update    | `OutputEvent::BookDelta`      | 22     |
+//! | `S`  | Snapshot Start  | snapshot channel only         | 17     |
+//! | `F`  | Snapshot End    | snapshot channel only         | 9      |
+//!
+//! Rejections are private to the submitting client and are not published.
+//!
+//! ## MoldUDP64 Framing
+//!
+//! ```text
+//! | session [u8;10] | sequence u64 | count u16 | (len u16 | message)* |
+//! ```
+//!
+//! `sequence` is the sequence number of the first message in the packet;
+//! subsequent messages are numbered implicitly. A packet with `count == 0`
+//! is a heartbeat.
+
+use std::fmt;
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use crate::command::{OutputEvent, Side};
+use crate::recovery::RetransmitBuffer;
+
+/// Length of the MoldUDP64 session identifier
+pub const SESSION_LEN: usize = 10;
+
+/// Length of the MoldUDP64 downstream packet header
+pub const MOLD_HEADER_LEN: usize = SESSION_LEN + 8 + 2;
+
+/// Default maximum packet size (fits a standard Ethernet MTU)
+pub const DEFAULT_MAX_PACKET: usize = 1400;
+
+/// Message count value that marks the end of a session
+pub const END_OF_SESSION: u16 = 0xFFFF;
+
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Errors produced while decoding packets or messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Buffer ended before the expected number of bytes
+    Truncated,
+    /// Unrecognised message type byte
+    UnknownType(u8),
+    /// Side byte was neither `B` nor `S`
+    InvalidSide(u8),
+    /// Message block length does not match the fixed length for its type
+    LengthMismatch { msg_type: u8, len: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "buffer truncated"),
+            DecodeError::UnknownType(t) => write!(f, "unknown message type {:#04x}", t),
+            DecodeError::InvalidSide(s) => write!(f, "invalid side byte {:#04x}", s),
+            DecodeError::LengthMismatch { msg_type, len } => {
+                write!(f, "message type {:?} has length {}", *msg_type as char, len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+// ============================================================================
+// Messages
+// ============================================================================
+
+/// A single ITCH-style market data message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItchMessage {
+    /// A new order is resting in the book
+    AddOrder {
+        order_id: u64,
+        side: Side,
+        price: u64,
+        qty: u32,
+    },
+    /// A resting order was (partially) executed
+    OrderExecuted {
+        maker_order_id: u64,
+        taker_order_id: u64,
+        /// Side of the aggressing (taker) order
+        taker_side: Side,
+        price: u64,
+        qty: u32,
+        /// Feed-unique identifier for this execution
+        match_number: u64,
+    },
+    /// A resting order's quantity was reduced without execution; the order
+    /// keeps its queue position
+    OrderCancel {
+        order_id: u64,
+        canceled_qty: u32,
+        remaining: u32,
+    },
+    /// A resting order was removed without execution
+    OrderDelete {
+        order_id: u64,
+        canceled_qty: u32,
+    },
+    /// Aggregated price level state (0 qty = level removed)
+    LevelUpdate {
+        side: Side,
+        price: u64,
+        qty: u64,
+        count: u32,
+    },
+    /// Start of a book snapshot reflecting all messages up to `last_seq`
+    SnapshotStart {
+        last_seq: u64,
+        level_count: u32,
+        order_count: u32,
+    },
+    /// End of a book snapshot
+    SnapshotEnd {
+        last_seq: u64,
+    },
+}
+
+impl ItchMessage {
+    pub const ADD_ORDER: u8 = b'A';
+    pub const ORDER_EXECUTED: u8 = b'E';
+    pub const ORDER_CANCEL: u8 = b'X';
+    pub const ORDER_DELETE: u8 = b'D';
+    pub const LEVEL_UPDATE: u8 = b'L';
+    pub const SNAPSHOT_START: u8 = b'S';
+    pub const SNAPSHOT_END: u8 = b'F';
+
+    /// Fixed encoded length for a message type byte
+    #[inline]
+    pub const fn len_for_type(msg_type: u8) -> Option<usize> {
+        match msg_type {
+            Self::ADD_ORDER => Some(22),
+            Self::ORDER_EXECUTED => Some(38),
+            Self::ORDER_CANCEL => Some(17),
+            Self::ORDER_DELETE => Some(13),
+            Self::LEVEL_UPDATE => Some(22),
+            Self::SNAPSHOT_START => Some(17),
+            Self::SNAPSHOT_END => Some(9),
+            _ => None,
+        }
+    }
+
+    /// Message type byte
+    #[inline]
+    pub const fn msg_type(&self) -> u8 {
+        match self {
+            ItchMessage::AddOrder { .. } => Self::ADD_ORDER,
+            ItchMessage::OrderExecuted { .. } => Self::ORDER_EXECUTED,
+            ItchMessage::OrderCancel { .. } => Self::ORDER_CANCEL,
+            ItchMessage::OrderDelete { .. } => Self::ORDER_DELETE,
+            ItchMessage::LevelUpdate { .. } => Self::LEVEL_UPDATE,
+            ItchMessage::SnapshotStart { .. } => Self::SNAPSHOT_START,
+            ItchMessage::SnapshotEnd { .. } => Self::SNAPSHOT_END,
+        }
+    }
+
+    /// Encoded length of this message in bytes
+    #[inline]
+    pub const fn encoded_len(&self) -> usize {
+        match Self::len_for_type(self.msg_type()) {
+            Some(len) => len,
+            None => 0,
+        }
+    }
+
+    /// Encode into `out`, returning the number of bytes written.
+    ///
+    /// # Panics
+    /// Panics if `out` is shorter than `encoded_len()`.
+    pub fn encode(&self, out: &mut [u8]) -> usize {
+        let len = self.encoded_len();
+        let out = &mut out[..len];
+        out[0] = self.msg_type();
+        match *self {
+            ItchMessage::AddOrder { order_id, side, price, qty } => {
+                out[1] = side_to_byte(side);
+                out[2..10].copy_from_slice(&order_id.to_be_bytes());
+                out[10..18].copy_from_slice(&price.to_be_bytes());
+                out[18..22].copy_from_slice(&qty.to_be_bytes());
+            }
+            ItchMessage::OrderExecuted {
+                maker_order_id,
+                taker_order_id,
+                taker_side,
+                price,
+                qty,
+                match_number,
+            } => {
+                out[1] = side_to_byte(taker_side);
+                out[2..10].copy_from_slice(&maker_order_id.to_be_bytes());
+                out[10..18].copy_from_slice(&taker_order_id.to_be_bytes());
+                out[18..26].copy_from_slice(&price.to_be_bytes());
+                out[26..30].copy_from_slice(&qty.to_be_bytes());
+                out[30..38].copy_from_slice(&match_number.to_be_bytes());
+            }
+            ItchMessage::OrderCancel { order_id, canceled_qty, remaining } => {
+                out[1..9].copy_from_slice(&order_id.to_be_bytes());
+                out[9..13].copy_from_slice(&canceled_qty.to_be_bytes());
+                out[13..17].copy_from_slice(&remaining.to_be_bytes());
+            }
+            ItchMessage::OrderDelete { order_id, canceled_qty } => {
+                out[1..9].copy_from_slice(&order_id.to_be_bytes());
+                out[9..13].copy_from_slice(&canceled_qty.to_be_bytes());
+            }
+            ItchMessage::LevelUpdate { side, price, qty, count } => {
+                out[1] = side_to_byte(side);
+                out[2..10].copy_from_slice(&price.to_be_bytes());
+                out[10..18].copy_from_slice(&qty.to_be_bytes());
+                out[18..22].copy_from_slice(&count.to_be_bytes());
+            }
+            ItchMessage::SnapshotStart { last_seq, level_count, order_count } => {
+                out[1..9].copy_from_slice(&last_seq.to_be_bytes());
+                out[9..13].copy_from_slice(&level_count.to_be_bytes());
+                out[13..17].copy_from_slice(&order_count.to_be_bytes());
+            }
+            ItchMessage::SnapshotEnd { last_seq } => {
+                out[1..9].copy_from_slice(&last_seq.to_be_bytes());
+            }
+        }
+        len
+    }
+
+    /// Decode a single message from the start of `buf`.
+    pub fn decode(buf: &[u8]) -> Result<Self, DecodeError> {
+        let msg_type = *buf.first().ok_or(DecodeError::Truncated)?;
+        let len = Self::len_for_type(msg_type).ok_or(DecodeError::UnknownType(msg_type))?;
+        if buf.len() < len {
+            return Err(DecodeError::Truncated);
+        }
+
+        let msg = match msg_type {
+            Self::ADD_ORDER => ItchMessage::AddOrder {
+                side: byte_to_side(buf[1])?,
+                order_id: read_u64(&buf[2..10]),
+                price: read_u64(&buf[10..18]),
+                qty: read_u32(&buf[18..22]),
+            },
+            Self::ORDER_EXECUTED => ItchMessage::OrderExecuted {
+                taker_side: byte_to_side(buf[1])?,
+                maker_order_id: read_u64(&buf[2..10]),
+                taker_order_id: read_u64(&buf[10..18]),
+                price: read_u64(&buf[18..26]),
+                qty: read_u32(&buf[26..30]),
+                match_number: read_u64(&buf[30..38]),
+            },
+            Self::ORDER_CANCEL => ItchMessage::OrderCancel {
+                order_id: read_u64(&buf[1..9]),
+                canceled_qty: read_u32(&buf[9..13]),
+                remaining: read_u32(&buf[13..17]),
+            },
+            Self::ORDER_DELETE => ItchMessage::OrderDelete {
+                order_id: read_u64(&buf[1..9]),
+                canceled_qty: read_u32(&buf[9..13]),
+            },
+            Self::LEVEL_UPDATE => ItchMessage::LevelUpdate {
+                side: byte_to_side(buf[1])?,
+                price: read_u64(&buf[2..10]),
+                qty: read_u64(&buf[10..18]),
+                count: read_u32(&buf[18..22]),
+            },
+            Self::SNAPSHOT_START => ItchMessage::SnapshotStart {
+                last_seq: read_u64(&buf[1..9]),
+                level_count: read_u32(&buf[9..13]),
+                order_count: read_u32(&buf[13..17]),
+            },
+            Self::SNAPSHOT_END => ItchMessage::SnapshotEnd {
+                last_seq: read_u64(&buf[1..9]),
+            },
+            _ => unreachable!("length lookup already rejected unknown types"),
+        };
+        Ok(msg)
+    }
+}
+
+#[inline]
+fn side_to_byte(side: Side) -> u8 {
+    match side {
+        Side::Bid => b'B',
+        Side::Ask => b'S',
+    }
+}
+
+#[inline]
+fn byte_to_side(b: u8) -> Result<Side, DecodeError> {
+    match b {
+        b'B' => Ok(Side::Bid),
+        b'S' => Ok(Side::Ask),
+        other => Err(DecodeError::InvalidSide(other)),
+    }
+}
+
+#[inline]
+fn read_u64(b: &[u8]) -> u64 {
+    u64::from_be_bytes(b.try_into().unwrap())
+}
+
+#[inline]
+fn read_u32(b: &[u8]) -> u32 {
+    u32::from_be_bytes(b.try_into().unwrap())
+}
+
+#[inline]
+fn read_u16(b: &[u8]) -> u16 {
+    u16::from_be_bytes(b.try_into().unwrap())
+}
+
+// ============================================================================
+// Encoder
+// ============================================================================
+
+/// Converts engine output events into ITCH messages.
+///
+/// Assigns a monotonically increasing match number to every execution.
+#[derive(Debug)]
+pub struct ItchEncoder {
+    next_match: u64,
+}
+
+impl Default for ItchEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ItchEncoder {
+    pub fn new() -> Self {
+        Self { next_match: 1 }
+    }
+
+    /// Map an output event to its public market data message.
+    ///
+    /// Returns `None` for private events (rejections) and BBO events,
+    /// which receivers derive from level updates.
+    pub fn encode(&mut self, event: &OutputEvent) -> Option<ItchMessage> {
+        match *event {
+            OutputEvent::Accepted(a) => Some(ItchMessage::AddOrder {
+                order_id: a.order_id,
+                side: a.side,
+                price: a.price,
+                qty: a.qty,
+            }),
+            OutputEvent::Trade(t) => {
+                let match_number = self.next_match;
+                self.next_match += 1;
+                Some(ItchMessage::OrderExecuted {
+                    maker_order_id: t.maker_order_id,
+                    taker_order_id: t.taker_order_id,
+                    taker_side: t.taker_side,
+                    price: t.price,
+                    qty: t.qty,
+                    match_number,
+                })
+            }
+            OutputEvent::Canceled(c) => Some(ItchMessage::OrderDelete {
+                order_id: c.order_id,
+                canceled_qty: c.canceled_qty,
+            }),
+            OutputEvent::Reduced(r) => Some(ItchMessage::OrderCancel {
+                order_id: r.order_id,
+                canceled_qty: r.reduced_by,
+                remaining: r.remaining,
+            }),
+            OutputEvent::BookDelta(b) => Some(ItchMessage::LevelUpdate {
+                side: b.side,
+                price: b.price,
+                qty: b.new_qty,
+                count: b.new_count,
+            }),
+            OutputEvent::Rejected(_) | OutputEvent::Bbo(_) | OutputEvent::ArenaHighWater(_) => None,
+        }
+    }
+}
+
+// ============================================================================
+// MoldUDP64 Framing
+// ============================================================================
+
+/// Builds MoldUDP64 downstream packets into a reusable buffer.
+pub struct MoldPacketizer {
+    session: [u8; SESSION_LEN],
+    /// Sequence number of the first message in the current packet
+    next_seq: u64,
+    count: u16,
+    max_len: usize,
+    buf: Vec<u8>,
+}
+
+impl MoldPacketizer {
+    /// Create a packetizer starting at sequence number 1.
+    ///
+    /// The session name is truncated or space-padded to 10 bytes.
+    pub fn new(session: &str, max_len: usize) -> Self {
+        assert!(max_len > MOLD_HEADER_LEN, "max_len must exceed the MoldUDP64 header");
+        let mut packetizer = Self {
+            session: session_bytes(session),
+            next_seq: 1,
+            count: 0,
+            max_len,
+            buf: Vec::with_capacity(max_len),
+        };
+        packetizer.write_header();
+        packetizer
+    }
+
+    /// Session identifier
+    #[inline]
+    pub fn session(&self) -> [u8; SESSION_LEN] {
+        self.session
+    }
+
+    /// Sequence number that the next appended message will receive
+    #[inline]
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq + self.count as u64
+    }
+
+    /// Number of messages in the pending packet
+    #[inline]
+    pub fn count(&self) -> u16 {
+        self.count
+    }
+
+    /// Returns true if no messages are pending
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Append a message to the pending packet.
+    ///
+    /// Returns `false` (and leaves the packet unchanged) if it does not fit.
+    pub fn try_push(&mut self, msg: &ItchMessage) -> bool {
+        let len = msg.encoded_len();
+        if self.buf.len() + 2 + len > self.max_len || self.count == END_OF_SESSION - 1 {
+            return false;
+        }
+        self.buf.extend_from_slice(&(len as u16).to_be_bytes());
+        let start = self.buf.len();
+        self.buf.resize(start + len, 0);
+        msg.encode(&mut self.buf[start..]);
+        self.count += 1;
+        true
+    }
+
+    /// The pending packet bytes (header + messages)
+    #[inline]
+    pub fn packet(&mut self) -> &[u8] {
+        self.buf[SESSION_LEN + 8..MOLD_HEADER_LEN].copy_from_slice(&self.count.to_be_bytes());
+        &self.buf
+    }
+
+    /// Discard the pending packet and restart numbering at `seq`.
+    pub fn restart_at(&mut self, seq: u64) {
+        self.next_seq = seq;
+        self.count = 0;
+        self.write_header();
+    }
+
+    /// Discard the pending packet and advance the sequence number past it.
+    pub fn reset(&mut self) {
+        self.next_seq += self.count as u64;
+        self.count = 0;
+        self.write_header();
+    }
+
+    fn write_header(&mut self) {
+        self.buf.clear();
+        self.buf.extend_from_slice(&self.session);
+        self.buf.extend_from_slice(&self.next_seq.to_be_bytes());
+        self.buf.extend_from_slice(&0u16.to_be_bytes());
+    }
+}
+
+/// Convert a session name into a fixed 10-byte, space-padded identifier
+pub fn session_bytes(session: &str) -> [u8; SESSION_LEN] {
+    let mut out = [b' '; SESSION_LEN];
+    for (dst, src) in out.iter_mut().zip(session.bytes()) {
+        *dst = src;
+    }
+    out
+}
+
+/// A parsed MoldUDP64 downstream packet
+#[derive(Clone, Copy, Debug)]
+pub struct MoldPacket<'a> {
+    /// Session identifier
+    pub session: [u8; SESSION_LEN],
+    /// Sequence number of the first message
+    pub sequence: u64,
+    /// Message count (0 = heartbeat, 0xFFFF = end of session)
+    pub count: u16,
+    payload: &'a [u8],
+}
+
+impl<'a> MoldPacket<'a> {
+    /// Parse the packet header; message blocks are decoded lazily.
+    pub fn parse(buf: &'a [u8]) -> Result<Self, DecodeError> {
+        if buf.len() < MOLD_HEADER_LEN {
+            return Err(DecodeError::Truncated);
+        }
+        let mut session = [0u8; SESSION_LEN];
+        session.copy_from_slice(&buf[..SESSION_LEN]);
+        Ok(Self {
+            session,
+            sequence: read_u64(&buf[SESSION_LEN..SESSION_LEN + 8]),
+            count: read_u16(&buf[SESSION_LEN + 8..MOLD_HEADER_LEN]),
+            payload: &buf[MOLD_HEADER_LEN..],
+        })
+    }
+
+    /// Returns true if this is a heartbeat (no messages)
+    #[inline]
+    pub fn is_heartbeat(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns true if this packet marks the end of the session
+    #[inline]
+    pub fn is_end_of_session(&self) -> bool {
+        self.count == END_OF_SESSION
+    }
+
+    /// Iterate over `(sequence, message)` pairs in the packet
+    pub fn messages(&self) -> MoldMessages<'a> {
+        let count = if self.is_end_of_session() { 0 } else { self.count };
+        MoldMessages {
+            payload: self.payload,
+            seq: self.sequence,
+            remaining: count,
+        }
+    }
+}
+
+/// Iterator over the message blocks of a `MoldPacket`
+pub struct MoldMessages<'a> {
+    payload: &'a [u8],
+    seq: u64,
+    remaining: u16,
+}
+
+impl Iterator for MoldMessages<'_> {
+    type Item = Result<(u64, ItchMessage), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        if self.payload.len() < 2 {
+            self.remaining = 0;
+            return Some(Err(DecodeError::Truncated));
+        }
+        let len = read_u16(&self.payload[..2]) as usize;
+        if self.payload.len() < 2 + len || len == 0 {
+            self.remaining = 0;
+            return Some(Err(DecodeError::Truncated));
+        }
+        let block = &self.payload[2..2 + len];
+        self.payload = &self.payload[2 + len..];
+
+        let seq = self.seq;
+        self.seq += 1;
+
+        let msg_type = block[0];
+        if let Some(expected) = ItchMessage::len_for_type(msg_type) {
+            if expected != len {
+                return Some(Err(DecodeError::LengthMismatch { msg_type, len }));
+            }
+        }
+        Some(ItchMessage::decode(block).map(|msg| (seq, msg)))
+    }
+}
+
+// ============================================================================
+// UDP Transport
+// ============================================================================
+
+/// Publishes engine output events as MoldUDP64 packets over UDP.
+pub struct UdpPublisher {
+    socket: UdpSocket,
+    encoder: ItchEncoder,
+    packetizer: MoldPacketizer,
+    /// Recently published messages, kept for retransmission requests
+    history: Option<RetransmitBuffer>,
+}
+
+impl UdpPublisher {
+    /// Bind to `bind_addr` and send to `dest_addr`.
+    pub fn new<A: ToSocketAddrs, B: ToSocketAddrs>(
+        bind_addr: A,
+        dest_addr: B,
+        session: &str,
+    ) -> io::Result<Self> {
+        let socket = UdpSocket::bind(bind_addr)?;
+        socket.connect(dest_addr)?;
+        Ok(Self {
+            socket,
+            encoder: ItchEncoder::new(),
+            packetizer: MoldPacketizer::new(session, DEFAULT_MAX_PACKET),
+            history: None,
+        })
+    }
+
+    /// Retain the last `capacity` published messages for retransmission.
+    pub fn with_history(mut self, capacity: usize) -> Self {
+        self.history = Some(RetransmitBuffer::new(capacity));
+        self
+    }
+
+    /// Retained message history (if enabled with `with_history`)
+    #[inline]
+    pub fn history(&self) -> Option<&RetransmitBuffer> {
+        self.history.as_ref()
+    }
+
+    /// Session identifier
+    #[inline]
+    pub fn session(&self) -> [u8; SESSION_LEN] {
+        self.packetizer.session()
+    }
+
+    /// Sequence number of the last published message (0 if none)
+    #[inline]
+    pub fn last_seq(&self) -> u64 {
+        self.packetizer.next_seq() - 1
+    }
+
+    /// Sequence number the next published message will receive
+    #[inline]
+    pub fn next_seq(&self) -> u64 {
+        self.packetizer.next_seq()
+    }
+
+    /// Encode and send a batch of events (e.g. the output of one command).
+    ///
+    /// Returns the number of messages published.
+    pub fn publish(&mut self, events: &[OutputEvent]) -> io::Result<usize> {
+        let mut published = 0;
+        for event in events {
+            if let Some(msg) = self.encoder.encode(event) {
+                if let Some(history) = self.history.as_mut() {
+                    history.push(self.packetizer.next_seq(), msg);
+                }
+                if !self.packetizer.try_push(&msg) {
+                    self.flush()?;
+                    let pushed = self.packetizer.try_push(&msg);
+                    debug_assert!(pushed, "message larger than an empty packet");
+                }
+                published += 1;
+            }
+        }
+        self.flush()?;
+        Ok(published)
+    }
+
+    /// Send a heartbeat carrying the next expected sequence number.
+    pub fn heartbeat(&mut self) -> io::Result<()> {
+        debug_assert!(self.packetizer.is_empty());
+        self.socket.send(self.packetizer.packet())?;
+        Ok(())
+    }
+
+    /// Send any pending messages.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.packetizer.is_empty() {
+            return Ok(());
+        }
+        self.socket.send(self.packetizer.packet())?;
+        self.packetizer.reset();
+        Ok(())
+    }
+
+    /// Local socket address (useful when bound to port 0)
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.socket.local_addr()
+    }
+}
+
+/// Receives and decodes MoldUDP64 packets from UDP.
+pub struct UdpReceiver {
+    socket: UdpSocket,
+    buf: Vec<u8>,
+}
+
+impl UdpReceiver {
+    /// Bind a receiving socket.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(addr)?,
+            buf: vec![0u8; 65536],
+        })
+    }
+
+    /// Underlying socket (e.g. to set timeouts)
+    #[inline]
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Block for one packet and append its `(sequence, message)` pairs to `out`.
+    ///
+    /// Returns the packet's first sequence number and message count.
+    pub fn recv_into(&mut self, out: &mut Vec<(u64, ItchMessage)>) -> io::Result<(u64, u16)> {
+        let n = self.socket.recv(&mut self.buf)?;
+        let packet = MoldPacket::parse(&self.buf[..n])
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        for item in packet.messages() {
+            out.push(item.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?);
+        }
+        Ok((packet.sequence, packet.count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{BookUpdate, OrderAccepted, OrderCanceled, OrderReduced, OrderRejected, RejectReason, TradeEvent};
+    use crate::engine::Engine;
+    use crate::command::{Command, PlaceOrder};
+    use std::time::Duration;
+
+    fn sample_messages() -> Vec<ItchMessage> {
+        vec![
+            ItchMessage::AddOrder { order_id: 7, side: Side::Bid, price: 10000, qty: 50 },
+            ItchMessage::OrderExecuted {
+                maker_order_id: 7,
+                taker_order_id: 8,
+                taker_side: Side::Ask,
+                price: 10000,
+                qty: 20,
+                match_number: 1,
+            },
+            ItchMessage::OrderCancel { order_id: 7, canceled_qty: 10, remaining: 20 },
+            ItchMessage::OrderDelete { order_id: 7, canceled_qty: 20 },
+            ItchMessage::LevelUpdate { side: Side::Ask, price: u64::MAX, qty: u64::MAX, count: 3 },
+            ItchMessage::SnapshotStart { last_seq: 42, level_count: 2, order_count: 5 },
+            ItchMessage::SnapshotEnd { last_seq: 42 },
+        ]
+    }
+
+    #[test]
+    fn test_message_roundtrip() {
+        for msg in sample_messages() {
+            let mut buf = [0u8; 64];
+            let len = msg.encode(&mut buf);
+            assert_eq!(len, msg.encoded_len());
+            assert_eq!(ItchMessage::decode(&buf[..len]), Ok(msg));
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(ItchMessage::decode(&[]), Err(DecodeError::Truncated));
+        assert_eq!(ItchMessage::decode(b"Z"), Err(DecodeError::UnknownType(b'Z')));
+        assert_eq!(ItchMessage::decode(&[b'A', b'B', 0]), Err(DecodeError::Truncated));
+
+        let mut buf = [0u8; 22];
+        ItchMessage::AddOrder { order_id: 1, side: Side::Bid, price: 1, qty: 1 }.encode(&mut buf);
+        buf[1] = b'X';
+        assert_eq!(ItchMessage::decode(&buf), Err(DecodeError::InvalidSide(b'X')));
+    }
+
+    #[test]
+    fn test_encoder_event_mapping() {
+        let mut encoder = ItchEncoder::new();
+
+        let trade = OutputEvent::Trade(TradeEvent {
+            price: 100,
+            qty: 5,
+            maker_order_id: 1,
+            taker_order_id: 2,
+            maker_user_id: 10,
+            taker_user_id: 20,
+            taker_side: Side::Bid,
+        });
+        let first = encoder.encode(&trade);
+        let second = encoder.encode(&trade);
+        assert!(matches!(first, Some(ItchMessage::OrderExecuted { match_number: 1, .. })));
+        assert!(matches!(second, Some(ItchMessage::OrderExecuted { match_number: 2, .. })));
+
+        let accepted = OutputEvent::Accepted(OrderAccepted { order_id: 3, price: 99, qty: 4, side: Side::Ask, handle: Default::default() });
+        assert_eq!(
+            encoder.encode(&accepted),
+            Some(ItchMessage::AddOrder { order_id: 3, side: Side::Ask, price: 99, qty: 4 })
+        );
+
+        let canceled = OutputEvent::Canceled(OrderCanceled { order_id: 3, canceled_qty: 4 });
+        assert_eq!(encoder.encode(&canceled), Some(ItchMessage::OrderDelete { order_id: 3, canceled_qty: 4 }));
+
+        let reduced = OutputEvent::Reduced(OrderReduced { order_id: 3, reduced_by: 1, remaining: 3 });
+        assert_eq!(encoder.encode(&reduced), Some(ItchMessage::OrderCancel { order_id: 3, canceled_qty: 1, remaining: 3 }));
+
+        let delta = OutputEvent::BookDelta(BookUpdate { side: Side::Ask, price: 99, new_qty: 0, new_count: 0 });
+        assert_eq!(
+            encoder.encode(&delta),
+            Some(ItchMessage::LevelUpdate { side: Side::Ask, price: 99, qty: 0, count: 0 })
+        );
+
+        let rejected = OutputEvent::Rejected(OrderRejected { order_id: 9, reason: RejectReason::OrderNotFound });
+        assert_eq!(encoder.encode(&rejected), None);
+    }
+
+    #[test]
+    fn test_packetizer_sequence_numbers() {
+        let mut packetizer = MoldPacketizer::new("FLASHLOB", DEFAULT_MAX_PACKET);
+        let messages = sample_messages();
+
+        for msg in &messages {
+            assert!(packetizer.try_push(msg));
+        }
+        assert_eq!(packetizer.next_seq(), 8);
+
+        let bytes = packetizer.packet().to_vec();
+        let packet = MoldPacket::parse(&bytes).unwrap();
+        assert_eq!(&packet.session, b"FLASHLOB  ");
+        assert_eq!(packet.sequence, 1);
+        assert_eq!(packet.count, 7);
+
+        let decoded: Vec<_> = packet.messages().collect::<Result<_, _>>().unwrap();
+        let expected: Vec<_> = messages.iter().copied().enumerate().map(|(i, m)| (i as u64 + 1, m)).collect();
+        assert_eq!(decoded, expected);
+
+        // Next packet continues the sequence
+        packetizer.reset();
+        assert!(packetizer.is_empty());
+        packetizer.try_push(&messages[0]);
+        let bytes = packetizer.packet().to_vec();
+        assert_eq!(MoldPacket::parse(&bytes).unwrap().sequence, 8);
+    }
+
+    #[test]
+    fn test_packetizer_respects_max_len() {
+        // Header (20) + two 'A' blocks (24 each) = 68
+        let mut packetizer = MoldPacketizer::new("S", 70);
+        let msg = ItchMessage::AddOrder { order_id: 1, side: Side::Bid, price: 1, qty: 1 };
+        assert!(packetizer.try_push(&msg));
+        assert!(packetizer.try_push(&msg));
+        assert!(!packetizer.try_push(&msg));
+        assert_eq!(packetizer.count(), 2);
+        assert!(packetizer.packet().len() <= 70);
+    }
+
+    #[test]
+    fn test_heartbeat_packet() {
+        let mut packetizer = MoldPacketizer::new("S", DEFAULT_MAX_PACKET);
+        let bytes = packetizer.packet().to_vec();
+        let packet = MoldPacket::parse(&bytes).unwrap();
+        assert!(packet.is_heartbeat());
+        assert_eq!(packet.messages().count(), 0);
+    }
+
+    #[test]
+    fn test_udp_loopback() {
+        let mut receiver = UdpReceiver::bind("127.0.0.1:0").unwrap();
+        receiver.socket().set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let dest = receiver.socket().local_addr().unwrap();
+        let mut publisher = UdpPublisher::new("127.0.0.1:0", dest, "TEST").unwrap();
+
+        let mut engine = Engine::new(100);
+        let events = engine.process_command(Command::Place(PlaceOrder::limit(1, 1, Side::Ask, 10000, 100)));
+        assert_eq!(publisher.publish(events).unwrap(), 2);
+        let events = engine.process_command(Command::Place(PlaceOrder::limit(2, 2, Side::Bid, 10000, 40)));
+        assert_eq!(publisher.publish(events).unwrap(), 2);
+
+        let mut out = Vec::new();
+        assert_eq!(receiver.recv_into(&mut out).unwrap(), (1, 2));
+        assert_eq!(receiver.recv_into(&mut out).unwrap(), (3, 2));
+
+        assert!(matches!(out[0], (1, ItchMessage::AddOrder { order_id: 1, qty: 100, .. })));
+        assert!(matches!(out[1], (2, ItchMessage::LevelUpdate { qty: 100, count: 1, .. })));
+        assert!(matches!(out[2], (3, ItchMessage::OrderExecuted { maker_order_id: 1, taker_order_id: 2, qty: 40, .. })));
+        assert!(matches!(out[3], (4, ItchMessage::LevelUpdate { qty: 60, count: 1, .. })));
+        assert_eq!(publisher.next_seq(), 5);
+    }
+}
@@ -0,0 +1,329 @@
+//! L2 Replay - Synthetic order book from aggregated depth diffs.
+//!
+//! Replays Tardis `incremental_book_L2` exports (Binance, Bybit, ...) into
+//! the engine. Each displayed level is represented by one or more synthetic
+//! orders owned by `SYNTHETIC_USER_ID`, so simulated strategy orders placed
+//! through the same engine queue behind displayed size and are matched by
+//! the normal matching rules.
+//!
+//! Size increases join the back of the level as a new synthetic order.
+//! Size decreases are taken from the synthetic orders according to the
+//! `ShrinkPolicy`; strategy orders are never touched by feed updates.
+
+use std::collections::VecDeque;
+
+use rust_decimal::Decimal;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+
+use crate::coinbase::{scale_qty, scale_value, ParseError, Scale, ScaleTable};
+use crate::command::{Command, OutputEvent, PlaceOrder, Side};
+use crate::engine::Engine;
+
+/// User ID that owns all synthetic liquidity
+pub const SYNTHETIC_USER_ID: u64 = u64::MAX;
+
+/// First synthetic order ID; strategy order IDs must stay below this
+pub const SYNTHETIC_ID_BASE: u64 = 1 << 62;
+
+/// One row of a Tardis `incremental_book_L2` CSV
+#[derive(Debug, Deserialize)]
+pub struct TardisL2Row {
+    #[serde(default)]
+    pub exchange: Option<String>,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    /// Exchange timestamp (microseconds since epoch)
+    pub timestamp: u64,
+    pub local_timestamp: u64,
+    /// True for rows belonging to a full book snapshot
+    pub is_snapshot: bool,
+    pub side: String,
+    pub price: Decimal,
+    /// New absolute size at this price (0 removes the level)
+    pub amount: Decimal,
+}
+
+/// A parsed level update
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LevelUpdate {
+    pub side: Side,
+    pub price: u64,
+    pub qty: u32,
+    pub is_snapshot: bool,
+}
+
+impl TardisL2Row {
+    /// Convert to a level update using the row's product scale.
+    pub fn to_update(&self, scales: &ScaleTable) -> Result<LevelUpdate, ParseError> {
+        let scale: Scale = scales.get(self.symbol.as_deref());
+        let side = match self.side.as_str() {
+            "bid" | "buy" => Side::Bid,
+            "ask" | "sell" => Side::Ask,
+            other => return Err(ParseError::InvalidSide(other.to_string())),
+        };
+        Ok(LevelUpdate {
+            side,
+            price: scale_value(self.price, scale.price, "price")?,
+            qty: scale_qty(self.amount, &scale, "amount")?,
+            is_snapshot: self.is_snapshot,
+        })
+    }
+}
+
+/// Which synthetic orders absorb a displayed size decrease
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShrinkPolicy {
+    /// Remove the most recently added synthetic size first. Strategy orders
+    /// only advance when liquidity ahead of them trades (conservative).
+    #[default]
+    Back,
+    /// Remove from the front of the queue, as if every decrease were a
+    /// trade (optimistic).
+    Front,
+}
+
+/// Engine whose book mirrors an L2 feed through synthetic orders.
+pub struct SyntheticBook {
+    engine: Engine,
+    /// Synthetic order IDs per level, in queue order
+    levels: FxHashMap<(Side, u64), VecDeque<u64>>,
+    next_id: u64,
+    policy: ShrinkPolicy,
+    /// Whether the last applied update was part of a snapshot
+    in_snapshot: bool,
+}
+
+impl SyntheticBook {
+    pub fn new(capacity: u32, policy: ShrinkPolicy) -> Self {
+        Self {
+            engine: Engine::new(capacity),
+            levels: FxHashMap::default(),
+            next_id: SYNTHETIC_ID_BASE,
+            policy,
+            in_snapshot: false,
+        }
+    }
+
+    /// The underlying engine
+    #[inline]
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Submit a strategy command; its events are returned as usual.
+    pub fn submit(&mut self, cmd: Command) -> &[OutputEvent] {
+        self.engine.process_command(cmd)
+    }
+
+    /// Returns true if an order ID belongs to synthetic liquidity
+    #[inline]
+    pub fn is_synthetic(order_id: u64) -> bool {
+        order_id >= SYNTHETIC_ID_BASE
+    }
+
+    /// Apply a feed update, appending the engine's events to `events`.
+    ///
+    /// The first snapshot row after incremental rows clears all synthetic
+    /// liquidity before it is applied. Trades in `events` are strategy
+    /// orders filled by liquidity moving through their price.
+    pub fn apply(&mut self, update: &LevelUpdate, events: &mut Vec<OutputEvent>) {
+        if update.is_snapshot && !self.in_snapshot {
+            self.clear_synthetic(events);
+        }
+        self.in_snapshot = update.is_snapshot;
+        self.set_level(update.side, update.price, update.qty, events);
+    }
+
+    /// Displayed (synthetic) size at a level
+    pub fn synthetic_qty(&self, side: Side, price: u64) -> u32 {
+        let arena = &self.engine.matcher.arena;
+        let book = &self.engine.matcher.book;
+        self.levels.get(&(side, price)).map_or(0, |queue| {
+            queue.iter()
+                .filter_map(|id| book.get_order(*id))
+                .map(|info| arena.get_handle(info.handle).qty)
+                .sum()
+        })
+    }
+
+    /// Remove all synthetic liquidity, leaving strategy orders in place.
+    pub fn clear_synthetic(&mut self, events: &mut Vec<OutputEvent>) {
+        for (_, queue) in self.levels.drain() {
+            for id in queue {
+                self.engine.matcher.process_reduce(id, u32::MAX, events);
+            }
+        }
+    }
+
+    /// Set the synthetic size at a level to `qty`.
+    fn set_level(&mut self, side: Side, price: u64, qty: u32, events: &mut Vec<OutputEvent>) {
+        let key = (side, price);
+        self.prune(key);
+        let current = self.synthetic_qty(side, price);
+
+        if qty > current {
+            self.remove_stale_crossing(side, price, events);
+
+            let order_id = self.next_id;
+            self.next_id += 1;
+            self.engine.matcher.process_place(
+                PlaceOrder::limit(order_id, SYNTHETIC_USER_ID, side, price, qty - current),
+                events,
+            );
+            if self.engine.matcher.book.contains_order(order_id) {
+                self.levels.entry(key).or_default().push_back(order_id);
+            }
+        } else if qty < current {
+            let mut excess = current - qty;
+            let Some(queue) = self.levels.get(&key) else { return };
+            let ids: Vec<u64> = match self.policy {
+                ShrinkPolicy::Back => queue.iter().rev().copied().collect(),
+                ShrinkPolicy::Front => queue.iter().copied().collect(),
+            };
+            for id in ids {
+                if excess == 0 {
+                    break;
+                }
+                let before = self.order_qty(id);
+                self.engine.matcher.process_reduce(id, excess, events);
+                excess -= before.min(excess);
+            }
+            self.prune(key);
+        }
+    }
+
+    /// Cancel synthetic liquidity on the opposite side that `price` would cross.
+    ///
+    /// The exchange book never crosses, so such levels are stale (e.g. a
+    /// removal later in the same batch). Strategy orders are left to match.
+    fn remove_stale_crossing(&mut self, side: Side, price: u64, events: &mut Vec<OutputEvent>) {
+        let opposite = side.opposite();
+        let stale: Vec<(Side, u64)> = self.levels.keys()
+            .filter(|(s, p)| *s == opposite && match side {
+                Side::Bid => *p <= price,
+                Side::Ask => *p >= price,
+            })
+            .copied()
+            .collect();
+        for key in stale {
+            if let Some(queue) = self.levels.remove(&key) {
+                for id in queue {
+                    self.engine.matcher.process_reduce(id, u32::MAX, events);
+                }
+            }
+        }
+    }
+
+    fn order_qty(&self, order_id: u64) -> u32 {
+        self.engine.matcher.book.get_order(order_id)
+            .map_or(0, |info| self.engine.matcher.arena.get_handle(info.handle).qty)
+    }
+
+    /// Forget synthetic orders that were filled or canceled
+    fn prune(&mut self, key: (Side, u64)) {
+        let book = &self.engine.matcher.book;
+        if let Some(queue) = self.levels.get_mut(&key) {
+            queue.retain(|id| book.contains_order(*id));
+            if queue.is_empty() {
+                self.levels.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(side: Side, price: u64, qty: u32) -> LevelUpdate {
+        LevelUpdate { side, price, qty, is_snapshot: false }
+    }
+
+    fn apply(book: &mut SyntheticBook, u: LevelUpdate) -> Vec<OutputEvent> {
+        let mut events = Vec::new();
+        book.apply(&u, &mut events);
+        events
+    }
+
+    #[test]
+    fn test_strategy_order_queues_behind_displayed_size() {
+        let mut book = SyntheticBook::new(1000, ShrinkPolicy::Back);
+        apply(&mut book, update(Side::Bid, 100, 50));
+
+        // Strategy joins behind 50 displayed
+        book.submit(Command::Place(PlaceOrder::limit(1, 7, Side::Bid, 100, 10)));
+        // More displayed size arrives behind the strategy order
+        apply(&mut book, update(Side::Bid, 100, 80));
+        assert_eq!(book.synthetic_qty(Side::Bid, 100), 80);
+
+        // Back policy: the decrease removes the newer 30 first
+        apply(&mut book, update(Side::Bid, 100, 50));
+
+        // A sell for 55 fills 50 synthetic then 5 of the strategy order
+        let events = book.submit(Command::Place(PlaceOrder::limit(2, 8, Side::Ask, 100, 55))).to_vec();
+        let strategy_fill: u32 = events.iter()
+            .filter_map(|e| match e {
+                OutputEvent::Trade(t) if t.maker_order_id == 1 => Some(t.qty),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(strategy_fill, 5);
+    }
+
+    #[test]
+    fn test_front_policy_advances_queue() {
+        let mut book = SyntheticBook::new(1000, ShrinkPolicy::Front);
+        apply(&mut book, update(Side::Ask, 100, 50));
+        book.submit(Command::Place(PlaceOrder::limit(1, 7, Side::Ask, 100, 10)));
+        apply(&mut book, update(Side::Ask, 100, 20));
+
+        let events = book.submit(Command::Place(PlaceOrder::limit(2, 8, Side::Bid, 100, 25))).to_vec();
+        assert!(events.iter().any(|e| matches!(e, OutputEvent::Trade(t) if t.maker_order_id == 1 && t.qty == 5)));
+    }
+
+    #[test]
+    fn test_market_moving_through_strategy_order_fills_it() {
+        let mut book = SyntheticBook::new(1000, ShrinkPolicy::Back);
+        apply(&mut book, update(Side::Bid, 99, 10));
+        apply(&mut book, update(Side::Ask, 101, 10));
+        book.submit(Command::Place(PlaceOrder::limit(1, 7, Side::Bid, 100, 5)));
+
+        // Ask moves down to 100: stale synthetic bids are not crossed, the strategy bid is
+        let events = apply(&mut book, update(Side::Ask, 100, 8));
+        assert!(events.iter().any(|e| matches!(e, OutputEvent::Trade(t) if t.maker_order_id == 1 && t.qty == 5)));
+        assert_eq!(book.engine().best_bid(), Some(99));
+        assert_eq!(book.synthetic_qty(Side::Ask, 100), 3);
+
+        // A stale crossing synthetic level is dropped rather than matched
+        let events = apply(&mut book, update(Side::Bid, 101, 4));
+        assert!(!events.iter().any(|e| matches!(e, OutputEvent::Trade(_))));
+        assert_eq!(book.engine().best_ask(), None);
+        assert_eq!(book.engine().best_bid(), Some(101));
+    }
+
+    #[test]
+    fn test_snapshot_resets_synthetic_liquidity() {
+        let mut book = SyntheticBook::new(1000, ShrinkPolicy::Back);
+        apply(&mut book, update(Side::Bid, 100, 10));
+        apply(&mut book, update(Side::Bid, 98, 10));
+        book.submit(Command::Place(PlaceOrder::limit(1, 7, Side::Bid, 97, 5)));
+
+        let snap = LevelUpdate { side: Side::Bid, price: 99, qty: 20, is_snapshot: true };
+        apply(&mut book, snap);
+        apply(&mut book, LevelUpdate { side: Side::Ask, price: 101, qty: 20, is_snapshot: true });
+
+        let bids: Vec<_> = book.engine().matcher.book.bids.prices().collect();
+        assert_eq!(bids, vec![97, 99]);
+        assert_eq!(book.engine().best_ask(), Some(101));
+    }
+
+    #[test]
+    fn test_parse_row() {
+        let data = "exchange,symbol,timestamp,local_timestamp,is_snapshot,side,price,amount\n\
+                    binance,BTCUSDT,1,2,false,ask,100.5,0.25\n";
+        let row: TardisL2Row = csv::Reader::from_reader(data.as_bytes()).deserialize().next().unwrap().unwrap();
+        let scales = ScaleTable::new(Scale { price: 10, size: 100 });
+        assert_eq!(row.to_update(&scales), Ok(LevelUpdate { side: Side::Ask, price: 1005, qty: 25, is_snapshot: false }));
+    }
+}
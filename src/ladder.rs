@@ -0,0 +1,522 @@
+//! Price Ladder - Array-indexed price levels for bounded tick ranges.
+//!
+//! One side of the book stored as a preallocated array of `PriceLevel`s,
+//! indexed by tick offset from a base price. A bitmap of occupied slots
+//! gives fast best-price lookup and ordered iteration. Lookups are a
+//! subtraction and a shift instead of a tree walk, and adding a level never
+//! allocates.
+//!
+//! When a price falls outside the window, the ladder recenters around the
+//! live levels by shifting them in place. Only if the live range itself is
+//! wider than the window does it grow (and allocate). The live range of a
+//! side may span at most `MAX_SPAN_TICKS`; prices further out are refused.
+
+use std::ops::{Bound, RangeBounds};
+
+use crate::price_level::PriceLevel;
+
+/// Bits per bitmap word
+const WORD_BITS: usize = 64;
+
+/// Widest range of ticks the live levels of one side may span, which bounds
+/// the window (and its allocation) to twice this
+pub const MAX_SPAN_TICKS: u64 = 1 << 20;
+
+/// Window and tick size for a `PriceLadder`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LadderConfig {
+    /// Lowest price in the initial window
+    pub base_price: u64,
+    /// Price increment between slots; prices must be multiples of it
+    pub tick_size: u64,
+    /// Number of slots in the window (rounded up to a multiple of 64)
+    pub ticks: u32,
+}
+
+impl LadderConfig {
+    pub fn new(base_price: u64, tick_size: u64, ticks: u32) -> Self {
+        Self { base_price, tick_size, ticks }
+    }
+
+    /// Window centered on `mid_price`
+    pub fn centered(mid_price: u64, tick_size: u64, ticks: u32) -> Self {
+        let half = (ticks as u64 / 2) * tick_size;
+        Self::new(mid_price.saturating_sub(half), tick_size, ticks)
+    }
+}
+
+impl Default for LadderConfig {
+    fn default() -> Self {
+        Self::new(0, 1, 4096)
+    }
+}
+
+/// One book side as a tick-indexed array with an occupancy bitmap
+#[derive(Clone, Debug)]
+pub struct PriceLadder {
+    levels: Vec<PriceLevel>,
+    occupied: Vec<u64>,
+    /// Second bitmap used while recentering
+    scratch: Vec<u64>,
+    /// Tick number (price / tick) of slot 0
+    base_tick: u64,
+    tick: u64,
+    len: usize,
+    /// Lowest and highest occupied slots (valid when `len > 0`)
+    lowest: usize,
+    highest: usize,
+    recenters: u64,
+}
+
+impl PriceLadder {
+    pub fn new(config: LadderConfig) -> Self {
+        assert!(config.tick_size > 0, "tick size must be non-zero");
+        let slots = (config.ticks.max(1) as usize).div_ceil(WORD_BITS) * WORD_BITS;
+        Self {
+            levels: vec![PriceLevel::new(); slots],
+            occupied: vec![0; slots / WORD_BITS],
+            scratch: vec![0; slots / WORD_BITS],
+            base_tick: config.base_price / config.tick_size,
+            tick: config.tick_size,
+            len: 0,
+            lowest: 0,
+            highest: 0,
+            recenters: 0,
+        }
+    }
+
+    /// Returns true if `price` is on the tick grid
+    #[inline]
+    pub fn is_aligned(&self, price: u64) -> bool {
+        price.is_multiple_of(self.tick)
+    }
+
+    /// Returns true if a level can be stored at `price`: on the tick grid and
+    /// within `MAX_SPAN_TICKS` of every live level
+    #[inline]
+    pub fn accepts(&self, price: u64) -> bool {
+        if !self.is_aligned(price) {
+            return false;
+        }
+        if self.len == 0 {
+            return true;
+        }
+        let tick = price / self.tick;
+        let lo = (self.base_tick + self.lowest as u64).min(tick);
+        let hi = (self.base_tick + self.highest as u64).max(tick);
+        hi - lo < MAX_SPAN_TICKS
+    }
+
+    #[inline]
+    pub fn tick_size(&self) -> u64 {
+        self.tick
+    }
+
+    /// Lowest and highest price the window currently covers
+    #[inline]
+    pub fn window(&self) -> (u64, u64) {
+        (self.price_at(0), self.price_at(self.levels.len() - 1))
+    }
+
+    /// Times the window has moved or grown
+    #[inline]
+    pub fn recenters(&self) -> u64 {
+        self.recenters
+    }
+
+    /// Number of occupied levels
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn get(&self, price: u64) -> Option<&PriceLevel> {
+        let idx = self.occupied_slot(price)?;
+        Some(&self.levels[idx])
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, price: u64) -> Option<&mut PriceLevel> {
+        let idx = self.occupied_slot(price)?;
+        Some(&mut self.levels[idx])
+    }
+
+    /// Level at `price`, created empty if absent. Recenters if the price
+    /// is outside the window.
+    pub fn get_or_insert(&mut self, price: u64) -> &mut PriceLevel {
+        debug_assert!(self.accepts(price), "price {} not on tick grid or out of reach", price);
+        let idx = match self.slot(price) {
+            Some(idx) => idx,
+            None => {
+                self.recenter(price / self.tick);
+                self.slot(price).expect("recentered window contains price")
+            }
+        };
+
+        if !self.is_set(idx) {
+            self.set(idx);
+            if self.len == 0 {
+                self.lowest = idx;
+                self.highest = idx;
+            } else {
+                self.lowest = self.lowest.min(idx);
+                self.highest = self.highest.max(idx);
+            }
+            self.len += 1;
+        }
+        &mut self.levels[idx]
+    }
+
+    /// Remove the level at `price`, returning it
+    pub fn remove(&mut self, price: u64) -> Option<PriceLevel> {
+        let idx = self.occupied_slot(price)?;
+        let level = std::mem::take(&mut self.levels[idx]);
+        self.occupied[idx / WORD_BITS] &= !(1 << (idx % WORD_BITS));
+        self.len -= 1;
+        if self.len > 0 {
+            if idx == self.lowest {
+                self.lowest = self.next_set(idx + 1).expect("occupied slot above");
+            }
+            if idx == self.highest {
+                self.highest = self.prev_set(idx - 1).expect("occupied slot below");
+            }
+        }
+        Some(level)
+    }
+
+    /// Lowest occupied level
+    #[inline]
+    pub fn first(&self) -> Option<(u64, &PriceLevel)> {
+        (self.len > 0).then(|| (self.price_at(self.lowest), &self.levels[self.lowest]))
+    }
+
+    /// Highest occupied level
+    #[inline]
+    pub fn last(&self) -> Option<(u64, &PriceLevel)> {
+        (self.len > 0).then(|| (self.price_at(self.highest), &self.levels[self.highest]))
+    }
+
+    /// Occupied levels within a price range, ascending
+    pub fn range<R: RangeBounds<u64>>(&self, range: R) -> LadderIter<'_> {
+        let empty = LadderIter { ladder: self, front: 0, back: 0 };
+        if self.len == 0 {
+            return empty;
+        }
+
+        // Clamp to the occupied span, in slot units
+        let lo = match range.start_bound() {
+            Bound::Included(&p) => self.slot_at_or_above(p),
+            Bound::Excluded(&p) => p.checked_add(1).and_then(|p| self.slot_at_or_above(p)),
+            Bound::Unbounded => Some(0),
+        };
+        let hi = match range.end_bound() {
+            Bound::Included(&p) => self.slot_at_or_below(p),
+            Bound::Excluded(&p) => p.checked_sub(1).and_then(|p| self.slot_at_or_below(p)),
+            Bound::Unbounded => Some(self.levels.len() - 1),
+        };
+        match (lo, hi) {
+            (Some(lo), Some(hi)) if lo.max(self.lowest) <= hi.min(self.highest) => LadderIter {
+                ladder: self,
+                front: lo.max(self.lowest),
+                back: hi.min(self.highest) + 1,
+            },
+            _ => empty,
+        }
+    }
+
+    /// All occupied levels, ascending
+    #[inline]
+    pub fn iter(&self) -> LadderIter<'_> {
+        self.range(..)
+    }
+
+    pub fn clear(&mut self) {
+        if self.len > 0 {
+            self.levels[self.lowest..=self.highest].fill(PriceLevel::new());
+        }
+        self.occupied.fill(0);
+        self.len = 0;
+    }
+
+    // ========================================================================
+    // Internals
+    // ========================================================================
+
+    #[inline]
+    fn price_at(&self, idx: usize) -> u64 {
+        (self.base_tick + idx as u64) * self.tick
+    }
+
+    /// Slot for a price inside the window
+    #[inline]
+    fn slot(&self, price: u64) -> Option<usize> {
+        let idx = (price / self.tick).checked_sub(self.base_tick)?;
+        (idx < self.levels.len() as u64).then_some(idx as usize)
+    }
+
+    #[inline]
+    fn occupied_slot(&self, price: u64) -> Option<usize> {
+        if !self.is_aligned(price) {
+            return None;
+        }
+        self.slot(price).filter(|&idx| self.is_set(idx))
+    }
+
+    /// First slot whose price is >= `price` (None if above the window)
+    fn slot_at_or_above(&self, price: u64) -> Option<usize> {
+        let tick = price.div_ceil(self.tick);
+        let idx = tick.saturating_sub(self.base_tick);
+        (idx < self.levels.len() as u64).then_some(idx as usize)
+    }
+
+    /// Last slot whose price is <= `price` (None if below the window)
+    fn slot_at_or_below(&self, price: u64) -> Option<usize> {
+        let idx = (price / self.tick).checked_sub(self.base_tick)?;
+        Some(idx.min(self.levels.len() as u64 - 1) as usize)
+    }
+
+    #[inline]
+    fn is_set(&self, idx: usize) -> bool {
+        self.occupied[idx / WORD_BITS] & (1 << (idx % WORD_BITS)) != 0
+    }
+
+    #[inline]
+    fn set(&mut self, idx: usize) {
+        self.occupied[idx / WORD_BITS] |= 1 << (idx % WORD_BITS);
+    }
+
+    /// First occupied slot at or after `from`
+    fn next_set(&self, from: usize) -> Option<usize> {
+        let mut word = from / WORD_BITS;
+        if word >= self.occupied.len() {
+            return None;
+        }
+        let mut bits = self.occupied[word] & (!0u64 << (from % WORD_BITS));
+        loop {
+            if bits != 0 {
+                return Some(word * WORD_BITS + bits.trailing_zeros() as usize);
+            }
+            word += 1;
+            bits = *self.occupied.get(word)?;
+        }
+    }
+
+    /// Last occupied slot at or before `upto`
+    fn prev_set(&self, upto: usize) -> Option<usize> {
+        let mut word = upto / WORD_BITS;
+        let shift = WORD_BITS - 1 - upto % WORD_BITS;
+        let mut bits = self.occupied[word] & (!0u64 >> shift);
+        loop {
+            if bits != 0 {
+                return Some(word * WORD_BITS + WORD_BITS - 1 - bits.leading_zeros() as usize);
+            }
+            word = word.checked_sub(1)?;
+            bits = self.occupied[word];
+        }
+    }
+
+    /// Move (or grow) the window so it covers the live levels and `tick`
+    #[cold]
+    fn recenter(&mut self, tick: u64) {
+        self.recenters += 1;
+        let slots = self.levels.len() as u64;
+
+        let (lo, hi) = if self.len == 0 {
+            (tick, tick)
+        } else {
+            let lo = self.base_tick + self.lowest as u64;
+            let hi = self.base_tick + self.highest as u64;
+            (lo.min(tick), hi.max(tick))
+        };
+        let span = hi - lo + 1;
+
+        if span > slots {
+            self.grow(lo, span);
+            return;
+        }
+
+        // Center the live span in the window
+        let new_base = self.clamp_base(lo.saturating_sub((slots - span) / 2), slots);
+        if self.len > 0 {
+            self.shift(new_base);
+        }
+        self.base_tick = new_base;
+    }
+
+    /// Move occupied levels so slot 0 becomes tick `new_base`
+    fn shift(&mut self, new_base: u64) {
+        let (lowest, highest, base_tick) = (self.lowest, self.highest, self.base_tick);
+        let target = |idx: usize| (base_tick + idx as u64 - new_base) as usize;
+        let (new_lowest, new_highest) = (target(lowest), target(highest));
+
+        // Live slots move as one block; clear what the block leaves behind
+        self.levels.copy_within(lowest..=highest, new_lowest);
+        let stale = if new_lowest > lowest {
+            lowest..new_lowest.min(highest + 1)
+        } else {
+            (new_highest + 1).max(lowest)..highest + 1
+        };
+        self.levels[stale].fill(PriceLevel::new());
+
+        self.scratch.fill(0);
+        let mut idx = self.next_set(lowest);
+        while let Some(i) = idx.filter(|&i| i <= highest) {
+            let t = target(i);
+            self.scratch[t / WORD_BITS] |= 1 << (t % WORD_BITS);
+            idx = self.next_set(i + 1);
+        }
+        std::mem::swap(&mut self.occupied, &mut self.scratch);
+        self.lowest = new_lowest;
+        self.highest = new_highest;
+    }
+
+    /// Lower `base` so a window of `slots` ticks ends at or below `u64::MAX`
+    fn clamp_base(&self, base: u64, slots: u64) -> u64 {
+        base.min((u64::MAX / self.tick).saturating_sub(slots - 1))
+    }
+
+    /// Reallocate with a window at least twice the live span
+    fn grow(&mut self, lo: u64, span: u64) {
+        let slots = ((span * 2) as usize).next_power_of_two().max(WORD_BITS);
+        let new_base = self.clamp_base(lo.saturating_sub((slots as u64 - span) / 2), slots as u64);
+
+        let mut levels = vec![PriceLevel::new(); slots];
+        let mut occupied = vec![0u64; slots / WORD_BITS];
+        let mut idx = if self.len > 0 { self.next_set(self.lowest) } else { None };
+        while let Some(i) = idx {
+            let t = (self.base_tick + i as u64 - new_base) as usize;
+            levels[t] = self.levels[i];
+            occupied[t / WORD_BITS] |= 1 << (t % WORD_BITS);
+            idx = self.next_set(i + 1);
+        }
+
+        if self.len > 0 {
+            self.lowest = (self.base_tick + self.lowest as u64 - new_base) as usize;
+            self.highest = (self.base_tick + self.highest as u64 - new_base) as usize;
+        }
+        self.levels = levels;
+        self.occupied = occupied;
+        self.scratch = vec![0; slots / WORD_BITS];
+        self.base_tick = new_base;
+    }
+}
+
+/// Occupied ladder levels in a slot range, ascending (double-ended)
+#[derive(Clone, Debug)]
+pub struct LadderIter<'a> {
+    ladder: &'a PriceLadder,
+    /// Next slot to inspect from the front
+    front: usize,
+    /// One past the next slot to inspect from the back
+    back: usize,
+}
+
+impl<'a> Iterator for LadderIter<'a> {
+    type Item = (u64, &'a PriceLevel);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+        let idx = self.ladder.next_set(self.front).filter(|&i| i < self.back)?;
+        self.front = idx + 1;
+        Some((self.ladder.price_at(idx), &self.ladder.levels[idx]))
+    }
+}
+
+impl DoubleEndedIterator for LadderIter<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {
+            return None;
+        }
+        let idx = self.ladder.prev_set(self.back - 1).filter(|&i| i >= self.front)?;
+        self.back = idx;
+        Some((self.ladder.price_at(idx), &self.ladder.levels[idx]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prices(ladder: &PriceLadder) -> Vec<u64> {
+        ladder.iter().map(|(p, _)| p).collect()
+    }
+
+    #[test]
+    fn test_insert_remove_and_bounds() {
+        let mut ladder = PriceLadder::new(LadderConfig::new(1000, 5, 128));
+        for price in [1100, 1005, 1500, 1250] {
+            ladder.get_or_insert(price).total_qty = price;
+        }
+        assert_eq!(prices(&ladder), vec![1005, 1100, 1250, 1500]);
+        assert_eq!(ladder.first().unwrap().0, 1005);
+        assert_eq!(ladder.last().unwrap().0, 1500);
+
+        assert_eq!(ladder.remove(1005).unwrap().total_qty, 1005);
+        assert_eq!(ladder.remove(1500).unwrap().total_qty, 1500);
+        assert!(ladder.remove(1500).is_none());
+        assert_eq!((ladder.first().unwrap().0, ladder.last().unwrap().0), (1100, 1250));
+        assert!(ladder.get(1101).is_none());
+
+        // Ranges in both directions
+        let below: Vec<_> = ladder.range(..=1200).rev().map(|(p, _)| p).collect();
+        assert_eq!(below, vec![1100]);
+        let above: Vec<_> = ladder.range(1101..).map(|(p, _)| p).collect();
+        assert_eq!(above, vec![1250]);
+        assert_eq!(ladder.range(2000..).count(), 0);
+        assert_eq!(ladder.range(..900).count(), 0);
+    }
+
+    #[test]
+    fn test_recenter_keeps_levels() {
+        let mut ladder = PriceLadder::new(LadderConfig::new(0, 1, 64));
+        for price in [10, 20, 30] {
+            ladder.get_or_insert(price).count = price as u32;
+        }
+
+        // Drift up past the window: shifts in place
+        ladder.get_or_insert(70).count = 70;
+        assert_eq!(ladder.recenters(), 1);
+        assert_eq!(prices(&ladder), vec![10, 20, 30, 70]);
+        assert!(ladder.iter().all(|(p, l)| l.count == p as u32));
+
+        // Drift down far enough that the live span no longer fits: grows
+        ladder.get_or_insert(1).count = 1;
+        ladder.remove(10);
+        ladder.get_or_insert(200).count = 200;
+        assert_eq!(prices(&ladder), vec![1, 20, 30, 70, 200]);
+        assert!(ladder.iter().all(|(p, l)| l.count == p as u32));
+        let (lo, hi) = ladder.window();
+        assert!(lo <= 1 && hi >= 200);
+    }
+
+    #[test]
+    fn test_span_limit_and_extreme_prices() {
+        let mut ladder = PriceLadder::new(LadderConfig::default());
+        assert!(ladder.accepts(u64::MAX));
+        ladder.get_or_insert(u64::MAX).count = 1;
+        ladder.get_or_insert(u64::MAX - 100).count = 2;
+        assert_eq!(prices(&ladder), vec![u64::MAX - 100, u64::MAX]);
+        assert_eq!(ladder.window().1, u64::MAX);
+
+        // Too far from the live levels to fit in one window
+        assert!(!ladder.accepts(1000));
+        assert!(ladder.accepts(u64::MAX - (MAX_SPAN_TICKS - 1)));
+        assert!(!ladder.accepts(u64::MAX - MAX_SPAN_TICKS));
+
+        ladder.remove(u64::MAX);
+        ladder.remove(u64::MAX - 100);
+        assert!(ladder.accepts(1000));
+        ladder.get_or_insert(0).count = 3;
+        assert_eq!(prices(&ladder), vec![0]);
+    }
+}
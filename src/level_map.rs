@@ -0,0 +1,397 @@
+//! Level Map - Ordered price levels in pooled skip list nodes.
+//!
+//! The sparse level storage behind `LevelStorage::Tree`. Levels live in
+//! skip list nodes drawn from a `Vec` pool with a free list, so adding and
+//! removing levels reuses slots instead of allocating; the pool only grows
+//! when more levels are live at once than ever before. Node heights come
+//! from a fixed-seed generator, so the layout is deterministic.
+//!
+//! Lookup, insertion and removal are O(log n) expected; the best level on
+//! either end is O(1).
+
+use std::ops::{Bound, RangeBounds};
+
+use crate::price_level::PriceLevel;
+
+/// Maximum node height (4^12 levels before searches degrade)
+const MAX_HEIGHT: usize = 12;
+
+/// Null node link
+const NIL: u32 = u32::MAX;
+
+/// Sentinel node before the first level
+const HEAD: u32 = 0;
+
+/// Levels preallocated by `LevelMap::new`
+pub const DEFAULT_LEVEL_CAPACITY: usize = 1024;
+
+#[derive(Clone, Debug)]
+struct Node {
+    price: u64,
+    level: PriceLevel,
+    /// Successor at each height (free list link in `next[0]` when unused)
+    next: [u32; MAX_HEIGHT],
+    /// Predecessor at height 0 (`HEAD` for the first level)
+    prev: u32,
+}
+
+impl Node {
+    const fn new(price: u64) -> Self {
+        Self { price, level: PriceLevel::new(), next: [NIL; MAX_HEIGHT], prev: HEAD }
+    }
+}
+
+/// Ordered map from price to `PriceLevel` with pooled nodes
+#[derive(Clone, Debug)]
+pub struct LevelMap {
+    /// Node pool; slot 0 is the head sentinel
+    nodes: Vec<Node>,
+    /// First free slot, chained through `next[0]`
+    free: u32,
+    /// Last level (`HEAD` when empty)
+    tail: u32,
+    len: usize,
+    /// Heights in use
+    height: usize,
+    /// xorshift state for node heights
+    seed: u64,
+}
+
+impl LevelMap {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_LEVEL_CAPACITY)
+    }
+
+    /// Create a map with nodes preallocated for `levels` levels
+    pub fn with_capacity(levels: usize) -> Self {
+        let mut nodes = Vec::with_capacity(levels + 1);
+        nodes.push(Node::new(0));
+        Self { nodes, free: NIL, tail: HEAD, len: 0, height: 1, seed: 0x9E37_79B9_7F4A_7C15 }
+    }
+
+    /// Number of levels the pool holds without allocating
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity() - 1
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn get(&self, price: u64) -> Option<&PriceLevel> {
+        let i = self.find(price)?;
+        Some(&self.nodes[i as usize].level)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, price: u64) -> Option<&mut PriceLevel> {
+        let i = self.find(price)?;
+        Some(&mut self.nodes[i as usize].level)
+    }
+
+    /// Level at `price`, created empty if absent
+    pub fn get_or_insert(&mut self, price: u64) -> &mut PriceLevel {
+        let update = self.search(price);
+        let found = self.nodes[update[0] as usize].next[0];
+        if found != NIL && self.nodes[found as usize].price == price {
+            return &mut self.nodes[found as usize].level;
+        }
+
+        let height = self.random_height();
+        if height > self.height {
+            // update[h] is already HEAD above the old height
+            self.height = height;
+        }
+        let i = self.alloc_node(price);
+        for (h, &prev) in update.iter().enumerate().take(height) {
+            self.nodes[i as usize].next[h] = self.nodes[prev as usize].next[h];
+            self.nodes[prev as usize].next[h] = i;
+        }
+        self.nodes[i as usize].prev = update[0];
+        match self.nodes[i as usize].next[0] {
+            NIL => self.tail = i,
+            next => self.nodes[next as usize].prev = i,
+        }
+        self.len += 1;
+        &mut self.nodes[i as usize].level
+    }
+
+    pub fn remove(&mut self, price: u64) -> Option<PriceLevel> {
+        let update = self.search(price);
+        let i = self.nodes[update[0] as usize].next[0];
+        if i == NIL || self.nodes[i as usize].price != price {
+            return None;
+        }
+
+        for (h, &prev) in update.iter().enumerate().take(self.height) {
+            if self.nodes[prev as usize].next[h] != i {
+                break;
+            }
+            self.nodes[prev as usize].next[h] = self.nodes[i as usize].next[h];
+        }
+        match self.nodes[i as usize].next[0] {
+            NIL => self.tail = update[0],
+            next => self.nodes[next as usize].prev = update[0],
+        }
+        while self.height > 1 && self.nodes[HEAD as usize].next[self.height - 1] == NIL {
+            self.height -= 1;
+        }
+
+        let level = self.nodes[i as usize].level;
+        self.nodes[i as usize].next[0] = self.free;
+        self.free = i;
+        self.len -= 1;
+        Some(level)
+    }
+
+    /// Lowest-priced level
+    #[inline]
+    pub fn first(&self) -> Option<(u64, &PriceLevel)> {
+        self.entry(self.nodes[HEAD as usize].next[0])
+    }
+
+    /// Highest-priced level
+    #[inline]
+    pub fn last(&self) -> Option<(u64, &PriceLevel)> {
+        if self.tail == HEAD { None } else { self.entry(self.tail) }
+    }
+
+    /// Levels within a price range, ascending
+    pub fn range<R: RangeBounds<u64>>(&self, range: R) -> LevelMapIter<'_> {
+        let front = match range.start_bound() {
+            Bound::Included(&p) => self.lower_bound(p),
+            Bound::Excluded(&p) => p.checked_add(1).map_or(NIL, |p| self.lower_bound(p)),
+            Bound::Unbounded => self.nodes[HEAD as usize].next[0],
+        };
+        let back = match range.end_bound() {
+            Bound::Included(&p) => p.checked_add(1).map_or(self.tail, |p| self.last_below(p)),
+            Bound::Excluded(&p) => self.last_below(p),
+            Bound::Unbounded => self.tail,
+        };
+        let empty = front == NIL || back == HEAD || self.nodes[front as usize].price > self.nodes[back as usize].price;
+        if empty {
+            LevelMapIter { map: self, front: NIL, back: NIL }
+        } else {
+            LevelMapIter { map: self, front, back }
+        }
+    }
+
+    /// All levels, ascending
+    #[inline]
+    pub fn iter(&self) -> LevelMapIter<'_> {
+        self.range(..)
+    }
+
+    /// Remove all levels, keeping the pool
+    pub fn clear(&mut self) {
+        self.nodes.truncate(1);
+        self.nodes[HEAD as usize].next = [NIL; MAX_HEIGHT];
+        self.free = NIL;
+        self.tail = HEAD;
+        self.len = 0;
+        self.height = 1;
+    }
+
+    #[inline]
+    fn entry(&self, i: u32) -> Option<(u64, &PriceLevel)> {
+        if i == NIL {
+            return None;
+        }
+        let node = &self.nodes[i as usize];
+        Some((node.price, &node.level))
+    }
+
+    /// Rightmost node below `price` at each height
+    fn search(&self, price: u64) -> [u32; MAX_HEIGHT] {
+        let mut update = [HEAD; MAX_HEIGHT];
+        let mut x = HEAD;
+        for h in (0..self.height).rev() {
+            loop {
+                let next = self.nodes[x as usize].next[h];
+                if next == NIL || self.nodes[next as usize].price >= price {
+                    break;
+                }
+                x = next;
+            }
+            update[h] = x;
+        }
+        update
+    }
+
+    /// Node holding `price`. Matching works at the ends of the book, so
+    /// those are checked before searching.
+    #[inline]
+    fn find(&self, price: u64) -> Option<u32> {
+        let first = self.nodes[HEAD as usize].next[0];
+        if first == NIL {
+            return None;
+        }
+        let i = if self.nodes[first as usize].price == price {
+            first
+        } else if self.nodes[self.tail as usize].price == price {
+            self.tail
+        } else {
+            self.lower_bound(price)
+        };
+        (i != NIL && self.nodes[i as usize].price == price).then_some(i)
+    }
+
+    /// First node with a price of at least `price`, or `NIL`
+    #[inline]
+    fn lower_bound(&self, price: u64) -> u32 {
+        let update = self.search(price);
+        self.nodes[update[0] as usize].next[0]
+    }
+
+    /// Last node with a price below `price`, or `HEAD`
+    #[inline]
+    fn last_below(&self, price: u64) -> u32 {
+        self.search(price)[0]
+    }
+
+    fn alloc_node(&mut self, price: u64) -> u32 {
+        if self.free != NIL {
+            let i = self.free;
+            self.free = self.nodes[i as usize].next[0];
+            self.nodes[i as usize] = Node::new(price);
+            return i;
+        }
+        // Pool exhausted: grows (and allocates) only past the high-water mark
+        self.nodes.push(Node::new(price));
+        (self.nodes.len() - 1) as u32
+    }
+
+    /// Height with P(h) = 4^-(h-1)
+    fn random_height(&mut self) -> usize {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (1 + self.seed.trailing_zeros() as usize / 2).min(MAX_HEIGHT)
+    }
+}
+
+impl Default for LevelMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Levels of a `LevelMap` in a price range, ascending (double-ended)
+#[derive(Clone, Debug)]
+pub struct LevelMapIter<'a> {
+    map: &'a LevelMap,
+    /// Next node from the front (`NIL` when exhausted)
+    front: u32,
+    /// Next node from the back
+    back: u32,
+}
+
+impl<'a> Iterator for LevelMapIter<'a> {
+    type Item = (u64, &'a PriceLevel);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.front;
+        if i == NIL {
+            return None;
+        }
+        if i == self.back {
+            self.front = NIL;
+            self.back = NIL;
+        } else {
+            self.front = self.map.nodes[i as usize].next[0];
+        }
+        self.map.entry(i)
+    }
+}
+
+impl DoubleEndedIterator for LevelMapIter<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let i = self.back;
+        if i == NIL {
+            return None;
+        }
+        if i == self.front {
+            self.front = NIL;
+            self.back = NIL;
+        } else {
+            self.back = self.map.nodes[i as usize].prev;
+        }
+        self.map.entry(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn prices(map: &LevelMap) -> Vec<u64> {
+        map.iter().map(|(p, _)| p).collect()
+    }
+
+    #[test]
+    fn test_matches_btreemap() {
+        let mut map = LevelMap::with_capacity(16);
+        let mut reference = BTreeMap::new();
+        let mut seed = 12345u64;
+        for _ in 0..20_000 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let price = (seed >> 33) % 500;
+            if seed & 1 == 0 {
+                map.get_or_insert(price).total_qty = price;
+                reference.insert(price, price);
+            } else {
+                assert_eq!(map.remove(price).map(|l| l.total_qty), reference.remove(&price));
+            }
+            assert_eq!(map.len(), reference.len());
+            assert_eq!(map.first().map(|(p, _)| p), reference.keys().next().copied());
+            assert_eq!(map.last().map(|(p, _)| p), reference.keys().next_back().copied());
+        }
+        assert_eq!(prices(&map), reference.keys().copied().collect::<Vec<_>>());
+
+        let (lo, hi) = (120, 380);
+        let expected: Vec<_> = reference.range(lo..hi).rev().map(|(&p, _)| p).collect();
+        let actual: Vec<_> = map.range(lo..hi).rev().map(|(p, _)| p).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_ranges_and_reuse() {
+        let mut map = LevelMap::with_capacity(4);
+        for price in [10, 20, 30, 40] {
+            map.get_or_insert(price);
+        }
+        assert_eq!(map.range(15..=30).map(|(p, _)| p).collect::<Vec<_>>(), [20, 30]);
+        assert_eq!(map.range(..10).count(), 0);
+        assert_eq!(map.range(41..).count(), 0);
+        assert_eq!(map.range(21..30).count(), 0);
+        assert_eq!(map.range(..=u64::MAX).count(), 4);
+
+        // Mixed ends meet in the middle
+        let mut it = map.iter();
+        assert_eq!(it.next().map(|(p, _)| p), Some(10));
+        assert_eq!(it.next_back().map(|(p, _)| p), Some(40));
+        assert_eq!(it.next_back().map(|(p, _)| p), Some(30));
+        assert_eq!(it.next().map(|(p, _)| p), Some(20));
+        assert!(it.next().is_none() && it.next_back().is_none());
+
+        // Freed nodes are reused without growing the pool
+        let capacity = map.capacity();
+        map.remove(20);
+        map.remove(40);
+        map.get_or_insert(25);
+        map.get_or_insert(50);
+        assert_eq!(map.capacity(), capacity);
+        assert_eq!(prices(&map), [10, 25, 30, 50]);
+    }
+}
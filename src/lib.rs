@@ -18,16 +18,52 @@
 //! ```
 
 pub mod arena;
+pub mod arena_memory;
 pub mod command;
 pub mod price_level;
+pub mod ladder;
+pub mod level_map;
+pub mod book_side;
 pub mod order_book;
+pub mod order_index;
+pub mod backend;
+pub mod vec_book;
+pub mod depth;
+pub mod queue_position;
 pub mod matching;
+pub mod validate;
+#[cfg(feature = "reference")]
+pub mod reference;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+pub mod order_status;
 pub mod engine;
 pub mod coinbase;
+pub mod interner;
+pub mod lobster;
+pub mod l2_replay;
+pub mod backtest;
+pub mod itch;
+pub mod recovery;
+pub mod mirror;
+#[cfg(feature = "ws")]
+pub mod ws;
 
 // Re-exports for convenience
-pub use arena::{Arena, ArenaIndex, OrderNode, NULL_INDEX};
-pub use command::{Command, PlaceOrder, CancelOrder, ModifyOrder, OrderType, Side, TradeEvent, BookUpdate, OutputEvent};
+pub use arena::{Arena, ArenaError, ArenaGrowth, ArenaHandle, ArenaIndex, OrderNode, NULL_INDEX};
+pub use arena_memory::{AllocReport, AllocStrategy, HugePages};
+pub use command::{Command, PlaceOrder, CancelOrder, ModifyOrder, OrderType, Side, TradeEvent, BookUpdate, BboUpdate, ArenaHighWater, OrderHandle, OutputEvent};
 pub use price_level::PriceLevel;
 pub use order_book::OrderBook;
-pub use engine::Engine;
+pub use backend::BookBackend;
+pub use vec_book::VecBook;
+pub use book_side::LevelStorage;
+pub use ladder::LadderConfig;
+pub use depth::{FillEstimate, LevelDepth};
+pub use queue_position::QueuePosition;
+pub use order_status::{OrderState, OrderStatus};
+pub use validate::InvariantViolation;
+pub use engine::{Engine, EngineConfig};
+#[cfg(feature = "runtime")]
+pub use engine::EngineInput;
+pub use mirror::BookMirror;
@@ -0,0 +1,678 @@
+//! LOBSTER - Import and export of LOBSTER message/orderbook files.
+//!
+//! LOBSTER ships each session as two headerless CSV files with one row per
+//! event:
+//!
+//! - **message**: `time, type, order_id, size, price, direction`, where time
+//!   is seconds after midnight and direction is 1 (buy) or -1 (sell)
+//! - **orderbook**: `ask_price_1, ask_size_1, bid_price_1, bid_size_1, ...`
+//!   for N levels, describing the book *after* the matching message
+//!
+//! Prices are integers (dollars x 10000 in LOBSTER data) and are passed
+//! through unchanged; the engine has no notion of tick size.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use rustc_hash::FxHashMap;
+
+use crate::backend::BookBackend;
+use crate::command::{CancelOrder, Command, OrderType, OutputEvent, PlaceOrder, Side};
+use crate::engine::Engine;
+use crate::mirror::BookMirror;
+
+/// Price written for an empty ask level
+pub const EMPTY_ASK_PRICE: i64 = 9_999_999_999;
+/// Price written for an empty bid level
+pub const EMPTY_BID_PRICE: i64 = -9_999_999_999;
+
+/// Default first ID for synthetic aggressor orders (see `CommandConverter`)
+pub const DEFAULT_SYNTHETIC_ID_BASE: u64 = 1 << 63;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// LOBSTER message event types
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum LobsterEvent {
+    /// Submission of a new limit order
+    Submit = 1,
+    /// Cancellation (partial deletion) of a limit order
+    PartialCancel = 2,
+    /// Deletion (total) of a limit order
+    Delete = 3,
+    /// Execution of a visible limit order
+    ExecuteVisible = 4,
+    /// Execution of a hidden limit order
+    ExecuteHidden = 5,
+    /// Auction / cross trade
+    Cross = 6,
+    /// Trading halt indicator
+    Halt = 7,
+}
+
+impl LobsterEvent {
+    fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            1 => LobsterEvent::Submit,
+            2 => LobsterEvent::PartialCancel,
+            3 => LobsterEvent::Delete,
+            4 => LobsterEvent::ExecuteVisible,
+            5 => LobsterEvent::ExecuteHidden,
+            6 => LobsterEvent::Cross,
+            7 => LobsterEvent::Halt,
+            _ => return None,
+        })
+    }
+}
+
+/// One row of a message file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LobsterMessage {
+    /// Nanoseconds after midnight
+    pub time_ns: u64,
+    pub event: LobsterEvent,
+    pub order_id: u64,
+    pub size: u32,
+    /// Price (0 for halt rows)
+    pub price: u64,
+    /// Side of the limit order (for executions, the resting order)
+    pub side: Side,
+}
+
+/// One row of an orderbook file; empty levels are omitted
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LobsterBookRow {
+    /// `(price, size)` best first
+    pub asks: Vec<(u64, u64)>,
+    /// `(price, size)` best first
+    pub bids: Vec<(u64, u64)>,
+}
+
+/// Errors reading LOBSTER files
+#[derive(Debug)]
+pub enum LobsterError {
+    Csv(csv::Error),
+    /// Row could not be interpreted
+    Invalid { line: u64, message: String },
+}
+
+impl fmt::Display for LobsterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LobsterError::Csv(e) => write!(f, "csv error: {}", e),
+            LobsterError::Invalid { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for LobsterError {}
+
+impl From<csv::Error> for LobsterError {
+    fn from(e: csv::Error) -> Self {
+        LobsterError::Csv(e)
+    }
+}
+
+fn invalid(record: &csv::StringRecord, message: impl Into<String>) -> LobsterError {
+    LobsterError::Invalid {
+        line: record.position().map_or(0, |p| p.line()),
+        message: message.into(),
+    }
+}
+
+fn field<'a>(record: &'a csv::StringRecord, idx: usize, name: &str) -> Result<&'a str, LobsterError> {
+    record.get(idx).map(str::trim).ok_or_else(|| invalid(record, format!("missing {}", name)))
+}
+
+fn parse_field<T: std::str::FromStr>(record: &csv::StringRecord, idx: usize, name: &str) -> Result<T, LobsterError> {
+    let raw = field(record, idx, name)?;
+    raw.parse().map_err(|_| invalid(record, format!("invalid {} {:?}", name, raw)))
+}
+
+/// Parse `seconds.fraction` into nanoseconds
+fn parse_time(raw: &str) -> Option<u64> {
+    let (secs, frac) = raw.split_once('.').unwrap_or((raw, ""));
+    if frac.len() > 9 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let secs: u64 = secs.parse().ok()?;
+    let frac_ns = if frac.is_empty() { 0 } else { frac.parse::<u64>().ok()? * 10u64.pow(9 - frac.len() as u32) };
+    secs.checked_mul(NANOS_PER_SEC)?.checked_add(frac_ns)
+}
+
+fn format_time(time_ns: u64) -> String {
+    format!("{}.{:09}", time_ns / NANOS_PER_SEC, time_ns % NANOS_PER_SEC)
+}
+
+fn headerless<R: Read>(reader: R) -> csv::Reader<R> {
+    csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(reader)
+}
+
+// ============================================================================
+// Reading
+// ============================================================================
+
+/// Reads a LOBSTER message file.
+pub struct MessageReader<R: Read> {
+    inner: csv::Reader<R>,
+    record: csv::StringRecord,
+}
+
+impl<R: Read> MessageReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { inner: headerless(reader), record: csv::StringRecord::new() }
+    }
+
+    fn parse(record: &csv::StringRecord) -> Result<LobsterMessage, LobsterError> {
+        let time_raw = field(record, 0, "time")?;
+        let time_ns = parse_time(time_raw).ok_or_else(|| invalid(record, format!("invalid time {:?}", time_raw)))?;
+        let code: u8 = parse_field(record, 1, "event type")?;
+        let event = LobsterEvent::from_code(code).ok_or_else(|| invalid(record, format!("unknown event type {}", code)))?;
+        let order_id = parse_field(record, 2, "order id")?;
+        let size = parse_field(record, 3, "size")?;
+        let price: i64 = parse_field(record, 4, "price")?;
+        let direction: i8 = parse_field(record, 5, "direction")?;
+
+        if event == LobsterEvent::Halt {
+            // Halt rows carry -1 placeholders
+            return Ok(LobsterMessage { time_ns, event, order_id, size, price: 0, side: Side::Bid });
+        }
+
+        let price = u64::try_from(price).map_err(|_| invalid(record, format!("negative price {}", price)))?;
+        let side = match direction {
+            1 => Side::Bid,
+            -1 => Side::Ask,
+            other => return Err(invalid(record, format!("invalid direction {}", other))),
+        };
+        Ok(LobsterMessage { time_ns, event, order_id, size, price, side })
+    }
+}
+
+impl<R: Read> Iterator for MessageReader<R> {
+    type Item = Result<LobsterMessage, LobsterError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner.read_record(&mut self.record) {
+            Ok(true) => Some(Self::parse(&self.record)),
+            Ok(false) => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+/// Reads a LOBSTER orderbook file.
+pub struct BookReader<R: Read> {
+    inner: csv::Reader<R>,
+    record: csv::StringRecord,
+}
+
+impl<R: Read> BookReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { inner: headerless(reader), record: csv::StringRecord::new() }
+    }
+
+    fn parse(record: &csv::StringRecord) -> Result<LobsterBookRow, LobsterError> {
+        if record.is_empty() || !record.len().is_multiple_of(4) {
+            return Err(invalid(record, format!("expected 4 columns per level, got {}", record.len())));
+        }
+
+        let mut row = LobsterBookRow::default();
+        for level in 0..record.len() / 4 {
+            let base = level * 4;
+            let ask_price: i64 = parse_field(record, base, "ask price")?;
+            let ask_size: u64 = parse_field(record, base + 1, "ask size")?;
+            let bid_price: i64 = parse_field(record, base + 2, "bid price")?;
+            let bid_size: u64 = parse_field(record, base + 3, "bid size")?;
+
+            if ask_price != EMPTY_ASK_PRICE && ask_size > 0 {
+                let price = u64::try_from(ask_price).map_err(|_| invalid(record, "negative ask price"))?;
+                row.asks.push((price, ask_size));
+            }
+            if bid_price != EMPTY_BID_PRICE && bid_size > 0 {
+                let price = u64::try_from(bid_price).map_err(|_| invalid(record, "negative bid price"))?;
+                row.bids.push((price, bid_size));
+            }
+        }
+        Ok(row)
+    }
+}
+
+impl<R: Read> Iterator for BookReader<R> {
+    type Item = Result<LobsterBookRow, LobsterError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner.read_record(&mut self.record) {
+            Ok(true) => Some(Self::parse(&self.record)),
+            Ok(false) => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+/// Converts LOBSTER messages into engine commands.
+///
+/// - Submissions become limit orders and deletions become cancels.
+/// - Visible executions become IOC orders from a synthetic aggressor on the
+///   opposite side, which the engine matches against the resting order at
+///   the head of that level.
+/// - Partial cancels become an in-place reduction (`LobsterAction::Reduce`),
+///   which keeps the order's queue priority.
+/// - Hidden executions, crosses and halts do not affect the visible book
+///   and are skipped.
+pub struct CommandConverter {
+    /// Open orders: side, price and remaining size
+    open: FxHashMap<u64, (Side, u64, u32)>,
+    next_synthetic: u64,
+}
+
+impl CommandConverter {
+    pub fn new() -> Self {
+        Self::with_synthetic_id_base(DEFAULT_SYNTHETIC_ID_BASE)
+    }
+
+    /// Synthetic aggressor IDs count up from `base`, which must not collide
+    /// with the file's order IDs.
+    pub fn with_synthetic_id_base(base: u64) -> Self {
+        Self { open: FxHashMap::default(), next_synthetic: base }
+    }
+
+    /// Action for a message, or `None` if it has no visible book effect.
+    pub fn convert(&mut self, msg: &LobsterMessage) -> Option<LobsterAction> {
+        match msg.event {
+            LobsterEvent::Submit => {
+                self.open.insert(msg.order_id, (msg.side, msg.price, msg.size));
+                Some(LobsterAction::Command(Command::Place(PlaceOrder::limit(msg.order_id, 0, msg.side, msg.price, msg.size))))
+            }
+            LobsterEvent::Delete => {
+                self.open.remove(&msg.order_id);
+                Some(LobsterAction::Command(Command::Cancel(CancelOrder { order_id: msg.order_id })))
+            }
+            LobsterEvent::PartialCancel => {
+                let (_, _, remaining) = self.reduce(msg.order_id, msg.size)?;
+                if remaining == 0 {
+                    return Some(LobsterAction::Command(Command::Cancel(CancelOrder { order_id: msg.order_id })));
+                }
+                Some(LobsterAction::Reduce { order_id: msg.order_id, by: msg.size })
+            }
+            LobsterEvent::ExecuteVisible => {
+                self.reduce(msg.order_id, msg.size);
+                let order_id = self.next_synthetic;
+                self.next_synthetic += 1;
+                Some(LobsterAction::Command(Command::Place(PlaceOrder {
+                    order_id,
+                    user_id: 0,
+                    side: msg.side.opposite(),
+                    price: msg.price,
+                    qty: msg.size,
+                    order_type: OrderType::IOC,
+                })))
+            }
+            LobsterEvent::ExecuteHidden | LobsterEvent::Cross | LobsterEvent::Halt => None,
+        }
+    }
+
+    /// Reduce an open order, returning its state after the reduction
+    fn reduce(&mut self, order_id: u64, by: u32) -> Option<(Side, u64, u32)> {
+        let order = self.open.get_mut(&order_id)?;
+        order.2 = order.2.saturating_sub(by);
+        let state = *order;
+        if state.2 == 0 {
+            self.open.remove(&order_id);
+        }
+        Some(state)
+    }
+}
+
+/// What a LOBSTER message does to the engine's book
+#[derive(Clone, Copy, Debug)]
+pub enum LobsterAction {
+    Command(Command),
+    /// Reduce a resting order in place, keeping its queue priority (there is
+    /// no `Command` for this; see `MatchingEngine::process_reduce`)
+    Reduce { order_id: u64, by: u32 },
+}
+
+impl LobsterAction {
+    /// Apply to an engine, returning the events produced
+    pub fn apply<B: BookBackend>(self, engine: &mut Engine<B>) -> &[OutputEvent] {
+        match self {
+            LobsterAction::Command(cmd) => engine.process_command(cmd),
+            LobsterAction::Reduce { order_id, by } => {
+                engine.event_buffer.clear();
+                engine.matcher.process_reduce(order_id, by, &mut engine.event_buffer);
+                &engine.event_buffer
+            }
+        }
+    }
+}
+
+impl Default for CommandConverter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// Writing
+// ============================================================================
+
+/// A message row awaiting the book state that follows it
+#[derive(Clone, Copy, Debug)]
+struct PendingRow {
+    event: LobsterEvent,
+    order_id: u64,
+    size: u32,
+    price: u64,
+    side: Side,
+}
+
+/// Writes an engine `OutputEvent` stream as LOBSTER message and orderbook files.
+///
+/// Accepted orders are written as submissions, cancels as deletions,
+/// in-place reductions as partial cancels and trades as visible executions
+/// of the maker. Each message row is paired
+/// with the top-N book after the `BookDelta`s that follow it.
+pub struct LobsterWriter<M: Write, B: Write> {
+    messages: csv::Writer<M>,
+    book: csv::Writer<B>,
+    levels: usize,
+    mirror: BookMirror,
+    /// Side, price and remaining size of resting orders, for delete rows
+    orders: FxHashMap<u64, (Side, u64, u32)>,
+    pending: Option<(u64, PendingRow)>,
+}
+
+impl<M: Write, B: Write> LobsterWriter<M, B> {
+    pub fn new(messages: M, book: B, levels: usize) -> Self {
+        let builder = || {
+            let mut b = csv::WriterBuilder::new();
+            b.has_headers(false);
+            b
+        };
+        Self {
+            messages: builder().from_writer(messages),
+            book: builder().from_writer(book),
+            levels,
+            mirror: BookMirror::new(),
+            orders: FxHashMap::default(),
+            pending: None,
+        }
+    }
+
+    /// Write the events produced by one command at `time_ns` after midnight.
+    pub fn write_events(&mut self, time_ns: u64, events: &[OutputEvent]) -> io::Result<()> {
+        for event in events {
+            let row = match *event {
+                OutputEvent::Accepted(a) => {
+                    self.orders.insert(a.order_id, (a.side, a.price, a.qty));
+                    Some(PendingRow {
+                        event: LobsterEvent::Submit,
+                        order_id: a.order_id,
+                        size: a.qty,
+                        price: a.price,
+                        side: a.side,
+                    })
+                }
+                OutputEvent::Canceled(c) => self.orders.remove(&c.order_id).map(|(side, price, _)| PendingRow {
+                    event: LobsterEvent::Delete,
+                    order_id: c.order_id,
+                    size: c.canceled_qty,
+                    price,
+                    side,
+                }),
+                OutputEvent::Reduced(r) => self.orders.get_mut(&r.order_id).map(|order| {
+                    order.2 = r.remaining;
+                    PendingRow {
+                        event: LobsterEvent::PartialCancel,
+                        order_id: r.order_id,
+                        size: r.reduced_by,
+                        price: order.1,
+                        side: order.0,
+                    }
+                }),
+                OutputEvent::Trade(t) => {
+                    // Forget makers once fully filled
+                    if let Some(order) = self.orders.get_mut(&t.maker_order_id) {
+                        order.2 = order.2.saturating_sub(t.qty);
+                        if order.2 == 0 {
+                            self.orders.remove(&t.maker_order_id);
+                        }
+                    }
+                    Some(PendingRow {
+                        event: LobsterEvent::ExecuteVisible,
+                        order_id: t.maker_order_id,
+                        size: t.qty,
+                        price: t.price,
+                        side: t.taker_side.opposite(),
+                    })
+                }
+                OutputEvent::BookDelta(_) => {
+                    // L2-only mirror: level deltas cannot be inconsistent
+                    let _ = self.mirror.apply(event);
+                    None
+                }
+                OutputEvent::Rejected(_) | OutputEvent::Bbo(_) | OutputEvent::ArenaHighWater(_) => None,
+            };
+
+            if let Some(row) = row {
+                self.write_pending()?;
+                self.pending = Some((time_ns, row));
+            }
+        }
+        self.write_pending()
+    }
+
+    /// Flush both files.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.messages.flush()?;
+        self.book.flush()
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        let Some((time_ns, row)) = self.pending.take() else {
+            return Ok(());
+        };
+
+        let direction = match row.side {
+            Side::Bid => "1",
+            Side::Ask => "-1",
+        };
+        self.messages.write_record([
+            format_time(time_ns),
+            (row.event as u8).to_string(),
+            row.order_id.to_string(),
+            row.size.to_string(),
+            row.price.to_string(),
+            direction.to_string(),
+        ])?;
+
+        let mut asks = self.mirror.top_levels(Side::Ask, self.levels);
+        let mut bids = self.mirror.top_levels(Side::Bid, self.levels);
+        let mut record = Vec::with_capacity(self.levels * 4);
+        for _ in 0..self.levels {
+            match asks.next() {
+                Some((price, level)) => {
+                    record.push(price.to_string());
+                    record.push(level.qty.to_string());
+                }
+                None => {
+                    record.push(EMPTY_ASK_PRICE.to_string());
+                    record.push("0".to_string());
+                }
+            }
+            match bids.next() {
+                Some((price, level)) => {
+                    record.push(price.to_string());
+                    record.push(level.qty.to_string());
+                }
+                None => {
+                    record.push(EMPTY_BID_PRICE.to_string());
+                    record.push("0".to_string());
+                }
+            }
+        }
+        self.book.write_record(&record)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("34200.004241176"), Some(34_200_004_241_176));
+        assert_eq!(parse_time("34200.5"), Some(34_200_500_000_000));
+        assert_eq!(parse_time("34200"), Some(34_200_000_000_000));
+        assert_eq!(parse_time("1.0000000001"), None);
+        assert_eq!(format_time(34_200_004_241_176), "34200.004241176");
+    }
+
+    #[test]
+    fn test_read_messages() {
+        let data = "34200.01,1,11,100,5000000,1\n34200.02,4,11,40,5000000,1\n34200.03,7,0,0,-1,-1\n";
+        let msgs: Vec<_> = MessageReader::new(data.as_bytes()).collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(msgs.len(), 3);
+        assert_eq!(msgs[0].event, LobsterEvent::Submit);
+        assert_eq!((msgs[0].order_id, msgs[0].size, msgs[0].price, msgs[0].side), (11, 100, 5_000_000, Side::Bid));
+        assert_eq!(msgs[1].event, LobsterEvent::ExecuteVisible);
+        assert_eq!(msgs[2].event, LobsterEvent::Halt);
+
+        let bad = "34200.01,9,11,100,5000000,1\n";
+        assert!(MessageReader::new(bad.as_bytes()).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_read_book_row() {
+        let data = "5000100,30,5000000,60,9999999999,0,-9999999999,0\n";
+        let row = BookReader::new(data.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(row.asks, vec![(5_000_100, 30)]);
+        assert_eq!(row.bids, vec![(5_000_000, 60)]);
+    }
+
+    #[test]
+    fn test_convert_execution_and_partial_cancel() {
+        let mut engine = Engine::new(100);
+        let mut conv = CommandConverter::with_synthetic_id_base(1000);
+        let data = "1.0,1,1,100,500,-1\n2.0,1,2,50,500,-1\n3.0,4,1,30,500,-1\n4.0,2,2,20,500,-1\n4.5,2,1,10,500,-1\n5.0,5,9,10,500,1\n";
+
+        for msg in MessageReader::new(data.as_bytes()) {
+            if let Some(action) = conv.convert(&msg.unwrap()) {
+                action.apply(&mut engine);
+            }
+        }
+
+        let level = engine.matcher.book.get_level(Side::Ask, 500).unwrap();
+        assert_eq!((level.total_qty, level.count), (60 + 30, 2));
+        assert!(!engine.matcher.book.contains_order(1000));
+        // The partial cancel kept order 1 at the head of the queue
+        let position = engine.matcher.book.queue_position(&engine.matcher.arena, 1).unwrap();
+        assert_eq!(position.orders_ahead, 0);
+    }
+
+    #[test]
+    fn test_write_events() {
+        let mut engine = Engine::new(100);
+        let mut messages = Vec::new();
+        let mut book = Vec::new();
+        {
+            let mut writer = LobsterWriter::new(&mut messages, &mut book, 2);
+            let cmds = [
+                Command::Place(PlaceOrder::limit(1, 1, Side::Ask, 500, 10)),
+                Command::Place(PlaceOrder::limit(2, 1, Side::Bid, 490, 5)),
+                Command::Place(PlaceOrder::limit(3, 2, Side::Bid, 500, 4)),
+                Command::Cancel(CancelOrder { order_id: 2 }),
+            ];
+            for (i, cmd) in cmds.into_iter().enumerate() {
+                writer.write_events(i as u64 * NANOS_PER_SEC, engine.process_command(cmd)).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+
+        let messages = String::from_utf8(messages).unwrap();
+        let book = String::from_utf8(book).unwrap();
+        assert_eq!(messages.lines().collect::<Vec<_>>(), vec![
+            "0.000000000,1,1,10,500,-1",
+            "1.000000000,1,2,5,490,1",
+            "2.000000000,4,1,4,500,-1",
+            "3.000000000,3,2,5,490,1",
+        ]);
+        assert_eq!(book.lines().nth(2), Some("500,6,490,5,9999999999,0,-9999999999,0"));
+        assert_eq!(book.lines().nth(3), Some("500,6,-9999999999,0,9999999999,0,-9999999999,0"));
+    }
+
+    #[test]
+    fn test_writer_forgets_filled_makers() {
+        let mut engine = Engine::new(100);
+        let mut writer = LobsterWriter::new(Vec::new(), Vec::new(), 1);
+        let actions = [
+            LobsterAction::Command(Command::Place(PlaceOrder::limit(1, 1, Side::Ask, 500, 10))),
+            LobsterAction::Reduce { order_id: 1, by: 3 },
+            LobsterAction::Command(Command::Place(PlaceOrder::limit(2, 2, Side::Bid, 500, 4))),
+            LobsterAction::Command(Command::Place(PlaceOrder::limit(3, 2, Side::Bid, 500, 3))),
+        ];
+        let remaining = [Some(10), Some(7), Some(3), None];
+        for (i, action) in actions.into_iter().enumerate() {
+            writer.write_events(i as u64, action.apply(&mut engine)).unwrap();
+            assert_eq!(writer.orders.get(&1).map(|o| o.2), remaining[i], "after action {}", i);
+        }
+        assert!(writer.orders.is_empty());
+    }
+
+    #[test]
+    fn test_round_trip_replays_identical_book() {
+        use rand::prelude::*;
+        use rand_chacha::ChaCha8Rng;
+
+        const LEVELS: usize = 5;
+        let mut rng = ChaCha8Rng::seed_from_u64(0x10B5);
+        let mut engine = Engine::new(10_000);
+        let mut messages = Vec::new();
+        let mut book = Vec::new();
+        let mut live = Vec::new();
+
+        let mut writer = LobsterWriter::new(&mut messages, &mut book, LEVELS);
+        for i in 1..2000u64 {
+            let op = rng.gen_range(0..10);
+            let action = if live.is_empty() || op < 6 {
+                live.push(i);
+                let side = if rng.gen_bool(0.5) { Side::Bid } else { Side::Ask };
+                LobsterAction::Command(Command::Place(PlaceOrder::limit(i, 1, side, rng.gen_range(95..105), rng.gen_range(1..50))))
+            } else if op < 8 {
+                // Partial cancel, or a full one if it takes everything left
+                LobsterAction::Reduce { order_id: live[rng.gen_range(0..live.len())], by: rng.gen_range(1..30) }
+            } else {
+                let idx = rng.gen_range(0..live.len());
+                LobsterAction::Command(Command::Cancel(CancelOrder { order_id: live.swap_remove(idx) }))
+            };
+            writer.write_events(i * 1000, action.apply(&mut engine)).unwrap();
+            live.retain(|&id| engine.matcher.book.contains_order(id));
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut replay = Engine::new(10_000);
+        let mut conv = CommandConverter::new();
+        let rows = MessageReader::new(messages.as_slice()).zip(BookReader::new(book.as_slice()));
+        let mut count = 0;
+        let mut partial_cancels = 0;
+        for (msg, row) in rows {
+            let msg = msg.unwrap();
+            partial_cancels += (msg.event == LobsterEvent::PartialCancel) as usize;
+            if let Some(action) = conv.convert(&msg) {
+                action.apply(&mut replay);
+            }
+            let row = row.unwrap();
+            let asks: Vec<_> = replay.matcher.book.top_levels(Side::Ask, LEVELS).map(|l| (l.price, l.qty)).collect();
+            let bids: Vec<_> = replay.matcher.book.top_levels(Side::Bid, LEVELS).map(|l| (l.price, l.qty)).collect();
+            assert_eq!((asks, bids), (row.asks, row.bids), "row {}", count);
+            count += 1;
+        }
+        assert!(count > 2000);
+        assert!(partial_cancels > 100, "only {} partial cancels", partial_cancels);
+    }
+}
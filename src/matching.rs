@@ -5,11 +5,17 @@
 //! 2. RESTING: Place remaining quantity in the book
 
 use crate::arena::{Arena, ArenaIndex, NULL_INDEX};
+use crate::backend::BookBackend;
+use crate::book_side::LevelStorage;
 use crate::command::{
-    BookUpdate, CancelOrder, OutputEvent, PlaceOrder, Side, TradeEvent,
-    OrderAccepted, OrderCanceled, OrderRejected, RejectReason, OrderType,
+    ArenaHighWater, BookUpdate, CancelOrder, OrderHandle, OutputEvent, PlaceOrder, Side, TradeEvent,
+    OrderAccepted, OrderCanceled, OrderReduced, OrderRejected, RejectReason, OrderType,
 };
-use crate::order_book::OrderBook;
+use crate::depth::LevelOrders;
+use crate::order_book::{OrderBook, OrderInfo};
+use crate::order_status::{OrderHistory, OrderState, OrderStatus};
+use crate::price_level::PriceLevel;
+use crate::queue_position::QueuePosition;
 
 /// Result of processing a place order command
 #[derive(Debug)]
@@ -24,20 +30,78 @@ pub struct PlaceResult {
     pub resting_qty: u32,
 }
 
-/// The matching engine core
-pub struct MatchingEngine {
+/// What `process_place` would do with an order, from `simulate_place`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlaceSimulation {
+    /// Trades that would be generated, in order
+    pub fills: Vec<TradeEvent>,
+    /// Total quantity filled
+    pub filled_qty: u32,
+    /// Sum of price x qty over the fills
+    pub notional: u128,
+    /// Unfilled quantity
+    pub residual_qty: u32,
+    /// Whether the residual would rest in the book (Limit orders)
+    pub rests: bool,
+    /// Rejection, if any. `ArenaFull` is raised after crossing, so fills
+    /// may still be present.
+    pub rejected: Option<RejectReason>,
+}
+
+impl PlaceSimulation {
+    /// Average fill price
+    #[inline]
+    pub fn avg_price(&self) -> Option<f64> {
+        (self.filled_qty > 0).then(|| self.notional as f64 / self.filled_qty as f64)
+    }
+}
+
+/// The matching engine core, generic over the order book backend
+pub struct MatchingEngine<B: BookBackend = OrderBook> {
     /// Memory arena for order nodes
     pub arena: Arena,
     /// The limit order book
-    pub book: OrderBook,
+    pub book: B,
+    /// Recently finished orders (disabled by default)
+    history: OrderHistory,
 }
 
 impl MatchingEngine {
     /// Create a new matching engine with the specified capacity
     pub fn new(capacity: u32) -> Self {
+        Self::with_storage(capacity, LevelStorage::Tree)
+    }
+    
+    /// Create a matching engine with the given price level storage
+    pub fn with_storage(capacity: u32, storage: LevelStorage) -> Self {
+        Self::with_book(capacity, OrderBook::with_storage(storage, capacity as usize))
+    }
+    
+    /// Orders and quantity ahead of a resting order at its price level
+    #[inline]
+    pub fn queue_position(&self, order_id: u64) -> Option<QueuePosition> {
+        self.book.queue_position(&self.arena, order_id)
+    }
+    
+    /// Maintain queue positions incrementally (O(log n) lookups)
+    pub fn enable_queue_tracking(&mut self) {
+        self.book.enable_queue_tracking(&self.arena);
+    }
+}
+
+impl<B: BookBackend> MatchingEngine<B> {
+    /// Create a matching engine over an empty book from any backend
+    pub fn with_book(capacity: u32, book: B) -> Self {
+        Self::with_arena(Arena::new(capacity), book)
+    }
+    
+    /// Create a matching engine over an empty book with a prepared arena
+    /// (e.g. `Arena::growable`)
+    pub fn with_arena(arena: Arena, book: B) -> Self {
         Self {
-            arena: Arena::new(capacity),
-            book: OrderBook::with_capacity(1000, capacity as usize),
+            arena,
+            book,
+            history: OrderHistory::default(),
         }
     }
     
@@ -62,6 +126,10 @@ impl MatchingEngine {
                 order_id: order.order_id,
                 reason: RejectReason::InvalidQuantity,
             }));
+            // A live order with this ID keeps its status
+            if !self.book.contains_order(order.order_id) {
+                self.record_unrested(&order, 0, OrderState::Rejected);
+            }
             return;
         }
         
@@ -77,11 +145,12 @@ impl MatchingEngine {
         // For FOK orders: Check if we can fill the entire quantity
         if order.order_type == OrderType::FOK {
             let available = self.calculate_available_qty(&order);
-            if available < order.qty {
+            if available < order.qty as u64 {
                 events.push(OutputEvent::Rejected(OrderRejected {
                     order_id: order.order_id,
                     reason: RejectReason::InsufficientLiquidity,
                 }));
+                self.record_unrested(&order, order.qty, OrderState::Rejected);
                 return;
             }
         }
@@ -96,19 +165,19 @@ impl MatchingEngine {
             match order.order_type {
                 OrderType::Limit => {
                     // Rest the order in the book
-                    if let Some(_arena_idx) = self.rest_order(&order, remaining_qty, events) {
-                        // Order is now resting
-                    } else {
-                        // Arena is full
+                    if let Err(reason) = self.rest_order(&order, remaining_qty, events) {
+                        // Arena is full, or the price can't be stored
                         events.push(OutputEvent::Rejected(OrderRejected {
                             order_id: order.order_id,
-                            reason: RejectReason::ArenaFull,
+                            reason,
                         }));
+                        self.record_unrested(&order, remaining_qty, OrderState::Rejected);
                     }
                 }
                 OrderType::IOC => {
                     // IOC: Cancel unfilled portion silently (no resting)
                     // We don't emit a cancel event since the order never rested
+                    self.record_unrested(&order, remaining_qty, OrderState::Canceled);
                 }
                 OrderType::FOK => {
                     // This shouldn't happen since we pre-checked availability
@@ -116,24 +185,109 @@ impl MatchingEngine {
                     unreachable!("FOK order should have been fully filled or rejected");
                 }
             }
+        } else {
+            self.record_unrested(&order, 0, OrderState::Filled);
         }
     }
     
+    /// Dry-run a place order against the current book.
+    ///
+    /// Reports the fills, average price and residual `process_place` would
+    /// produce (including FOK/IOC handling) without touching the arena or
+    /// the book.
+    pub fn simulate_place(&self, order: PlaceOrder) -> PlaceSimulation {
+        let mut sim = PlaceSimulation { residual_qty: order.qty, ..Default::default() };
+        
+        if order.qty == 0 {
+            sim.rejected = Some(RejectReason::InvalidQuantity);
+            return sim;
+        }
+        if self.book.contains_order(order.order_id) {
+            sim.rejected = Some(RejectReason::DuplicateOrderId);
+            return sim;
+        }
+        if order.order_type == OrderType::FOK && self.calculate_available_qty(&order) < order.qty as u64 {
+            sim.rejected = Some(RejectReason::InsufficientLiquidity);
+            return sim;
+        }
+        
+        // Walk the crossing levels in price-time priority
+        let mut remaining = order.qty;
+        let freed_slots = match order.side {
+            Side::Bid => self.simulate_fills(&order, self.book.level_range(Side::Ask, ..=order.price), &mut remaining, &mut sim),
+            Side::Ask => self.simulate_fills(&order, self.book.level_range(Side::Bid, order.price..).rev(), &mut remaining, &mut sim),
+        };
+        sim.filled_qty = order.qty - remaining;
+        sim.residual_qty = remaining;
+        
+        if remaining > 0 && order.order_type == OrderType::Limit {
+            // Crossing only empties the opposite side, so the resting side's
+            // storage answers the same now as after the fills
+            if !self.book.accepts_price(order.side, order.price) {
+                sim.rejected = Some(RejectReason::InvalidPrice);
+            } else if self.arena.allocated() - freed_slots >= self.arena.max_capacity() {
+                // Makers filled completely give their slots back before resting
+                sim.rejected = Some(RejectReason::ArenaFull);
+            } else {
+                sim.rests = true;
+            }
+        }
+        sim
+    }
+    
+    /// Fill `remaining` against `levels` (best first) into `sim`.
+    ///
+    /// # Returns
+    /// Number of makers that would be filled completely
+    fn simulate_fills<'a>(
+        &'a self,
+        order: &PlaceOrder,
+        levels: impl Iterator<Item = (u64, &'a PriceLevel)>,
+        remaining: &mut u32,
+        sim: &mut PlaceSimulation,
+    ) -> u32 {
+        let mut freed_slots = 0;
+        for (price, level) in levels {
+            for maker in LevelOrders::new(&self.arena, level.head) {
+                if *remaining == 0 {
+                    return freed_slots;
+                }
+                let qty = (*remaining).min(maker.qty);
+                if qty == maker.qty {
+                    freed_slots += 1;
+                }
+                *remaining -= qty;
+                sim.notional += price as u128 * qty as u128;
+                sim.fills.push(TradeEvent {
+                    price,
+                    qty,
+                    maker_order_id: maker.order_id,
+                    taker_order_id: order.order_id,
+                    maker_user_id: maker.user_id,
+                    taker_user_id: order.user_id,
+                    taker_side: order.side,
+                });
+            }
+        }
+        freed_slots
+    }
+    
     /// Calculate the total available quantity at prices that cross with the order.
-    /// Used for FOK order validation.
-    fn calculate_available_qty(&self, order: &PlaceOrder) -> u32 {
-        let mut available = 0u32;
+    /// Used for FOK order validation. Summed in u64 like the level totals, since
+    /// resting quantity can exceed `u32::MAX`.
+    fn calculate_available_qty(&self, order: &PlaceOrder) -> u64 {
+        let mut available = 0u64;
         
-        // Iterate through opposite side levels using efficient BTreeMap range scan
+        // Iterate through opposite side levels using the backend's range scan
         match order.side {
             Side::Bid => {
                 // For a bid, check check all ask levels <= order price
                 // Asks are increasingly ordered. We want all asks from min to order.price
-                for (_, level) in self.book.asks.range(..=order.price) {
-                    available = available.saturating_add(level.total_qty as u32);
+                for (_, level) in self.book.level_range(Side::Ask, ..=order.price) {
+                    available = available.saturating_add(level.total_qty);
                     // Optimization: We could early exit if available >= order.qty 
                     // loop break optimization is valid for FOK check
-                    if available >= order.qty {
+                    if available >= order.qty as u64 {
                         return available;
                     }
                 }
@@ -143,9 +297,9 @@ impl MatchingEngine {
                 // Bids are increasingly ordered. We want all bids from order.price to max
                 // Note: Standard matching logic usually walks best->worst. 
                 // range(order.price..) gives us all bids >= price.
-                for (_, level) in self.book.bids.range(order.price..) {
-                    available = available.saturating_add(level.total_qty as u32);
-                    if available >= order.qty {
+                for (_, level) in self.book.level_range(Side::Bid, order.price..).rev() {
+                    available = available.saturating_add(level.total_qty);
+                    if available >= order.qty as u64 {
                         return available;
                     }
                 }
@@ -269,12 +423,14 @@ impl MatchingEngine {
                 // Re-borrow level mutably
                 let level = self.book.get_level_mut(maker_side, price).unwrap();
                 level.pop_front(&mut self.arena);
-                self.book.remove_order_from_map(maker_order_id);
+                if let Some(info) = self.book.remove_order_from_map(maker_order_id) {
+                    self.history.record(finished_status(maker_order_id, &info, 0, OrderState::Filled));
+                }
                 self.arena.free(maker_idx);
                 
                 // Check if level is now empty
                 let level = self.book.get_level(maker_side, price);
-                if level.map_or(true, |l| l.is_empty()) {
+                if level.is_none_or(|l| l.is_empty()) {
                     // Emit book update (level removed)
                     events.push(OutputEvent::BookDelta(BookUpdate {
                         side: maker_side,
@@ -296,6 +452,7 @@ impl MatchingEngine {
             } else {
                 // Maker partially filled - update quantity
                 self.arena.get_mut(maker_idx).qty = new_maker_qty;
+                self.book.note_qty_reduced(maker_order_id, trade_qty);
                 
                 // Update level total
                 let level = self.book.get_level_mut(maker_side, price).unwrap();
@@ -317,15 +474,23 @@ impl MatchingEngine {
     /// Rest an order in the book (passive posting).
     ///
     /// # Returns
-    /// Arena index of the new order, or `None` if arena is full
+    /// Arena index of the new order, or `InvalidPrice` if the level storage
+    /// can't hold the price (ladder: off-tick or out of span) and `ArenaFull`
+    /// if the arena is full
     fn rest_order(
         &mut self,
         order: &PlaceOrder,
         qty: u32,
         events: &mut Vec<OutputEvent>,
-    ) -> Option<ArenaIndex> {
+    ) -> Result<ArenaIndex, RejectReason> {
+        // Checked only here so marketable orders trade the same on every
+        // backend; just the remainder is refused
+        if !self.book.accepts_price(order.side, order.price) {
+            return Err(RejectReason::InvalidPrice);
+        }
+        
         // Allocate node
-        let arena_idx = self.arena.alloc()?;
+        let arena_idx = self.arena.alloc().ok_or(RejectReason::ArenaFull)?;
         
         // Populate node
         let node = self.arena.get_mut(arena_idx);
@@ -343,6 +508,12 @@ impl MatchingEngine {
             order.price,
             arena_idx,
         );
+        if qty != order.qty {
+            // Partially filled while crossing; status counts those fills too
+            if let Some(info) = self.book.get_order_mut(order.order_id) {
+                info.original_qty = order.qty;
+            }
+        }
         
         // Emit accepted event
         events.push(OutputEvent::Accepted(OrderAccepted {
@@ -350,6 +521,7 @@ impl MatchingEngine {
             price: order.price,
             qty,
             side: order.side,
+            handle: self.arena.handle(arena_idx).into(),
         }));
         
         // Emit book update
@@ -361,7 +533,15 @@ impl MatchingEngine {
             new_count: level.count,
         }));
         
-        Some(arena_idx)
+        if self.arena.take_high_watermark() {
+            events.push(OutputEvent::ArenaHighWater(ArenaHighWater {
+                allocated: self.arena.allocated(),
+                capacity: self.arena.capacity(),
+                max_capacity: self.arena.max_capacity(),
+            }));
+        }
+        
+        Ok(arena_idx)
     }
     
     /// Process a cancel order command.
@@ -383,17 +563,73 @@ impl MatchingEngine {
         };
         
         // Get canceled quantity before removal
-        let canceled_qty = self.arena.get(info.arena_index).qty;
+        let arena_idx = self.arena.resolve(info.handle);
+        let canceled_qty = self.arena.get(arena_idx).qty;
         
         // Remove from book
         self.book.remove_order(&mut self.arena, cancel.order_id);
+        self.finish_cancel(cancel.order_id, &info, arena_idx, canceled_qty, events);
+    }
+    
+    /// Cancel an order via the handle from its `OrderAccepted`.
+    ///
+    /// The handle is checked against the arena node (generation and order
+    /// ID), which locates the order without an order ID lookup, and the
+    /// order is dropped from the book through its arena slot, so the cancel
+    /// does no hashing (unless queue tracking or order history is enabled,
+    /// which are keyed by ID). A stale or mismatched handle is rejected with
+    /// `OrderNotFound`.
+    ///
+    /// # Returns
+    /// The canceled order's info, or `None` if rejected
+    pub fn process_cancel_by_handle(
+        &mut self,
+        cancel: CancelOrder,
+        handle: OrderHandle,
+        events: &mut Vec<OutputEvent>,
+    ) -> Option<OrderInfo> {
+        let arena_idx = match self.arena.check(handle.into()) {
+            Ok(idx) if self.arena.is_live(idx) && self.arena.get(idx).order_id == cancel.order_id => idx,
+            _ => {
+                events.push(OutputEvent::Rejected(OrderRejected {
+                    order_id: cancel.order_id,
+                    reason: RejectReason::OrderNotFound,
+                }));
+                return None;
+            }
+        };
+        
+        // Every live node is resting, so the order is in the book
+        let info = self.book.remove_order_by_handle(cancel.order_id, handle.into())
+            .expect("live arena node missing from the order map");
+        let canceled_qty = self.arena.get(arena_idx).qty;
+        let level = self.book.get_level_mut(info.side, info.price)
+            .expect("resting order's level missing");
+        if level.remove(&mut self.arena, arena_idx) {
+            self.book.remove_empty_level(info.side, info.price);
+        }
+        
+        self.finish_cancel(cancel.order_id, &info, arena_idx, canceled_qty, events);
+        Some(info)
+    }
+    
+    /// Record, free and report an order already removed from the book
+    fn finish_cancel(
+        &mut self,
+        order_id: u64,
+        info: &OrderInfo,
+        arena_idx: ArenaIndex,
+        canceled_qty: u32,
+        events: &mut Vec<OutputEvent>,
+    ) {
+        self.history.record(finished_status(order_id, info, canceled_qty, OrderState::Canceled));
         
         // Free arena slot
-        self.arena.free(info.arena_index);
+        self.arena.free(arena_idx);
         
         // Emit canceled event
         events.push(OutputEvent::Canceled(OrderCanceled {
-            order_id: cancel.order_id,
+            order_id,
             canceled_qty,
         }));
         
@@ -405,7 +641,99 @@ impl MatchingEngine {
             new_qty,
             new_count,
         }));
-        
+    }
+
+    /// Reduce a resting order's quantity in place, keeping queue priority.
+    ///
+    /// Used when replaying external feeds that report size decreases; there
+    /// is no `Command` for it. Reducing by the full remaining quantity (or
+    /// more) cancels the order. A partial reduction emits `Reduced` followed
+    /// by the level's `BookDelta`.
+    ///
+    /// # Returns
+    /// The order's remaining quantity, or `None` if it was not found
+    pub fn process_reduce(&mut self, order_id: u64, reduce_by: u32, events: &mut Vec<OutputEvent>) -> Option<u32> {
+        let info = *self.book.get_order(order_id)?;
+        let node = self.arena.get_handle_mut(info.handle);
+
+        if reduce_by >= node.qty {
+            self.process_cancel(CancelOrder { order_id }, events);
+            return Some(0);
+        }
+        if reduce_by == 0 {
+            return Some(node.qty);
+        }
+
+        node.qty -= reduce_by;
+        let remaining = node.qty;
+        self.book.note_qty_reduced(order_id, reduce_by);
+        if let Some(info) = self.book.get_order_mut(order_id) {
+            info.reduced_qty += reduce_by;
+        }
+        let level = self.book.get_level_mut(info.side, info.price)?;
+        level.subtract_qty(reduce_by);
+        events.push(OutputEvent::Reduced(OrderReduced {
+            order_id,
+            reduced_by: reduce_by,
+            remaining,
+        }));
+        events.push(OutputEvent::BookDelta(BookUpdate {
+            side: info.side,
+            price: info.price,
+            new_qty: level.total_qty,
+            new_count: level.count,
+        }));
+        Some(remaining)
+    }
+
+    /// Execute a resting order against an aggressor outside the engine
+    /// (e.g. a historical trade in a backtest), keeping queue priority.
+    ///
+    /// Unlike `process_reduce` the quantity counts as filled, and an order
+    /// filled completely leaves the book as `Filled`. Emits a `Trade` with
+    /// taker order and user ID 0, then the level's `BookDelta`. `qty` is
+    /// capped at the order's remaining quantity.
+    ///
+    /// # Returns
+    /// The order's remaining quantity, or `None` if it was not found
+    pub fn process_external_fill(&mut self, order_id: u64, qty: u32, events: &mut Vec<OutputEvent>) -> Option<u32> {
+        let info = *self.book.get_order(order_id)?;
+        let arena_idx = self.arena.resolve(info.handle);
+        let node = self.arena.get(arena_idx);
+        let qty = qty.min(node.qty);
+        let remaining = node.qty - qty;
+        if qty == 0 {
+            return Some(remaining);
+        }
+
+        events.push(OutputEvent::Trade(TradeEvent {
+            price: info.price,
+            qty,
+            maker_order_id: order_id,
+            taker_order_id: 0,
+            maker_user_id: node.user_id,
+            taker_user_id: 0,
+            taker_side: info.side.opposite(),
+        }));
+
+        if remaining == 0 {
+            self.book.remove_order(&mut self.arena, order_id);
+            self.history.record(finished_status(order_id, &info, 0, OrderState::Filled));
+            self.arena.free(arena_idx);
+        } else {
+            self.arena.get_mut(arena_idx).qty = remaining;
+            self.book.note_qty_reduced(order_id, qty);
+            self.book.get_level_mut(info.side, info.price)?.subtract_qty(qty);
+        }
+
+        let (new_qty, new_count) = self.book.depth_at(info.side, info.price);
+        events.push(OutputEvent::BookDelta(BookUpdate {
+            side: info.side,
+            price: info.price,
+            new_qty,
+            new_count,
+        }));
+        Some(remaining)
     }
     
     // ========================================================================
@@ -436,6 +764,74 @@ impl MatchingEngine {
         self.book.order_count()
     }
     
+    /// Quantities and state of a live order, or of a finished one still in
+    /// the history window
+    pub fn order_status(&self, order_id: u64) -> Option<OrderStatus> {
+        match self.book.get_order(order_id) {
+            Some(info) => Some(self.live_status(order_id, info)),
+            None => self.history.get(order_id).copied(),
+        }
+    }
+    
+    /// A user's resting orders, in no particular order.
+    ///
+    /// Scans the order map, so this is O(resting orders).
+    pub fn open_orders(&self, user_id: u64) -> impl Iterator<Item = OrderStatus> + '_ {
+        self.book.orders()
+            .filter(move |(_, info)| info.user_id == user_id)
+            .map(|(order_id, info)| self.live_status(order_id, info))
+    }
+    
+    /// Recently finished orders
+    #[inline]
+    pub fn history(&self) -> &OrderHistory {
+        &self.history
+    }
+    
+    /// Keep up to `capacity` finished orders for `order_status` (0 = off).
+    /// Clears the current history.
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history = OrderHistory::new(capacity);
+    }
+    
+    fn live_status(&self, order_id: u64, info: &OrderInfo) -> OrderStatus {
+        let remaining_qty = self.arena.get_handle(info.handle).qty;
+        let filled_qty = info.original_qty - info.reduced_qty - remaining_qty;
+        OrderStatus {
+            order_id,
+            user_id: info.user_id,
+            side: info.side,
+            price: info.price,
+            original_qty: info.original_qty,
+            remaining_qty,
+            filled_qty,
+            reduced_qty: info.reduced_qty,
+            state: if filled_qty == 0 { OrderState::New } else { OrderState::PartiallyFilled },
+        }
+    }
+    
+    /// Record an order that finished without resting
+    fn record_unrested(&mut self, order: &PlaceOrder, unfilled: u32, state: OrderState) {
+        self.history.record(OrderStatus {
+            order_id: order.order_id,
+            user_id: order.user_id,
+            side: order.side,
+            price: order.price,
+            original_qty: order.qty,
+            remaining_qty: 0,
+            filled_qty: order.qty - unfilled,
+            reduced_qty: 0,
+            state,
+        });
+    }
+    
+    /// Orders resting at a price, in time priority
+    #[inline]
+    pub fn level_orders(&self, side: Side, price: u64) -> LevelOrders<'_> {
+        let head = self.book.get_level(side, price).map_or(NULL_INDEX, |l| l.head);
+        LevelOrders::new(&self.arena, head)
+    }
+    
     /// Warm up the engine (pre-fault memory pages)
     pub fn warm_up(&mut self) {
         self.arena.warm_up();
@@ -463,35 +859,45 @@ impl MatchingEngine {
         self.book.order_count().hash(&mut hasher);
         self.arena.allocated().hash(&mut hasher);
         
-        // Hash all bid levels (sorted by price for consistency)
-        let mut bid_prices: Vec<_> = self.book.bids.keys().copied().collect();
-        bid_prices.sort_unstable();
-        for price in bid_prices {
+        // Hash all bid levels (ascending price, independent of storage)
+        for (price, level) in self.book.level_range(Side::Bid, ..) {
             price.hash(&mut hasher);
-            if let Some(level) = self.book.bids.get(&price) {
-                level.total_qty.hash(&mut hasher);
-                level.count.hash(&mut hasher);
-            }
+            level.total_qty.hash(&mut hasher);
+            level.count.hash(&mut hasher);
         }
         
-        // Hash all ask levels (sorted by price for consistency)
-        let mut ask_prices: Vec<_> = self.book.asks.keys().copied().collect();
-        ask_prices.sort_unstable();
-        for price in ask_prices {
+        // Hash all ask levels (ascending price, independent of storage)
+        for (price, level) in self.book.level_range(Side::Ask, ..) {
             price.hash(&mut hasher);
-            if let Some(level) = self.book.asks.get(&price) {
-                level.total_qty.hash(&mut hasher);
-                level.count.hash(&mut hasher);
-            }
+            level.total_qty.hash(&mut hasher);
+            level.count.hash(&mut hasher);
         }
         
         hasher.finish()
     }
 }
 
+/// Status of an order leaving the book with `unfilled` quantity
+fn finished_status(order_id: u64, info: &OrderInfo, unfilled: u32, state: OrderState) -> OrderStatus {
+    OrderStatus {
+        order_id,
+        user_id: info.user_id,
+        side: info.side,
+        price: info.price,
+        original_qty: info.original_qty,
+        remaining_qty: 0,
+        filled_qty: info.original_qty - info.reduced_qty - unfilled,
+        reduced_qty: info.reduced_qty,
+        state,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::arena::ArenaGrowth;
+    use crate::ladder::LadderConfig;
+    use crate::vec_book::VecBook;
     
     fn place_order(
         order_id: u64,
@@ -510,476 +916,756 @@ mod tests {
         }
     }
     
-    #[test]
-    fn test_place_bid_no_match() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        let order = place_order(1, 100, Side::Bid, 10000, 100);
-        let mut events = Vec::new();
-        engine.process_place(order, &mut events);
-        
-        // Should get Accepted + BookDelta
-        assert_eq!(events.len(), 2);
-        assert!(matches!(events[0], OutputEvent::Accepted(_)));
-        assert!(matches!(events[1], OutputEvent::BookDelta(_)));
-        
-        assert_eq!(engine.best_bid(), Some(10000));
-        assert_eq!(engine.best_ask(), None);
-        assert_eq!(engine.order_count(), 1);
+    /// Instantiates `matching_tests!` once per backend, each in its own
+    /// module with a matching `new_engine`
+    macro_rules! backend_tests {
+        ($($backend:ident => $book:expr;)*) => {$(
+            mod $backend {
+                use super::*;
+
+                fn new_engine(capacity: u32) -> MatchingEngine<impl BookBackend> {
+                    MatchingEngine::with_book(capacity, ($book)(capacity as usize))
+                }
+
+                matching_tests!();
+            }
+        )*};
     }
+
+    macro_rules! matching_tests {
+        () => {
+            #[test]
+            fn test_place_bid_no_match() {
+                let mut engine = new_engine(1000);
+        
+                let order = place_order(1, 100, Side::Bid, 10000, 100);
+                let mut events = Vec::new();
+                engine.process_place(order, &mut events);
+        
+                // Should get Accepted + BookDelta
+                assert_eq!(events.len(), 2);
+                assert!(matches!(events[0], OutputEvent::Accepted(_)));
+                assert!(matches!(events[1], OutputEvent::BookDelta(_)));
+        
+                assert_eq!(engine.best_bid(), Some(10000));
+                assert_eq!(engine.best_ask(), None);
+                assert_eq!(engine.order_count(), 1);
+            }
     
-    #[test]
-    fn test_place_ask_no_match() {
-        let mut engine = MatchingEngine::new(1000);
+            #[test]
+            fn test_place_ask_no_match() {
+                let mut engine = new_engine(1000);
         
-        let order = place_order(1, 100, Side::Ask, 10100, 100);
-        let mut events = Vec::new();
-        engine.process_place(order, &mut events);
+                let order = place_order(1, 100, Side::Ask, 10100, 100);
+                let mut events = Vec::new();
+                engine.process_place(order, &mut events);
         
-        assert_eq!(events.len(), 2);
-        assert_eq!(engine.best_bid(), None);
-        assert_eq!(engine.best_ask(), Some(10100));
-    }
+                assert_eq!(events.len(), 2);
+                assert_eq!(engine.best_bid(), None);
+                assert_eq!(engine.best_ask(), Some(10100));
+            }
     
-    #[test]
-    fn test_full_match() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        // Place resting ask
-        let ask = place_order(1, 100, Side::Ask, 10000, 100);
-        let mut events = Vec::new();
-        engine.process_place(ask, &mut events);
-        events.clear();
-        
-        // Place crossing bid
-        let bid = place_order(2, 200, Side::Bid, 10000, 100);
-        engine.process_place(bid, &mut events);
-        
-        // Should get Trade + BookDelta (level removed)
-        let trades: Vec<_> = events.iter()
-            .filter(|e| matches!(e, OutputEvent::Trade(_)))
-            .collect();
-        assert_eq!(trades.len(), 1);
-        
-        if let OutputEvent::Trade(t) = trades[0] {
-            assert_eq!(t.price, 10000);
-            assert_eq!(t.qty, 100);
-            assert_eq!(t.maker_order_id, 1);
-            assert_eq!(t.taker_order_id, 2);
-            assert_eq!(t.taker_side, Side::Bid);
-        }
+            #[test]
+            fn test_full_match() {
+                let mut engine = new_engine(1000);
+        
+                // Place resting ask
+                let ask = place_order(1, 100, Side::Ask, 10000, 100);
+                let mut events = Vec::new();
+                engine.process_place(ask, &mut events);
+                events.clear();
+        
+                // Place crossing bid
+                let bid = place_order(2, 200, Side::Bid, 10000, 100);
+                engine.process_place(bid, &mut events);
+        
+                // Should get Trade + BookDelta (level removed)
+                let trades: Vec<_> = events.iter()
+                    .filter(|e| matches!(e, OutputEvent::Trade(_)))
+                    .collect();
+                assert_eq!(trades.len(), 1);
+        
+                if let OutputEvent::Trade(t) = trades[0] {
+                    assert_eq!(t.price, 10000);
+                    assert_eq!(t.qty, 100);
+                    assert_eq!(t.maker_order_id, 1);
+                    assert_eq!(t.taker_order_id, 2);
+                    assert_eq!(t.taker_side, Side::Bid);
+                }
         
-        // Book should be empty
-        assert_eq!(engine.order_count(), 0);
-        assert_eq!(engine.best_bid(), None);
-        assert_eq!(engine.best_ask(), None);
-    }
+                // Book should be empty
+                assert_eq!(engine.order_count(), 0);
+                assert_eq!(engine.best_bid(), None);
+                assert_eq!(engine.best_ask(), None);
+            }
     
-    #[test]
-    fn test_partial_match_taker_remains() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        // Place small resting ask
-        let ask = place_order(1, 100, Side::Ask, 10000, 50);
-        let mut events = Vec::new();
-        engine.process_place(ask, &mut events);
-        events.clear();
-        
-        // Place larger crossing bid
-        let bid = place_order(2, 200, Side::Bid, 10000, 100);
-        engine.process_place(bid, &mut events);
-        
-        // Should trade 50, then rest 50
-        let trades: Vec<_> = events.iter()
-            .filter(|e| matches!(e, OutputEvent::Trade(_)))
-            .collect();
-        assert_eq!(trades.len(), 1);
-        
-        if let OutputEvent::Trade(t) = trades[0] {
-            assert_eq!(t.qty, 50);
-        }
+            #[test]
+            fn test_partial_match_taker_remains() {
+                let mut engine = new_engine(1000);
+        
+                // Place small resting ask
+                let ask = place_order(1, 100, Side::Ask, 10000, 50);
+                let mut events = Vec::new();
+                engine.process_place(ask, &mut events);
+                events.clear();
+        
+                // Place larger crossing bid
+                let bid = place_order(2, 200, Side::Bid, 10000, 100);
+                engine.process_place(bid, &mut events);
+        
+                // Should trade 50, then rest 50
+                let trades: Vec<_> = events.iter()
+                    .filter(|e| matches!(e, OutputEvent::Trade(_)))
+                    .collect();
+                assert_eq!(trades.len(), 1);
+        
+                if let OutputEvent::Trade(t) = trades[0] {
+                    assert_eq!(t.qty, 50);
+                }
         
-        // Taker should be resting
-        let accepted: Vec<_> = events.iter()
-            .filter(|e| matches!(e, OutputEvent::Accepted(_)))
-            .collect();
-        assert_eq!(accepted.len(), 1);
+                // Taker should be resting
+                let accepted: Vec<_> = events.iter()
+                    .filter(|e| matches!(e, OutputEvent::Accepted(_)))
+                    .collect();
+                assert_eq!(accepted.len(), 1);
         
-        if let OutputEvent::Accepted(a) = accepted[0] {
-            assert_eq!(a.order_id, 2);
-            assert_eq!(a.qty, 50);
-        }
+                if let OutputEvent::Accepted(a) = accepted[0] {
+                    assert_eq!(a.order_id, 2);
+                    assert_eq!(a.qty, 50);
+                }
         
-        // Book state
-        assert_eq!(engine.order_count(), 1);
-        assert_eq!(engine.best_bid(), Some(10000));
-        assert_eq!(engine.best_ask(), None);
-    }
+                // Book state
+                assert_eq!(engine.order_count(), 1);
+                assert_eq!(engine.best_bid(), Some(10000));
+                assert_eq!(engine.best_ask(), None);
+            }
     
-    #[test]
-    fn test_partial_match_maker_remains() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        // Place large resting ask
-        let ask = place_order(1, 100, Side::Ask, 10000, 100);
-        let mut events = Vec::new();
-        engine.process_place(ask, &mut events);
-        events.clear();
-        
-        // Place smaller crossing bid
-        let bid = place_order(2, 200, Side::Bid, 10000, 30);
-        engine.process_place(bid, &mut events);
-        
-        // Maker should have 70 remaining
-        assert_eq!(engine.order_count(), 1);
-        assert_eq!(engine.best_ask(), Some(10000));
-        
-        let (qty, count) = engine.book.depth_at(Side::Ask, 10000);
-        assert_eq!(qty, 70);
-        assert_eq!(count, 1);
-    }
+            #[test]
+            fn test_partial_match_maker_remains() {
+                let mut engine = new_engine(1000);
+        
+                // Place large resting ask
+                let ask = place_order(1, 100, Side::Ask, 10000, 100);
+                let mut events = Vec::new();
+                engine.process_place(ask, &mut events);
+                events.clear();
+        
+                // Place smaller crossing bid
+                let bid = place_order(2, 200, Side::Bid, 10000, 30);
+                engine.process_place(bid, &mut events);
+        
+                // Maker should have 70 remaining
+                assert_eq!(engine.order_count(), 1);
+                assert_eq!(engine.best_ask(), Some(10000));
+        
+                let (qty, count) = engine.book.depth_at(Side::Ask, 10000);
+                assert_eq!(qty, 70);
+                assert_eq!(count, 1);
+            }
     
-    #[test]
-    fn test_match_multiple_levels() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        // Place asks at multiple levels
-        let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Ask, 10000, 50), &mut events);
-        engine.process_place(place_order(2, 100, Side::Ask, 10010, 50), &mut events);
-        engine.process_place(place_order(3, 100, Side::Ask, 10020, 50), &mut events);
-        events.clear(); // Clear setup events
-        
-        // Place large crossing bid
-        let bid = place_order(4, 200, Side::Bid, 10020, 120);
-        engine.process_place(bid, &mut events);
-        
-        // Should match all of level 10000 (50), all of 10010 (50), part of 10020 (20)
-        let trades: Vec<_> = events.iter()
-            .filter_map(|e| if let OutputEvent::Trade(t) = e { Some(t) } else { None })
-            .collect();
-        
-        assert_eq!(trades.len(), 3);
-        assert_eq!(trades[0].price, 10000);
-        assert_eq!(trades[0].qty, 50);
-        assert_eq!(trades[1].price, 10010);
-        assert_eq!(trades[1].qty, 50);
-        assert_eq!(trades[2].price, 10020);
-        assert_eq!(trades[2].qty, 20);
-        
-        // 30 remaining at 10020
-        assert_eq!(engine.order_count(), 1);
-        assert_eq!(engine.best_ask(), Some(10020));
-    }
+            #[test]
+            fn test_match_multiple_levels() {
+                let mut engine = new_engine(1000);
+        
+                // Place asks at multiple levels
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 50), &mut events);
+                engine.process_place(place_order(2, 100, Side::Ask, 10010, 50), &mut events);
+                engine.process_place(place_order(3, 100, Side::Ask, 10020, 50), &mut events);
+                events.clear(); // Clear setup events
+        
+                // Place large crossing bid
+                let bid = place_order(4, 200, Side::Bid, 10020, 120);
+                engine.process_place(bid, &mut events);
+        
+                // Should match all of level 10000 (50), all of 10010 (50), part of 10020 (20)
+                let trades: Vec<_> = events.iter()
+                    .filter_map(|e| if let OutputEvent::Trade(t) = e { Some(t) } else { None })
+                    .collect();
+        
+                assert_eq!(trades.len(), 3);
+                assert_eq!(trades[0].price, 10000);
+                assert_eq!(trades[0].qty, 50);
+                assert_eq!(trades[1].price, 10010);
+                assert_eq!(trades[1].qty, 50);
+                assert_eq!(trades[2].price, 10020);
+                assert_eq!(trades[2].qty, 20);
+        
+                // 30 remaining at 10020
+                assert_eq!(engine.order_count(), 1);
+                assert_eq!(engine.best_ask(), Some(10020));
+            }
     
-    #[test]
-    fn test_cancel_order() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        // Place order
-        let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Bid, 10000, 100), &mut events);
-        assert_eq!(engine.order_count(), 1);
-        events.clear();
-        
-        // Cancel it
-        engine.process_cancel(CancelOrder { order_id: 1 }, &mut events);
-        
-        assert_eq!(events.len(), 2);
-        assert!(matches!(events[0], OutputEvent::Canceled(_)));
-        assert!(matches!(events[1], OutputEvent::BookDelta(_)));
-        
-        if let OutputEvent::Canceled(c) = &events[0] {
-            assert_eq!(c.order_id, 1);
-            assert_eq!(c.canceled_qty, 100);
-        }
+            #[test]
+            fn test_cancel_order() {
+                let mut engine = new_engine(1000);
+        
+                // Place order
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Bid, 10000, 100), &mut events);
+                assert_eq!(engine.order_count(), 1);
+                events.clear();
+        
+                // Cancel it
+                engine.process_cancel(CancelOrder { order_id: 1 }, &mut events);
+        
+                assert_eq!(events.len(), 2);
+                assert!(matches!(events[0], OutputEvent::Canceled(_)));
+                assert!(matches!(events[1], OutputEvent::BookDelta(_)));
+        
+                if let OutputEvent::Canceled(c) = &events[0] {
+                    assert_eq!(c.order_id, 1);
+                    assert_eq!(c.canceled_qty, 100);
+                }
         
-        assert_eq!(engine.order_count(), 0);
-        assert_eq!(engine.best_bid(), None);
-    }
+                assert_eq!(engine.order_count(), 0);
+                assert_eq!(engine.best_bid(), None);
+            }
     
-    #[test]
-    fn test_cancel_nonexistent() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        let mut events = Vec::new();
-        engine.process_cancel(CancelOrder { order_id: 999 }, &mut events);
-        
-        assert_eq!(events.len(), 1);
-        assert!(matches!(
-            events[0],
-            OutputEvent::Rejected(OrderRejected {
-                reason: RejectReason::OrderNotFound,
-                ..
-            })
-        ));
-    }
+            #[test]
+            fn test_cancel_by_handle() {
+                let mut engine = new_engine(1000);
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Bid, 10000, 100), &mut events);
+                engine.process_place(place_order(2, 100, Side::Bid, 10000, 50), &mut events);
+                let handle = match events[0] {
+                    OutputEvent::Accepted(a) => a.handle,
+                    ref e => panic!("expected Accepted, got {:?}", e),
+                };
+                events.clear();
+        
+                let info = engine.process_cancel_by_handle(CancelOrder { order_id: 1 }, handle, &mut events);
+                assert_eq!(info.map(|i| i.price), Some(10000));
+                assert!(matches!(events[0], OutputEvent::Canceled(OrderCanceled { order_id: 1, canceled_qty: 100 })));
+                assert!(matches!(events[1], OutputEvent::BookDelta(BookUpdate { new_qty: 50, new_count: 1, .. })));
+                assert_eq!(engine.order_count(), 1);
+                assert!(!engine.book.contains_order(1));
+        
+                // The last order at the level takes the level with it
+                events.clear();
+                engine.process_place(place_order(3, 100, Side::Ask, 10100, 10), &mut events);
+                let OutputEvent::Accepted(accepted) = events[0] else { panic!("expected Accepted") };
+                events.clear();
+                engine.process_cancel_by_handle(CancelOrder { order_id: 3 }, accepted.handle, &mut events);
+                assert!(matches!(events[1], OutputEvent::BookDelta(BookUpdate { new_qty: 0, new_count: 0, .. })));
+                assert_eq!(engine.best_ask(), None);
+            }
     
-    #[test]
-    fn test_duplicate_order_id() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Bid, 10000, 100), &mut events);
-        events.clear();
-        engine.process_place(place_order(1, 200, Side::Ask, 10100, 50), &mut events);
-        
-        assert_eq!(events.len(), 1);
-        assert!(matches!(
-            events[0],
-            OutputEvent::Rejected(OrderRejected {
-                reason: RejectReason::DuplicateOrderId,
-                ..
-            })
-        ));
-    }
+            #[test]
+            fn test_cancel_by_stale_handle_rejected() {
+                let mut engine = new_engine(1000);
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Bid, 10000, 100), &mut events);
+                let OutputEvent::Accepted(accepted) = events[0] else { panic!("expected Accepted") };
+                engine.process_cancel(CancelOrder { order_id: 1 }, &mut events);
+        
+                // Order 2 reuses the freed slot; the old handle must not reach it
+                engine.process_place(place_order(2, 100, Side::Bid, 10000, 100), &mut events);
+                for (order_id, handle) in [(1, accepted.handle), (2, accepted.handle), (1, OrderHandle::default())] {
+                    events.clear();
+                    assert!(engine.process_cancel_by_handle(CancelOrder { order_id }, handle, &mut events).is_none());
+                    assert!(matches!(
+                        events[0],
+                        OutputEvent::Rejected(OrderRejected { reason: RejectReason::OrderNotFound, .. })
+                    ));
+                }
+                assert!(engine.book.contains_order(2));
+                assert_eq!(engine.order_count(), 1);
+            }
     
-    #[test]
-    fn test_zero_quantity_rejected() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Bid, 10000, 0), &mut events);
-        
-        assert_eq!(events.len(), 1);
-        assert!(matches!(
-            events[0],
-            OutputEvent::Rejected(OrderRejected {
-                reason: RejectReason::InvalidQuantity,
-                ..
-            })
-        ));
-    }
+            #[test]
+            fn test_cancel_nonexistent() {
+                let mut engine = new_engine(1000);
+        
+                let mut events = Vec::new();
+                engine.process_cancel(CancelOrder { order_id: 999 }, &mut events);
+        
+                assert_eq!(events.len(), 1);
+                assert!(matches!(
+                    events[0],
+                    OutputEvent::Rejected(OrderRejected {
+                        reason: RejectReason::OrderNotFound,
+                        ..
+                    })
+                ));
+            }
     
-    #[test]
-    fn test_fifo_order_priority() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        // Place 3 asks at same price (FIFO order: 1, 2, 3)
-        let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Ask, 10000, 100), &mut events);
-        engine.process_place(place_order(2, 101, Side::Ask, 10000, 100), &mut events);
-        engine.process_place(place_order(3, 102, Side::Ask, 10000, 100), &mut events);
-        events.clear();
-        
-        // Match against first two
-        engine.process_place(place_order(4, 200, Side::Bid, 10000, 200), &mut events);
-        
-        let trades: Vec<_> = events.iter()
-            .filter_map(|e| if let OutputEvent::Trade(t) = e { Some(t) } else { None })
-            .collect();
-        
-        assert_eq!(trades.len(), 2);
-        assert_eq!(trades[0].maker_order_id, 1); // First in
-        assert_eq!(trades[1].maker_order_id, 2); // Second in
-        
-        // Order 3 should still be resting
-        assert_eq!(engine.order_count(), 1);
-    }
+            #[test]
+            fn test_duplicate_order_id() {
+                let mut engine = new_engine(1000);
+        
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Bid, 10000, 100), &mut events);
+                events.clear();
+                engine.process_place(place_order(1, 200, Side::Ask, 10100, 50), &mut events);
+        
+                assert_eq!(events.len(), 1);
+                assert!(matches!(
+                    events[0],
+                    OutputEvent::Rejected(OrderRejected {
+                        reason: RejectReason::DuplicateOrderId,
+                        ..
+                    })
+                ));
+            }
     
-    #[test]
-    fn test_price_time_priority() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        // Place asks at different prices
-        let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Ask, 10020, 100), &mut events); // Worst
-        engine.process_place(place_order(2, 100, Side::Ask, 10000, 100), &mut events); // Best
-        engine.process_place(place_order(3, 100, Side::Ask, 10010, 100), &mut events); // Middle
-        events.clear();
-        
-        // Match - should go 10000 -> 10010 -> 10020
-        engine.process_place(place_order(4, 200, Side::Bid, 10020, 250), &mut events);
-        
-        let trades: Vec<_> = events.iter()
-            .filter_map(|e| if let OutputEvent::Trade(t) = e { Some(t) } else { None })
-            .collect();
-        
-        assert_eq!(trades.len(), 3);
-        assert_eq!(trades[0].price, 10000);
-        assert_eq!(trades[1].price, 10010);
-        assert_eq!(trades[2].price, 10020);
-    }
+            #[test]
+            fn test_zero_quantity_rejected() {
+                let mut engine = new_engine(1000);
+        
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Bid, 10000, 0), &mut events);
+        
+                assert_eq!(events.len(), 1);
+                assert!(matches!(
+                    events[0],
+                    OutputEvent::Rejected(OrderRejected {
+                        reason: RejectReason::InvalidQuantity,
+                        ..
+                    })
+                ));
+            }
     
-    // =========================================================================
-    // IOC (Immediate-Or-Cancel) Order Type Tests
-    // =========================================================================
+            #[test]
+            fn test_fifo_order_priority() {
+                let mut engine = new_engine(1000);
+        
+                // Place 3 asks at same price (FIFO order: 1, 2, 3)
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 100), &mut events);
+                engine.process_place(place_order(2, 101, Side::Ask, 10000, 100), &mut events);
+                engine.process_place(place_order(3, 102, Side::Ask, 10000, 100), &mut events);
+                events.clear();
+        
+                // Match against first two
+                engine.process_place(place_order(4, 200, Side::Bid, 10000, 200), &mut events);
+        
+                let trades: Vec<_> = events.iter()
+                    .filter_map(|e| if let OutputEvent::Trade(t) = e { Some(t) } else { None })
+                    .collect();
+        
+                assert_eq!(trades.len(), 2);
+                assert_eq!(trades[0].maker_order_id, 1); // First in
+                assert_eq!(trades[1].maker_order_id, 2); // Second in
+        
+                // Order 3 should still be resting
+                assert_eq!(engine.order_count(), 1);
+            }
     
-    fn ioc_order(
-        order_id: u64,
-        user_id: u64,
-        side: Side,
-        price: u64,
-        qty: u32,
-    ) -> PlaceOrder {
-        PlaceOrder {
-            order_id,
-            user_id,
-            side,
-            price,
-            qty,
-            order_type: OrderType::IOC,
-        }
-    }
+            #[test]
+            fn test_price_time_priority() {
+                let mut engine = new_engine(1000);
+        
+                // Place asks at different prices
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10020, 100), &mut events); // Worst
+                engine.process_place(place_order(2, 100, Side::Ask, 10000, 100), &mut events); // Best
+                engine.process_place(place_order(3, 100, Side::Ask, 10010, 100), &mut events); // Middle
+                events.clear();
+        
+                // Match - should go 10000 -> 10010 -> 10020
+                engine.process_place(place_order(4, 200, Side::Bid, 10020, 250), &mut events);
+        
+                let trades: Vec<_> = events.iter()
+                    .filter_map(|e| if let OutputEvent::Trade(t) = e { Some(t) } else { None })
+                    .collect();
+        
+                assert_eq!(trades.len(), 3);
+                assert_eq!(trades[0].price, 10000);
+                assert_eq!(trades[1].price, 10010);
+                assert_eq!(trades[2].price, 10020);
+            }
     
-    #[test]
-    fn test_ioc_full_fill() {
-        let mut engine = MatchingEngine::new(1000);
+            // =========================================================================
+            // IOC (Immediate-Or-Cancel) Order Type Tests
+            // =========================================================================
+    
+            fn ioc_order(
+                order_id: u64,
+                user_id: u64,
+                side: Side,
+                price: u64,
+                qty: u32,
+            ) -> PlaceOrder {
+                PlaceOrder {
+                    order_id,
+                    user_id,
+                    side,
+                    price,
+                    qty,
+                    order_type: OrderType::IOC,
+                }
+            }
+    
+            #[test]
+            fn test_ioc_full_fill() {
+                let mut engine = new_engine(1000);
         
-        // Place a resting ask
-        let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Ask, 10000, 100), &mut events);
-        events.clear();
+                // Place a resting ask
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 100), &mut events);
+                events.clear();
         
-        // IOC bid that fully matches
-        engine.process_place(ioc_order(2, 200, Side::Bid, 10000, 100), &mut events);
+                // IOC bid that fully matches
+                engine.process_place(ioc_order(2, 200, Side::Bid, 10000, 100), &mut events);
         
-        let trades = events.iter()
-            .filter(|e| matches!(e, OutputEvent::Trade(_)))
-            .count();
+                let trades = events.iter()
+                    .filter(|e| matches!(e, OutputEvent::Trade(_)))
+                    .count();
         
-        assert_eq!(trades, 1);
-        assert_eq!(engine.order_count(), 0); // No resting orders
-    }
+                assert_eq!(trades, 1);
+                assert_eq!(engine.order_count(), 0); // No resting orders
+            }
     
-    #[test]
-    fn test_ioc_partial_fill_no_rest() {
-        let mut engine = MatchingEngine::new(1000);
+            #[test]
+            fn test_ioc_partial_fill_no_rest() {
+                let mut engine = new_engine(1000);
         
-        // Place a small resting ask
-        let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Ask, 10000, 50), &mut events);
-        events.clear();
+                // Place a small resting ask
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 50), &mut events);
+                events.clear();
         
-        // IOC bid that partially matches - unfilled portion should be canceled
-        engine.process_place(ioc_order(2, 200, Side::Bid, 10000, 100), &mut events);
+                // IOC bid that partially matches - unfilled portion should be canceled
+                engine.process_place(ioc_order(2, 200, Side::Bid, 10000, 100), &mut events);
         
-        let trades = events.iter()
-            .filter(|e| matches!(e, OutputEvent::Trade(_)))
-            .count();
+                let trades = events.iter()
+                    .filter(|e| matches!(e, OutputEvent::Trade(_)))
+                    .count();
         
-        assert_eq!(trades, 1); // One trade for 50 qty
-        assert_eq!(engine.order_count(), 0); // IOC order should NOT rest
-    }
+                assert_eq!(trades, 1); // One trade for 50 qty
+                assert_eq!(engine.order_count(), 0); // IOC order should NOT rest
+            }
     
-    #[test]
-    fn test_ioc_no_match_no_rest() {
-        let mut engine = MatchingEngine::new(1000);
+            #[test]
+            fn test_ioc_no_match_no_rest() {
+                let mut engine = new_engine(1000);
         
-        // Place an ask above the IOC bid price
-        let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Ask, 10100, 100), &mut events);
-        events.clear();
+                // Place an ask above the IOC bid price
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10100, 100), &mut events);
+                events.clear();
         
-        // IOC bid that doesn't cross
-        engine.process_place(ioc_order(2, 200, Side::Bid, 10000, 100), &mut events);
+                // IOC bid that doesn't cross
+                engine.process_place(ioc_order(2, 200, Side::Bid, 10000, 100), &mut events);
         
-        // Should have no trades, no accepted (IOC doesn't rest)
-        let trades = events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count();
-        let accepted = events.iter().filter(|e| matches!(e, OutputEvent::Accepted(_))).count();
+                // Should have no trades, no accepted (IOC doesn't rest)
+                let trades = events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count();
+                let accepted = events.iter().filter(|e| matches!(e, OutputEvent::Accepted(_))).count();
         
-        assert_eq!(trades, 0);
-        assert_eq!(accepted, 0);
-        assert_eq!(engine.order_count(), 1); // Only the original ask
-    }
+                assert_eq!(trades, 0);
+                assert_eq!(accepted, 0);
+                assert_eq!(engine.order_count(), 1); // Only the original ask
+            }
     
-    // =========================================================================
-    // FOK (Fill-Or-Kill) Order Type Tests
-    // =========================================================================
+            // =========================================================================
+            // FOK (Fill-Or-Kill) Order Type Tests
+            // =========================================================================
     
-    fn fok_order(
-        order_id: u64,
-        user_id: u64,
-        side: Side,
-        price: u64,
-        qty: u32,
-    ) -> PlaceOrder {
-        PlaceOrder {
-            order_id,
-            user_id,
-            side,
-            price,
-            qty,
-            order_type: OrderType::FOK,
-        }
-    }
+            fn fok_order(
+                order_id: u64,
+                user_id: u64,
+                side: Side,
+                price: u64,
+                qty: u32,
+            ) -> PlaceOrder {
+                PlaceOrder {
+                    order_id,
+                    user_id,
+                    side,
+                    price,
+                    qty,
+                    order_type: OrderType::FOK,
+                }
+            }
     
-    #[test]
-    fn test_fok_full_fill() {
-        let mut engine = MatchingEngine::new(1000);
+            #[test]
+            fn test_fok_full_fill() {
+                let mut engine = new_engine(1000);
         
-        // Place enough liquidity
-        let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Ask, 10000, 100), &mut events);
-        events.clear();
+                // Place enough liquidity
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 100), &mut events);
+                events.clear();
         
-        // FOK bid that fully matches
-        engine.process_place(fok_order(2, 200, Side::Bid, 10000, 100), &mut events);
+                // FOK bid that fully matches
+                engine.process_place(fok_order(2, 200, Side::Bid, 10000, 100), &mut events);
         
-        let trades = events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count();
-        let rejected = events.iter().filter(|e| matches!(e, OutputEvent::Rejected(_))).count();
+                let trades = events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count();
+                let rejected = events.iter().filter(|e| matches!(e, OutputEvent::Rejected(_))).count();
         
-        assert_eq!(trades, 1);
-        assert_eq!(rejected, 0);
-        assert_eq!(engine.order_count(), 0);
-    }
+                assert_eq!(trades, 1);
+                assert_eq!(rejected, 0);
+                assert_eq!(engine.order_count(), 0);
+            }
     
-    #[test]
-    fn test_fok_insufficient_liquidity_rejected() {
-        let mut engine = MatchingEngine::new(1000);
+            #[test]
+            fn test_fok_insufficient_liquidity_rejected() {
+                let mut engine = new_engine(1000);
+        
+                // Place smaller liquidity than FOK needs
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 50), &mut events);
+                events.clear();
+        
+                // FOK bid that can't fully fill
+                engine.process_place(fok_order(2, 200, Side::Bid, 10000, 100), &mut events);
+        
+                let trades = events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count();
+                let rejected = events.iter()
+                    .filter_map(|e| if let OutputEvent::Rejected(r) = e { Some(r) } else { None })
+                    .collect::<Vec<_>>();
+        
+                assert_eq!(trades, 0); // No trades - order was rejected
+                assert_eq!(rejected.len(), 1);
+                assert_eq!(rejected[0].reason, crate::command::RejectReason::InsufficientLiquidity);
+                assert_eq!(engine.order_count(), 1); // Original ask still there
+            }
+    
+            #[test]
+            fn test_fok_no_liquidity_rejected() {
+                let mut engine = new_engine(1000);
         
-        // Place smaller liquidity than FOK needs
-        let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Ask, 10000, 50), &mut events);
-        events.clear();
+                // No resting orders
         
-        // FOK bid that can't fully fill
-        engine.process_place(fok_order(2, 200, Side::Bid, 10000, 100), &mut events);
+                // FOK bid with no matching liquidity
+                let mut events = Vec::new();
+                engine.process_place(fok_order(1, 200, Side::Bid, 10000, 100), &mut events);
         
-        let trades = events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count();
-        let rejected = events.iter()
-            .filter_map(|e| if let OutputEvent::Rejected(r) = e { Some(r) } else { None })
-            .collect::<Vec<_>>();
+                let rejected = events.iter().filter(|e| matches!(e, OutputEvent::Rejected(_))).count();
         
-        assert_eq!(trades, 0); // No trades - order was rejected
-        assert_eq!(rejected.len(), 1);
-        assert_eq!(rejected[0].reason, crate::command::RejectReason::InsufficientLiquidity);
-        assert_eq!(engine.order_count(), 1); // Original ask still there
-    }
+                assert_eq!(rejected, 1);
+                assert_eq!(engine.order_count(), 0);
+            }
     
-    #[test]
-    fn test_fok_no_liquidity_rejected() {
-        let mut engine = MatchingEngine::new(1000);
+            #[test]
+            fn test_fok_multi_level_fill() {
+                let mut engine = new_engine(1000);
         
-        // No resting orders
+                // Place liquidity across multiple levels
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 30), &mut events);
+                engine.process_place(place_order(2, 100, Side::Ask, 10010, 40), &mut events);
+                engine.process_place(place_order(3, 100, Side::Ask, 10020, 50), &mut events);
+                events.clear();
         
-        // FOK bid with no matching liquidity
-        let mut events = Vec::new();
-        engine.process_place(fok_order(1, 200, Side::Bid, 10000, 100), &mut events);
+                // FOK bid that can fill across levels
+                engine.process_place(fok_order(4, 200, Side::Bid, 10020, 100), &mut events);
         
-        let rejected = events.iter().filter(|e| matches!(e, OutputEvent::Rejected(_))).count();
+                let trades = events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count();
         
-        assert_eq!(rejected, 1);
-        assert_eq!(engine.order_count(), 0);
+                assert_eq!(trades, 3); // Matched all 3 levels
+                assert_eq!(engine.order_count(), 1); // 20 remaining at 10020
+            }
+
+            #[test]
+            fn test_fok_level_total_above_u32_max() {
+                let mut engine = new_engine(1000);
+                let mut events = Vec::new();
+
+                // Level total is exactly 2^32, which used to truncate to 0
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 1 << 31), &mut events);
+                engine.process_place(place_order(2, 100, Side::Ask, 10000, 1 << 31), &mut events);
+                assert_eq!(engine.book.get_level(Side::Ask, 10000).unwrap().total_qty, 1 << 32);
+                events.clear();
+
+                engine.process_place(fok_order(3, 200, Side::Bid, 10000, u32::MAX), &mut events);
+                let filled: u64 = events
+                    .iter()
+                    .filter_map(|e| if let OutputEvent::Trade(t) = e { Some(t.qty as u64) } else { None })
+                    .sum();
+                assert_eq!(filled, u32::MAX as u64);
+                assert_eq!(engine.order_count(), 1);
+            }
+
+            #[test]
+            fn test_reduce_keeps_priority() {
+                let mut engine = new_engine(1000);
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 30), &mut events);
+                engine.process_place(place_order(2, 100, Side::Ask, 10000, 40), &mut events);
+                events.clear();
+
+                assert_eq!(engine.process_reduce(1, 20, &mut events), Some(10));
+                assert!(matches!(events[..], [
+                    OutputEvent::Reduced(OrderReduced { order_id: 1, reduced_by: 20, remaining: 10 }),
+                    OutputEvent::BookDelta(BookUpdate { new_qty: 50, new_count: 2, .. }),
+                ]));
+
+                // Order 1 is still first in the queue
+                events.clear();
+                engine.process_place(place_order(3, 200, Side::Bid, 10000, 10), &mut events);
+                assert!(matches!(events[0], OutputEvent::Trade(TradeEvent { maker_order_id: 1, qty: 10, .. })));
+
+                // Reducing by the full size cancels; unknown orders are ignored
+                events.clear();
+                assert_eq!(engine.process_reduce(2, 40, &mut events), Some(0));
+                assert!(matches!(events[0], OutputEvent::Canceled(_)));
+                assert_eq!(engine.process_reduce(2, 1, &mut events), None);
+                assert_eq!(engine.order_count(), 0);
+            }
+
+            #[test]
+            fn test_external_fill_counts_as_execution() {
+                let mut engine = new_engine(1000);
+                engine.set_history_capacity(8);
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 30), &mut events);
+                engine.process_place(place_order(2, 100, Side::Ask, 10000, 40), &mut events);
+                events.clear();
+
+                assert_eq!(engine.process_external_fill(1, 20, &mut events), Some(10));
+                assert!(matches!(events[..], [
+                    OutputEvent::Trade(TradeEvent { maker_order_id: 1, taker_order_id: 0, qty: 20, taker_side: Side::Bid, .. }),
+                    OutputEvent::BookDelta(BookUpdate { new_qty: 50, new_count: 2, .. }),
+                ]));
+                let status = engine.order_status(1).unwrap();
+                assert_eq!((status.filled_qty, status.reduced_qty, status.state), (20, 0, OrderState::PartiallyFilled));
+
+                // Over-fills are capped; the order leaves the book filled
+                events.clear();
+                assert_eq!(engine.process_external_fill(1, 50, &mut events), Some(0));
+                assert!(matches!(events[0], OutputEvent::Trade(TradeEvent { qty: 10, .. })));
+                assert!(matches!(events[1], OutputEvent::BookDelta(BookUpdate { new_qty: 40, new_count: 1, .. })));
+                let status = engine.order_status(1).unwrap();
+                assert_eq!((status.filled_qty, status.state), (30, OrderState::Filled));
+                assert_eq!(engine.process_external_fill(1, 1, &mut events), None);
+                assert_eq!(engine.validate(), Ok(()));
+            }
+
+            #[test]
+            fn test_order_status_lifecycle() {
+                let mut engine = new_engine(1000);
+                engine.set_history_capacity(8);
+                let mut events = Vec::new();
+
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 50), &mut events);
+                engine.process_place(place_order(2, 100, Side::Ask, 10100, 50), &mut events);
+                engine.process_place(place_order(3, 200, Side::Bid, 9900, 10), &mut events);
+                let status = engine.order_status(1).unwrap();
+                assert_eq!((status.original_qty, status.remaining_qty, status.state), (50, 50, OrderState::New));
+
+                // Taker fills order 1 completely and rests its remainder
+                engine.process_place(place_order(4, 200, Side::Bid, 10000, 80), &mut events);
+                let maker = engine.order_status(1).unwrap();
+                assert_eq!((maker.filled_qty, maker.state), (50, OrderState::Filled));
+                let taker = engine.order_status(4).unwrap();
+                assert_eq!((taker.original_qty, taker.remaining_qty, taker.filled_qty), (80, 30, 50));
+                assert_eq!(taker.state, OrderState::PartiallyFilled);
+
+                let mut open: Vec<_> = engine.open_orders(200).map(|s| s.order_id).collect();
+                open.sort_unstable();
+                assert_eq!(open, vec![3, 4]);
+
+                engine.process_cancel(CancelOrder { order_id: 4 }, &mut events);
+                let canceled = engine.order_status(4).unwrap();
+                assert_eq!((canceled.remaining_qty, canceled.filled_qty, canceled.state), (0, 50, OrderState::Canceled));
+
+                // IOC remainder is cancelled, not rested
+                engine.process_place(ioc_order(5, 300, Side::Bid, 10100, 70), &mut events);
+                let ioc = engine.order_status(5).unwrap();
+                assert_eq!((ioc.filled_qty, ioc.state), (50, OrderState::Canceled));
+                assert_eq!(engine.open_orders(100).count(), 0);
+            }
+
+            #[test]
+            fn test_order_status_after_reduce_and_invalid_resubmit() {
+                let mut engine = new_engine(1000);
+                engine.set_history_capacity(8);
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 50), &mut events);
+                engine.process_place(place_order(2, 200, Side::Bid, 10000, 10), &mut events);
+                engine.process_reduce(1, 15, &mut events);
+
+                let status = engine.order_status(1).unwrap();
+                assert_eq!((status.original_qty, status.remaining_qty, status.filled_qty, status.reduced_qty), (50, 25, 10, 15));
+
+                // A zero-quantity order reusing a live ID is rejected without
+                // touching the live order's status
+                engine.process_place(place_order(1, 300, Side::Bid, 9000, 0), &mut events);
+                assert_eq!(engine.order_status(1).unwrap().state, OrderState::PartiallyFilled);
+
+                engine.process_cancel(CancelOrder { order_id: 1 }, &mut events);
+                let canceled = engine.order_status(1).unwrap();
+                assert_eq!((canceled.filled_qty, canceled.reduced_qty, canceled.state), (10, 15, OrderState::Canceled));
+            }
+
+            #[test]
+            fn test_simulate_place_matches_process_place() {
+                let mut engine = new_engine(4);
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 30), &mut events);
+                engine.process_place(place_order(2, 101, Side::Ask, 10000, 40), &mut events);
+                engine.process_place(place_order(3, 102, Side::Ask, 10100, 50), &mut events);
+                let hash = engine.state_hash();
+
+                let order = place_order(4, 200, Side::Bid, 10100, 100);
+                let sim = engine.simulate_place(order);
+                assert_eq!(engine.state_hash(), hash);
+                assert_eq!((sim.filled_qty, sim.residual_qty, sim.rests, sim.rejected), (100, 0, false, None));
+                assert_eq!(sim.avg_price(), Some((70.0 * 10000.0 + 30.0 * 10100.0) / 100.0));
+
+                // FOK beyond available liquidity, IOC remainder, zero quantity
+                let fok = engine.simulate_place(fok_order(5, 200, Side::Bid, 10100, 121));
+                assert_eq!((fok.rejected, fok.fills.len()), (Some(RejectReason::InsufficientLiquidity), 0));
+                let ioc = engine.simulate_place(ioc_order(6, 200, Side::Bid, 10000, 100));
+                assert_eq!((ioc.filled_qty, ioc.residual_qty, ioc.rests), (70, 30, false));
+                assert_eq!(engine.simulate_place(place_order(7, 200, Side::Bid, 10000, 0)).rejected, Some(RejectReason::InvalidQuantity));
+
+                // Arena has one free slot; a non-crossing order rests, and fills free more
+                engine.process_place(place_order(8, 200, Side::Bid, 9000, 10), &mut events);
+                assert_eq!(engine.simulate_place(place_order(9, 200, Side::Bid, 9000, 10)).rejected, Some(RejectReason::ArenaFull));
+                assert!(engine.simulate_place(place_order(9, 200, Side::Bid, 10000, 80)).rests);
+
+                events.clear();
+                engine.process_place(order, &mut events);
+                let trades: Vec<_> = events.iter().filter_map(|e| if let OutputEvent::Trade(t) = e { Some(*t) } else { None }).collect();
+                assert_eq!(trades, sim.fills);
+            }
+
+            #[test]
+            fn test_order_history_disabled_by_default() {
+                let mut engine = new_engine(1000);
+                let mut events = Vec::new();
+                engine.process_place(place_order(1, 100, Side::Ask, 10000, 50), &mut events);
+                engine.process_cancel(CancelOrder { order_id: 1 }, &mut events);
+                assert!(engine.order_status(1).is_none());
+                assert!(engine.history().is_empty());
+            }
+        };
+    }
+
+    backend_tests! {
+        tree => |orders| OrderBook::with_storage(LevelStorage::Tree, orders);
+        ladder => |orders| OrderBook::with_storage(LevelStorage::Ladder(LadderConfig::default()), orders);
+        vec_book => VecBook::with_capacity;
     }
     
     #[test]
-    fn test_fok_multi_level_fill() {
-        let mut engine = MatchingEngine::new(1000);
-        
-        // Place liquidity across multiple levels
+    fn test_growable_arena_rests_past_first_chunk() {
+        let growth = ArenaGrowth { chunk_size: 4, max_capacity: 10, high_watermark: 8 };
+        let mut engine = MatchingEngine::with_arena(Arena::growable(growth), OrderBook::new());
         let mut events = Vec::new();
-        engine.process_place(place_order(1, 100, Side::Ask, 10000, 30), &mut events);
-        engine.process_place(place_order(2, 100, Side::Ask, 10010, 40), &mut events);
-        engine.process_place(place_order(3, 100, Side::Ask, 10020, 50), &mut events);
-        events.clear();
         
-        // FOK bid that can fill across levels
-        engine.process_place(fok_order(4, 200, Side::Bid, 10020, 100), &mut events);
+        for id in 1..=10 {
+            events.clear();
+            engine.process_place(place_order(id, 1, Side::Bid, 9000 + id, 10), &mut events);
+            let warned = events.iter().any(|e| matches!(e, OutputEvent::ArenaHighWater(_)));
+            assert_eq!(warned, id == 8, "order {id}");
+            assert!(matches!(events[0], OutputEvent::Accepted(_)));
+        }
+        assert_eq!(engine.arena.capacity(), 10);
+        assert_eq!(engine.simulate_place(place_order(11, 1, Side::Bid, 8000, 10)).rejected, Some(RejectReason::ArenaFull));
         
-        let trades = events.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count();
+        // Past the hard cap, orders are rejected as with a fixed arena
+        events.clear();
+        engine.process_place(place_order(11, 1, Side::Bid, 8000, 10), &mut events);
+        assert!(matches!(events[0], OutputEvent::Rejected(OrderRejected { reason: RejectReason::ArenaFull, .. })));
         
-        assert_eq!(trades, 3); // Matched all 3 levels
-        assert_eq!(engine.order_count(), 1); // 20 remaining at 10020
+        // Sweeping the book frees slots across every chunk
+        events.clear();
+        engine.process_place(place_order(12, 2, Side::Ask, 9000, 100), &mut events);
+        assert_eq!(engine.book.order_count(), 0);
+        assert!(engine.arena.is_empty());
     }
 }
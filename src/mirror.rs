@@ -0,0 +1,477 @@
+//! Book Mirror - Client-side book reconstruction from output events.
+//!
+//! Applies a stream of `OutputEvent`s to maintain an L2 copy of the book
+//! (and optionally an L3 copy with every resting order in time priority).
+//! Consumers get top-N and BBO queries without writing their own
+//! `BookUpdate` handling.
+//!
+//! In L3 mode every `BookDelta` is cross-checked against the sum of the
+//! mirrored orders at that level, so a dropped or reordered event is
+//! detected at the first level it touches.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+use rustc_hash::FxHashMap;
+
+use crate::command::{OutputEvent, Side};
+
+/// Aggregated state of a mirrored price level
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MirrorLevel {
+    /// Total resting quantity
+    pub qty: u64,
+    /// Number of resting orders
+    pub count: u32,
+}
+
+/// A price and its aggregated level state
+pub type LevelEntry = (u64, MirrorLevel);
+
+/// A mirrored resting order (L3 mode)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MirrorOrder {
+    pub order_id: u64,
+    pub side: Side,
+    pub price: u64,
+    /// Remaining quantity
+    pub qty: u32,
+}
+
+/// Inconsistencies detected while applying events
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MirrorError {
+    /// Sequence number did not follow the previous one
+    SequenceGap { expected: u64, received: u64 },
+    /// Event referenced an order the mirror does not know (L3 mode)
+    UnknownOrder(u64),
+    /// `Accepted` for an order that is already resting (L3 mode)
+    DuplicateOrder(u64),
+    /// `Trade` for more than the maker's remaining quantity (L3 mode)
+    Overfill { order_id: u64, qty: u32, remaining: u32 },
+    /// `Reduced` that does not leave the mirrored order at the reported
+    /// remaining quantity (L3 mode)
+    ReduceMismatch { order_id: u64, mirrored: u32, reduced_by: u32, remaining: u32 },
+    /// `BookDelta` disagrees with the mirrored orders at that level (L3 mode)
+    LevelMismatch {
+        side: Side,
+        price: u64,
+        delta: MirrorLevel,
+        orders: MirrorLevel,
+    },
+}
+
+impl fmt::Display for MirrorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MirrorError::SequenceGap { expected, received } => {
+                write!(f, "sequence gap: expected {}, received {}", expected, received)
+            }
+            MirrorError::UnknownOrder(id) => write!(f, "unknown order {}", id),
+            MirrorError::DuplicateOrder(id) => write!(f, "duplicate order {}", id),
+            MirrorError::Overfill { order_id, qty, remaining } => {
+                write!(f, "order {} filled {} with only {} remaining", order_id, qty, remaining)
+            }
+            MirrorError::ReduceMismatch { order_id, mirrored, reduced_by, remaining } => write!(
+                f,
+                "order {} reduced by {} to {}, but mirror has {}",
+                order_id, reduced_by, remaining, mirrored
+            ),
+            MirrorError::LevelMismatch { side, price, delta, orders } => write!(
+                f,
+                "{:?} level {} mismatch: delta {:?}, orders {:?}",
+                side, price, delta, orders
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MirrorError {}
+
+/// Mirrored orders at one level (L3 mode)
+#[derive(Debug, Default)]
+struct OrderQueue {
+    /// Order IDs in time priority
+    ids: VecDeque<u64>,
+    /// Running totals of the orders in `ids`
+    totals: MirrorLevel,
+}
+
+/// Per-order state for L3 mode
+#[derive(Debug, Default)]
+struct OrderState {
+    orders: FxHashMap<u64, MirrorOrder>,
+    /// Orders per (side, price)
+    queues: FxHashMap<(Side, u64), OrderQueue>,
+}
+
+impl OrderState {
+    #[inline]
+    fn level_totals(&self, side: Side, price: u64) -> MirrorLevel {
+        self.queues.get(&(side, price)).map_or(MirrorLevel::default(), |q| q.totals)
+    }
+
+    fn insert(&mut self, order: MirrorOrder) {
+        let queue = self.queues.entry((order.side, order.price)).or_default();
+        queue.ids.push_back(order.order_id);
+        queue.totals.qty += order.qty as u64;
+        queue.totals.count += 1;
+        self.orders.insert(order.order_id, order);
+    }
+
+    /// Take `qty` off an order, removing it once nothing is left. The
+    /// order is left untouched if it is unknown or has less than `qty`.
+    fn fill(&mut self, order_id: u64, qty: u32) -> Result<(), MirrorError> {
+        let order = self.orders.get_mut(&order_id).ok_or(MirrorError::UnknownOrder(order_id))?;
+        if qty > order.qty {
+            return Err(MirrorError::Overfill { order_id, qty, remaining: order.qty });
+        }
+        order.qty -= qty;
+        let (key, done) = ((order.side, order.price), order.qty == 0);
+        if let Some(queue) = self.queues.get_mut(&key) {
+            queue.totals.qty -= qty as u64;
+        }
+        if done {
+            self.remove(order_id);
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, order_id: u64) -> Option<MirrorOrder> {
+        let order = self.orders.remove(&order_id)?;
+        let key = (order.side, order.price);
+        if let Some(queue) = self.queues.get_mut(&key) {
+            if let Some(pos) = queue.ids.iter().position(|id| *id == order_id) {
+                queue.ids.remove(pos);
+                queue.totals.qty -= order.qty as u64;
+                queue.totals.count -= 1;
+            }
+            if queue.ids.is_empty() {
+                self.queues.remove(&key);
+            }
+        }
+        Some(order)
+    }
+}
+
+/// Client-side copy of the order book built from `OutputEvent`s.
+#[derive(Debug, Default)]
+pub struct BookMirror {
+    bids: BTreeMap<u64, MirrorLevel>,
+    asks: BTreeMap<u64, MirrorLevel>,
+    l3: Option<OrderState>,
+    /// Next expected sequence number (once sequencing has started)
+    next_seq: Option<u64>,
+}
+
+impl BookMirror {
+    /// Create an L2-only mirror.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a mirror that also tracks individual orders (L3).
+    pub fn with_orders() -> Self {
+        Self {
+            l3: Some(OrderState::default()),
+            ..Self::default()
+        }
+    }
+
+    /// Returns true if individual orders are tracked
+    #[inline]
+    pub fn tracks_orders(&self) -> bool {
+        self.l3.is_some()
+    }
+
+    /// Apply an event carrying a feed sequence number.
+    ///
+    /// The first call sets the baseline; later calls must be contiguous.
+    /// An event that is rejected (gap or inconsistency) leaves the mirror,
+    /// including the expected sequence number, unchanged.
+    pub fn apply_sequenced(&mut self, seq: u64, event: &OutputEvent) -> Result<(), MirrorError> {
+        if let Some(expected) = self.next_seq {
+            if seq != expected {
+                return Err(MirrorError::SequenceGap { expected, received: seq });
+            }
+        }
+        self.apply(event)?;
+        self.next_seq = Some(seq + 1);
+        Ok(())
+    }
+
+    /// Apply a single event. A rejected event leaves the mirror unchanged.
+    pub fn apply(&mut self, event: &OutputEvent) -> Result<(), MirrorError> {
+        match *event {
+            OutputEvent::BookDelta(d) => {
+                let delta = MirrorLevel { qty: d.new_qty, count: d.new_count };
+                if let Some(l3) = &self.l3 {
+                    let orders = l3.level_totals(d.side, d.price);
+                    if orders != delta {
+                        return Err(MirrorError::LevelMismatch { side: d.side, price: d.price, delta, orders });
+                    }
+                }
+
+                let levels = match d.side {
+                    Side::Bid => &mut self.bids,
+                    Side::Ask => &mut self.asks,
+                };
+                if d.new_count == 0 {
+                    levels.remove(&d.price);
+                } else {
+                    levels.insert(d.price, delta);
+                }
+            }
+            OutputEvent::Accepted(a) => {
+                if let Some(l3) = &mut self.l3 {
+                    if l3.orders.contains_key(&a.order_id) {
+                        return Err(MirrorError::DuplicateOrder(a.order_id));
+                    }
+                    l3.insert(MirrorOrder {
+                        order_id: a.order_id,
+                        side: a.side,
+                        price: a.price,
+                        qty: a.qty,
+                    });
+                }
+            }
+            OutputEvent::Trade(t) => {
+                if let Some(l3) = &mut self.l3 {
+                    l3.fill(t.maker_order_id, t.qty)?;
+                }
+            }
+            OutputEvent::Reduced(r) => {
+                if let Some(l3) = &mut self.l3 {
+                    let mirrored = l3.orders.get(&r.order_id).ok_or(MirrorError::UnknownOrder(r.order_id))?.qty;
+                    // A reduction to zero is reported as a cancel instead
+                    if r.remaining == 0 || mirrored.checked_sub(r.reduced_by) != Some(r.remaining) {
+                        return Err(MirrorError::ReduceMismatch {
+                            order_id: r.order_id,
+                            mirrored,
+                            reduced_by: r.reduced_by,
+                            remaining: r.remaining,
+                        });
+                    }
+                    l3.fill(r.order_id, r.reduced_by)?;
+                }
+            }
+            OutputEvent::Canceled(c) => {
+                if let Some(l3) = &mut self.l3 {
+                    l3.remove(c.order_id).ok_or(MirrorError::UnknownOrder(c.order_id))?;
+                }
+            }
+            OutputEvent::Rejected(_) | OutputEvent::Bbo(_) | OutputEvent::ArenaHighWater(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Apply a batch of events (e.g. the output of one command).
+    pub fn apply_all(&mut self, events: &[OutputEvent]) -> Result<(), MirrorError> {
+        events.iter().try_for_each(|e| self.apply(e))
+    }
+
+    /// Discard all state, including the sequence baseline.
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        if let Some(l3) = &mut self.l3 {
+            l3.orders.clear();
+            l3.queues.clear();
+        }
+        self.next_seq = None;
+    }
+
+    // ========================================================================
+    // Queries
+    // ========================================================================
+
+    /// Best bid as `(price, level)`
+    #[inline]
+    pub fn best_bid(&self) -> Option<LevelEntry> {
+        self.bids.iter().next_back().map(|(p, l)| (*p, *l))
+    }
+
+    /// Best ask as `(price, level)`
+    #[inline]
+    pub fn best_ask(&self) -> Option<LevelEntry> {
+        self.asks.iter().next().map(|(p, l)| (*p, *l))
+    }
+
+    /// Best bid and ask
+    #[inline]
+    pub fn bbo(&self) -> (Option<LevelEntry>, Option<LevelEntry>) {
+        (self.best_bid(), self.best_ask())
+    }
+
+    /// Up to `n` levels on a side, best price first
+    pub fn top_levels(&self, side: Side, n: usize) -> impl Iterator<Item = LevelEntry> + '_ {
+        let (bids, asks) = match side {
+            Side::Bid => (Some(self.bids.iter().rev()), None),
+            Side::Ask => (None, Some(self.asks.iter())),
+        };
+        bids.into_iter().flatten().chain(asks.into_iter().flatten()).take(n).map(|(p, l)| (*p, *l))
+    }
+
+    /// Aggregated state at a price
+    #[inline]
+    pub fn level(&self, side: Side, price: u64) -> Option<MirrorLevel> {
+        match side {
+            Side::Bid => self.bids.get(&price).copied(),
+            Side::Ask => self.asks.get(&price).copied(),
+        }
+    }
+
+    /// Number of levels on a side
+    #[inline]
+    pub fn level_count(&self, side: Side) -> usize {
+        match side {
+            Side::Bid => self.bids.len(),
+            Side::Ask => self.asks.len(),
+        }
+    }
+
+    /// Returns true if best bid >= best ask
+    pub fn is_crossed(&self) -> bool {
+        matches!(self.bbo(), (Some((bid, _)), Some((ask, _))) if bid >= ask)
+    }
+
+    /// Look up a resting order (L3 mode only)
+    #[inline]
+    pub fn order(&self, order_id: u64) -> Option<&MirrorOrder> {
+        self.l3.as_ref()?.orders.get(&order_id)
+    }
+
+    /// Number of resting orders (L3 mode only)
+    #[inline]
+    pub fn order_count(&self) -> Option<usize> {
+        self.l3.as_ref().map(|l3| l3.orders.len())
+    }
+
+    /// Orders at a level in time priority (empty in L2 mode)
+    pub fn orders_at(&self, side: Side, price: u64) -> impl Iterator<Item = &MirrorOrder> + '_ {
+        let l3 = self.l3.as_ref();
+        l3.and_then(|l3| l3.queues.get(&(side, price)))
+            .into_iter()
+            .flat_map(|q| &q.ids)
+            .filter_map(move |id| l3.and_then(|l3| l3.orders.get(id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{BookUpdate, CancelOrder, Command, OrderReduced, PlaceOrder, TradeEvent};
+    use crate::engine::Engine;
+
+    fn run(engine: &mut Engine, mirror: &mut BookMirror, cmd: Command) {
+        let events = engine.process_command(cmd);
+        mirror.apply_all(events).unwrap();
+    }
+
+    #[test]
+    fn test_l2_mirror() {
+        let mut engine = Engine::new(100);
+        let mut mirror = BookMirror::new();
+
+        run(&mut engine, &mut mirror, Command::Place(PlaceOrder::limit(1, 1, Side::Bid, 100, 10)));
+        run(&mut engine, &mut mirror, Command::Place(PlaceOrder::limit(2, 1, Side::Bid, 99, 5)));
+        run(&mut engine, &mut mirror, Command::Place(PlaceOrder::limit(3, 1, Side::Ask, 102, 7)));
+
+        assert_eq!(mirror.best_bid(), Some((100, MirrorLevel { qty: 10, count: 1 })));
+        assert_eq!(mirror.best_ask(), Some((102, MirrorLevel { qty: 7, count: 1 })));
+        let bids: Vec<_> = mirror.top_levels(Side::Bid, 5).map(|(p, _)| p).collect();
+        assert_eq!(bids, vec![100, 99]);
+        assert_eq!(mirror.order_count(), None);
+
+        run(&mut engine, &mut mirror, Command::Place(PlaceOrder::limit(4, 2, Side::Ask, 99, 12)));
+        assert_eq!(mirror.best_bid(), Some((99, MirrorLevel { qty: 3, count: 1 })));
+        assert!(!mirror.is_crossed());
+    }
+
+    #[test]
+    fn test_l3_mirror_queue_order() {
+        let mut engine = Engine::new(100);
+        let mut mirror = BookMirror::with_orders();
+
+        for id in 1..=3 {
+            run(&mut engine, &mut mirror, Command::Place(PlaceOrder::limit(id, 1, Side::Ask, 100, 10)));
+        }
+        run(&mut engine, &mut mirror, Command::Cancel(CancelOrder { order_id: 2 }));
+        run(&mut engine, &mut mirror, Command::Place(PlaceOrder::limit(4, 2, Side::Bid, 100, 4)));
+
+        let queue: Vec<_> = mirror.orders_at(Side::Ask, 100).map(|o| (o.order_id, o.qty)).collect();
+        assert_eq!(queue, vec![(1, 6), (3, 10)]);
+        assert_eq!(mirror.order_count(), Some(2));
+    }
+
+    #[test]
+    fn test_l3_mirror_detects_missing_event() {
+        let mut engine = Engine::new(100);
+        let mut mirror = BookMirror::with_orders();
+
+        run(&mut engine, &mut mirror, Command::Place(PlaceOrder::limit(1, 1, Side::Ask, 100, 10)));
+        let events = engine.process_command(Command::Place(PlaceOrder::limit(2, 1, Side::Ask, 100, 5))).to_vec();
+
+        // Drop the Accepted event; the level delta no longer adds up
+        let err = mirror.apply(&events[1]).unwrap_err();
+        assert!(matches!(err, MirrorError::LevelMismatch { price: 100, .. }));
+    }
+
+    #[test]
+    fn test_rejected_event_leaves_no_trace() {
+        let mut engine = Engine::new(100);
+        let mut mirror = BookMirror::with_orders();
+        let placed = engine.process_command(Command::Place(PlaceOrder::limit(1, 1, Side::Ask, 100, 10))).to_vec();
+        for (seq, event) in placed.iter().enumerate() {
+            mirror.apply_sequenced(seq as u64, event).unwrap();
+        }
+
+        // Delta without its order event: neither L2 nor the sequence move
+        let bogus = OutputEvent::BookDelta(BookUpdate { side: Side::Ask, price: 100, new_qty: 25, new_count: 2 });
+        assert!(matches!(mirror.apply_sequenced(2, &bogus), Err(MirrorError::LevelMismatch { .. })));
+        assert_eq!(mirror.level(Side::Ask, 100), Some(MirrorLevel { qty: 10, count: 1 }));
+
+        // Fill for more than the maker has left is reported, not clamped
+        let overfill = OutputEvent::Trade(TradeEvent {
+            price: 100,
+            qty: 11,
+            maker_order_id: 1,
+            taker_order_id: 2,
+            maker_user_id: 1,
+            taker_user_id: 2,
+            taker_side: Side::Bid,
+        });
+        assert_eq!(mirror.apply_sequenced(2, &overfill), Err(MirrorError::Overfill { order_id: 1, qty: 11, remaining: 10 }));
+        assert_eq!(mirror.order(1).map(|o| o.qty), Some(10));
+
+        let events = engine.process_command(Command::Place(PlaceOrder::limit(2, 2, Side::Bid, 100, 4))).to_vec();
+        for (seq, event) in (2..).zip(&events) {
+            mirror.apply_sequenced(seq, event).unwrap();
+        }
+        assert_eq!(mirror.level(Side::Ask, 100), Some(MirrorLevel { qty: 6, count: 1 }));
+
+        // A reduction must leave the order at the reported size
+        let reduced = OutputEvent::Reduced(OrderReduced { order_id: 1, reduced_by: 2, remaining: 5 });
+        assert!(matches!(mirror.apply(&reduced), Err(MirrorError::ReduceMismatch { mirrored: 6, .. })));
+        let mut events = Vec::new();
+        engine.matcher.process_reduce(1, 2, &mut events);
+        mirror.apply_all(&events).unwrap();
+        assert_eq!(mirror.order(1).map(|o| o.qty), Some(4));
+        assert_eq!(mirror.level(Side::Ask, 100), Some(MirrorLevel { qty: 4, count: 1 }));
+    }
+
+    #[test]
+    fn test_sequence_gap() {
+        let mut engine = Engine::new(100);
+        let mut mirror = BookMirror::new();
+        let events = engine.process_command(Command::Place(PlaceOrder::limit(1, 1, Side::Bid, 100, 10))).to_vec();
+
+        mirror.apply_sequenced(10, &events[0]).unwrap();
+        assert_eq!(
+            mirror.apply_sequenced(12, &events[1]),
+            Err(MirrorError::SequenceGap { expected: 11, received: 12 })
+        );
+        mirror.apply_sequenced(11, &events[1]).unwrap();
+        assert_eq!(mirror.best_bid().map(|(p, _)| p), Some(100));
+    }
+}
@@ -3,11 +3,18 @@
 //! Maintains bid and ask price levels with O(1) best-price access
 //! and O(1) order lookup for cancellation.
 
+use std::ops::RangeBounds;
+
 use rustc_hash::FxHashMap;
-use std::collections::BTreeMap;
-use crate::arena::{Arena, ArenaIndex};
+use crate::arena::{Arena, ArenaHandle, ArenaIndex, NULL_INDEX};
+use crate::backend::BookBackend;
+use crate::book_side::{BookSide, LevelStorage};
 use crate::command::Side;
+use crate::depth::{FillEstimate, LevelOrders, Levels};
+use crate::level_map::LevelMap;
+use crate::order_index::OrderIndex;
 use crate::price_level::PriceLevel;
+use crate::queue_position::{QueuePosition, QueueTracker};
 
 /// Mapping from OrderId to ArenaIndex for O(1) cancel lookup
 pub type OrderMap = FxHashMap<u64, ArenaIndex>;
@@ -15,27 +22,35 @@ pub type OrderMap = FxHashMap<u64, ArenaIndex>;
 /// Order metadata stored alongside the arena index
 #[derive(Clone, Copy, Debug)]
 pub struct OrderInfo {
-    /// Index in the arena
-    pub arena_index: ArenaIndex,
+    /// Checked reference to the order's arena node
+    pub handle: ArenaHandle,
     /// Order side (needed for cancel to find correct book side)
     pub side: Side,
     /// Price level (needed for cancel to find the PriceLevel)
     pub price: u64,
     /// User ID (needed for modify order)
     pub user_id: u64,
+    /// Quantity the order was placed with (for fill status)
+    pub original_qty: u32,
+    /// Quantity removed by in-place reductions (`process_reduce`)
+    pub reduced_qty: u32,
 }
 
-/// Sparse Order Book using BTreeMap for ordered price levels.
+/// Order Book with ordered price levels per side.
 ///
-/// Ensures O(1) (amortized) access to best bid/ask and O(log N) level insertion.
-/// Order lookup (ID -> PriceLevel) remains O(1) via FxHashMap.
+/// Levels live in a `BookSide`: a pooled `LevelMap` by default (O(log N) level
+/// insertion), or a tick-indexed ladder (O(1)) selected via `LevelStorage`.
+/// Order lookup (ID -> PriceLevel) remains O(1) via FxHashMap, and orders
+/// can also be removed by arena handle without hashing (see `OrderIndex`).
 pub struct OrderBook {
     /// Bid price levels (buy orders) - Ordered
-    pub bids: BTreeMap<u64, PriceLevel>,
+    pub bids: BookSide,
     /// Ask price levels (sell orders) - Ordered
-    pub asks: BTreeMap<u64, PriceLevel>,
-    /// Order lookup map: OrderId -> OrderInfo (Keep O(1))
-    order_map: FxHashMap<u64, OrderInfo>,
+    pub asks: BookSide,
+    /// Order lookup by ID and by arena slot (Keep O(1))
+    order_map: OrderIndex,
+    /// Incremental queue positions (off by default)
+    queue: Option<Box<QueueTracker>>,
 }
 
 impl OrderBook {
@@ -43,20 +58,43 @@ impl OrderBook {
     /// Create a new empty order book
     pub fn new() -> Self {
         Self {
-            bids: BTreeMap::new(),
-            asks: BTreeMap::new(),
-            order_map: FxHashMap::default(),
+            bids: BookSide::default(),
+            asks: BookSide::default(),
+            order_map: OrderIndex::default(),
+            queue: None,
         }
     }
     
     /// Create a new order book with pre-allocated capacity
-    /// Create a new order book with pre-allocated capacity for order map.
-    /// BTreeMap nodes are allocated on demand.
-    pub fn with_capacity(_levels: usize, orders: usize) -> Self {
+    /// Create a new order book with pre-allocated capacity for order map
+    /// and for `levels` level nodes per side.
+    pub fn with_capacity(levels: usize, orders: usize) -> Self {
+        Self {
+            bids: BookSide::Tree(LevelMap::with_capacity(levels)),
+            asks: BookSide::Tree(LevelMap::with_capacity(levels)),
+            order_map: OrderIndex::with_capacity(orders),
+            queue: None,
+        }
+    }
+    
+    /// Create an order book with the given level storage on both sides.
+    pub fn with_storage(storage: LevelStorage, orders: usize) -> Self {
         Self {
-            bids: BTreeMap::new(),
-            asks: BTreeMap::new(),
-            order_map: FxHashMap::with_capacity_and_hasher(orders, Default::default()),
+            bids: BookSide::new(storage),
+            asks: BookSide::new(storage),
+            order_map: OrderIndex::with_capacity(orders),
+            queue: None,
+        }
+    }
+    
+    /// Returns true if the level storage for `side` can hold `price`
+    /// (always for `Tree`; for `Ladder`, tick-aligned prices within
+    /// `MAX_SPAN_TICKS` of that side's live levels)
+    #[inline]
+    pub fn accepts_price(&self, side: Side, price: u64) -> bool {
+        match side {
+            Side::Bid => self.bids.accepts_price(price),
+            Side::Ask => self.asks.accepts_price(price),
         }
     }
     
@@ -64,18 +102,16 @@ impl OrderBook {
     // Best Price Access
     // ========================================================================
     
-    /// Get the best bid price (highest buy price)
-    #[inline]
     /// Get the best bid price (highest buy price)
     #[inline]
     pub fn best_bid(&self) -> Option<u64> {
-        self.bids.keys().next_back().copied()
+        self.bids.last().map(|(p, _)| p)
     }
     
     /// Get the best ask price (lowest sell price)
     #[inline]
     pub fn best_ask(&self) -> Option<u64> {
-        self.asks.keys().next().copied()
+        self.asks.first().map(|(p, _)| p)
     }
     
     /// Get the best price on a given side
@@ -104,8 +140,8 @@ impl OrderBook {
     #[inline]
     pub fn get_level(&self, side: Side, price: u64) -> Option<&PriceLevel> {
         match side {
-            Side::Bid => self.bids.get(&price),
-            Side::Ask => self.asks.get(&price),
+            Side::Bid => self.bids.get(price),
+            Side::Ask => self.asks.get(price),
         }
     }
     
@@ -113,8 +149,8 @@ impl OrderBook {
     #[inline]
     pub fn get_level_mut(&mut self, side: Side, price: u64) -> Option<&mut PriceLevel> {
         match side {
-            Side::Bid => self.bids.get_mut(&price),
-            Side::Ask => self.asks.get_mut(&price),
+            Side::Bid => self.bids.get_mut(price),
+            Side::Ask => self.asks.get_mut(price),
         }
     }
     
@@ -122,8 +158,8 @@ impl OrderBook {
     #[inline]
     pub fn get_or_create_level(&mut self, side: Side, price: u64) -> &mut PriceLevel {
         match side {
-            Side::Bid => self.bids.entry(price).or_insert_with(PriceLevel::new),
-            Side::Ask => self.asks.entry(price).or_insert_with(PriceLevel::new),
+            Side::Bid => self.bids.get_or_insert(price),
+            Side::Ask => self.asks.get_or_insert(price),
         }
     }
     
@@ -152,23 +188,30 @@ impl OrderBook {
         arena_index: ArenaIndex,
     ) -> bool {
         // Check for duplicate order ID
-        if self.order_map.contains_key(&order_id) {
+        if self.order_map.contains(order_id) {
             return false;
         }
         
         // Add to order lookup map
         self.order_map.insert(order_id, OrderInfo {
-            arena_index,
+            handle: arena.handle(arena_index),
             side,
             price,
             user_id,
+            original_qty: arena.get(arena_index).qty,
+            reduced_qty: 0,
         });
         
         // Add to price level
-        let level = self.get_or_create_level(side, price);
+        let level = match side {
+            Side::Bid => self.bids.get_or_insert(price),
+            Side::Ask => self.asks.get_or_insert(price),
+        };
         level.push_back(arena, arena_index);
+        if let Some(queue) = &mut self.queue {
+            queue.push(arena, level, order_id, side, price, arena.get(arena_index).qty);
+        }
         
-        // Update best price cache - No longer needed with BTreeMap
         // self.update_best_price_on_add(side, price);
         
         true
@@ -184,22 +227,25 @@ impl OrderBook {
     /// The removed order's info if found, or `None` if not found
     pub fn remove_order(&mut self, arena: &mut Arena, order_id: u64) -> Option<OrderInfo> {
         // Look up order
-        let info = self.order_map.remove(&order_id)?;
+        let info = self.order_map.remove(order_id)?;
+        if let Some(queue) = &mut self.queue {
+            queue.remove(order_id);
+        }
         
         // Remove from price level
         let level = match info.side {
-            Side::Bid => self.bids.get_mut(&info.price),
-            Side::Ask => self.asks.get_mut(&info.price),
+            Side::Bid => self.bids.get_mut(info.price),
+            Side::Ask => self.asks.get_mut(info.price),
         };
         
         if let Some(level) = level {
-            let is_empty = level.remove(arena, info.arena_index);
+            let is_empty = level.remove(arena, arena.resolve(info.handle));
             
             // Clean up empty level and update best price
             if is_empty {
                 match info.side {
-                    Side::Bid => { self.bids.remove(&info.price); },
-                    Side::Ask => { self.asks.remove(&info.price); },
+                    Side::Bid => { self.bids.remove(info.price); },
+                    Side::Ask => { self.asks.remove(info.price); },
                 }
             }
         }
@@ -210,20 +256,161 @@ impl OrderBook {
     /// Look up an order by ID.
     #[inline]
     pub fn get_order(&self, order_id: u64) -> Option<&OrderInfo> {
-        self.order_map.get(&order_id)
+        self.order_map.get(order_id)
+    }
+    
+    /// Look up an order by ID (mutable).
+    #[inline]
+    pub(crate) fn get_order_mut(&mut self, order_id: u64) -> Option<&mut OrderInfo> {
+        self.order_map.get_mut(order_id)
+    }
+    
+    /// Iterate all resting orders (unordered).
+    #[inline]
+    pub fn orders(&self) -> impl Iterator<Item = (u64, &OrderInfo)> + '_ {
+        self.order_map.iter()
     }
     
     /// Check if an order exists.
     #[inline]
     pub fn contains_order(&self, order_id: u64) -> bool {
-        self.order_map.contains_key(&order_id)
+        self.order_map.contains(order_id)
     }
     
     /// Remove an order from the order map only (after matching).
     /// Call this when an order is fully filled during matching.
     #[inline]
-    pub fn remove_order_from_map(&mut self, order_id: u64) {
-        self.order_map.remove(&order_id);
+    pub fn remove_order_from_map(&mut self, order_id: u64) -> Option<OrderInfo> {
+        if let Some(queue) = &mut self.queue {
+            queue.remove(order_id);
+        }
+        self.order_map.remove(order_id)
+    }
+
+    /// Forget an order already unlinked from its level, through its handle
+    /// (no ID hashing unless queue tracking is enabled).
+    #[inline]
+    pub fn remove_order_by_handle(&mut self, order_id: u64, handle: ArenaHandle) -> Option<OrderInfo> {
+        let info = self.order_map.remove_by_handle(order_id, handle)?;
+        if let Some(queue) = &mut self.queue {
+            queue.remove(order_id);
+        }
+        Some(info)
+    }
+
+    /// Record an in-place quantity decrease of a resting order.
+    /// Call this alongside `PriceLevel::subtract_qty`.
+    #[inline]
+    pub fn note_qty_reduced(&mut self, order_id: u64, qty: u32) {
+        if let Some(queue) = &mut self.queue {
+            queue.reduce(order_id, qty);
+        }
+    }
+
+    // ========================================================================
+    // Depth Queries
+    // ========================================================================
+    
+    /// All levels on a side, best price first
+    #[inline]
+    pub fn levels(&self, side: Side) -> Levels<'_> {
+        match side {
+            Side::Bid => Levels::new(self.bids.iter(), side),
+            Side::Ask => Levels::new(self.asks.iter(), side),
+        }
+    }
+    
+    /// The best `n` levels on a side
+    #[inline]
+    pub fn top_levels(&self, side: Side, n: usize) -> std::iter::Take<Levels<'_>> {
+        self.levels(side).take(n)
+    }
+    
+    /// Orders resting at a price, in time priority (empty if no level)
+    pub fn level_orders<'a>(&self, arena: &'a Arena, side: Side, price: u64) -> LevelOrders<'a> {
+        let head = self.get_level(side, price).map_or(NULL_INDEX, |l| l.head);
+        LevelOrders::new(arena, head)
+    }
+    
+    /// Total quantity and order count at prices at least as good as `price`
+    /// (bids at or above, asks at or below)
+    pub fn cumulative_depth(&self, side: Side, price: u64) -> (u64, u32) {
+        let sum = |(qty, count): (u64, u32), level: &PriceLevel| (qty + level.total_qty, count + level.count);
+        match side {
+            Side::Bid => self.bids.range(price..).map(|(_, l)| l).fold((0, 0), sum),
+            Side::Ask => self.asks.range(..=price).map(|(_, l)| l).fold((0, 0), sum),
+        }
+    }
+    
+    /// Walk the opposite side as a market order of `qty` on `side` would.
+    ///
+    /// The estimate is partial if the book runs out; see
+    /// `FillEstimate::is_complete`.
+    pub fn cost_to_fill(&self, side: Side, qty: u64) -> FillEstimate {
+        let mut estimate = FillEstimate { requested: qty, ..Default::default() };
+        for level in self.levels(side.opposite()) {
+            if estimate.filled_qty == qty {
+                break;
+            }
+            let take = level.qty.min(qty - estimate.filled_qty);
+            estimate.filled_qty += take;
+            estimate.notional += level.price as u128 * take as u128;
+            estimate.worst_price = Some(level.price);
+            estimate.levels += 1;
+        }
+        estimate
+    }
+    
+    /// Average price of a market order of `qty` on `side`, if the book can
+    /// fill all of it
+    pub fn vwap_for_size(&self, side: Side, qty: u64) -> Option<f64> {
+        let estimate = self.cost_to_fill(side, qty);
+        if estimate.is_complete() { estimate.vwap() } else { None }
+    }
+    
+    // ========================================================================
+    // Queue Position
+    // ========================================================================
+
+    /// Orders and quantity ahead of a resting order at its price level.
+    ///
+    /// O(log n) with queue tracking enabled, otherwise a walk from the
+    /// level head (O(orders ahead)).
+    pub fn queue_position(&self, arena: &Arena, order_id: u64) -> Option<QueuePosition> {
+        if let Some(queue) = &self.queue {
+            return queue.position(order_id);
+        }
+
+        let info = self.order_map.get(order_id)?;
+        let level = self.get_level(info.side, info.price)?;
+        let mut position = QueuePosition::default();
+        let mut idx = level.head;
+        while idx != info.handle.index && idx != NULL_INDEX {
+            let node = arena.get(idx);
+            position.orders_ahead += 1;
+            position.qty_ahead += node.qty as u64;
+            idx = node.next;
+        }
+        Some(position)
+    }
+
+    /// Enable incremental queue-position tracking, indexing the current book.
+    pub fn enable_queue_tracking(&mut self, arena: &Arena) {
+        let mut queue = QueueTracker::new();
+        queue.index_side(arena, Side::Bid, self.bids.iter());
+        queue.index_side(arena, Side::Ask, self.asks.iter());
+        self.queue = Some(Box::new(queue));
+    }
+
+    /// Drop the queue-position index; lookups fall back to walking the level.
+    pub fn disable_queue_tracking(&mut self) {
+        self.queue = None;
+    }
+
+    /// Returns true if incremental queue tracking is enabled
+    #[inline]
+    pub fn tracks_queue(&self) -> bool {
+        self.queue.is_some()
     }
     
     // ========================================================================
@@ -233,15 +420,14 @@ impl OrderBook {
     
     /// Remove an empty price level.
     ///
-    /// Since we use BTreeMap, we just remove the key.
     /// Best price is automatically handled by the ordered structure.
     pub fn remove_empty_level(&mut self, side: Side, price: u64) {
         match side {
             Side::Bid => {
-                self.bids.remove(&price);
+                self.bids.remove(price);
             }
             Side::Ask => {
-                self.asks.remove(&price);
+                self.asks.remove(price);
             }
         }
     }
@@ -278,6 +464,9 @@ impl OrderBook {
         self.bids.clear();
         self.asks.clear();
         self.order_map.clear();
+        if let Some(queue) = &mut self.queue {
+            queue.clear();
+        }
     }
     
     /// Calculate spread (best_ask - best_bid)
@@ -315,6 +504,113 @@ impl std::fmt::Debug for OrderBook {
     }
 }
 
+impl BookBackend for OrderBook {
+    #[inline]
+    fn accepts_price(&self, side: Side, price: u64) -> bool {
+        OrderBook::accepts_price(self, side, price)
+    }
+
+    #[inline]
+    fn best_bid(&self) -> Option<u64> {
+        OrderBook::best_bid(self)
+    }
+
+    #[inline]
+    fn best_ask(&self) -> Option<u64> {
+        OrderBook::best_ask(self)
+    }
+
+    #[inline]
+    fn get_level(&self, side: Side, price: u64) -> Option<&PriceLevel> {
+        OrderBook::get_level(self, side, price)
+    }
+
+    #[inline]
+    fn get_level_mut(&mut self, side: Side, price: u64) -> Option<&mut PriceLevel> {
+        OrderBook::get_level_mut(self, side, price)
+    }
+
+    #[inline]
+    fn level_range<R: RangeBounds<u64>>(
+        &self,
+        side: Side,
+        range: R,
+    ) -> impl DoubleEndedIterator<Item = (u64, &PriceLevel)> + '_ {
+        match side {
+            Side::Bid => self.bids.range(range),
+            Side::Ask => self.asks.range(range),
+        }
+    }
+
+    #[inline]
+    fn remove_empty_level(&mut self, side: Side, price: u64) {
+        OrderBook::remove_empty_level(self, side, price)
+    }
+
+    #[inline]
+    fn add_order(
+        &mut self,
+        arena: &mut Arena,
+        order_id: u64,
+        user_id: u64,
+        side: Side,
+        price: u64,
+        arena_index: ArenaIndex,
+    ) -> bool {
+        OrderBook::add_order(self, arena, order_id, user_id, side, price, arena_index)
+    }
+
+    #[inline]
+    fn remove_order(&mut self, arena: &mut Arena, order_id: u64) -> Option<OrderInfo> {
+        OrderBook::remove_order(self, arena, order_id)
+    }
+
+    #[inline]
+    fn remove_order_from_map(&mut self, order_id: u64) -> Option<OrderInfo> {
+        OrderBook::remove_order_from_map(self, order_id)
+    }
+
+    #[inline]
+    fn remove_order_by_handle(&mut self, order_id: u64, handle: ArenaHandle) -> Option<OrderInfo> {
+        OrderBook::remove_order_by_handle(self, order_id, handle)
+    }
+
+    #[inline]
+    fn get_order(&self, order_id: u64) -> Option<&OrderInfo> {
+        OrderBook::get_order(self, order_id)
+    }
+
+    #[inline]
+    fn get_order_mut(&mut self, order_id: u64) -> Option<&mut OrderInfo> {
+        OrderBook::get_order_mut(self, order_id)
+    }
+
+    #[inline]
+    fn contains_order(&self, order_id: u64) -> bool {
+        OrderBook::contains_order(self, order_id)
+    }
+
+    #[inline]
+    fn orders(&self) -> impl Iterator<Item = (u64, &OrderInfo)> + '_ {
+        OrderBook::orders(self)
+    }
+
+    #[inline]
+    fn order_count(&self) -> usize {
+        OrderBook::order_count(self)
+    }
+
+    #[inline]
+    fn note_qty_reduced(&mut self, order_id: u64, qty: u32) {
+        OrderBook::note_qty_reduced(self, order_id, qty)
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        OrderBook::clear(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -435,7 +731,7 @@ mod tests {
         let info = book.remove_order(&mut arena, 1);
         assert!(info.is_some());
         let info = info.unwrap();
-        assert_eq!(info.arena_index, idx);
+        assert_eq!(info.handle, arena.handle(idx));
         assert_eq!(info.side, Side::Bid);
         assert_eq!(info.price, 10000);
         
@@ -529,4 +825,92 @@ mod tests {
         
         assert_eq!(book.depth_at(Side::Bid, 10000), (350, 2));
     }
+
+    #[test]
+    fn test_depth_queries() {
+        let mut arena = Arena::new(100);
+        let mut book = OrderBook::new();
+        for (id, side, price, qty) in [
+            (1, Side::Ask, 10100, 100),
+            (2, Side::Ask, 10100, 50),
+            (3, Side::Ask, 10200, 200),
+            (4, Side::Ask, 10300, 300),
+            (5, Side::Bid, 9900, 10),
+            (6, Side::Bid, 9800, 20),
+        ] {
+            let idx = create_order(&mut arena, id, price, qty);
+            book.add_order(&mut arena, id, 1, side, price, idx);
+        }
+
+        let bids: Vec<_> = book.top_levels(Side::Bid, 5).map(|l| (l.price, l.qty, l.count)).collect();
+        assert_eq!(bids, vec![(9900, 10, 1), (9800, 20, 1)]);
+        let asks: Vec<_> = book.top_levels(Side::Ask, 2).map(|l| l.price).collect();
+        assert_eq!(asks, vec![10100, 10200]);
+
+        let ids: Vec<_> = book.level_orders(&arena, Side::Ask, 10100).map(|n| n.order_id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(book.level_orders(&arena, Side::Ask, 10150).count(), 0);
+
+        assert_eq!(book.cumulative_depth(Side::Ask, 10200), (350, 3));
+        assert_eq!(book.cumulative_depth(Side::Bid, 9800), (30, 2));
+        assert_eq!(book.cumulative_depth(Side::Bid, 9950), (0, 0));
+
+        // Buy 250: 150 @ 10100 + 100 @ 10200
+        let estimate = book.cost_to_fill(Side::Bid, 250);
+        assert!(estimate.is_complete());
+        assert_eq!((estimate.notional, estimate.worst_price, estimate.levels), (2_535_000, Some(10200), 2));
+        assert_eq!(book.vwap_for_size(Side::Bid, 250), Some(10140.0));
+
+        // Sell more than the bids hold
+        let estimate = book.cost_to_fill(Side::Ask, 100);
+        assert_eq!((estimate.filled_qty, estimate.is_complete()), (30, false));
+        assert_eq!(book.vwap_for_size(Side::Ask, 100), None);
+    }
+
+    #[test]
+    fn test_queue_position_walk_and_tracked() {
+        let mut arena = Arena::new(100);
+        let mut book = OrderBook::new();
+
+        for (id, qty) in [(1, 100), (2, 200), (3, 300)] {
+            let idx = create_order(&mut arena, id, 10000, qty);
+            book.add_order(&mut arena, id, 1, Side::Bid, 10000, idx);
+        }
+        let walked: Vec<_> = (1..=3).map(|id| book.queue_position(&arena, id).unwrap()).collect();
+        assert_eq!(walked[2], QueuePosition { orders_ahead: 2, qty_ahead: 300 });
+
+        book.enable_queue_tracking(&arena);
+        for id in 1..=3 {
+            assert_eq!(book.queue_position(&arena, id), Some(walked[id as usize - 1]));
+        }
+
+        let idx = create_order(&mut arena, 4, 10000, 50);
+        book.add_order(&mut arena, 4, 1, Side::Bid, 10000, idx);
+        book.remove_order(&mut arena, 2);
+        assert_eq!(book.queue_position(&arena, 4), Some(QueuePosition { orders_ahead: 2, qty_ahead: 400 }));
+        assert_eq!(book.queue_position(&arena, 2), None);
+    }
+
+    #[test]
+    fn test_queue_tracking_survives_compaction() {
+        let mut arena = Arena::new(1000);
+        let mut book = OrderBook::new();
+        book.enable_queue_tracking(&arena);
+
+        // Churn one level so departed slots pile up and get compacted
+        for id in 1..=400u64 {
+            let idx = create_order(&mut arena, id, 10000, id as u32);
+            book.add_order(&mut arena, id, 1, Side::Bid, 10000, idx);
+            if id % 5 != 0 {
+                book.remove_order(&mut arena, id);
+            }
+        }
+
+        let mut expected = QueuePosition::default();
+        for id in (5..=400u64).step_by(5) {
+            assert_eq!(book.queue_position(&arena, id), Some(expected));
+            expected.orders_ahead += 1;
+            expected.qty_ahead += id;
+        }
+    }
 }
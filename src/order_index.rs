@@ -0,0 +1,208 @@
+//! Order Index - Resting order lookup by ID and by arena slot.
+//!
+//! Order info lives in a table indexed by arena slot, and an `FxHashMap`
+//! maps order IDs to slots. Lookups and removals by ID hash as usual;
+//! removal through a handle (cancel by handle) only clears the slot and
+//! leaves the ID entry behind as stale. A stale entry no longer resolves,
+//! since its slot is empty or holds another order, so lookups by ID treat
+//! it as absent. Stale entries are swept once they outnumber live orders,
+//! keeping removal by handle O(1) amortized without hashing.
+
+use rustc_hash::FxHashMap;
+
+use crate::arena::{ArenaHandle, ArenaIndex};
+use crate::order_book::OrderInfo;
+
+/// Stale ID entries tolerated before a sweep, however small the book
+const MIN_STALE_SWEEP: usize = 1024;
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    order_id: u64,
+    info: OrderInfo,
+}
+
+/// Resting orders by ID and by arena slot
+#[derive(Clone, Debug, Default)]
+pub struct OrderIndex {
+    /// Order in each arena slot (`None` for free slots)
+    slots: Vec<Option<Entry>>,
+    /// Order ID -> arena slot, including stale entries
+    ids: FxHashMap<u64, ArenaIndex>,
+    /// Entries in `ids` whose order was removed by handle
+    stale: usize,
+    len: usize,
+}
+
+impl OrderIndex {
+    /// Create an index with room for `orders` orders in arena slots
+    /// `0..orders`
+    pub fn with_capacity(orders: usize) -> Self {
+        Self {
+            slots: vec![None; orders],
+            ids: FxHashMap::with_capacity_and_hasher(orders, Default::default()),
+            stale: 0,
+            len: 0,
+        }
+    }
+
+    /// Entry in `slot` if it holds `order_id`
+    #[inline]
+    fn resolve(&self, slot: ArenaIndex, order_id: u64) -> Option<&Entry> {
+        self.slots.get(slot as usize)?.as_ref().filter(|e| e.order_id == order_id)
+    }
+
+    #[inline]
+    pub fn get(&self, order_id: u64) -> Option<&OrderInfo> {
+        let &slot = self.ids.get(&order_id)?;
+        self.resolve(slot, order_id).map(|e| &e.info)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, order_id: u64) -> Option<&mut OrderInfo> {
+        let &slot = self.ids.get(&order_id)?;
+        self.slots[slot as usize].as_mut().filter(|e| e.order_id == order_id).map(|e| &mut e.info)
+    }
+
+    #[inline]
+    pub fn contains(&self, order_id: u64) -> bool {
+        self.get(order_id).is_some()
+    }
+
+    /// Add an order. The caller ensures `order_id` isn't already resting.
+    #[inline]
+    pub fn insert(&mut self, order_id: u64, info: OrderInfo) {
+        let slot = info.handle.index as usize;
+        if slot >= self.slots.len() {
+            self.slots.resize(slot + 1, None);
+        }
+        debug_assert!(self.slots[slot].is_none(), "arena slot {} already indexed", slot);
+        self.slots[slot] = Some(Entry { order_id, info });
+        if self.ids.insert(order_id, slot as ArenaIndex).is_some() {
+            // Replaced a stale entry for a canceled order with the same ID
+            self.stale -= 1;
+        }
+        self.len += 1;
+    }
+
+    /// Remove an order by ID
+    #[inline]
+    pub fn remove(&mut self, order_id: u64) -> Option<OrderInfo> {
+        let slot = self.ids.remove(&order_id)?;
+        let entry = &mut self.slots[slot as usize];
+        if entry.is_some_and(|e| e.order_id == order_id) {
+            self.len -= 1;
+            entry.take().map(|e| e.info)
+        } else {
+            self.stale -= 1;
+            None
+        }
+    }
+
+    /// Remove an order through its handle, without hashing. Returns `None`
+    /// unless the handle's slot holds `order_id` at the handle's generation.
+    ///
+    /// # Complexity
+    /// O(1) amortized (stale ID entries are swept in bulk)
+    #[inline]
+    pub fn remove_by_handle(&mut self, order_id: u64, handle: ArenaHandle) -> Option<OrderInfo> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if !slot.is_some_and(|e| e.order_id == order_id && e.info.handle == handle) {
+            return None;
+        }
+        let entry = slot.take()?;
+        self.len -= 1;
+        self.stale += 1;
+        if self.stale > self.len.max(MIN_STALE_SWEEP) {
+            self.sweep();
+        }
+        Some(entry.info)
+    }
+
+    /// Drop all stale ID entries
+    #[cold]
+    fn sweep(&mut self) {
+        let slots = &self.slots;
+        self.ids.retain(|&order_id, &mut slot| {
+            slots[slot as usize].as_ref().is_some_and(|e| e.order_id == order_id)
+        });
+        self.stale = 0;
+    }
+
+    /// All orders, in arena slot order
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &OrderInfo)> + '_ {
+        self.slots.iter().flatten().map(|e| (e.order_id, &e.info))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.fill(None);
+        self.ids.clear();
+        self.stale = 0;
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Side;
+
+    fn info(index: ArenaIndex, generation: u32) -> OrderInfo {
+        OrderInfo { handle: ArenaHandle { index, generation }, side: Side::Bid, price: 100, user_id: 1, original_qty: 10, reduced_qty: 0 }
+    }
+
+    #[test]
+    fn test_remove_by_handle_leaves_stale_id() {
+        let mut index = OrderIndex::with_capacity(4);
+        index.insert(7, info(2, 1));
+        index.insert(8, info(3, 1));
+
+        // Wrong ID or generation is refused
+        assert!(index.remove_by_handle(8, info(2, 1).handle).is_none());
+        assert!(index.remove_by_handle(7, info(2, 3).handle).is_none());
+
+        assert_eq!(index.remove_by_handle(7, info(2, 1).handle).unwrap().handle.index, 2);
+        assert_eq!((index.len(), index.stale), (1, 1));
+        assert!(!index.contains(7));
+        assert!(index.remove(7).is_none());
+        assert_eq!(index.stale, 0);
+
+        // Slot reused by another order: the old ID still doesn't resolve
+        index.remove_by_handle(8, info(3, 1).handle).unwrap();
+        index.insert(9, info(3, 3));
+        assert!(!index.contains(8));
+        assert_eq!(index.get(9).unwrap().handle.generation, 3);
+
+        // Reusing the ID replaces the stale entry
+        index.insert(8, info(0, 1));
+        assert_eq!(index.stale, 0);
+        assert_eq!(index.iter().map(|(id, _)| id).collect::<Vec<_>>(), [8, 9]);
+    }
+
+    #[test]
+    fn test_stale_entries_are_swept() {
+        let mut index = OrderIndex::default();
+        for round in 0..3u32 {
+            for slot in 0..1_000 {
+                index.insert(round as u64 * 1_000 + slot as u64, info(slot, round * 2 + 1));
+            }
+            for slot in 0..1_000 {
+                index.remove_by_handle(round as u64 * 1_000 + slot as u64, info(slot, round * 2 + 1).handle).unwrap();
+            }
+        }
+        assert!(index.is_empty());
+        assert!(index.ids.len() <= MIN_STALE_SWEEP);
+        assert_eq!(index.ids.len(), index.stale);
+    }
+}
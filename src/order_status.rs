@@ -0,0 +1,179 @@
+//! Order Status - Read API for live orders and a bounded history of
+//! finished ones.
+//!
+//! Live orders are answered from the book and arena. Orders that leave the
+//! book (filled, cancelled, or rejected) are recorded in `OrderHistory`, a
+//! fixed-size window of the most recent finished orders. The window is off
+//! (size 0) by default so the matching path does no extra work.
+
+use std::collections::VecDeque;
+
+use rustc_hash::FxHashMap;
+
+use crate::command::Side;
+
+/// Lifecycle state of an order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderState {
+    /// Resting with no fills
+    New,
+    /// Resting with some quantity filled
+    PartiallyFilled,
+    /// Fully filled
+    Filled,
+    /// Cancelled (including the unfilled remainder of an IOC)
+    Canceled,
+    /// Rejected (FOK without liquidity, full arena, zero quantity)
+    Rejected,
+}
+
+impl OrderState {
+    /// Returns true if the order is still in the book
+    #[inline]
+    pub fn is_open(self) -> bool {
+        matches!(self, OrderState::New | OrderState::PartiallyFilled)
+    }
+}
+
+/// Snapshot of an order's quantities and state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OrderStatus {
+    pub order_id: u64,
+    pub user_id: u64,
+    pub side: Side,
+    pub price: u64,
+    /// Quantity placed
+    pub original_qty: u32,
+    /// Quantity still resting (0 once finished)
+    pub remaining_qty: u32,
+    /// Cumulative filled quantity
+    pub filled_qty: u32,
+    /// Quantity removed by in-place reductions (`process_reduce`)
+    pub reduced_qty: u32,
+    pub state: OrderState,
+}
+
+/// Bounded window of finished orders, oldest evicted first
+#[derive(Debug)]
+pub struct OrderHistory {
+    capacity: usize,
+    /// Finished orders by ID, tagged with their insertion sequence
+    orders: FxHashMap<u64, (u64, OrderStatus)>,
+    /// Insertion order as (order_id, sequence)
+    fifo: VecDeque<(u64, u64)>,
+    next_seq: u64,
+}
+
+impl OrderHistory {
+    /// Create a history holding up to `capacity` finished orders (0 = off)
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            orders: FxHashMap::with_capacity_and_hasher(capacity, Default::default()),
+            fifo: VecDeque::with_capacity(capacity),
+            next_seq: 0,
+        }
+    }
+
+    /// Maximum number of finished orders kept
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns true if finished orders are being recorded
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Record a finished order, evicting the oldest if full
+    pub fn record(&mut self, status: OrderStatus) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.fifo.len() == self.capacity {
+            if let Some((order_id, seq)) = self.fifo.pop_front() {
+                // The ID may have been reused and recorded again since
+                if self.orders.get(&order_id).is_some_and(|&(s, _)| s == seq) {
+                    self.orders.remove(&order_id);
+                }
+            }
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.fifo.push_back((status.order_id, seq));
+        self.orders.insert(status.order_id, (seq, status));
+    }
+
+    /// Status of a finished order still in the window
+    #[inline]
+    pub fn get(&self, order_id: u64) -> Option<&OrderStatus> {
+        self.orders.get(&order_id).map(|(_, status)| status)
+    }
+
+    /// Number of finished orders held
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    /// Returns true if no finished orders are held
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.orders.clear();
+        self.fifo.clear();
+    }
+}
+
+impl Default for OrderHistory {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished(order_id: u64) -> OrderStatus {
+        OrderStatus {
+            order_id,
+            user_id: 1,
+            side: Side::Bid,
+            price: 100,
+            original_qty: 10,
+            remaining_qty: 0,
+            filled_qty: 10,
+            reduced_qty: 0,
+            state: OrderState::Filled,
+        }
+    }
+
+    #[test]
+    fn test_history_evicts_oldest() {
+        let mut history = OrderHistory::new(3);
+        for id in 1..=4 {
+            history.record(finished(id));
+        }
+        assert!(history.get(1).is_none());
+        assert!((2..=4).all(|id| history.get(id).is_some()));
+
+        // Reused ID: evicting its old entry must not drop the new one
+        history.record(finished(3));
+        history.record(finished(5));
+        assert!(history.get(2).is_none());
+        assert!(history.get(3).is_some());
+    }
+
+    #[test]
+    fn test_disabled_history_records_nothing() {
+        let mut history = OrderHistory::default();
+        history.record(finished(1));
+        assert!(history.is_empty());
+    }
+}
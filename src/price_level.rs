@@ -9,7 +9,7 @@ use crate::arena::{Arena, ArenaIndex, NULL_INDEX};
 ///
 /// Orders are processed in FIFO order (price-time priority).
 /// The doubly-linked structure enables O(1) cancel from any position.
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug)]
 pub struct PriceLevel {
     /// Index of the oldest order (highest priority, first to match)
     pub head: ArenaIndex,
@@ -187,6 +187,12 @@ impl PriceLevel {
     }
 }
 
+impl Default for PriceLevel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -215,6 +221,15 @@ mod tests {
         assert_eq!(level.tail, NULL_INDEX);
     }
     
+    #[test]
+    fn test_default_is_empty_level() {
+        // A derived Default would set head/tail to 0, a valid arena index
+        let level = PriceLevel::default();
+        assert!(level.is_empty());
+        assert_eq!(level.head, NULL_INDEX);
+        assert_eq!(level.tail, NULL_INDEX);
+    }
+    
     #[test]
     fn test_push_single() {
         let mut arena = Arena::new(10);
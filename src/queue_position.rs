@@ -0,0 +1,222 @@
+//! Queue Position - Size ahead of a resting order in its price level.
+//!
+//! Without tracking, `OrderBook::queue_position` walks the level's linked
+//! list from the head, which is O(orders ahead). With incremental tracking
+//! enabled, each level keeps Fenwick trees of quantity and count indexed by
+//! arrival sequence, so lookups and updates are O(log n). Slots of departed
+//! orders are compacted away as the level grows.
+
+use rustc_hash::FxHashMap;
+
+use crate::arena::{Arena, NULL_INDEX};
+use crate::command::Side;
+use crate::price_level::PriceLevel;
+
+/// Levels smaller than this are never compacted
+const MIN_COMPACT_SLOTS: usize = 64;
+
+/// Orders and quantity ahead of a resting order at its price level
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueuePosition {
+    /// Orders with higher priority at the same price
+    pub orders_ahead: u32,
+    /// Total quantity of those orders
+    pub qty_ahead: u64,
+}
+
+/// Where a tracked order sits
+#[derive(Clone, Copy, Debug)]
+struct Slot {
+    side: Side,
+    price: u64,
+    /// Index into the level's trees
+    index: usize,
+    qty: u32,
+}
+
+/// Fenwick trees over one level's arrival sequence
+#[derive(Clone, Debug)]
+struct LevelQueue {
+    /// 1-based trees; slot 0 unused
+    qty: Vec<u64>,
+    count: Vec<u32>,
+    live: usize,
+}
+
+impl LevelQueue {
+    fn new() -> Self {
+        Self { qty: vec![0], count: vec![0], live: 0 }
+    }
+
+    #[inline]
+    fn slots(&self) -> usize {
+        self.qty.len() - 1
+    }
+
+    /// Append a slot; returns its index
+    fn push(&mut self, qty: u32) -> usize {
+        let i = self.qty.len();
+        let lowbit = i & i.wrapping_neg();
+        // Node i covers (i - lowbit, i]: the new value plus the existing tail of that range
+        let (q_hi, c_hi) = self.prefix(i - 1);
+        let (q_lo, c_lo) = self.prefix(i - lowbit);
+        self.qty.push(qty as u64 + q_hi - q_lo);
+        self.count.push(1 + c_hi - c_lo);
+        self.live += 1;
+        i
+    }
+
+    /// Sum over slots 1..=i
+    fn prefix(&self, mut i: usize) -> (u64, u32) {
+        let (mut qty, mut count) = (0, 0);
+        while i > 0 {
+            qty += self.qty[i];
+            count += self.count[i];
+            i &= i - 1;
+        }
+        (qty, count)
+    }
+
+    /// Subtract from slot i
+    fn sub(&mut self, mut i: usize, qty: u32, count: u32) {
+        while i < self.qty.len() {
+            self.qty[i] -= qty as u64;
+            self.count[i] -= count;
+            i += i & i.wrapping_neg();
+        }
+    }
+}
+
+impl Default for LevelQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Incremental queue-position index for an `OrderBook`
+#[derive(Clone, Debug, Default)]
+pub struct QueueTracker {
+    orders: FxHashMap<u64, Slot>,
+    bids: FxHashMap<u64, LevelQueue>,
+    asks: FxHashMap<u64, LevelQueue>,
+}
+
+impl QueueTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build from the current contents of a book side
+    pub fn index_side<'a>(
+        &mut self,
+        arena: &Arena,
+        side: Side,
+        levels: impl Iterator<Item = (u64, &'a PriceLevel)>,
+    ) {
+        for (price, level) in levels {
+            self.index_level(arena, side, price, level);
+        }
+    }
+
+    /// Position of a tracked order
+    pub fn position(&self, order_id: u64) -> Option<QueuePosition> {
+        let slot = self.orders.get(&order_id)?;
+        let (qty_ahead, orders_ahead) = self.levels(slot.side).get(&slot.price)?.prefix(slot.index - 1);
+        Some(QueuePosition { orders_ahead, qty_ahead })
+    }
+
+    /// An order joined the back of its level
+    pub fn push(&mut self, arena: &Arena, level: &PriceLevel, order_id: u64, side: Side, price: u64, qty: u32) {
+        let compact = self.levels(side).get(&price)
+            .is_some_and(|q| q.slots() >= MIN_COMPACT_SLOTS && q.slots() >= 4 * q.live);
+        if compact {
+            // Re-reads the level, which already contains the new order
+            self.index_level(arena, side, price, level);
+            return;
+        }
+        let index = self.levels_mut(side).entry(price).or_default().push(qty);
+        self.orders.insert(order_id, Slot { side, price, index, qty });
+    }
+
+    /// An order's quantity went down (partial fill or reduce)
+    pub fn reduce(&mut self, order_id: u64, by: u32) {
+        let Some(slot) = self.orders.get_mut(&order_id) else { return };
+        slot.qty -= by;
+        let (side, price, index) = (slot.side, slot.price, slot.index);
+        if let Some(queue) = self.levels_mut(side).get_mut(&price) {
+            queue.sub(index, by, 0);
+        }
+    }
+
+    /// An order left the book
+    pub fn remove(&mut self, order_id: u64) {
+        let Some(slot) = self.orders.remove(&order_id) else { return };
+        let levels = self.levels_mut(slot.side);
+        if let Some(queue) = levels.get_mut(&slot.price) {
+            queue.live -= 1;
+            if queue.live == 0 {
+                levels.remove(&slot.price);
+            } else {
+                queue.sub(slot.index, slot.qty, 1);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.orders.clear();
+        self.bids.clear();
+        self.asks.clear();
+    }
+
+    /// (Re)build one level's trees from its linked list
+    fn index_level(&mut self, arena: &Arena, side: Side, price: u64, level: &PriceLevel) {
+        let mut queue = LevelQueue::new();
+        let mut idx = level.head;
+        while idx != NULL_INDEX {
+            let node = arena.get(idx);
+            let index = queue.push(node.qty);
+            self.orders.insert(node.order_id, Slot { side, price, index, qty: node.qty });
+            idx = node.next;
+        }
+        self.levels_mut(side).insert(price, queue);
+    }
+
+    #[inline]
+    fn levels(&self, side: Side) -> &FxHashMap<u64, LevelQueue> {
+        match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        }
+    }
+
+    #[inline]
+    fn levels_mut(&mut self, side: Side) -> &mut FxHashMap<u64, LevelQueue> {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fenwick_append_and_remove() {
+        let mut queue = LevelQueue::new();
+        for qty in 1..=100 {
+            queue.push(qty);
+        }
+        // Slots 1..=i hold 1..=i
+        for i in 0..=100usize {
+            assert_eq!(queue.prefix(i), ((i * (i + 1) / 2) as u64, i as u32));
+        }
+
+        queue.sub(10, 10, 1);
+        queue.sub(50, 20, 0);
+        assert_eq!(queue.prefix(9), (45, 9));
+        assert_eq!(queue.prefix(10), (45, 9));
+        assert_eq!(queue.prefix(50), (1275 - 10 - 20, 49));
+    }
+}
@@ -0,0 +1,755 @@
+//! Market data gap recovery.
+//!
+//! Subscribers that miss packets on the sequenced feed have two ways back:
+//!
+//! - **Retransmission**: a MoldUDP64-style request server that replays a
+//!   range of recent messages from an in-memory ring (`RetransmitBuffer`).
+//! - **Snapshots**: a separate channel that periodically publishes a full
+//!   L2/L3 image of the book tagged with the last applied sequence number.
+//!   A late joiner buffers the live feed, waits for a snapshot, and then
+//!   applies only live messages with `seq > last_seq`.
+//!
+//! Both services are polled from the publishing thread, so the ring needs
+//! no locking.
+//!
+//! ## Request Format
+//!
+//! Requests use the MoldUDP64 header layout:
+//!
+//! ```text
+//! | session [u8;10] | first sequence u64 | count u16 |
+//! ```
+//!
+//! The server answers with one downstream packet holding as many of the
+//! requested messages as fit. If the range has already been evicted, it
+//! answers with an empty packet whose sequence is the oldest retained
+//! message, telling the client to fall back to a snapshot.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::arena::Arena;
+use crate::command::Side;
+use crate::itch::{
+    session_bytes, DecodeError, ItchMessage, MoldPacket, MoldPacketizer, DEFAULT_MAX_PACKET,
+    MOLD_HEADER_LEN, SESSION_LEN,
+};
+use crate::order_book::OrderBook;
+
+// ============================================================================
+// Retransmission Ring
+// ============================================================================
+
+/// Fixed-capacity ring of recently published messages, indexed by sequence.
+pub struct RetransmitBuffer {
+    slots: Vec<Option<ItchMessage>>,
+    /// Oldest retained sequence number
+    first_seq: u64,
+    /// Sequence number the next pushed message is expected to have
+    next_seq: u64,
+}
+
+impl RetransmitBuffer {
+    /// Create a ring retaining the last `capacity` messages.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Capacity must be non-zero");
+        Self {
+            slots: vec![None; capacity],
+            first_seq: 1,
+            next_seq: 1,
+        }
+    }
+
+    /// Record a published message.
+    ///
+    /// Sequence numbers are expected to be contiguous; a discontinuity
+    /// discards the retained history.
+    pub fn push(&mut self, seq: u64, msg: ItchMessage) {
+        if seq != self.next_seq {
+            self.slots.iter_mut().for_each(|s| *s = None);
+            self.first_seq = seq;
+        }
+        let capacity = self.slots.len() as u64;
+        if seq - self.first_seq >= capacity {
+            self.first_seq = seq + 1 - capacity;
+        }
+        self.slots[(seq % capacity) as usize] = Some(msg);
+        self.next_seq = seq + 1;
+    }
+
+    /// Look up a retained message by sequence number.
+    #[inline]
+    pub fn get(&self, seq: u64) -> Option<&ItchMessage> {
+        if seq < self.first_seq || seq >= self.next_seq {
+            return None;
+        }
+        self.slots[(seq % self.slots.len() as u64) as usize].as_ref()
+    }
+
+    /// Oldest retained sequence number
+    #[inline]
+    pub fn first_seq(&self) -> u64 {
+        self.first_seq
+    }
+
+    /// Sequence number after the newest retained message
+    #[inline]
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Number of retained messages
+    #[inline]
+    pub fn len(&self) -> usize {
+        (self.next_seq - self.first_seq) as usize
+    }
+
+    /// Returns true if nothing has been retained
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.next_seq == self.first_seq
+    }
+
+    /// Maximum number of retained messages
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+}
+
+// ============================================================================
+// Retransmission Server / Client
+// ============================================================================
+
+/// Serves retransmission requests from a `RetransmitBuffer`.
+pub struct RetransmitServer {
+    socket: UdpSocket,
+    session: [u8; SESSION_LEN],
+    packetizer: MoldPacketizer,
+    request_buf: [u8; 64],
+}
+
+impl RetransmitServer {
+    /// Bind a non-blocking request socket for `session`.
+    pub fn bind<A: ToSocketAddrs>(addr: A, session: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            session: session_bytes(session),
+            packetizer: MoldPacketizer::new(session, DEFAULT_MAX_PACKET),
+            request_buf: [0u8; 64],
+        })
+    }
+
+    /// Local socket address (useful when bound to port 0)
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Answer all pending requests without blocking.
+    ///
+    /// Returns the number of requests served. Malformed requests and
+    /// requests for other sessions are ignored.
+    pub fn poll(&mut self, history: &RetransmitBuffer) -> io::Result<usize> {
+        let mut served = 0;
+        loop {
+            let (n, from) = match self.socket.recv_from(&mut self.request_buf) {
+                Ok(r) => r,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(served),
+                Err(e) => return Err(e),
+            };
+            let request = match MoldPacket::parse(&self.request_buf[..n]) {
+                Ok(p) if n == MOLD_HEADER_LEN && p.session == self.session => p,
+                _ => continue,
+            };
+            self.serve(request.sequence, request.count, from, history)?;
+            served += 1;
+        }
+    }
+
+    fn serve(
+        &mut self,
+        seq: u64,
+        count: u16,
+        to: SocketAddr,
+        history: &RetransmitBuffer,
+    ) -> io::Result<()> {
+        // Evicted or future ranges get an empty packet pointing at what we have
+        if seq < history.first_seq() {
+            self.packetizer.restart_at(history.first_seq());
+        } else if seq >= history.next_seq() {
+            self.packetizer.restart_at(history.next_seq());
+        } else {
+            self.packetizer.restart_at(seq);
+            let end = history.next_seq().min(seq + count as u64);
+            for s in seq..end {
+                let msg = history.get(s).expect("sequence within retained range");
+                if !self.packetizer.try_push(msg) {
+                    break;
+                }
+            }
+        }
+        self.socket.send_to(self.packetizer.packet(), to)?;
+        Ok(())
+    }
+}
+
+/// Requests retransmissions from a `RetransmitServer`.
+pub struct RetransmitClient {
+    socket: UdpSocket,
+    session: [u8; SESSION_LEN],
+    buf: Vec<u8>,
+}
+
+impl RetransmitClient {
+    /// Bind a client socket and connect it to `server`.
+    pub fn connect<A: ToSocketAddrs, B: ToSocketAddrs>(
+        bind_addr: A,
+        server: B,
+        session: &str,
+    ) -> io::Result<Self> {
+        let socket = UdpSocket::bind(bind_addr)?;
+        socket.connect(server)?;
+        Ok(Self {
+            socket,
+            session: session_bytes(session),
+            buf: vec![0u8; 65536],
+        })
+    }
+
+    /// Underlying socket (e.g. to set timeouts)
+    #[inline]
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Request `count` messages starting at `seq` and block for the reply.
+    ///
+    /// Appends the returned `(sequence, message)` pairs to `out` and returns
+    /// the reply's first sequence number and message count. A reply with
+    /// zero messages and `sequence > seq` means the range was evicted.
+    pub fn fetch(
+        &mut self,
+        seq: u64,
+        count: u16,
+        out: &mut Vec<(u64, ItchMessage)>,
+    ) -> io::Result<(u64, u16)> {
+        let mut request = [0u8; MOLD_HEADER_LEN];
+        request[..SESSION_LEN].copy_from_slice(&self.session);
+        request[SESSION_LEN..SESSION_LEN + 8].copy_from_slice(&seq.to_be_bytes());
+        request[SESSION_LEN + 8..].copy_from_slice(&count.to_be_bytes());
+        self.socket.send(&request)?;
+
+        let n = self.socket.recv(&mut self.buf)?;
+        let packet = MoldPacket::parse(&self.buf[..n]).map_err(invalid_data)?;
+        for item in packet.messages() {
+            out.push(item.map_err(invalid_data)?);
+        }
+        Ok((packet.sequence, packet.count))
+    }
+}
+
+fn invalid_data(e: DecodeError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+// ============================================================================
+// Sequence Tracking
+// ============================================================================
+
+/// Result of checking a packet against the expected sequence number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeqCheck {
+    /// Packet continues the stream (or overlaps it); apply messages `>= expected`
+    InOrder,
+    /// Messages `[first_missing, first_missing + count)` were skipped
+    Gap { first_missing: u64, count: u64 },
+    /// Every message in the packet has already been seen
+    Stale,
+}
+
+/// Receive-side sequence number tracker for detecting gaps.
+#[derive(Clone, Copy, Debug)]
+pub struct SequenceTracker {
+    expected: u64,
+}
+
+impl SequenceTracker {
+    /// Expect the stream to start at sequence 1.
+    pub fn new() -> Self {
+        Self { expected: 1 }
+    }
+
+    /// Expect the stream to resume at `seq` (e.g. after a snapshot).
+    pub fn starting_at(seq: u64) -> Self {
+        Self { expected: seq }
+    }
+
+    /// Next sequence number expected
+    #[inline]
+    pub fn expected(&self) -> u64 {
+        self.expected
+    }
+
+    /// Check a packet's first sequence and message count, advancing the
+    /// expected sequence past it.
+    pub fn on_packet(&mut self, first_seq: u64, count: u16) -> SeqCheck {
+        let end = first_seq + count as u64;
+        let check = if end <= self.expected {
+            SeqCheck::Stale
+        } else if first_seq > self.expected {
+            SeqCheck::Gap {
+                first_missing: self.expected,
+                count: first_seq - self.expected,
+            }
+        } else {
+            SeqCheck::InOrder
+        };
+        self.expected = self.expected.max(end);
+        check
+    }
+}
+
+impl Default for SequenceTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// Book Snapshots
+// ============================================================================
+
+/// Aggregated state of one price level
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LevelSnapshot {
+    pub side: Side,
+    pub price: u64,
+    pub qty: u64,
+    pub count: u32,
+}
+
+/// A resting order in a snapshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OrderSnapshot {
+    pub order_id: u64,
+    pub side: Side,
+    pub price: u64,
+    pub qty: u32,
+}
+
+/// Full L2/L3 image of the book as of sequence `last_seq`.
+///
+/// Levels and orders are listed bids first, best price first; orders
+/// within a level are in time priority.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BookSnapshot {
+    /// Sequence number of the last feed message reflected in this image
+    pub last_seq: u64,
+    pub levels: Vec<LevelSnapshot>,
+    pub orders: Vec<OrderSnapshot>,
+}
+
+impl BookSnapshot {
+    /// Capture the current book state.
+    pub fn capture(book: &OrderBook, arena: &Arena, last_seq: u64) -> Self {
+        let mut snapshot = Self {
+            last_seq,
+            levels: Vec::with_capacity(book.bid_levels() + book.ask_levels()),
+            orders: Vec::with_capacity(book.order_count()),
+        };
+
+        for side in [Side::Bid, Side::Ask] {
+            for level in book.levels(side) {
+                snapshot.levels.push(LevelSnapshot {
+                    side,
+                    price: level.price,
+                    qty: level.qty,
+                    count: level.count,
+                });
+                for node in book.level_orders(arena, side, level.price) {
+                    snapshot.orders.push(OrderSnapshot {
+                        order_id: node.order_id,
+                        side,
+                        price: level.price,
+                        qty: node.qty,
+                    });
+                }
+            }
+        }
+
+        snapshot
+    }
+
+    /// Messages that make up this snapshot on the wire, in order.
+    pub fn messages(&self) -> impl Iterator<Item = ItchMessage> + '_ {
+        let start = ItchMessage::SnapshotStart {
+            last_seq: self.last_seq,
+            level_count: self.levels.len() as u32,
+            order_count: self.orders.len() as u32,
+        };
+        let levels = self.levels.iter().map(|l| ItchMessage::LevelUpdate {
+            side: l.side,
+            price: l.price,
+            qty: l.qty,
+            count: l.count,
+        });
+        let orders = self.orders.iter().map(|o| ItchMessage::AddOrder {
+            order_id: o.order_id,
+            side: o.side,
+            price: o.price,
+            qty: o.qty,
+        });
+        let end = ItchMessage::SnapshotEnd { last_seq: self.last_seq };
+
+        std::iter::once(start)
+            .chain(levels)
+            .chain(orders)
+            .chain(std::iter::once(end))
+    }
+}
+
+/// Periodically publishes book snapshots on their own MoldUDP64 session.
+pub struct SnapshotPublisher {
+    socket: UdpSocket,
+    packetizer: MoldPacketizer,
+    interval: Duration,
+    last_published: Option<Instant>,
+}
+
+impl SnapshotPublisher {
+    /// Bind to `bind_addr` and send snapshots to `dest_addr` every `interval`.
+    pub fn new<A: ToSocketAddrs, B: ToSocketAddrs>(
+        bind_addr: A,
+        dest_addr: B,
+        session: &str,
+        interval: Duration,
+    ) -> io::Result<Self> {
+        let socket = UdpSocket::bind(bind_addr)?;
+        socket.connect(dest_addr)?;
+        Ok(Self {
+            socket,
+            packetizer: MoldPacketizer::new(session, DEFAULT_MAX_PACKET),
+            interval,
+            last_published: None,
+        })
+    }
+
+    /// Publish a snapshot if the interval has elapsed since the last one.
+    ///
+    /// Returns true if a snapshot was sent.
+    pub fn maybe_publish(&mut self, book: &OrderBook, arena: &Arena, last_seq: u64) -> io::Result<bool> {
+        let due = self.last_published.is_none_or(|t| t.elapsed() >= self.interval);
+        if due {
+            self.publish(&BookSnapshot::capture(book, arena, last_seq))?;
+        }
+        Ok(due)
+    }
+
+    /// Publish a snapshot immediately.
+    pub fn publish(&mut self, snapshot: &BookSnapshot) -> io::Result<()> {
+        for msg in snapshot.messages() {
+            if !self.packetizer.try_push(&msg) {
+                self.flush()?;
+                self.packetizer.try_push(&msg);
+            }
+        }
+        self.flush()?;
+        self.last_published = Some(Instant::now());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.packetizer.is_empty() {
+            self.socket.send(self.packetizer.packet())?;
+            self.packetizer.reset();
+        }
+        Ok(())
+    }
+}
+
+/// Reassembles snapshots from the snapshot channel's message stream.
+///
+/// A gap on the snapshot channel discards the partial snapshot; assembly
+/// resumes at the next `SnapshotStart`.
+#[derive(Debug, Default)]
+pub struct SnapshotAssembler {
+    tracker: SequenceTracker,
+    pending: Option<(BookSnapshot, u32, u32)>,
+}
+
+impl SnapshotAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one `(sequence, message)` pair from the snapshot channel.
+    ///
+    /// Returns the snapshot once its `SnapshotEnd` arrives intact.
+    pub fn push(&mut self, seq: u64, msg: ItchMessage) -> Option<BookSnapshot> {
+        match self.tracker.on_packet(seq, 1) {
+            SeqCheck::Stale => return None,
+            SeqCheck::Gap { .. } => self.pending = None,
+            SeqCheck::InOrder => {}
+        }
+
+        match msg {
+            ItchMessage::SnapshotStart { last_seq, level_count, order_count } => {
+                let snapshot = BookSnapshot {
+                    last_seq,
+                    levels: Vec::with_capacity(level_count as usize),
+                    orders: Vec::with_capacity(order_count as usize),
+                };
+                self.pending = Some((snapshot, level_count, order_count));
+            }
+            ItchMessage::LevelUpdate { side, price, qty, count } => {
+                if let Some((snapshot, _, _)) = self.pending.as_mut() {
+                    snapshot.levels.push(LevelSnapshot { side, price, qty, count });
+                }
+            }
+            ItchMessage::AddOrder { order_id, side, price, qty } => {
+                if let Some((snapshot, _, _)) = self.pending.as_mut() {
+                    snapshot.orders.push(OrderSnapshot { order_id, side, price, qty });
+                }
+            }
+            ItchMessage::SnapshotEnd { last_seq } => {
+                let (snapshot, levels, orders) = self.pending.take()?;
+                let complete = snapshot.last_seq == last_seq
+                    && snapshot.levels.len() == levels as usize
+                    && snapshot.orders.len() == orders as usize;
+                return complete.then_some(snapshot);
+            }
+            ItchMessage::OrderExecuted { .. } | ItchMessage::OrderCancel { .. } | ItchMessage::OrderDelete { .. } => {
+                // Not part of a snapshot; treat as corruption
+                self.pending = None;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{CancelOrder, Command, PlaceOrder};
+    use crate::engine::Engine;
+    use crate::itch::{UdpPublisher, UdpReceiver};
+    use std::collections::BTreeMap;
+
+    fn add(order_id: u64) -> ItchMessage {
+        ItchMessage::AddOrder { order_id, side: Side::Bid, price: 100, qty: 1 }
+    }
+
+    #[test]
+    fn test_retransmit_buffer_eviction() {
+        let mut buffer = RetransmitBuffer::new(3);
+        assert!(buffer.is_empty());
+
+        for seq in 1..=5 {
+            buffer.push(seq, add(seq));
+        }
+
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.first_seq(), 3);
+        assert_eq!(buffer.next_seq(), 6);
+        assert!(buffer.get(2).is_none());
+        assert_eq!(buffer.get(3), Some(&add(3)));
+        assert_eq!(buffer.get(5), Some(&add(5)));
+        assert!(buffer.get(6).is_none());
+    }
+
+    #[test]
+    fn test_retransmit_buffer_discontinuity_resets() {
+        let mut buffer = RetransmitBuffer::new(8);
+        buffer.push(1, add(1));
+        buffer.push(2, add(2));
+        buffer.push(10, add(10));
+
+        assert_eq!(buffer.first_seq(), 10);
+        assert_eq!(buffer.len(), 1);
+        assert!(buffer.get(1).is_none());
+    }
+
+    #[test]
+    fn test_sequence_tracker() {
+        let mut tracker = SequenceTracker::new();
+        assert_eq!(tracker.on_packet(1, 3), SeqCheck::InOrder);
+        assert_eq!(tracker.on_packet(4, 0), SeqCheck::Stale);
+        assert_eq!(tracker.on_packet(7, 2), SeqCheck::Gap { first_missing: 4, count: 3 });
+        assert_eq!(tracker.expected(), 9);
+        assert_eq!(tracker.on_packet(4, 3), SeqCheck::Stale);
+        assert_eq!(tracker.on_packet(8, 2), SeqCheck::InOrder);
+        assert_eq!(tracker.expected(), 10);
+    }
+
+    #[test]
+    fn test_retransmit_roundtrip() {
+        let mut receiver = UdpReceiver::bind("127.0.0.1:0").unwrap();
+        let dest = receiver.socket().local_addr().unwrap();
+        let mut publisher = UdpPublisher::new("127.0.0.1:0", dest, "RETX")
+            .unwrap()
+            .with_history(4);
+
+        let mut engine = Engine::new(100);
+        for i in 0..3 {
+            let events = engine.process_command(Command::Place(PlaceOrder::limit(i, 1, Side::Bid, 100 + i, 10)));
+            publisher.publish(events).unwrap();
+        }
+        // Six messages published, only the last four retained
+        assert_eq!(publisher.last_seq(), 6);
+
+        // Drain the live feed; pretend it was lost
+        let mut live = Vec::new();
+        for _ in 0..3 {
+            receiver.recv_into(&mut live).unwrap();
+        }
+
+        let mut server = RetransmitServer::bind("127.0.0.1:0", "RETX").unwrap();
+        let mut client = RetransmitClient::connect("127.0.0.1:0", server.local_addr().unwrap(), "RETX").unwrap();
+        client.socket().set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let history = publisher.history().unwrap();
+
+        let fetch = |client: &mut RetransmitClient, server: &mut RetransmitServer, seq, count| {
+            let mut out = Vec::new();
+            let request = std::thread::scope(|s| {
+                let handle = s.spawn(|| client.fetch(seq, count, &mut out));
+                while !handle.is_finished() {
+                    server.poll(history).unwrap();
+                }
+                handle.join().unwrap().unwrap()
+            });
+            (request, out)
+        };
+
+        let ((seq, count), out) = fetch(&mut client, &mut server, 4, 10);
+        assert_eq!((seq, count), (4, 3));
+        assert_eq!(out, live[3..].to_vec());
+
+        // Evicted range points the client at the oldest retained message
+        let ((seq, count), out) = fetch(&mut client, &mut server, 1, 2);
+        assert_eq!((seq, count), (3, 0));
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_capture_preserves_priority() {
+        let mut engine = Engine::new(100);
+        engine.process_command(Command::Place(PlaceOrder::limit(1, 1, Side::Bid, 100, 10)));
+        engine.process_command(Command::Place(PlaceOrder::limit(2, 1, Side::Bid, 100, 20)));
+        engine.process_command(Command::Place(PlaceOrder::limit(3, 1, Side::Bid, 101, 5)));
+        engine.process_command(Command::Place(PlaceOrder::limit(4, 1, Side::Ask, 105, 7)));
+
+        let snapshot = BookSnapshot::capture(&engine.matcher.book, &engine.matcher.arena, 99);
+        assert_eq!(snapshot.last_seq, 99);
+        assert_eq!(
+            snapshot.levels,
+            vec![
+                LevelSnapshot { side: Side::Bid, price: 101, qty: 5, count: 1 },
+                LevelSnapshot { side: Side::Bid, price: 100, qty: 30, count: 2 },
+                LevelSnapshot { side: Side::Ask, price: 105, qty: 7, count: 1 },
+            ]
+        );
+        let ids: Vec<_> = snapshot.orders.iter().map(|o| o.order_id).collect();
+        assert_eq!(ids, vec![3, 1, 2, 4]);
+    }
+
+    #[test]
+    fn test_snapshot_assembler_discards_on_gap() {
+        let mut engine = Engine::new(100);
+        engine.process_command(Command::Place(PlaceOrder::limit(1, 1, Side::Bid, 100, 10)));
+        let snapshot = BookSnapshot::capture(&engine.matcher.book, &engine.matcher.arena, 2);
+        let messages: Vec<_> = snapshot.messages().collect();
+        assert_eq!(messages.len(), 4);
+
+        let mut assembler = SnapshotAssembler::new();
+        // Drop the level message of the first copy
+        assert!(assembler.push(1, messages[0]).is_none());
+        assert!(assembler.push(3, messages[2]).is_none());
+        assert!(assembler.push(4, messages[3]).is_none());
+
+        // Second copy arrives intact
+        let mut result = None;
+        for (i, msg) in messages.iter().enumerate() {
+            result = assembler.push(5 + i as u64, *msg);
+        }
+        assert_eq!(result, Some(snapshot));
+    }
+
+    /// Minimal L2 book applied from feed messages
+    fn apply(levels: &mut BTreeMap<(u8, u64), (u64, u32)>, msg: &ItchMessage) {
+        if let ItchMessage::LevelUpdate { side, price, qty, count } = *msg {
+            if qty == 0 {
+                levels.remove(&(side as u8, price));
+            } else {
+                levels.insert((side as u8, price), (qty, count));
+            }
+        }
+    }
+
+    #[test]
+    fn test_late_joiner_syncs_from_snapshot() {
+        let mut receiver = UdpReceiver::bind("127.0.0.1:0").unwrap();
+        receiver.socket().set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut snap_receiver = UdpReceiver::bind("127.0.0.1:0").unwrap();
+        snap_receiver.socket().set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut publisher = UdpPublisher::new("127.0.0.1:0", receiver.socket().local_addr().unwrap(), "LIVE").unwrap();
+        let mut snapshots = SnapshotPublisher::new(
+            "127.0.0.1:0",
+            snap_receiver.socket().local_addr().unwrap(),
+            "SNAP",
+            Duration::from_secs(3600),
+        )
+        .unwrap();
+
+        let mut engine = Engine::new(100);
+        let commands = [
+            Command::Place(PlaceOrder::limit(1, 1, Side::Bid, 100, 10)),
+            Command::Place(PlaceOrder::limit(2, 1, Side::Ask, 102, 10)),
+            Command::Place(PlaceOrder::limit(3, 2, Side::Ask, 100, 4)),
+        ];
+        for cmd in commands {
+            let events = engine.process_command(cmd);
+            publisher.publish(events).unwrap();
+        }
+        assert!(snapshots.maybe_publish(&engine.matcher.book, &engine.matcher.arena, publisher.last_seq()).unwrap());
+        assert!(!snapshots.maybe_publish(&engine.matcher.book, &engine.matcher.arena, publisher.last_seq()).unwrap());
+
+        let later = [
+            Command::Place(PlaceOrder::limit(4, 1, Side::Bid, 101, 3)),
+            Command::Cancel(CancelOrder { order_id: 2 }),
+        ];
+        for cmd in later {
+            let events = engine.process_command(cmd);
+            publisher.publish(events).unwrap();
+        }
+
+        // Late joiner: everything on the live feed is buffered, snapshot decides the cut
+        let mut live = Vec::new();
+        for _ in 0..(commands.len() + later.len()) {
+            receiver.recv_into(&mut live).unwrap();
+        }
+        let mut assembler = SnapshotAssembler::new();
+        let mut snap_msgs = Vec::new();
+        snap_receiver.recv_into(&mut snap_msgs).unwrap();
+        let snapshot = snap_msgs
+            .into_iter()
+            .find_map(|(seq, msg)| assembler.push(seq, msg))
+            .expect("complete snapshot");
+
+        let mut levels = BTreeMap::new();
+        for l in &snapshot.levels {
+            apply(&mut levels, &ItchMessage::LevelUpdate { side: l.side, price: l.price, qty: l.qty, count: l.count });
+        }
+        for (_, msg) in live.iter().filter(|(seq, _)| *seq > snapshot.last_seq) {
+            apply(&mut levels, msg);
+        }
+
+        let expected: BTreeMap<_, _> = engine.matcher.book.bids.iter().map(|(p, l)| ((Side::Bid as u8, p), (l.total_qty, l.count)))
+            .chain(engine.matcher.book.asks.iter().map(|(p, l)| ((Side::Ask as u8, p), (l.total_qty, l.count))))
+            .collect();
+        assert_eq!(levels, expected);
+    }
+}
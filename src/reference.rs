@@ -0,0 +1,314 @@
+//! Reference Matcher - A slow but obviously-correct model of the engine.
+//!
+//! Resting orders live in one `Vec` in arrival order. Each fill scans it for
+//! the best-priced crossing maker (earliest first on ties), and level totals
+//! are recomputed by summing. No arena, maps or intrusive lists, so the
+//! model can be checked by reading it; differential tests run the same
+//! commands through `Engine` and compare the full event streams.
+//!
+//! It mirrors the engine's default tree backend with `EngineConfig::default()`
+//! output: no delta conflation or BBO events, and no `ArenaHighWater`
+//! warnings. `OrderAccepted::handle` is always the default handle; callers
+//! pass the engine's handles to `bind_handle`, and a handle command is
+//! rejected with `OrderNotFound` unless its handle was bound to the order's
+//! current placement (a handle goes stale once its order leaves the book,
+//! even if the ID is placed again).
+
+use std::collections::HashMap;
+
+use crate::command::{
+    BookUpdate, CancelOrder, Command, ModifyOrder, OrderAccepted, OrderCanceled, OrderHandle, OrderRejected,
+    OrderType, OutputEvent, PlaceOrder, RejectReason, Side, TradeEvent,
+};
+
+/// A resting order
+#[derive(Clone, Copy, Debug)]
+struct RestingOrder {
+    order_id: u64,
+    user_id: u64,
+    side: Side,
+    price: u64,
+    qty: u32,
+    /// Unique per accepted order, so a re-placed ID is told apart
+    placement: u64,
+}
+
+/// Naive order book and matcher producing the engine's event stream
+#[derive(Clone, Debug)]
+pub struct ReferenceMatcher {
+    /// Resting orders, oldest first
+    orders: Vec<RestingOrder>,
+    /// Most orders that may rest at once (the engine's arena capacity)
+    capacity: usize,
+    /// Placement each bound handle was issued for
+    handles: HashMap<OrderHandle, u64>,
+    next_placement: u64,
+}
+
+impl ReferenceMatcher {
+    pub fn new(capacity: usize) -> Self {
+        Self { orders: Vec::new(), capacity, handles: HashMap::new(), next_placement: 0 }
+    }
+
+    /// Apply a command, returning the events `Engine::process_command` emits
+    pub fn process(&mut self, cmd: Command) -> Vec<OutputEvent> {
+        let mut events = Vec::new();
+        match cmd {
+            Command::Place(order) => self.place(order, &mut events),
+            Command::Cancel(cancel) => {
+                self.cancel(cancel, &mut events);
+            }
+            Command::CancelByHandle(cancel, handle) => {
+                if self.is_current(cancel.order_id, handle) {
+                    self.cancel(cancel, &mut events);
+                } else {
+                    Self::reject(cancel.order_id, RejectReason::OrderNotFound, &mut events);
+                }
+            }
+            Command::Modify(modify) => self.modify(modify, &mut events),
+            Command::ModifyByHandle(modify, handle) => {
+                if self.is_current(modify.order_id, handle) {
+                    self.modify(modify, &mut events);
+                } else {
+                    Self::reject(modify.order_id, RejectReason::OrderNotFound, &mut events);
+                }
+            }
+        }
+        events
+    }
+
+    /// Record that the engine issued `handle` for the resting `order_id`.
+    /// Call after the command whose `OrderAccepted` carried the handle.
+    pub fn bind_handle(&mut self, handle: OrderHandle, order_id: u64) {
+        if let Some(order) = self.orders.iter().find(|o| o.order_id == order_id) {
+            self.handles.insert(handle, order.placement);
+        }
+    }
+
+    /// Whether `handle` was bound to the order now resting as `order_id`
+    fn is_current(&self, order_id: u64, handle: OrderHandle) -> bool {
+        let placement = self.handles.get(&handle);
+        self.orders.iter().any(|o| o.order_id == order_id && Some(&o.placement) == placement)
+    }
+
+    pub fn best_bid(&self) -> Option<u64> {
+        self.orders.iter().filter(|o| o.side == Side::Bid).map(|o| o.price).max()
+    }
+
+    pub fn best_ask(&self) -> Option<u64> {
+        self.orders.iter().filter(|o| o.side == Side::Ask).map(|o| o.price).min()
+    }
+
+    /// Total quantity and order count at a price
+    pub fn depth_at(&self, side: Side, price: u64) -> (u64, u32) {
+        self.orders
+            .iter()
+            .filter(|o| o.side == side && o.price == price)
+            .fold((0, 0), |(qty, count), o| (qty + o.qty as u64, count + 1))
+    }
+
+    pub fn order_count(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn contains_order(&self, order_id: u64) -> bool {
+        self.orders.iter().any(|o| o.order_id == order_id)
+    }
+
+    /// Resting quantity of an order
+    pub fn order_qty(&self, order_id: u64) -> Option<u32> {
+        self.orders.iter().find(|o| o.order_id == order_id).map(|o| o.qty)
+    }
+
+    /// Whether a taker at `price` on `side` would trade with a maker at `maker_price`
+    fn crosses(side: Side, price: u64, maker_price: u64) -> bool {
+        match side {
+            Side::Bid => price >= maker_price,
+            Side::Ask => price <= maker_price,
+        }
+    }
+
+    /// Position of the maker with the best price for `taker`, earliest first
+    fn best_maker(&self, taker: &PlaceOrder) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for (i, o) in self.orders.iter().enumerate() {
+            if o.side == taker.side || !Self::crosses(taker.side, taker.price, o.price) {
+                continue;
+            }
+            let better = match best {
+                None => true,
+                Some(b) => match taker.side {
+                    Side::Bid => o.price < self.orders[b].price,
+                    Side::Ask => o.price > self.orders[b].price,
+                },
+            };
+            if better {
+                best = Some(i);
+            }
+        }
+        best
+    }
+
+    fn reject(order_id: u64, reason: RejectReason, events: &mut Vec<OutputEvent>) {
+        events.push(OutputEvent::Rejected(OrderRejected { order_id, reason }));
+    }
+
+    fn delta(&self, side: Side, price: u64, events: &mut Vec<OutputEvent>) {
+        let (new_qty, new_count) = self.depth_at(side, price);
+        events.push(OutputEvent::BookDelta(BookUpdate { side, price, new_qty, new_count }));
+    }
+
+    fn place(&mut self, order: PlaceOrder, events: &mut Vec<OutputEvent>) {
+        if order.qty == 0 {
+            return Self::reject(order.order_id, RejectReason::InvalidQuantity, events);
+        }
+        if self.contains_order(order.order_id) {
+            return Self::reject(order.order_id, RejectReason::DuplicateOrderId, events);
+        }
+        if order.order_type == OrderType::FOK {
+            let available: u64 = self
+                .orders
+                .iter()
+                .filter(|o| o.side != order.side && Self::crosses(order.side, order.price, o.price))
+                .map(|o| o.qty as u64)
+                .sum();
+            if available < order.qty as u64 {
+                return Self::reject(order.order_id, RejectReason::InsufficientLiquidity, events);
+            }
+        }
+
+        let mut remaining = order.qty;
+        while remaining > 0 {
+            let Some(i) = self.best_maker(&order) else { break };
+            let maker = self.orders[i];
+            let qty = remaining.min(maker.qty);
+            events.push(OutputEvent::Trade(TradeEvent {
+                price: maker.price,
+                qty,
+                maker_order_id: maker.order_id,
+                taker_order_id: order.order_id,
+                maker_user_id: maker.user_id,
+                taker_user_id: order.user_id,
+                taker_side: order.side,
+            }));
+            remaining -= qty;
+            if qty == maker.qty {
+                self.orders.remove(i);
+            } else {
+                self.orders[i].qty -= qty;
+            }
+            self.delta(maker.side, maker.price, events);
+        }
+
+        if remaining == 0 || order.order_type != OrderType::Limit {
+            return;
+        }
+        if self.orders.len() >= self.capacity {
+            return Self::reject(order.order_id, RejectReason::ArenaFull, events);
+        }
+        self.orders.push(RestingOrder {
+            order_id: order.order_id,
+            user_id: order.user_id,
+            side: order.side,
+            price: order.price,
+            qty: remaining,
+            placement: self.next_placement,
+        });
+        self.next_placement += 1;
+        events.push(OutputEvent::Accepted(OrderAccepted {
+            order_id: order.order_id,
+            price: order.price,
+            qty: remaining,
+            side: order.side,
+            handle: OrderHandle::default(),
+        }));
+        self.delta(order.side, order.price, events);
+    }
+
+    /// Cancel an order, returning it if it was resting
+    fn cancel(&mut self, cancel: CancelOrder, events: &mut Vec<OutputEvent>) -> Option<RestingOrder> {
+        let Some(i) = self.orders.iter().position(|o| o.order_id == cancel.order_id) else {
+            Self::reject(cancel.order_id, RejectReason::OrderNotFound, events);
+            return None;
+        };
+        let order = self.orders.remove(i);
+        events.push(OutputEvent::Canceled(OrderCanceled { order_id: order.order_id, canceled_qty: order.qty }));
+        self.delta(order.side, order.price, events);
+        Some(order)
+    }
+
+    /// Cancel, then place the replacement as a limit order on the same side
+    fn modify(&mut self, modify: ModifyOrder, events: &mut Vec<OutputEvent>) {
+        if let Some(original) = self.cancel(CancelOrder { order_id: modify.order_id }, events) {
+            self.place(
+                PlaceOrder {
+                    order_id: modify.new_order_id,
+                    user_id: original.user_id,
+                    side: original.side,
+                    price: modify.new_price,
+                    qty: modify.new_qty,
+                    order_type: OrderType::Limit,
+                },
+                events,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::ArenaHandle;
+
+    #[test]
+    fn test_price_time_priority() {
+        let mut reference = ReferenceMatcher::new(10);
+        reference.process(Command::Place(PlaceOrder::limit(1, 1, Side::Ask, 101, 5)));
+        reference.process(Command::Place(PlaceOrder::limit(2, 1, Side::Ask, 100, 5)));
+        reference.process(Command::Place(PlaceOrder::limit(3, 1, Side::Ask, 100, 5)));
+
+        let events = reference.process(Command::Place(PlaceOrder::limit(4, 2, Side::Bid, 101, 12)));
+        let makers: Vec<_> = events
+            .iter()
+            .filter_map(|e| if let OutputEvent::Trade(t) = e { Some((t.maker_order_id, t.qty)) } else { None })
+            .collect();
+        assert_eq!(makers, [(2, 5), (3, 5), (1, 2)]);
+        assert_eq!(reference.depth_at(Side::Ask, 101), (3, 1));
+        assert_eq!(reference.best_bid(), None);
+    }
+
+    #[test]
+    fn test_fok_and_capacity() {
+        let mut reference = ReferenceMatcher::new(1);
+        reference.process(Command::Place(PlaceOrder::limit(1, 1, Side::Ask, 100, 5)));
+        let fok = PlaceOrder { order_type: OrderType::FOK, ..PlaceOrder::limit(2, 2, Side::Bid, 100, 6) };
+        assert!(matches!(
+            reference.process(Command::Place(fok))[..],
+            [OutputEvent::Rejected(OrderRejected { reason: RejectReason::InsufficientLiquidity, .. })]
+        ));
+        assert!(matches!(
+            reference.process(Command::Place(PlaceOrder::limit(3, 2, Side::Bid, 90, 6)))[..],
+            [OutputEvent::Rejected(OrderRejected { reason: RejectReason::ArenaFull, .. })]
+        ));
+    }
+
+    #[test]
+    fn test_stale_handles_rejected() {
+        let mut reference = ReferenceMatcher::new(10);
+        let first = OrderHandle::from(ArenaHandle { index: 0, generation: 1 });
+        let second = OrderHandle::from(ArenaHandle { index: 0, generation: 3 });
+        reference.process(Command::Place(PlaceOrder::limit(1, 1, Side::Ask, 100, 5)));
+        reference.bind_handle(first, 1);
+        reference.process(Command::Cancel(CancelOrder { order_id: 1 }));
+        reference.process(Command::Place(PlaceOrder::limit(1, 1, Side::Ask, 100, 5)));
+        reference.bind_handle(second, 1);
+
+        // The first placement's handle is stale though ID 1 rests again
+        let cancel = CancelOrder { order_id: 1 };
+        let rejected = [OutputEvent::Rejected(OrderRejected { order_id: 1, reason: RejectReason::OrderNotFound })];
+        assert_eq!(reference.process(Command::CancelByHandle(cancel, first)), rejected);
+        assert_eq!(reference.process(Command::CancelByHandle(cancel, OrderHandle::default())), rejected);
+        assert!(matches!(reference.process(Command::CancelByHandle(cancel, second))[0], OutputEvent::Canceled(_)));
+        assert_eq!(reference.order_count(), 0);
+    }
+}
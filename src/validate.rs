@@ -0,0 +1,226 @@
+//! Validate - Book invariant checker.
+//!
+//! `MatchingEngine::validate` walks every price level and cross-checks the
+//! intrusive lists, level totals, order map and arena. It is O(orders) and
+//! meant for tests and debugging; with the `validate` feature, `Engine`
+//! also runs it periodically while processing commands.
+
+use std::fmt;
+
+use crate::arena::{ArenaIndex, NULL_INDEX};
+use crate::backend::BookBackend;
+use crate::command::Side;
+use crate::matching::MatchingEngine;
+
+/// A broken book invariant, as found by `MatchingEngine::validate`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// A level with no orders is still in the book
+    EmptyLevel { side: Side, price: u64 },
+    /// `prev`/`next` links, head or tail disagree at a node
+    BrokenLink { side: Side, price: u64, index: ArenaIndex },
+    /// A node in a level is free, has another price, or has zero quantity
+    BadNode { side: Side, price: u64, index: ArenaIndex },
+    /// A level's `count` or `total_qty` differs from its orders
+    LevelTotals { side: Side, price: u64, count: u32, total_qty: u64 },
+    /// An order in a level has no matching order map entry
+    OrderMapMismatch { order_id: u64 },
+    /// Orders in levels, order map entries and allocated nodes disagree
+    CountMismatch { in_levels: usize, order_map: usize, allocated: u32 },
+    /// The cached best price differs from the best level
+    BestPriceMismatch { side: Side, cached: Option<u64>, actual: Option<u64> },
+    /// Best bid at or above best ask
+    CrossedBook { bid: u64, ask: u64 },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::EmptyLevel { side, price } => {
+                write!(f, "empty {:?} level at {}", side, price)
+            }
+            InvariantViolation::BrokenLink { side, price, index } => {
+                write!(f, "broken link at node {} in {:?} level {}", index, side, price)
+            }
+            InvariantViolation::BadNode { side, price, index } => {
+                write!(f, "bad node {} in {:?} level {}", index, side, price)
+            }
+            InvariantViolation::LevelTotals { side, price, count, total_qty } => write!(
+                f,
+                "{:?} level {} totals wrong: count {}, total_qty {}",
+                side, price, count, total_qty
+            ),
+            InvariantViolation::OrderMapMismatch { order_id } => {
+                write!(f, "order {} disagrees with the order map", order_id)
+            }
+            InvariantViolation::CountMismatch { in_levels, order_map, allocated } => write!(
+                f,
+                "{} orders in levels, {} in order map, {} allocated",
+                in_levels, order_map, allocated
+            ),
+            InvariantViolation::BestPriceMismatch { side, cached, actual } => write!(
+                f,
+                "best {:?} is {:?} but the best level is {:?}",
+                side, cached, actual
+            ),
+            InvariantViolation::CrossedBook { bid, ask } => {
+                write!(f, "crossed book: bid {} >= ask {}", bid, ask)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
+impl<B: BookBackend> MatchingEngine<B> {
+    /// Check the book's internal consistency.
+    ///
+    /// For every level: non-empty, symmetric `prev`/`next` links from head to
+    /// tail, nodes live at the level's price, and `count`/`total_qty` equal to
+    /// the sums over its orders. Every order must match its order map entry,
+    /// and orders in levels, the order map and allocated arena nodes must all
+    /// agree. Finally, the cached best prices must match the levels and the
+    /// book must not be crossed.
+    ///
+    /// # Complexity
+    /// O(orders + levels)
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        let mut in_levels = 0usize;
+        for side in [Side::Bid, Side::Ask] {
+            for (price, level) in self.book.level_range(side, ..) {
+                if level.count == 0 {
+                    return Err(InvariantViolation::EmptyLevel { side, price });
+                }
+                in_levels += self.validate_level(side, price, level.head, level.tail, level.count, level.total_qty)?;
+            }
+        }
+
+        let order_map = self.book.order_count();
+        let allocated = self.arena.allocated();
+        if in_levels != order_map || order_map != allocated as usize {
+            return Err(InvariantViolation::CountMismatch { in_levels, order_map, allocated });
+        }
+
+        let best_bid = self.book.level_range(Side::Bid, ..).next_back().map(|(p, _)| p);
+        let best_ask = self.book.level_range(Side::Ask, ..).next().map(|(p, _)| p);
+        for (side, cached, actual) in [(Side::Bid, self.book.best_bid(), best_bid), (Side::Ask, self.book.best_ask(), best_ask)] {
+            if cached != actual {
+                return Err(InvariantViolation::BestPriceMismatch { side, cached, actual });
+            }
+        }
+        if let (Some(bid), Some(ask)) = (best_bid, best_ask) {
+            if bid >= ask {
+                return Err(InvariantViolation::CrossedBook { bid, ask });
+            }
+        }
+        Ok(())
+    }
+
+    /// Walk one level's list, returning its number of orders
+    fn validate_level(
+        &self,
+        side: Side,
+        price: u64,
+        head: ArenaIndex,
+        tail: ArenaIndex,
+        count: u32,
+        total_qty: u64,
+    ) -> Result<usize, InvariantViolation> {
+        let broken = |index| InvariantViolation::BrokenLink { side, price, index };
+        let mut walked = 0u32;
+        let mut qty = 0u64;
+        let mut prev = NULL_INDEX;
+        let mut idx = head;
+
+        while idx != NULL_INDEX {
+            // More nodes than the level claims: a cycle or a miscounted level
+            if walked == count || idx >= self.arena.capacity() {
+                return Err(broken(idx));
+            }
+            let node = self.arena.get(idx);
+            if node.prev != prev {
+                return Err(broken(idx));
+            }
+            if !self.arena.is_live(idx) || node.price != price || node.qty == 0 {
+                return Err(InvariantViolation::BadNode { side, price, index: idx });
+            }
+            match self.book.get_order(node.order_id) {
+                Some(info) if info.side == side && info.price == price && info.handle == self.arena.handle(idx) => {}
+                _ => return Err(InvariantViolation::OrderMapMismatch { order_id: node.order_id }),
+            }
+
+            walked += 1;
+            qty += node.qty as u64;
+            prev = idx;
+            idx = node.next;
+        }
+
+        if prev != tail {
+            return Err(broken(tail));
+        }
+        if walked != count || qty != total_qty {
+            return Err(InvariantViolation::LevelTotals { side, price, count, total_qty });
+        }
+        Ok(walked as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{OutputEvent, PlaceOrder};
+
+    fn engine_with_orders() -> MatchingEngine {
+        let mut engine = MatchingEngine::new(100);
+        let mut events = Vec::new();
+        for (id, side, price) in [(1, Side::Bid, 99), (2, Side::Bid, 99), (3, Side::Bid, 98), (4, Side::Ask, 101)] {
+            engine.process_place(PlaceOrder::limit(id, 1, side, price, 10), &mut events);
+        }
+        engine
+    }
+
+    #[test]
+    fn test_valid_book_passes() {
+        let mut engine = engine_with_orders();
+        assert_eq!(engine.validate(), Ok(()));
+
+        let mut events = Vec::new();
+        engine.process_place(PlaceOrder::limit(5, 2, Side::Ask, 98, 25), &mut events);
+        assert!(events.iter().any(|e| matches!(e, OutputEvent::Trade(_))));
+        assert_eq!(engine.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_detects_corruption() {
+        let mut engine = engine_with_orders();
+        let head = engine.book.get_level(Side::Bid, 99).unwrap().head;
+        engine.book.get_level_mut(Side::Bid, 99).unwrap().total_qty += 1;
+        assert_eq!(
+            engine.validate(),
+            Err(InvariantViolation::LevelTotals { side: Side::Bid, price: 99, count: 2, total_qty: 21 })
+        );
+
+        let mut engine = engine_with_orders();
+        let second = engine.arena.get(head).next;
+        engine.arena.get_mut(second).prev = NULL_INDEX;
+        assert_eq!(
+            engine.validate(),
+            Err(InvariantViolation::BrokenLink { side: Side::Bid, price: 99, index: second })
+        );
+
+        let mut engine = engine_with_orders();
+        engine.arena.get_mut(head).price = 97;
+        assert_eq!(engine.validate(), Err(InvariantViolation::BadNode { side: Side::Bid, price: 99, index: head }));
+
+        let mut engine = engine_with_orders();
+        engine.arena.get_mut(head).order_id = 42;
+        assert_eq!(engine.validate(), Err(InvariantViolation::OrderMapMismatch { order_id: 42 }));
+
+        let mut engine = engine_with_orders();
+        engine.arena.alloc().unwrap();
+        assert_eq!(
+            engine.validate(),
+            Err(InvariantViolation::CountMismatch { in_levels: 4, order_map: 4, allocated: 5 })
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! Vec Book - Order book backend with levels in sorted vectors.
+//!
+//! Each side is a `Vec` of levels sorted so the best price is last: the
+//! top of book is a `last()`, and levels added or removed near it shift
+//! only a few elements. Level lookup is a binary search. Suits books with
+//! a few dozen active levels clustered around the touch.
+
+use std::ops::{Bound, RangeBounds};
+use std::slice;
+
+use crate::arena::{Arena, ArenaHandle, ArenaIndex};
+use crate::backend::BookBackend;
+use crate::command::Side;
+use crate::order_book::OrderInfo;
+use crate::order_index::OrderIndex;
+use crate::price_level::PriceLevel;
+
+/// Sort key: ascending price for bids, descending for asks, so the best
+/// level is always last. Its own inverse.
+#[inline]
+fn key(side: Side, price: u64) -> u64 {
+    match side {
+        Side::Bid => price,
+        Side::Ask => !price,
+    }
+}
+
+/// Order book with each side in a sorted `Vec`
+pub struct VecBook {
+    /// Levels keyed by `key(Side::Bid, price)`, ascending
+    bids: Vec<(u64, PriceLevel)>,
+    /// Levels keyed by `key(Side::Ask, price)`, ascending
+    asks: Vec<(u64, PriceLevel)>,
+    order_map: OrderIndex,
+}
+
+impl VecBook {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Create a book with the order map pre-allocated for `orders`
+    pub fn with_capacity(orders: usize) -> Self {
+        Self {
+            bids: Vec::new(),
+            asks: Vec::new(),
+            order_map: OrderIndex::with_capacity(orders),
+        }
+    }
+
+    /// Number of levels on a side
+    #[inline]
+    pub fn level_count(&self, side: Side) -> usize {
+        self.side(side).len()
+    }
+
+    #[inline]
+    fn side(&self, side: Side) -> &Vec<(u64, PriceLevel)> {
+        match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        }
+    }
+
+    #[inline]
+    fn side_mut(&mut self, side: Side) -> &mut Vec<(u64, PriceLevel)> {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        }
+    }
+
+    /// Position of the level at `price`, or where it would be inserted
+    #[inline]
+    fn find(&self, side: Side, price: u64) -> Result<usize, usize> {
+        let k = key(side, price);
+        self.side(side).binary_search_by_key(&k, |&(k, _)| k)
+    }
+}
+
+impl Default for VecBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Inclusive price bounds of a range, or `None` if it is empty
+fn inclusive_bounds<R: RangeBounds<u64>>(range: &R) -> Option<(u64, u64)> {
+    let lo = match range.start_bound() {
+        Bound::Included(&p) => p,
+        Bound::Excluded(&p) => p.checked_add(1)?,
+        Bound::Unbounded => 0,
+    };
+    let hi = match range.end_bound() {
+        Bound::Included(&p) => p,
+        Bound::Excluded(&p) => p.checked_sub(1)?,
+        Bound::Unbounded => u64::MAX,
+    };
+    (lo <= hi).then_some((lo, hi))
+}
+
+impl BookBackend for VecBook {
+    #[inline]
+    fn best_bid(&self) -> Option<u64> {
+        self.bids.last().map(|&(k, _)| key(Side::Bid, k))
+    }
+
+    #[inline]
+    fn best_ask(&self) -> Option<u64> {
+        self.asks.last().map(|&(k, _)| key(Side::Ask, k))
+    }
+
+    #[inline]
+    fn get_level(&self, side: Side, price: u64) -> Option<&PriceLevel> {
+        let i = self.find(side, price).ok()?;
+        Some(&self.side(side)[i].1)
+    }
+
+    #[inline]
+    fn get_level_mut(&mut self, side: Side, price: u64) -> Option<&mut PriceLevel> {
+        let i = self.find(side, price).ok()?;
+        Some(&mut self.side_mut(side)[i].1)
+    }
+
+    fn level_range<R: RangeBounds<u64>>(
+        &self,
+        side: Side,
+        range: R,
+    ) -> impl DoubleEndedIterator<Item = (u64, &PriceLevel)> + '_ {
+        let levels = self.side(side);
+        let slice = match inclusive_bounds(&range) {
+            Some((lo, hi)) => {
+                let (k_lo, k_hi) = match side {
+                    Side::Bid => (lo, hi),
+                    Side::Ask => (key(side, hi), key(side, lo)),
+                };
+                let start = levels.partition_point(|&(k, _)| k < k_lo);
+                let end = levels.partition_point(|&(k, _)| k <= k_hi);
+                &levels[start..end]
+            }
+            None => &levels[..0],
+        };
+        VecLevels { levels: slice.iter(), side }
+    }
+
+    fn remove_empty_level(&mut self, side: Side, price: u64) {
+        if let Ok(i) = self.find(side, price) {
+            self.side_mut(side).remove(i);
+        }
+    }
+
+    fn add_order(
+        &mut self,
+        arena: &mut Arena,
+        order_id: u64,
+        user_id: u64,
+        side: Side,
+        price: u64,
+        arena_index: ArenaIndex,
+    ) -> bool {
+        if self.order_map.contains(order_id) {
+            return false;
+        }
+        self.order_map.insert(order_id, OrderInfo {
+            handle: arena.handle(arena_index),
+            side,
+            price,
+            user_id,
+            original_qty: arena.get(arena_index).qty,
+            reduced_qty: 0,
+        });
+
+        let i = match self.find(side, price) {
+            Ok(i) => i,
+            Err(i) => {
+                self.side_mut(side).insert(i, (key(side, price), PriceLevel::new()));
+                i
+            }
+        };
+        self.side_mut(side)[i].1.push_back(arena, arena_index);
+        true
+    }
+
+    fn remove_order(&mut self, arena: &mut Arena, order_id: u64) -> Option<OrderInfo> {
+        let info = self.order_map.remove(order_id)?;
+        if let Ok(i) = self.find(info.side, info.price) {
+            let levels = self.side_mut(info.side);
+            if levels[i].1.remove(arena, arena.resolve(info.handle)) {
+                levels.remove(i);
+            }
+        }
+        Some(info)
+    }
+
+    #[inline]
+    fn remove_order_from_map(&mut self, order_id: u64) -> Option<OrderInfo> {
+        self.order_map.remove(order_id)
+    }
+
+    #[inline]
+    fn remove_order_by_handle(&mut self, order_id: u64, handle: ArenaHandle) -> Option<OrderInfo> {
+        self.order_map.remove_by_handle(order_id, handle)
+    }
+
+    #[inline]
+    fn get_order(&self, order_id: u64) -> Option<&OrderInfo> {
+        self.order_map.get(order_id)
+    }
+
+    #[inline]
+    fn get_order_mut(&mut self, order_id: u64) -> Option<&mut OrderInfo> {
+        self.order_map.get_mut(order_id)
+    }
+
+    #[inline]
+    fn orders(&self) -> impl Iterator<Item = (u64, &OrderInfo)> + '_ {
+        self.order_map.iter()
+    }
+
+    #[inline]
+    fn order_count(&self) -> usize {
+        self.order_map.len()
+    }
+
+    fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.order_map.clear();
+    }
+}
+
+/// Levels of a `VecBook` side, ascending by price (double-ended)
+#[derive(Clone, Debug)]
+pub struct VecLevels<'a> {
+    levels: slice::Iter<'a, (u64, PriceLevel)>,
+    side: Side,
+}
+
+impl<'a> Iterator for VecLevels<'a> {
+    type Item = (u64, &'a PriceLevel);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, level) = match self.side {
+            Side::Bid => self.levels.next(),
+            Side::Ask => self.levels.next_back(),
+        }?;
+        Some((key(self.side, *k), level))
+    }
+}
+
+impl DoubleEndedIterator for VecLevels<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (k, level) = match self.side {
+            Side::Bid => self.levels.next_back(),
+            Side::Ask => self.levels.next(),
+        }?;
+        Some((key(self.side, *k), level))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(book: &mut VecBook, arena: &mut Arena, order_id: u64, side: Side, price: u64) {
+        let idx = arena.alloc().unwrap();
+        let node = arena.get_mut(idx);
+        node.order_id = order_id;
+        node.price = price;
+        node.qty = 10;
+        assert!(book.add_order(arena, order_id, 1, side, price, idx));
+    }
+
+    #[test]
+    fn test_levels_sorted_best_last() {
+        let mut arena = Arena::new(16);
+        let mut book = VecBook::new();
+        for (id, price) in [(1, 100), (2, 102), (3, 98), (4, 102)] {
+            add(&mut book, &mut arena, id, Side::Bid, price);
+            add(&mut book, &mut arena, id + 10, Side::Ask, price + 10);
+        }
+
+        assert_eq!(book.best_bid(), Some(102));
+        assert_eq!(book.best_ask(), Some(108));
+        assert_eq!(book.depth_at(Side::Bid, 102), (20, 2));
+
+        let bids: Vec<_> = book.level_range(Side::Bid, 99..).map(|(p, _)| p).collect();
+        assert_eq!(bids, [100, 102]);
+        let asks: Vec<_> = book.level_range(Side::Ask, ..=110).map(|(p, _)| p).collect();
+        assert_eq!(asks, [108, 110]);
+        let asks_desc: Vec<_> = book.level_range(Side::Ask, ..).rev().map(|(p, _)| p).collect();
+        assert_eq!(asks_desc, [112, 110, 108]);
+        assert_eq!(book.level_range(Side::Bid, 101..101).count(), 0);
+
+        book.remove_order(&mut arena, 3);
+        assert_eq!(book.level_count(Side::Bid), 2);
+        assert_eq!(book.order_count(), 7);
+    }
+}
@@ -0,0 +1,717 @@
+//! WebSocket JSON gateway for dashboards and prototypes.
+//!
+//! Each instrument gets its own `Engine` thread, fed and drained exclusively
+//! through rtrb ring buffers. The tokio side never touches the book:
+//!
+//! ```text
+//! [WS clients] --JSON--> [gateway task] --rtrb--> [Engine thread (per instrument)]
+//!                                                          |
+//! [WS clients] <--JSON-- [broadcast] <-- [feed thread] <--rtrb
+//! ```
+//!
+//! The feed thread keeps an L2 depth image per instrument (the same
+//! snapshot pattern as the TUI demo), so new depth/BBO subscribers get the
+//! current state immediately. It sleeps until the engine thread unparks it.
+//!
+//! Commands carry the ID of the connection that sent them, and the engine
+//! thread (`Engine::run` over `(ClientId, Command)` items) tags every event
+//! with it. Rings apply back-pressure rather than dropping events, so the
+//! depth image and order ownership never miss an update. A connection gets
+//! the reports for its own commands, and owns an order once it sees it
+//! accepted; it then also gets fills and cancels of that order caused by
+//! others, until the order is filled or canceled.
+//!
+//! Orders are stamped with the connection ID as `user_id`, and a connection
+//! may only cancel or modify orders it owns.
+//!
+//! ## Protocol
+//!
+//! Client to server:
+//!
+//! ```json
+//! {"op":"subscribe","instrument":"ETH-USD","channels":["depth","trades","bbo"]}
+//! {"op":"unsubscribe","instrument":"ETH-USD","channels":["trades"]}
+//! {"op":"submit","instrument":"ETH-USD","command":{"Place":{"order_id":1,"user_id":7,"side":"Bid","price":300000,"qty":5}}}
+//! ```
+//!
+//! Server to client: `depth`, `trade`, `bbo`, `order` (execution reports
+//! for commands and orders of the same connection) and `error` messages,
+//! tagged by a `type` field.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::book_side::LevelStorage;
+use crate::command::{Command, OutputEvent, PlaceOrder, Side, TradeEvent};
+use crate::engine::{Engine, EngineConfig};
+use crate::matching::MatchingEngine;
+use crate::mirror::BookMirror;
+
+/// Server configuration
+#[derive(Clone, Debug)]
+pub struct WsConfig {
+    /// Instrument symbols; one engine is started per symbol
+    pub instruments: Vec<String>,
+    /// Arena capacity of each engine
+    pub capacity: u32,
+    /// Number of levels per side in depth messages
+    pub depth_levels: usize,
+    /// Size of the command and event ring buffers
+    pub ring_size: usize,
+    /// Pin engine threads to a core (see `Engine::pin_to_core`)
+    pub pin_engines: bool,
+    /// Level storage per symbol; unlisted symbols use `LevelStorage::Tree`
+    pub level_storage: HashMap<String, LevelStorage>,
+}
+
+impl Default for WsConfig {
+    fn default() -> Self {
+        Self {
+            instruments: vec!["ETH-USD".to_string()],
+            capacity: 1_000_000,
+            depth_levels: 10,
+            ring_size: 65_536,
+            pin_engines: false,
+            level_storage: HashMap::new(),
+        }
+    }
+}
+
+// ============================================================================
+// JSON Messages
+// ============================================================================
+
+/// Market data channels a client can subscribe to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Depth,
+    Trades,
+    Bbo,
+}
+
+/// Messages sent by clients
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Subscribe { instrument: String, channels: Vec<Channel> },
+    Unsubscribe { instrument: String, channels: Vec<Channel> },
+    Submit { instrument: String, command: Command },
+}
+
+/// A `(price, total qty, order count)` level in a depth message
+pub type DepthLevel = (u64, u64, u32);
+
+/// A `(price, total qty)` top-of-book quote
+pub type Quote = (u64, u64);
+
+/// Messages sent to clients
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Top-N levels per side, best first
+    Depth { instrument: String, bids: Vec<DepthLevel>, asks: Vec<DepthLevel> },
+    /// Public trade print
+    Trade { instrument: String, trade: TradeEvent },
+    /// Best bid/ask as `(price, total qty)`
+    Bbo { instrument: String, bid: Option<Quote>, ask: Option<Quote> },
+    /// Execution report for an order submitted on this connection
+    Order { instrument: String, event: OutputEvent },
+    /// Request could not be processed
+    Error { message: String },
+}
+
+// ============================================================================
+// Per-Instrument State
+// ============================================================================
+
+/// L2 image maintained from `BookDelta` events
+#[derive(Debug, Default)]
+struct DepthImage {
+    book: BookMirror,
+}
+
+impl DepthImage {
+    fn apply(&mut self, event: &OutputEvent) {
+        // L2-only mirror: level deltas cannot be inconsistent
+        let _ = self.book.apply(event);
+    }
+
+    fn bbo(&self) -> (Option<Quote>, Option<Quote>) {
+        let (bid, ask) = self.book.bbo();
+        (bid.map(|(p, l)| (p, l.qty)), ask.map(|(p, l)| (p, l.qty)))
+    }
+
+    fn levels(&self, side: Side, n: usize) -> Vec<DepthLevel> {
+        self.book.top_levels(side, n).map(|(p, l)| (p, l.qty, l.count)).collect()
+    }
+
+    fn depth_message(&self, instrument: &str, n: usize) -> ServerMessage {
+        ServerMessage::Depth {
+            instrument: instrument.to_string(),
+            bids: self.levels(Side::Bid, n),
+            asks: self.levels(Side::Ask, n),
+        }
+    }
+
+    fn bbo_message(&self, instrument: &str) -> ServerMessage {
+        let (bid, ask) = self.bbo();
+        ServerMessage::Bbo { instrument: instrument.to_string(), bid, ask }
+    }
+}
+
+/// Connection that submitted a command
+type ClientId = u64;
+
+/// A command and the connection that sent it
+type Submission = (ClientId, Command);
+
+/// Item on the internal fan-out channel
+#[derive(Clone, Debug)]
+enum FeedItem {
+    /// Public market data for a channel
+    Public(Channel, Arc<ServerMessage>),
+    /// Execution report: instrument index and the client whose command
+    /// produced it
+    Private(usize, ClientId, OutputEvent),
+}
+
+struct Instrument {
+    symbol: String,
+    commands: mpsc::UnboundedSender<Submission>,
+    depth: Arc<RwLock<DepthImage>>,
+}
+
+/// Orders a connection owns, with their open quantity
+#[derive(Debug, Default)]
+struct OwnOrders {
+    open: HashMap<(usize, u64), u32>,
+}
+
+impl OwnOrders {
+    /// Returns true if the connection owns a live order
+    fn owns(&self, index: usize, order_id: u64) -> bool {
+        self.open.contains_key(&(index, order_id))
+    }
+
+    /// Track an execution report and return whether the connection should
+    /// receive it.
+    ///
+    /// `own_command` is true when the connection's own command produced the
+    /// event. Orders become owned when such a command is accepted, and stop
+    /// being owned once filled or canceled (a reduction only lowers the
+    /// open quantity); rejections never change
+    /// ownership (a duplicate-ID rejection leaves the live order owned).
+    fn route(&mut self, index: usize, own_command: bool, event: &OutputEvent) -> bool {
+        match *event {
+            OutputEvent::Accepted(a) => {
+                if own_command {
+                    self.open.insert((index, a.order_id), a.qty);
+                }
+                own_command
+            }
+            OutputEvent::Trade(t) => {
+                let key = (index, t.maker_order_id);
+                let maker = match self.open.get_mut(&key) {
+                    Some(open) => {
+                        *open = open.saturating_sub(t.qty);
+                        if *open == 0 {
+                            self.open.remove(&key);
+                        }
+                        true
+                    }
+                    None => false,
+                };
+                own_command || maker
+            }
+            OutputEvent::Canceled(c) => self.open.remove(&(index, c.order_id)).is_some() || own_command,
+            OutputEvent::Reduced(r) => match self.open.get_mut(&(index, r.order_id)) {
+                Some(open) => {
+                    *open = r.remaining;
+                    true
+                }
+                None => own_command,
+            },
+            OutputEvent::Rejected(_) => own_command,
+            OutputEvent::BookDelta(_) | OutputEvent::Bbo(_) | OutputEvent::ArenaHighWater(_) => false,
+        }
+    }
+}
+
+/// A running set of engines behind a WebSocket gateway.
+pub struct WsServer {
+    instruments: Arc<Vec<Instrument>>,
+    feed: broadcast::Sender<FeedItem>,
+    depth_levels: usize,
+}
+
+impl WsServer {
+    /// Start one engine thread and one feed thread per instrument.
+    ///
+    /// Must be called from within a tokio runtime (gateway tasks are spawned).
+    pub fn start(config: WsConfig) -> Self {
+        let (feed, _) = broadcast::channel(config.ring_size);
+        let mut instruments = Vec::with_capacity(config.instruments.len());
+
+        for (index, symbol) in config.instruments.iter().enumerate() {
+            let (mut cmd_tx, mut cmd_rx) = rtrb::RingBuffer::<Submission>::new(config.ring_size);
+            let (mut evt_tx, mut evt_rx) = rtrb::RingBuffer::<(ClientId, OutputEvent)>::new(config.ring_size);
+
+            let depth = Arc::new(RwLock::new(DepthImage::default()));
+            let feed_tx = feed.clone();
+            let feed_depth = depth.clone();
+            let feed_symbol = symbol.clone();
+            let depth_levels = config.depth_levels;
+            let feed_thread = thread::Builder::new()
+                .name(format!("feed-{}", symbol))
+                .spawn(move || {
+                    run_feed(index, &feed_symbol, depth_levels, &mut evt_rx, &feed_depth, &feed_tx)
+                })
+                .expect("spawn feed thread")
+                .thread()
+                .clone();
+
+            let capacity = config.capacity;
+            let pin = config.pin_engines;
+            let storage = config.level_storage.get(symbol).copied().unwrap_or_default();
+            thread::Builder::new()
+                .name(format!("engine-{}", symbol))
+                .spawn(move || {
+                    let matcher = MatchingEngine::with_storage(capacity, storage);
+                    let mut engine = Engine::with_matcher(matcher, EngineConfig::default());
+                    engine.run_notifying(&mut cmd_rx, &mut evt_tx, pin, || feed_thread.unpark())
+                })
+                .expect("spawn engine thread");
+
+            // Gateway: the only producer on the command ring
+            let (commands, mut gateway_rx) = mpsc::unbounded_channel::<Submission>();
+            tokio::spawn(async move {
+                while let Some(mut cmd) = gateway_rx.recv().await {
+                    loop {
+                        match cmd_tx.push(cmd) {
+                            Ok(()) => break,
+                            Err(rtrb::PushError::Full(c)) => {
+                                cmd = c;
+                                tokio::task::yield_now().await;
+                            }
+                        }
+                    }
+                }
+            });
+
+            instruments.push(Instrument { symbol: symbol.clone(), commands, depth });
+        }
+
+        Self {
+            instruments: Arc::new(instruments),
+            feed,
+            depth_levels: config.depth_levels,
+        }
+    }
+
+    /// Accept WebSocket connections until the listener fails.
+    pub async fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        let mut next_client: ClientId = 0;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let client = next_client;
+            next_client += 1;
+            let instruments = self.instruments.clone();
+            let feed = self.feed.subscribe();
+            let depth_levels = self.depth_levels;
+            tokio::spawn(async move {
+                // Connection errors only affect this client
+                let _ = handle_connection(stream, client, instruments, feed, depth_levels).await;
+            });
+        }
+    }
+}
+
+/// Drain engine output, maintain the depth image and fan out messages.
+fn run_feed(
+    index: usize,
+    symbol: &str,
+    depth_levels: usize,
+    events: &mut rtrb::Consumer<(ClientId, OutputEvent)>,
+    depth: &RwLock<DepthImage>,
+    feed: &broadcast::Sender<FeedItem>,
+) {
+    let mut last_bbo = (None, None);
+    loop {
+        let mut book_changed = false;
+        while let Ok((client, event)) = events.pop() {
+            match event {
+                OutputEvent::BookDelta(_) => {
+                    depth.write().unwrap().apply(&event);
+                    book_changed = true;
+                }
+                OutputEvent::Trade(trade) => {
+                    let msg = ServerMessage::Trade { instrument: symbol.to_string(), trade };
+                    let _ = feed.send(FeedItem::Public(Channel::Trades, Arc::new(msg)));
+                    let _ = feed.send(FeedItem::Private(index, client, event));
+                }
+                _ => {
+                    let _ = feed.send(FeedItem::Private(index, client, event));
+                }
+            }
+        }
+
+        if book_changed {
+            // Conflate: one depth message per drained batch, BBO only on change
+            let image = depth.read().unwrap();
+            let msg = image.depth_message(symbol, depth_levels);
+            let _ = feed.send(FeedItem::Public(Channel::Depth, Arc::new(msg)));
+            let bbo = image.bbo();
+            if bbo != last_bbo {
+                last_bbo = bbo;
+                let _ = feed.send(FeedItem::Public(Channel::Bbo, Arc::new(image.bbo_message(symbol))));
+            }
+        }
+        if events.is_empty() {
+            // The engine unparks after pushing, so a wakeup is never lost
+            thread::park();
+        }
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    client: ClientId,
+    instruments: Arc<Vec<Instrument>>,
+    mut feed: broadcast::Receiver<FeedItem>,
+    depth_levels: usize,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let ws = tokio_tungstenite::accept_async(stream).await?;
+    let (mut sink, mut source) = ws.split();
+
+    let mut subscriptions: HashSet<(usize, Channel)> = HashSet::new();
+    let mut own_orders = OwnOrders::default();
+
+    loop {
+        tokio::select! {
+            incoming = source.next() => {
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e),
+                };
+                let replies = handle_client_message(&text, client, &instruments, &mut subscriptions, &own_orders, depth_levels);
+                for reply in replies {
+                    sink.send(Message::Text(serde_json::to_string(&reply).unwrap())).await?;
+                }
+            }
+            item = feed.recv() => {
+                let msg = match item {
+                    Ok(FeedItem::Public(channel, msg)) => {
+                        let index = instrument_index(&instruments, &msg);
+                        if !index.is_some_and(|i| subscriptions.contains(&(i, channel))) {
+                            continue;
+                        }
+                        serde_json::to_string(&*msg).unwrap()
+                    }
+                    Ok(FeedItem::Private(index, submitter, event)) => {
+                        if !own_orders.route(index, submitter == client, &event) {
+                            continue;
+                        }
+                        let symbol = instruments[index].symbol.clone();
+                        serde_json::to_string(&ServerMessage::Order { instrument: symbol, event }).unwrap()
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        let message = format!("slow consumer: {} messages dropped", n);
+                        serde_json::to_string(&ServerMessage::Error { message }).unwrap()
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                };
+                sink.send(Message::Text(msg)).await?;
+            }
+        }
+    }
+}
+
+fn instrument_index(instruments: &[Instrument], msg: &ServerMessage) -> Option<usize> {
+    let symbol = match msg {
+        ServerMessage::Depth { instrument, .. }
+        | ServerMessage::Trade { instrument, .. }
+        | ServerMessage::Bbo { instrument, .. }
+        | ServerMessage::Order { instrument, .. } => instrument,
+        ServerMessage::Error { .. } => return None,
+    };
+    instruments.iter().position(|i| &i.symbol == symbol)
+}
+
+/// Apply one client request, returning any immediate replies.
+fn handle_client_message(
+    text: &str,
+    client: ClientId,
+    instruments: &[Instrument],
+    subscriptions: &mut HashSet<(usize, Channel)>,
+    own_orders: &OwnOrders,
+    depth_levels: usize,
+) -> Vec<ServerMessage> {
+    let request: ClientMessage = match serde_json::from_str(text) {
+        Ok(r) => r,
+        Err(e) => return vec![ServerMessage::Error { message: format!("invalid request: {}", e) }],
+    };
+    let symbol = match &request {
+        ClientMessage::Subscribe { instrument, .. }
+        | ClientMessage::Unsubscribe { instrument, .. }
+        | ClientMessage::Submit { instrument, .. } => instrument,
+    };
+    let index = match instruments.iter().position(|i| &i.symbol == symbol) {
+        Some(i) => i,
+        None => return vec![ServerMessage::Error { message: format!("unknown instrument {}", symbol) }],
+    };
+    let instrument = &instruments[index];
+
+    match request {
+        ClientMessage::Subscribe { channels, .. } => {
+            let mut replies = Vec::new();
+            let image = instrument.depth.read().unwrap();
+            for channel in channels {
+                subscriptions.insert((index, channel));
+                match channel {
+                    Channel::Depth => replies.push(image.depth_message(&instrument.symbol, depth_levels)),
+                    Channel::Bbo => replies.push(image.bbo_message(&instrument.symbol)),
+                    Channel::Trades => {}
+                }
+            }
+            replies
+        }
+        ClientMessage::Unsubscribe { channels, .. } => {
+            for channel in channels {
+                subscriptions.remove(&(index, channel));
+            }
+            Vec::new()
+        }
+        ClientMessage::Submit { command, .. } => {
+            let command = match command {
+                // Orders belong to the connection, whatever the client claims
+                Command::Place(place) => Command::Place(PlaceOrder { user_id: client, ..place }),
+                Command::Cancel(c) | Command::CancelByHandle(c, _) if !own_orders.owns(index, c.order_id) => {
+                    return vec![not_owned(c.order_id)];
+                }
+                Command::Modify(m) | Command::ModifyByHandle(m, _) if !own_orders.owns(index, m.order_id) => {
+                    return vec![not_owned(m.order_id)];
+                }
+                other => other,
+            };
+            if instrument.commands.send((client, command)).is_err() {
+                return vec![ServerMessage::Error { message: "engine unavailable".to_string() }];
+            }
+            Vec::new()
+        }
+    }
+}
+
+fn not_owned(order_id: u64) -> ServerMessage {
+    ServerMessage::Error { message: format!("order {} is not owned by this connection", order_id) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{
+        BookUpdate, CancelOrder, OrderAccepted, OrderCanceled, OrderHandle, OrderReduced, OrderRejected, PlaceOrder, RejectReason,
+    };
+    use std::time::Duration;
+    use tokio_tungstenite::connect_async;
+
+    #[test]
+    fn test_client_message_json() {
+        let json = r#"{"op":"submit","instrument":"ETH-USD","command":{"Place":{"order_id":1,"user_id":7,"side":"Bid","price":300000,"qty":5}}}"#;
+        match serde_json::from_str::<ClientMessage>(json).unwrap() {
+            ClientMessage::Submit { instrument, command: Command::Place(p) } => {
+                assert_eq!(instrument, "ETH-USD");
+                assert_eq!(p.order_id, 1);
+                assert_eq!(p.side, Side::Bid);
+                assert_eq!(p.order_type, crate::command::OrderType::Limit);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let cancel = ClientMessage::Submit {
+            instrument: "X".to_string(),
+            command: Command::Cancel(CancelOrder { order_id: 9 }),
+        };
+        let text = serde_json::to_string(&cancel).unwrap();
+        assert_eq!(text, r#"{"op":"submit","instrument":"X","command":{"Cancel":{"order_id":9}}}"#);
+    }
+
+    #[test]
+    fn test_depth_image() {
+        let delta = |side, price, new_qty, new_count| {
+            OutputEvent::BookDelta(BookUpdate { side, price, new_qty, new_count })
+        };
+        let mut image = DepthImage::default();
+        image.apply(&delta(Side::Bid, 100, 10, 1));
+        image.apply(&delta(Side::Bid, 101, 5, 2));
+        image.apply(&delta(Side::Ask, 103, 7, 1));
+        assert_eq!(image.bbo(), (Some((101, 5)), Some((103, 7))));
+
+        image.apply(&delta(Side::Bid, 101, 0, 0));
+        assert_eq!(image.bbo(), (Some((100, 10)), Some((103, 7))));
+
+        match image.depth_message("X", 1) {
+            ServerMessage::Depth { bids, asks, .. } => {
+                assert_eq!(bids, vec![(100, 10, 1)]);
+                assert_eq!(asks, vec![(103, 7, 1)]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_own_orders_routing() {
+        let accepted = |order_id, qty| {
+            OutputEvent::Accepted(OrderAccepted { order_id, price: 100, qty, side: Side::Ask, handle: OrderHandle::default() })
+        };
+        let fill = |maker_order_id, qty| {
+            OutputEvent::Trade(TradeEvent {
+                price: 100,
+                qty,
+                maker_order_id,
+                taker_order_id: 99,
+                maker_user_id: 1,
+                taker_user_id: 2,
+                taker_side: Side::Bid,
+            })
+        };
+        let rejected = |order_id, reason| OutputEvent::Rejected(OrderRejected { order_id, reason });
+        let mut own = OwnOrders::default();
+
+        // Cancelling someone else's ID gets the reply but no ownership
+        assert!(own.route(0, true, &rejected(5, RejectReason::OrderNotFound)));
+        assert!(!own.route(0, false, &fill(5, 1)));
+
+        // Accepted own order: fills by others are reported until it's gone
+        assert!(own.route(0, true, &accepted(1, 10)));
+        assert!(!own.route(1, false, &fill(1, 4)));
+        assert!(own.route(0, false, &fill(1, 4)));
+
+        // A duplicate-ID rejection keeps the live order owned
+        assert!(own.route(0, true, &rejected(1, RejectReason::DuplicateOrderId)));
+        assert!(own.route(0, false, &fill(1, 6)));
+        assert!(own.open.is_empty());
+        assert!(!own.route(0, false, &fill(1, 1)));
+
+        // A reduction lowers the open quantity without ending ownership
+        own.route(0, true, &accepted(3, 10));
+        assert!(own.route(0, false, &OutputEvent::Reduced(OrderReduced { order_id: 3, reduced_by: 7, remaining: 3 })));
+        assert!(own.route(0, false, &fill(3, 3)));
+        assert!(own.open.is_empty());
+
+        // Cancels by anyone end ownership
+        own.route(0, true, &accepted(2, 10));
+        assert!(own.route(0, false, &OutputEvent::Canceled(OrderCanceled { order_id: 2, canceled_qty: 10 })));
+        assert!(own.open.is_empty());
+    }
+
+    #[test]
+    fn test_submit_stamps_user_and_checks_ownership() {
+        let (commands, mut rx) = mpsc::unbounded_channel();
+        let instruments = [Instrument { symbol: "X".to_string(), commands, depth: Arc::default() }];
+        let mut subscriptions = HashSet::new();
+        let mut own = OwnOrders::default();
+        let mut submit = |command: Command, own: &OwnOrders| {
+            let text = serde_json::to_string(&ClientMessage::Submit { instrument: "X".to_string(), command }).unwrap();
+            handle_client_message(&text, 7, &instruments, &mut subscriptions, own, 10)
+        };
+
+        assert!(submit(Command::Place(PlaceOrder::limit(1, 999, Side::Bid, 100, 5)), &own).is_empty());
+        match rx.try_recv().unwrap() {
+            (7, Command::Place(p)) => assert_eq!(p.user_id, 7),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // Someone else's (or a not yet accepted) order can't be touched
+        let cancel = Command::Cancel(CancelOrder { order_id: 1 });
+        assert!(matches!(submit(cancel, &own)[..], [ServerMessage::Error { .. }]));
+        assert!(rx.try_recv().is_err());
+
+        own.route(0, true, &OutputEvent::Accepted(OrderAccepted {
+            order_id: 1, price: 100, qty: 5, side: Side::Bid, handle: OrderHandle::default(),
+        }));
+        assert!(submit(cancel, &own).is_empty());
+        assert!(matches!(rx.try_recv().unwrap(), (7, Command::Cancel(_))));
+    }
+
+    async fn next_json(
+        ws: &mut tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>>,
+    ) -> serde_json::Value {
+        loop {
+            let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+                .await
+                .expect("timed out waiting for message")
+                .unwrap()
+                .unwrap();
+            if let Message::Text(text) = msg {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_subscribe_and_trade() {
+        let config = WsConfig {
+            instruments: vec!["ETH-USD".to_string()],
+            capacity: 1000,
+            ring_size: 1024,
+            ..WsConfig::default()
+        };
+        let server = WsServer::start(config);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.serve(listener).await });
+
+        let (mut maker, _) = connect_async(format!("ws://{}", addr)).await.unwrap();
+        let (mut watcher, _) = connect_async(format!("ws://{}", addr)).await.unwrap();
+
+        let subscribe = ClientMessage::Subscribe {
+            instrument: "ETH-USD".to_string(),
+            channels: vec![Channel::Trades, Channel::Bbo],
+        };
+        watcher.send(Message::Text(serde_json::to_string(&subscribe).unwrap())).await.unwrap();
+        let initial = next_json(&mut watcher).await;
+        assert_eq!(initial["type"], "bbo");
+        assert!(initial["bid"].is_null());
+
+        let place = |cmd: PlaceOrder| {
+            let msg = ClientMessage::Submit { instrument: "ETH-USD".to_string(), command: Command::Place(cmd) };
+            Message::Text(serde_json::to_string(&msg).unwrap())
+        };
+        maker.send(place(PlaceOrder::limit(1, 1, Side::Ask, 300_000, 10))).await.unwrap();
+
+        let accepted = next_json(&mut maker).await;
+        assert_eq!(accepted["type"], "order");
+        assert_eq!(accepted["event"]["Accepted"]["order_id"], 1);
+
+        let bbo = next_json(&mut watcher).await;
+        assert_eq!(bbo["type"], "bbo");
+        assert_eq!(bbo["ask"], serde_json::json!([300_000, 10]));
+
+        maker.send(place(PlaceOrder::limit(2, 2, Side::Bid, 300_000, 4))).await.unwrap();
+
+        // Watcher sees the public print, maker sees fills for both of its orders
+        let trade = next_json(&mut watcher).await;
+        assert_eq!(trade["type"], "trade");
+        assert_eq!(trade["trade"]["qty"], 4);
+
+        let fill = next_json(&mut maker).await;
+        assert_eq!(fill["type"], "order");
+        assert_eq!(fill["event"]["Trade"]["maker_order_id"], 1);
+
+        let unknown = ClientMessage::Subscribe { instrument: "BTC-USD".to_string(), channels: vec![Channel::Depth] };
+        maker.send(Message::Text(serde_json::to_string(&unknown).unwrap())).await.unwrap();
+        let error = next_json(&mut maker).await;
+        assert_eq!(error["type"], "error");
+    }
+}
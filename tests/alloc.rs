@@ -0,0 +1,143 @@
+//! Allocation Test - Verifies the hot path does not touch the heap.
+//!
+//! A counting global allocator records allocations per thread. Each test
+//! warms an engine up to its steady state, then asserts that further
+//! place/cancel/match commands through `Engine::process_command` perform
+//! zero allocations.
+
+use flash_lob::{Engine, Command, PlaceOrder, CancelOrder, ModifyOrder, Side, OrderType, EngineConfig, LevelStorage, LadderConfig, BookBackend, VecBook};
+use flash_lob::matching::MatchingEngine;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// System allocator that counts allocations made by the current thread
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Allocations made by this thread while running `f`
+fn count_allocations(f: impl FnOnce()) -> u64 {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// Random place/cancel/modify flow with marketable orders, over a fixed
+/// band of prices
+fn run_flow<B: BookBackend>(engine: &mut Engine<B>, rng: &mut ChaCha8Rng, next_id: &mut u64, resting: &mut Vec<u64>, ops: usize) {
+    for _ in 0..ops {
+        *next_id += 1;
+        let order_id = *next_id;
+        let roll = rng.gen_range(0..100);
+        let cmd = if roll < 30 && !resting.is_empty() {
+            let idx = rng.gen_range(0..resting.len());
+            Command::Cancel(CancelOrder { order_id: resting.swap_remove(idx) })
+        } else if roll < 35 && !resting.is_empty() {
+            let idx = rng.gen_range(0..resting.len());
+            let old = resting.swap_remove(idx);
+            resting.push(order_id);
+            Command::Modify(ModifyOrder {
+                order_id: old,
+                new_order_id: order_id,
+                new_price: rng.gen_range(9_900..10_100),
+                new_qty: rng.gen_range(1..100),
+            })
+        } else {
+            resting.push(order_id);
+            Command::Place(PlaceOrder {
+                order_id,
+                user_id: rng.gen_range(1..10),
+                side: if rng.gen_bool(0.5) { Side::Bid } else { Side::Ask },
+                price: rng.gen_range(9_900..10_100),
+                qty: rng.gen_range(1..100),
+                order_type: match rng.gen_range(0..10) {
+                    0 => OrderType::IOC,
+                    1 => OrderType::FOK,
+                    _ => OrderType::Limit,
+                },
+            })
+        };
+        engine.process_command(cmd);
+        // Keep the bookkeeping bounded: drop IDs that are no longer resting
+        if resting.len() > 4_000 {
+            resting.retain(|&id| engine.matcher.book.contains_order(id));
+        }
+    }
+}
+
+/// Warm `engine` up with the random flow, then assert a further run is
+/// allocation-free
+fn assert_steady_state_allocation_free<B: BookBackend>(mut engine: Engine<B>) {
+    let mut rng = ChaCha8Rng::seed_from_u64(0xA110C);
+    let mut next_id = 0;
+    let mut resting = Vec::with_capacity(8_192);
+
+    run_flow(&mut engine, &mut rng, &mut next_id, &mut resting, 50_000);
+    let allocations = count_allocations(|| {
+        run_flow(&mut engine, &mut rng, &mut next_id, &mut resting, 50_000);
+    });
+    assert_eq!(allocations, 0, "steady-state flow allocated");
+}
+
+#[test]
+fn test_tree_storage_steady_state_does_not_allocate() {
+    assert_steady_state_allocation_free(Engine::new(100_000));
+}
+
+#[test]
+fn test_ladder_storage_steady_state_does_not_allocate() {
+    let storage = LevelStorage::Ladder(LadderConfig::centered(10_000, 1, 1_024));
+    let matcher = MatchingEngine::with_storage(100_000, storage);
+    assert_steady_state_allocation_free(Engine::with_matcher(matcher, EngineConfig::default()));
+}
+
+#[test]
+fn test_vec_book_steady_state_does_not_allocate() {
+    let matcher = MatchingEngine::with_book(100_000, VecBook::with_capacity(100_000));
+    assert_steady_state_allocation_free(Engine::with_matcher(matcher, EngineConfig::default()));
+}
+
+#[test]
+fn test_output_shaping_does_not_allocate() {
+    let config = EngineConfig { conflate_deltas: true, emit_bbo: true };
+    assert_steady_state_allocation_free(Engine::with_config(100_000, config));
+}
+
+#[test]
+fn test_first_commands_do_not_allocate() {
+    let mut engine = Engine::new(10_000);
+    let allocations = count_allocations(|| {
+        // Rest two levels per side, sweep through all of them, then cancel
+        for (order_id, side, price) in [(1, Side::Bid, 99), (2, Side::Bid, 98), (3, Side::Ask, 101), (4, Side::Ask, 102)] {
+            engine.process_command(Command::Place(PlaceOrder::limit(order_id, 1, side, price, 10)));
+        }
+        engine.process_command(Command::Place(PlaceOrder::limit(5, 2, Side::Bid, 102, 25)));
+        engine.process_command(Command::Place(PlaceOrder::limit(6, 2, Side::Ask, 98, 25)));
+        engine.process_command(Command::Cancel(CancelOrder { order_id: 5 }));
+        engine.process_command(Command::Cancel(CancelOrder { order_id: 6 }));
+    });
+    assert_eq!(allocations, 0);
+}
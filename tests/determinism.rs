@@ -68,16 +68,34 @@ fn hash_events(events: &[flash_lob::OutputEvent]) -> u64 {
                 c.order_id.hash(&mut hasher);
                 c.canceled_qty.hash(&mut hasher);
             }
+            flash_lob::OutputEvent::Reduced(r) => {
+                "Reduced".hash(&mut hasher);
+                r.order_id.hash(&mut hasher);
+                r.reduced_by.hash(&mut hasher);
+                r.remaining.hash(&mut hasher);
+            }
             flash_lob::OutputEvent::BookDelta(b) => {
                 "BookDelta".hash(&mut hasher);
                 b.price.hash(&mut hasher);
                 b.new_qty.hash(&mut hasher);
                 b.new_count.hash(&mut hasher);
             }
+            flash_lob::OutputEvent::Bbo(b) => {
+                "Bbo".hash(&mut hasher);
+                b.bid_price.hash(&mut hasher);
+                b.bid_qty.hash(&mut hasher);
+                b.ask_price.hash(&mut hasher);
+                b.ask_qty.hash(&mut hasher);
+            }
             flash_lob::OutputEvent::Rejected(r) => {
                 "Rejected".hash(&mut hasher);
                 r.order_id.hash(&mut hasher);
             }
+            flash_lob::OutputEvent::ArenaHighWater(w) => {
+                "ArenaHighWater".hash(&mut hasher);
+                w.allocated.hash(&mut hasher);
+                w.capacity.hash(&mut hasher);
+            }
         }
     }
     
@@ -1,135 +1,22 @@
 //! Fuzz Test - Compares Flash-LOB against a reference implementation.
 //!
-//! Uses a naive but correct reference implementation to verify
-//! the optimized engine produces identical results.
+//! Uses the crate's naive but correct `ReferenceMatcher` to verify the
+//! optimized engine produces identical results: best prices, counts and
+//! traded volume, and the full event stream for every command type. The
+//! byte-driven harness behind the cargo-fuzz targets in `fuzz/` is also run
+//! here over random inputs and known edge cases.
 
-use flash_lob::{Engine, Command, PlaceOrder, CancelOrder, Side, OutputEvent};
+use flash_lob::{Engine, Command, PlaceOrder, CancelOrder, ModifyOrder, Side, OrderType, OutputEvent, OrderHandle};
+use flash_lob::{BookBackend, EngineConfig, LadderConfig, LevelStorage, VecBook};
+use flash_lob::matching::MatchingEngine;
+use flash_lob::reference::ReferenceMatcher;
+use flash_lob::fuzzing::check_commands;
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
-use std::collections::BTreeMap;
+use std::collections::HashMap;
 
-/// Simple reference implementation for verification
-struct ReferenceBook {
-    bids: BTreeMap<u64, Vec<(u64, u32)>>, // price -> [(order_id, qty)]
-    asks: BTreeMap<u64, Vec<(u64, u32)>>,
-    orders: std::collections::HashMap<u64, (Side, u64)>, // order_id -> (side, price)
-}
-
-impl ReferenceBook {
-    fn new() -> Self {
-        Self {
-            bids: BTreeMap::new(),
-            asks: BTreeMap::new(),
-            orders: std::collections::HashMap::new(),
-        }
-    }
-    
-    fn best_bid(&self) -> Option<u64> {
-        self.bids.iter().rev().find(|(_, v)| !v.is_empty()).map(|(k, _)| *k)
-    }
-    
-    fn best_ask(&self) -> Option<u64> {
-        self.asks.iter().find(|(_, v)| !v.is_empty()).map(|(k, _)| *k)
-    }
-    
-    fn place(&mut self, order_id: u64, side: Side, price: u64, mut qty: u32) -> u32 {
-        // Simple crossing (no partial fills tracking, just quantity consumed)
-        let mut traded = 0u32;
-        
-        match side {
-            Side::Bid => {
-                // Match against asks
-                let mut prices_to_remove = Vec::new();
-                for (&ask_price, orders) in self.asks.iter_mut() {
-                    if ask_price > price || qty == 0 {
-                        break;
-                    }
-                    while !orders.is_empty() && qty > 0 {
-                        let trade_qty = orders[0].1.min(qty);
-                        orders[0].1 -= trade_qty;
-                        qty -= trade_qty;
-                        traded += trade_qty;
-                        
-                        if orders[0].1 == 0 {
-                            let (maker_id, _) = orders.remove(0);
-                            self.orders.remove(&maker_id);
-                        }
-                    }
-                    if orders.is_empty() {
-                        prices_to_remove.push(ask_price);
-                    }
-                }
-                for p in prices_to_remove {
-                    self.asks.remove(&p);
-                }
-                
-                // Rest
-                if qty > 0 {
-                    self.bids.entry(price).or_default().push((order_id, qty));
-                    self.orders.insert(order_id, (Side::Bid, price));
-                }
-            }
-            Side::Ask => {
-                // Match against bids (highest first)
-                let mut prices_to_remove = Vec::new();
-                let prices: Vec<_> = self.bids.keys().rev().copied().collect();
-                for bid_price in prices {
-                    if bid_price < price || qty == 0 {
-                        break;
-                    }
-                    let orders = self.bids.get_mut(&bid_price).unwrap();
-                    while !orders.is_empty() && qty > 0 {
-                        let trade_qty = orders[0].1.min(qty);
-                        orders[0].1 -= trade_qty;
-                        qty -= trade_qty;
-                        traded += trade_qty;
-                        
-                        if orders[0].1 == 0 {
-                            let (maker_id, _) = orders.remove(0);
-                            self.orders.remove(&maker_id);
-                        }
-                    }
-                    if orders.is_empty() {
-                        prices_to_remove.push(bid_price);
-                    }
-                }
-                for p in prices_to_remove {
-                    self.bids.remove(&p);
-                }
-                
-                // Rest
-                if qty > 0 {
-                    self.asks.entry(price).or_default().push((order_id, qty));
-                    self.orders.insert(order_id, (Side::Ask, price));
-                }
-            }
-        }
-        
-        traded
-    }
-    
-    fn cancel(&mut self, order_id: u64) -> bool {
-        if let Some((side, price)) = self.orders.remove(&order_id) {
-            let book = match side {
-                Side::Bid => &mut self.bids,
-                Side::Ask => &mut self.asks,
-            };
-            if let Some(orders) = book.get_mut(&price) {
-                orders.retain(|(id, _)| *id != order_id);
-                if orders.is_empty() {
-                    book.remove(&price);
-                }
-            }
-            true
-        } else {
-            false
-        }
-    }
-    
-    fn order_count(&self) -> usize {
-        self.orders.len()
-    }
-}
+/// Operations between full book invariant checks
+const VALIDATE_EVERY: usize = 250;
 
 fn generate_command(rng: &mut ChaCha8Rng, order_id: u64) -> PlaceOrder {
     PlaceOrder {
@@ -146,40 +33,38 @@ fn generate_command(rng: &mut ChaCha8Rng, order_id: u64) -> PlaceOrder {
 fn test_fuzz_best_prices() {
     const SEED: u64 = 0xFEEDFACE;
     const OPS: usize = 10_000;
-    
+
     let mut rng = ChaCha8Rng::seed_from_u64(SEED);
     let mut engine = Engine::new(100_000);
-    let mut reference = ReferenceBook::new();
-    
+    let mut reference = ReferenceMatcher::new(100_000);
+
     let mut next_order_id = 1u64;
     let mut active_orders: Vec<u64> = Vec::new();
-    
+
     for i in 0..OPS {
         // 70% place, 30% cancel
-        if active_orders.is_empty() || rng.gen_bool(0.7) {
+        let cmd = if active_orders.is_empty() || rng.gen_bool(0.7) {
             let order = generate_command(&mut rng, next_order_id);
             next_order_id += 1;
-            
-            // Run both
-            engine.process_command(Command::Place(order));
-            reference.place(order.order_id, order.side, order.price, order.qty);
-            
+
             // Track if it might be resting
             active_orders.push(order.order_id);
+            Command::Place(order)
         } else {
             let idx = rng.gen_range(0..active_orders.len());
-            let order_id = active_orders.swap_remove(idx);
-            
-            engine.process_command(Command::Cancel(CancelOrder { order_id }));
-            reference.cancel(order_id);
-        }
-        
+            Command::Cancel(CancelOrder { order_id: active_orders.swap_remove(idx) })
+        };
+
+        // Run both
+        engine.process_command(cmd);
+        reference.process(cmd);
+
         // Compare best prices
         let engine_bid = engine.best_bid();
         let engine_ask = engine.best_ask();
         let ref_bid = reference.best_bid();
         let ref_ask = reference.best_ask();
-        
+
         assert_eq!(
             engine_bid, ref_bid,
             "Best bid mismatch at op {}: engine={:?}, reference={:?}",
@@ -190,11 +75,14 @@ fn test_fuzz_best_prices() {
             "Best ask mismatch at op {}: engine={:?}, reference={:?}",
             i, engine_ask, ref_ask
         );
+        if i % VALIDATE_EVERY == 0 {
+            assert_eq!(engine.validate(), Ok(()), "invariant broken at op {}", i);
+        }
     }
-    
+
     println!("Fuzz test passed!");
     println!("  Operations: {}", OPS);
-    println!("  Final order count - Engine: {}, Reference: {}", 
+    println!("  Final order count - Engine: {}, Reference: {}",
              engine.order_count(), reference.order_count());
 }
 
@@ -202,22 +90,22 @@ fn test_fuzz_best_prices() {
 fn test_fuzz_order_count() {
     const SEED: u64 = 0xBADC0DE;
     const OPS: usize = 5_000;
-    
+
     let mut rng = ChaCha8Rng::seed_from_u64(SEED);
     let mut engine = Engine::new(100_000);
-    let mut reference = ReferenceBook::new();
-    
+    let mut reference = ReferenceMatcher::new(100_000);
+
     let mut next_order_id = 1u64;
     let mut active_orders: Vec<u64> = Vec::new();
-    
+
     for i in 0..OPS {
         if active_orders.is_empty() || rng.gen_bool(0.6) {
             let order = generate_command(&mut rng, next_order_id);
             next_order_id += 1;
-            
+
             let events = engine.process_command(Command::Place(order));
-            reference.place(order.order_id, order.side, order.price, order.qty);
-            
+            reference.process(Command::Place(order));
+
             // Check if order is resting
             let is_resting = events.iter().any(|e| matches!(e, OutputEvent::Accepted(_)));
             if is_resting {
@@ -226,11 +114,11 @@ fn test_fuzz_order_count() {
         } else {
             let idx = rng.gen_range(0..active_orders.len());
             let order_id = active_orders.swap_remove(idx);
-            
+
             engine.process_command(Command::Cancel(CancelOrder { order_id }));
-            reference.cancel(order_id);
+            reference.process(Command::Cancel(CancelOrder { order_id }));
         }
-        
+
         // Compare order counts periodically
         if i % 100 == 0 {
             assert_eq!(
@@ -238,8 +126,11 @@ fn test_fuzz_order_count() {
                 "Order count mismatch at op {}", i
             );
         }
+        if i % VALIDATE_EVERY == 0 {
+            assert_eq!(engine.validate(), Ok(()), "invariant broken at op {}", i);
+        }
     }
-    
+
     // Final comparison
     assert_eq!(engine.order_count(), reference.order_count());
     println!("Order count fuzz test passed!");
@@ -249,35 +140,236 @@ fn test_fuzz_order_count() {
 fn test_fuzz_trade_volume() {
     const SEED: u64 = 0x12345678;
     const OPS: usize = 5_000;
-    
+
     let mut rng = ChaCha8Rng::seed_from_u64(SEED);
     let mut engine = Engine::new(100_000);
-    let mut reference = ReferenceBook::new();
-    
+    let mut reference = ReferenceMatcher::new(100_000);
+
     let mut engine_traded = 0u64;
     let mut reference_traded = 0u64;
-    
+    let traded = |events: &[OutputEvent]| -> u64 {
+        events.iter()
+            .filter_map(|e| if let OutputEvent::Trade(t) = e { Some(t.qty as u64) } else { None })
+            .sum()
+    };
+
     for i in 0..OPS {
         let order = generate_command(&mut rng, i as u64);
-        
-        let events = engine.process_command(Command::Place(order));
-        let ref_qty = reference.place(order.order_id, order.side, order.price, order.qty);
-        
-        // Sum traded volume from engine events
-        let engine_qty: u32 = events.iter()
-            .filter_map(|e| if let OutputEvent::Trade(t) = e { Some(t.qty) } else { None })
-            .sum();
-        
-        engine_traded += engine_qty as u64;
-        reference_traded += ref_qty as u64;
+
+        engine_traded += traded(engine.process_command(Command::Place(order)));
+        reference_traded += traded(&reference.process(Command::Place(order)));
+        if i % VALIDATE_EVERY == 0 {
+            assert_eq!(engine.validate(), Ok(()), "invariant broken at op {}", i);
+        }
     }
-    
+
     assert_eq!(
         engine_traded, reference_traded,
         "Total traded volume mismatch: engine={}, reference={}",
         engine_traded, reference_traded
     );
-    
+
     println!("Trade volume fuzz test passed!");
     println!("  Total traded: {}", engine_traded);
 }
+
+// ============================================================================
+// Differential Testing - Full Event Streams
+// ============================================================================
+
+/// Random command mix covering every command and order type: limit, IOC
+/// and FOK places (some with zero quantity or a resting ID), cancels and
+/// modifies by ID and by handle, and cancels of unknown orders
+struct CommandGen {
+    rng: ChaCha8Rng,
+    next_order_id: u64,
+    /// Orders believed resting, with the handle from their OrderAccepted
+    resting: Vec<(u64, OrderHandle)>,
+}
+
+impl CommandGen {
+    fn new(seed: u64) -> Self {
+        Self { rng: ChaCha8Rng::seed_from_u64(seed), next_order_id: 1, resting: Vec::new() }
+    }
+
+    fn price(&mut self) -> u64 {
+        self.rng.gen_range(9_990..10_010)
+    }
+
+    fn qty(&mut self) -> u32 {
+        // Occasionally zero, to hit InvalidQuantity
+        if self.rng.gen_ratio(1, 100) { 0 } else { self.rng.gen_range(1..100) }
+    }
+
+    fn next(&mut self) -> Command {
+        let order_id = self.next_order_id;
+        self.next_order_id += 1;
+        let roll = self.rng.gen_range(0..100);
+
+        if roll < 25 && !self.resting.is_empty() {
+            let (old, handle) = self.resting.swap_remove(self.rng.gen_range(0..self.resting.len()));
+            let cancel = CancelOrder { order_id: old };
+            return if roll < 15 { Command::Cancel(cancel) } else { Command::CancelByHandle(cancel, handle) };
+        }
+        if roll < 35 && !self.resting.is_empty() {
+            let (old, handle) = self.resting.swap_remove(self.rng.gen_range(0..self.resting.len()));
+            let modify = ModifyOrder { order_id: old, new_order_id: order_id, new_price: self.price(), new_qty: self.qty() };
+            return if roll < 30 { Command::Modify(modify) } else { Command::ModifyByHandle(modify, handle) };
+        }
+        if roll < 37 {
+            // Unknown order
+            return Command::Cancel(CancelOrder { order_id: order_id + 1_000_000 });
+        }
+
+        // Reuse a resting ID now and then to hit DuplicateOrderId
+        let order_id = match self.resting.first() {
+            Some(&(id, _)) if roll < 39 => id,
+            _ => order_id,
+        };
+        let side = if self.rng.gen_bool(0.5) { Side::Bid } else { Side::Ask };
+        let order_type = match self.rng.gen_range(0..10) {
+            0 | 1 => OrderType::IOC,
+            2 | 3 => OrderType::FOK,
+            _ => OrderType::Limit,
+        };
+        let (price, qty) = (self.price(), self.qty());
+        Command::Place(PlaceOrder { order_id, user_id: self.rng.gen_range(1..20), side, price, qty, order_type })
+    }
+
+    /// Track accepted orders and their handles. A reused ID keeps its stale
+    /// entry, so stale handle commands are generated too.
+    fn observe(&mut self, events: &[OutputEvent]) {
+        for event in events {
+            if let OutputEvent::Accepted(a) = event {
+                self.resting.push((a.order_id, a.handle));
+            }
+        }
+    }
+}
+
+/// The engine's events with handles cleared (the reference has none)
+fn without_handles(events: &[OutputEvent]) -> Vec<OutputEvent> {
+    events.iter().map(|e| match *e {
+        OutputEvent::Accepted(a) => OutputEvent::Accepted(flash_lob::command::OrderAccepted { handle: OrderHandle::default(), ..a }),
+        e => e,
+    }).collect()
+}
+
+/// Run a random command stream through `engine` and the reference, asserting
+/// identical event streams and periodically identical books
+fn assert_matches_reference<B: BookBackend>(mut engine: Engine<B>, capacity: usize, seed: u64) {
+    const OPS: usize = 20_000;
+    let mut reference = ReferenceMatcher::new(capacity);
+    let mut gen = CommandGen::new(seed);
+
+    for i in 0..OPS {
+        let cmd = gen.next();
+        let events = engine.process_command(cmd);
+        assert_eq!(without_handles(events), reference.process(cmd), "op {}: {:?}", i, cmd);
+        gen.observe(events);
+        for event in events {
+            if let OutputEvent::Accepted(a) = event {
+                reference.bind_handle(a.handle, a.order_id);
+            }
+        }
+
+        if i % VALIDATE_EVERY == 0 {
+            assert_eq!(engine.validate(), Ok(()), "invariant broken at op {}", i);
+            assert_eq!(engine.best_bid(), reference.best_bid(), "op {}", i);
+            assert_eq!(engine.best_ask(), reference.best_ask(), "op {}", i);
+            assert_eq!(engine.order_count(), reference.order_count(), "op {}", i);
+            // Drop orders filled since they were accepted
+            gen.resting.retain(|&(id, _)| reference.contains_order(id));
+        }
+    }
+}
+
+#[test]
+fn test_differential_tree() {
+    assert_matches_reference(Engine::new(100_000), 100_000, 0xD1FF_0001);
+}
+
+#[test]
+fn test_differential_ladder() {
+    let matcher = MatchingEngine::with_storage(100_000, LevelStorage::Ladder(LadderConfig::default()));
+    assert_matches_reference(Engine::with_matcher(matcher, EngineConfig::default()), 100_000, 0xD1FF_0002);
+}
+
+#[test]
+fn test_differential_vec_book() {
+    let matcher = MatchingEngine::with_book(100_000, VecBook::with_capacity(100_000));
+    assert_matches_reference(Engine::with_matcher(matcher, EngineConfig::default()), 100_000, 0xD1FF_0003);
+}
+
+#[test]
+fn test_differential_near_capacity() {
+    // A small arena keeps ArenaFull rejections in the stream
+    assert_matches_reference(Engine::new(64), 64, 0xD1FF_0004);
+}
+
+#[test]
+fn test_differential_command_coverage() {
+    // Make sure the generator actually exercises every outcome
+    let mut engine = Engine::new(100_000);
+    let mut gen = CommandGen::new(0xD1FF_0001);
+    let mut seen: HashMap<&'static str, usize> = HashMap::new();
+    for _ in 0..20_000 {
+        let cmd = gen.next();
+        let events = engine.process_command(cmd);
+        gen.observe(events);
+        for event in events {
+            let kind = match event {
+                OutputEvent::Trade(_) => "trade",
+                OutputEvent::Accepted(_) => "accepted",
+                OutputEvent::Canceled(_) => "canceled",
+                OutputEvent::Rejected(r) => match r.reason {
+                    flash_lob::command::RejectReason::InvalidQuantity => "invalid_qty",
+                    flash_lob::command::RejectReason::DuplicateOrderId => "duplicate",
+                    flash_lob::command::RejectReason::OrderNotFound => "not_found",
+                    flash_lob::command::RejectReason::InsufficientLiquidity => "fok_rejected",
+                    _ => "other",
+                },
+                _ => continue,
+            };
+            *seen.entry(kind).or_default() += 1;
+        }
+        gen.resting.retain(|&(id, _)| engine.matcher.book.contains_order(id));
+    }
+    for kind in ["trade", "accepted", "canceled", "invalid_qty", "duplicate", "not_found", "fok_rejected"] {
+        assert!(seen.get(kind).copied().unwrap_or(0) > 0, "no {} events: {:?}", kind, seen);
+    }
+}
+
+#[test]
+fn test_fuzz_harness_random_inputs() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0xF022_0001);
+    for _ in 0..2_000 {
+        let len = rng.gen_range(1..512);
+        let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+        check_commands(&data);
+    }
+}
+
+#[test]
+fn test_fuzz_harness_edge_cases() {
+    // Backend byte, then (tag, order_id, user_id, side, price, qty) per place.
+    // Price byte 250 is u64::MAX (999 on the ladder) and 248 is 0; qty byte
+    // 253 is u32::MAX and 255 is 1 << 31
+    for backend in 0..3 {
+        // Extreme prices resting and crossing on both sides
+        check_commands(&[backend, 0, 1, 0, 1, 250, 5, 0, 2, 0, 0, 248, 5, 2, 3, 1, 0, 250, 7, 3, 4, 1, 1, 248, 7]);
+        // Level total above u32::MAX, then a FOK for u32::MAX against it
+        check_commands(&[backend, 0, 1, 0, 1, 0, 255, 0, 2, 0, 1, 0, 255, 3, 3, 1, 0, 0, 253]);
+        // Duplicate IDs, then a modify onto an existing ID
+        check_commands(&[backend, 0, 1, 0, 0, 0, 5, 0, 1, 1, 1, 3, 5, 0, 2, 0, 0, 1, 5, 5, 1, 2, 4, 5]);
+        // Handle commands (tag 6, pick byte) on a canceled-and-replaced ID's
+        // old handle, the current one, and a filled order's handle
+        check_commands(&[backend, 0, 0, 0, 0, 0, 5, 4, 0, 0, 0, 0, 0, 0, 5, 6, 0, 6, 1, 0, 1, 0, 1, 0, 5, 0, 2, 0, 0, 0, 5, 6, 2]);
+        // Fill the arena and keep placing
+        let mut data = vec![backend];
+        for id in 0..80u8 {
+            data.extend_from_slice(&[0, id, 0, id & 1, if id & 1 == 0 { 0 } else { 8 }, 253]);
+        }
+        check_commands(&data);
+    }
+}
@@ -6,7 +6,9 @@
 //! - Rapid order churn
 //! - Maximum values for prices and quantities
 
-use flash_lob::{Engine, Command, PlaceOrder, CancelOrder, Side, OutputEvent, OrderType};
+use flash_lob::{Engine, Command, PlaceOrder, CancelOrder, ModifyOrder, Side, OutputEvent, OrderType, BookMirror, EngineConfig, NULL_INDEX};
+use flash_lob::{Arena, ArenaGrowth, BookBackend, LadderConfig, LevelStorage, OrderBook, OrderHandle, VecBook};
+use flash_lob::matching::MatchingEngine;
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
 
@@ -118,7 +120,7 @@ fn test_arena_reuse_after_cancel() {
 
 #[test]
 fn test_single_price_level_contention() {
-    let mut engine = Engine::new(10_000);
+    let mut engine = MirroredEngine::new(10_000);
     const ORDERS_PER_SIDE: u64 = 1000;
     
     // Add many orders at the same price
@@ -193,7 +195,7 @@ fn test_fifo_priority_under_contention() {
 
 #[test]
 fn test_rapid_add_cancel_cycles() {
-    let mut engine = Engine::new(1000);
+    let mut engine = MirroredEngine::new(1000);
     const CYCLES: usize = 10_000;
     
     for cycle in 0..CYCLES {
@@ -221,7 +223,7 @@ fn test_rapid_add_cancel_cycles() {
 
 #[test]
 fn test_rapid_match_cycles() {
-    let mut engine = Engine::new(10_000);
+    let mut engine = MirroredEngine::new(10_000);
     const CYCLES: usize = 5_000;
     
     let mut total_trades = 0;
@@ -544,18 +546,30 @@ fn test_partial_match_across_levels() {
 
 #[test]
 fn test_large_random_workload() {
+    large_random_workload(EngineConfig::default());
+}
+
+#[test]
+fn test_large_random_workload_conflated() {
+    large_random_workload(EngineConfig { conflate_deltas: true, emit_bbo: true });
+}
+
+fn large_random_workload(config: EngineConfig) {
     const SEED: u64 = 0xABCDEF123456;
     const OPS: usize = 50_000;
     
     let mut rng = ChaCha8Rng::seed_from_u64(SEED);
-    let mut engine = Engine::new(100_000);
+    let mut engine = MirroredEngine::with_config(100_000, config);
     
     let mut next_order_id = 1u64;
     let mut resting_orders = Vec::new();
     let mut total_trades = 0u64;
     let mut total_cancels = 0u64;
     
-    for _ in 0..OPS {
+    for i in 0..OPS {
+        if i % 1_000 == 0 {
+            assert_eq!(engine.validate(), Ok(()), "invariant broken at op {}", i);
+        }
         let op = rng.gen_range(0..100);
         
         if op < 60 {
@@ -615,6 +629,42 @@ fn test_large_random_workload() {
     println!("  Final book size: {}", engine.order_count());
 }
 
+#[test]
+fn test_reduce_workload() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0x5EED_0035);
+    let mut engine = MirroredEngine::new(20_000);
+    let mut resting = Vec::new();
+    let mut reductions = 0;
+
+    for order_id in 1..=20_000u64 {
+        let op = rng.gen_range(0..100);
+        if op < 55 || resting.is_empty() {
+            let events = engine.process_command(Command::Place(PlaceOrder::limit(
+                order_id,
+                1,
+                if rng.gen_bool(0.5) { Side::Bid } else { Side::Ask },
+                rng.gen_range(995..1005) * 10,
+                rng.gen_range(1..100),
+            )));
+            if events.iter().any(|e| matches!(e, OutputEvent::Accepted(_))) {
+                resting.push(order_id);
+            }
+        } else {
+            // Reductions by more than the remaining size cancel the order
+            let idx = rng.gen_range(0..resting.len());
+            let events = if op < 90 {
+                engine.reduce(resting[idx], rng.gen_range(1..60))
+            } else {
+                engine.process_command(Command::Cancel(CancelOrder { order_id: resting[idx] }))
+            };
+            reductions += events.iter().filter(|e| matches!(e, OutputEvent::Reduced(_))).count();
+        }
+        resting.retain(|&id| engine.matcher.book.contains_order(id));
+    }
+
+    assert!(reductions > 1_000, "only {} in-place reductions", reductions);
+}
+
 // ============================================================================
 // Memory Leak Detection
 // ============================================================================
@@ -672,7 +722,7 @@ fn test_arena_returns_all_slots() {
 
 #[test]
 fn test_ioc_stress() {
-    let mut engine = Engine::new(10_000);
+    let mut engine = MirroredEngine::new(10_000);
     
     // Pre-populate with small liquidity across multiple price levels
     for i in 0..100 {
@@ -712,7 +762,7 @@ fn test_ioc_stress() {
 
 #[test]
 fn test_fok_stress() {
-    let mut engine = Engine::new(10_000);
+    let mut engine = MirroredEngine::new(10_000);
     
     // Pre-populate with consistent liquidity
     for i in 0..100 {
@@ -758,7 +808,7 @@ fn test_fok_stress() {
 
 #[test]
 fn test_ioc_large_sweep() {
-    let mut engine = Engine::new(10_000);
+    let mut engine = MirroredEngine::new(10_000);
     
     // Pre-populate 1000 small orders across 10 price levels
     for i in 0..1000 {
@@ -791,3 +841,427 @@ fn test_ioc_large_sweep() {
     assert_eq!(accepted, 0, "IOC should not rest");
 }
 
+
+// ============================================================================
+// Book Mirror Consistency
+// ============================================================================
+
+/// Assert that the mirror matches the engine's level at `price` and, in L3
+/// mode, its orders in time priority.
+fn assert_mirror_level(engine: &Engine, mirror: &BookMirror, side: Side, price: u64) {
+    let level = engine.matcher.book.get_level(side, price);
+    let mirrored = mirror.level(side, price);
+    assert_eq!(
+        mirrored.map(|l| (l.qty, l.count)),
+        level.map(|l| (l.total_qty, l.count)),
+        "{:?} level {}", side, price
+    );
+
+    if mirror.tracks_orders() {
+        let mut expected = Vec::new();
+        let mut idx = level.map_or(NULL_INDEX, |l| l.head);
+        while idx != NULL_INDEX {
+            let node = engine.matcher.arena.get(idx);
+            expected.push((node.order_id, node.qty));
+            idx = node.next;
+        }
+        let actual: Vec<_> = mirror.orders_at(side, price).map(|o| (o.order_id, o.qty)).collect();
+        assert_eq!(actual, expected, "{:?} queue at {}", side, price);
+    }
+}
+
+/// Assert that the mirror matches the engine's book level-for-level and,
+/// in L3 mode, order-for-order in time priority.
+fn assert_mirror_matches(engine: &Engine, mirror: &BookMirror) {
+    let book = &engine.matcher.book;
+    for side in [Side::Bid, Side::Ask] {
+        let levels = match side {
+            Side::Bid => &book.bids,
+            Side::Ask => &book.asks,
+        };
+        assert_eq!(mirror.level_count(side), levels.len(), "{:?} level count", side);
+        for (price, _) in levels.iter() {
+            assert_mirror_level(engine, mirror, side, price);
+        }
+    }
+
+    assert_eq!(mirror.best_bid().map(|(p, _)| p), book.best_bid());
+    assert_eq!(mirror.best_ask().map(|(p, _)| p), book.best_ask());
+    if let Some(count) = mirror.order_count() {
+        assert_eq!(count, engine.order_count());
+    }
+}
+
+/// Commands between full mirror comparisons in `MirroredEngine`
+const MIRROR_FULL_CHECK_EVERY: u64 = 1_000;
+
+/// An engine whose output feeds an L2 and an L3 `BookMirror`.
+///
+/// After every command the levels it touched, the top of book and the
+/// order count are compared against the engine; the whole book is
+/// compared every `MIRROR_FULL_CHECK_EVERY` commands and on drop.
+struct MirroredEngine {
+    engine: Engine,
+    l2: BookMirror,
+    l3: BookMirror,
+    seq: u64,
+    commands: u64,
+}
+
+impl MirroredEngine {
+    fn new(capacity: u32) -> Self {
+        Self::with_config(capacity, EngineConfig::default())
+    }
+
+    fn with_config(capacity: u32, config: EngineConfig) -> Self {
+        Self {
+            engine: Engine::with_config(capacity, config),
+            l2: BookMirror::new(),
+            l3: BookMirror::with_orders(),
+            seq: 1,
+            commands: 0,
+        }
+    }
+
+    fn process_command(&mut self, cmd: Command) -> &[OutputEvent] {
+        self.engine.process_command(cmd);
+        self.mirror_output()
+    }
+
+    /// Reduce a resting order in place (as feed replay does)
+    fn reduce(&mut self, order_id: u64, by: u32) -> &[OutputEvent] {
+        self.engine.event_buffer.clear();
+        self.engine.matcher.process_reduce(order_id, by, &mut self.engine.event_buffer);
+        self.mirror_output()
+    }
+
+    /// Feed the last output to both mirrors and check them against the book
+    fn mirror_output(&mut self) -> &[OutputEvent] {
+        let events = &self.engine.event_buffer;
+
+        let mut touched = Vec::new();
+        for event in events {
+            self.l2.apply_sequenced(self.seq, event).expect("L2 mirror rejected event");
+            self.l3.apply_sequenced(self.seq, event).expect("L3 mirror rejected event");
+            self.seq += 1;
+            match *event {
+                OutputEvent::BookDelta(d) => touched.push((d.side, d.price)),
+                OutputEvent::Bbo(b) => {
+                    let quote = (self.l2.best_bid().map(|(p, l)| (p, l.qty)), self.l2.best_ask().map(|(p, l)| (p, l.qty)));
+                    assert_eq!(quote, (b.bid_price.map(|p| (p, b.bid_qty)), b.ask_price.map(|p| (p, b.ask_qty))));
+                }
+                _ => {}
+            }
+        }
+
+        for (side, price) in touched {
+            assert_mirror_level(&self.engine, &self.l2, side, price);
+            assert_mirror_level(&self.engine, &self.l3, side, price);
+        }
+        let book = &self.engine.matcher.book;
+        for mirror in [&self.l2, &self.l3] {
+            assert!(!mirror.is_crossed());
+            assert_eq!(mirror.best_bid().map(|(p, _)| p), book.best_bid());
+            assert_eq!(mirror.best_ask().map(|(p, _)| p), book.best_ask());
+        }
+        assert_eq!(self.l3.order_count(), Some(self.engine.order_count()));
+
+        self.commands += 1;
+        if self.commands.is_multiple_of(MIRROR_FULL_CHECK_EVERY) {
+            self.assert_mirrors_match();
+        }
+        &self.engine.event_buffer
+    }
+
+    fn assert_mirrors_match(&self) {
+        assert_mirror_matches(&self.engine, &self.l2);
+        assert_mirror_matches(&self.engine, &self.l3);
+    }
+}
+
+impl std::ops::Deref for MirroredEngine {
+    type Target = Engine;
+
+    fn deref(&self) -> &Engine {
+        &self.engine
+    }
+}
+
+impl Drop for MirroredEngine {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.assert_mirrors_match();
+        }
+    }
+}
+
+// ============================================================================
+// Queue Position Tracking
+// ============================================================================
+
+#[test]
+fn test_tracked_queue_positions_match_walk() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0x9E3779B97F4A7C15);
+    let mut walked = Engine::new(50_000);
+    let mut tracked = Engine::new(50_000);
+    tracked.matcher.enable_queue_tracking();
+    let mut resting_orders = Vec::new();
+
+    // Few price levels so queues get deep and compaction kicks in
+    for order_id in 1..=30_000u64 {
+        let cmd = if rng.gen_range(0..100) < 55 || resting_orders.is_empty() {
+            resting_orders.push(order_id);
+            Command::Place(PlaceOrder::limit(
+                order_id,
+                1,
+                if rng.gen_bool(0.5) { Side::Bid } else { Side::Ask },
+                rng.gen_range(995..1005),
+                rng.gen_range(1..100),
+            ))
+        } else {
+            let idx = rng.gen_range(0..resting_orders.len());
+            Command::Cancel(CancelOrder { order_id: resting_orders.swap_remove(idx) })
+        };
+        walked.process_command(cmd);
+        tracked.process_command(cmd);
+
+        if order_id % 500 == 0 {
+            resting_orders.retain(|&id| walked.matcher.book.contains_order(id));
+            for &id in &resting_orders {
+                assert_eq!(tracked.matcher.queue_position(id), walked.matcher.queue_position(id), "order {}", id);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Dry-Run Simulation
+// ============================================================================
+
+#[test]
+fn test_simulate_place_predicts_process_place() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0xD2E5_1A7E);
+    let mut engine = Engine::new(2_000);
+    let mut resting_orders = Vec::new();
+
+    for order_id in 1..=20_000u64 {
+        if rng.gen_range(0..100) < 30 && !resting_orders.is_empty() {
+            let idx = rng.gen_range(0..resting_orders.len());
+            engine.process_command(Command::Cancel(CancelOrder { order_id: resting_orders.swap_remove(idx) }));
+            continue;
+        }
+
+        let order = PlaceOrder {
+            order_id,
+            user_id: rng.gen_range(1..50),
+            side: if rng.gen_bool(0.5) { Side::Bid } else { Side::Ask },
+            price: rng.gen_range(990..1010),
+            qty: rng.gen_range(0..200),
+            order_type: match rng.gen_range(0..10) {
+                0 => OrderType::IOC,
+                1 => OrderType::FOK,
+                _ => OrderType::Limit,
+            },
+        };
+        let hash = engine.matcher.state_hash();
+        let sim = engine.matcher.simulate_place(order);
+        assert_eq!(engine.matcher.state_hash(), hash);
+
+        let events = engine.process_command(Command::Place(order));
+        let trades: Vec<_> = events.iter()
+            .filter_map(|e| if let OutputEvent::Trade(t) = e { Some(*t) } else { None })
+            .collect();
+        let rejected = events.iter().find_map(|e| if let OutputEvent::Rejected(r) = e { Some(r.reason) } else { None });
+        let rested = events.iter().any(|e| matches!(e, OutputEvent::Accepted(_)));
+
+        assert_eq!(trades, sim.fills, "order {}", order_id);
+        assert_eq!(rejected, sim.rejected, "order {}", order_id);
+        assert_eq!(rested, sim.rests, "order {}", order_id);
+        if rested {
+            resting_orders.push(order_id);
+        }
+    }
+}
+
+// ============================================================================
+// Level Storage Backends
+// ============================================================================
+
+/// Drive `other` and a default (`LevelMap`) engine with the same drifting
+/// workload and assert identical events and state.
+fn assert_matches_tree_backend<B: BookBackend>(other: &mut Engine<B>, tick: u64, seed: u64) {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut tree = Engine::new(other.matcher.arena.max_capacity());
+    let mut resting_orders = Vec::new();
+    let mut mid_ticks: i64 = 2_000;
+
+    for order_id in 1..=50_000u64 {
+        mid_ticks = (mid_ticks + rng.gen_range(-2..=2)).max(100);
+        let price = (mid_ticks + rng.gen_range(-40..=40)) as u64 * tick;
+        let roll = rng.gen_range(0..100);
+        let cmd = if roll < 20 && !resting_orders.is_empty() {
+            let idx = rng.gen_range(0..resting_orders.len());
+            Command::Cancel(CancelOrder { order_id: resting_orders.swap_remove(idx) })
+        } else if roll < 25 && !resting_orders.is_empty() {
+            let idx = rng.gen_range(0..resting_orders.len());
+            Command::Modify(ModifyOrder {
+                order_id: resting_orders.swap_remove(idx),
+                new_order_id: order_id,
+                new_price: price,
+                new_qty: rng.gen_range(1..200),
+            })
+        } else {
+            Command::Place(PlaceOrder {
+                order_id,
+                user_id: rng.gen_range(1..50),
+                side: if rng.gen_bool(0.5) { Side::Bid } else { Side::Ask },
+                price,
+                qty: rng.gen_range(1..200),
+                order_type: match rng.gen_range(0..10) {
+                    0 => OrderType::IOC,
+                    1 => OrderType::FOK,
+                    _ => OrderType::Limit,
+                },
+            })
+        };
+
+        let expected = tree.process_command(cmd).to_vec();
+        let events = other.process_command(cmd);
+        assert_eq!(events, expected, "order {}", order_id);
+        if events.iter().any(|e| matches!(e, OutputEvent::Accepted(_))) {
+            resting_orders.push(order_id);
+        }
+
+        if order_id % 1_000 == 0 {
+            assert_eq!(other.state_hash(), tree.state_hash(), "order {}", order_id);
+            assert_eq!(other.validate(), Ok(()), "order {}", order_id);
+            assert_eq!(other.best_bid(), tree.best_bid());
+            assert_eq!(other.best_ask(), tree.best_ask());
+        }
+    }
+}
+
+#[test]
+fn test_ladder_storage_matches_tree() {
+    use flash_lob::book_side::BookSide;
+    use flash_lob::command::RejectReason;
+
+    const TICK: u64 = 5;
+    // Narrow window so the drifting mid forces recenters and growth
+    let storage = LevelStorage::Ladder(LadderConfig::centered(10_000, TICK, 64));
+    let mut ladder = Engine::with_matcher(MatchingEngine::with_storage(10_000, storage), EngineConfig::default());
+    assert_matches_tree_backend(&mut ladder, TICK, 0x01AD_DE12);
+
+    let recenters: u64 = [&ladder.matcher.book.bids, &ladder.matcher.book.asks].iter()
+        .map(|side| match side {
+            BookSide::Ladder(l) => l.recenters(),
+            BookSide::Tree(_) => 0,
+        })
+        .sum();
+    assert!(recenters > 0, "workload should drift outside the initial window");
+
+    // Off-tick prices cannot be stored in a ladder (this one can't cross)
+    let off_tick = PlaceOrder::limit(u64::MAX, 1, Side::Bid, 1, 10);
+    let events = ladder.process_command(Command::Place(off_tick));
+    assert!(matches!(events, [OutputEvent::Rejected(r)] if r.reason == RejectReason::InvalidPrice));
+}
+
+#[test]
+fn test_ladder_unstorable_prices_still_trade() {
+    // Off-tick and out-of-span prices only matter once an order would rest:
+    // marketable orders trade exactly as on the tree, and only a remainder
+    // that would rest is rejected
+    use flash_lob::command::RejectReason;
+
+    let storage = LevelStorage::Ladder(LadderConfig::centered(10_000, 5, 64));
+    let mut ladder = Engine::with_matcher(MatchingEngine::with_storage(1_000, storage), EngineConfig::default());
+    let mut tree = Engine::new(1_000);
+    let mut run = |cmd: Command| (tree.process_command(cmd).to_vec(), ladder.process_command(cmd).to_vec());
+
+    let setup = [(1, Side::Ask, 10_000), (2, Side::Ask, 10_005), (8, Side::Ask, 10_010), (3, Side::Bid, 9_995), (4, Side::Bid, 9_990)];
+    for (id, side, price) in setup {
+        let (t, l) = run(Command::Place(PlaceOrder::limit(id, 1, side, price, 10)));
+        assert_eq!(t, l);
+    }
+
+    // IOC at u64::MAX and FOK off-tick sweep the book on both backends
+    let ioc = PlaceOrder { order_type: OrderType::IOC, ..PlaceOrder::limit(5, 2, Side::Bid, u64::MAX, 15) };
+    let (t, l) = run(Command::Place(ioc));
+    assert_eq!(t, l);
+    assert_eq!(t.iter().filter(|e| matches!(e, OutputEvent::Trade(_))).count(), 2);
+    let fok = PlaceOrder { order_type: OrderType::FOK, ..PlaceOrder::limit(6, 2, Side::Ask, 9_991, 10) };
+    let (t, l) = run(Command::Place(fok));
+    assert_eq!(t, l);
+    assert!(matches!(t[0], OutputEvent::Trade(_)));
+
+    // An off-tick limit trades, then only its remainder is refused
+    let (t, l) = run(Command::Place(PlaceOrder::limit(7, 2, Side::Bid, 10_006, 10)));
+    assert_eq!(t[..2], l[..2]);
+    assert!(matches!(t[2], OutputEvent::Accepted(_)));
+    assert!(matches!(l[2..], [OutputEvent::Rejected(r)] if r.reason == RejectReason::InvalidPrice && r.order_id == 7));
+
+    // Resting far outside the ask side's span is refused
+    let (t, l) = run(Command::Place(PlaceOrder::limit(9, 2, Side::Ask, u64::MAX, 10)));
+    assert!(matches!(t[0], OutputEvent::Accepted(_)));
+    assert!(matches!(l[..], [OutputEvent::Rejected(r)] if r.reason == RejectReason::InvalidPrice));
+    assert_eq!(ladder.validate(), Ok(()));
+}
+
+#[test]
+fn test_vec_book_matches_tree() {
+    let book = VecBook::with_capacity(10_000);
+    let mut engine = Engine::with_matcher(MatchingEngine::with_book(10_000, book), EngineConfig::default());
+    assert_matches_tree_backend(&mut engine, 1, 0x5EC7_0B00);
+}
+
+#[test]
+fn test_handle_cancels_match_id_cancels() {
+    let mut rng = ChaCha8Rng::seed_from_u64(0x4A9D_1E00);
+    let mut by_id = Engine::new(10_000);
+    let mut by_handle = Engine::new(10_000);
+    // Resting orders with the handle from their OrderAccepted
+    let mut resting: Vec<(u64, OrderHandle)> = Vec::new();
+
+    for order_id in 1..=50_000u64 {
+        let roll = rng.gen_range(0..100);
+        let (id_cmd, handle_cmd) = if roll < 30 && !resting.is_empty() {
+            let (old, handle) = resting.swap_remove(rng.gen_range(0..resting.len()));
+            if roll < 20 {
+                let cancel = CancelOrder { order_id: old };
+                (Command::Cancel(cancel), Command::CancelByHandle(cancel, handle))
+            } else {
+                let modify = ModifyOrder { order_id: old, new_order_id: order_id, new_price: rng.gen_range(9_950..10_050), new_qty: rng.gen_range(1..200) };
+                (Command::Modify(modify), Command::ModifyByHandle(modify, handle))
+            }
+        } else {
+            let place = PlaceOrder::limit(order_id, rng.gen_range(1..50), if rng.gen_bool(0.5) { Side::Bid } else { Side::Ask }, rng.gen_range(9_950..10_050), rng.gen_range(1..200));
+            (Command::Place(place), Command::Place(place))
+        };
+
+        let expected = by_id.process_command(id_cmd).to_vec();
+        let events = by_handle.process_command(handle_cmd);
+        assert_eq!(events, expected, "order {}", order_id);
+        for event in events {
+            if let OutputEvent::Accepted(a) = event {
+                resting.push((a.order_id, a.handle));
+            }
+        }
+        // Handles of orders filled since are stale and must be rejected;
+        // drop them so the flow keeps hitting live orders
+        if order_id % 1_000 == 0 {
+            resting.retain(|(id, _)| by_handle.matcher.book.contains_order(*id));
+            assert_eq!(by_handle.state_hash(), by_id.state_hash(), "order {}", order_id);
+            assert_eq!(by_handle.validate(), Ok(()), "order {}", order_id);
+        }
+    }
+}
+
+#[test]
+fn test_growable_arena_matches_fixed() {
+    // No watermark, so the event stream is identical to a fixed arena's
+    let growth = ArenaGrowth { chunk_size: 256, max_capacity: 10_000, high_watermark: 0 };
+    let matcher = MatchingEngine::with_arena(Arena::growable(growth), OrderBook::new());
+    let mut engine = Engine::with_matcher(matcher, EngineConfig::default());
+    assert_matches_tree_backend(&mut engine, 1, 0x0C4E_A2A5);
+    assert!(engine.matcher.arena.capacity() > 256, "arena should have grown");
+}